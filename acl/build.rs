@@ -0,0 +1,7 @@
+fn main() {
+    tonic_build::configure()
+        .build_client(false)
+        .build_server(true)
+        .compile(&["../proto/acl.proto"], &["../proto"])
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+}
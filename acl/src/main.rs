@@ -0,0 +1,143 @@
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use trace_lib::*;
+use tracing::*;
+use tracing_futures::Instrument;
+
+mod store;
+use store::*;
+
+mod acl {
+    tonic::include_proto!("acl");
+}
+use acl::*;
+
+#[instrument]
+async fn get_redis_conn(url: &str) -> Result<redis::aio::MultiplexedConnection, tonic::Status> {
+    let client =
+        redis::Client::open(url).map_err(|e| tonic::Status::unavailable(format!("{:?}", e)))?;
+    match client.get_multiplexed_async_connection().await {
+        Ok((redis_conn, fut)) => {
+            tokio::spawn(fut);
+            Ok(redis_conn)
+        }
+        Err(e) => Err(tonic::Status::unavailable(format!("{:?}", e))),
+    }
+}
+
+fn to_role(role: i32) -> Result<Role, Status> {
+    Role::from_i32(role).ok_or_else(|| Status::invalid_argument(format!("Invalid role {}", role)))
+}
+
+#[derive(Debug)]
+struct AclService {
+    redis_url: String,
+}
+
+#[tonic::async_trait]
+impl acl_server::Acl for AclService {
+    #[instrument]
+    async fn get_role(
+        &self,
+        request: Request<GetRoleInput>,
+    ) -> Result<Response<GetRoleOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let (role, claimed) = store::get_role(&mut redis_conn, &msg.file, &msg.user)
+            .instrument(info_span!("get_role"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(GetRoleOutput {
+            role: role as i32,
+            claimed,
+        }))
+    }
+
+    #[instrument]
+    async fn claim_file(
+        &self,
+        request: Request<ClaimFileInput>,
+    ) -> Result<Response<ClaimFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let role = store::claim_file(&mut redis_conn, &msg.file, &msg.user)
+            .instrument(info_span!("claim_file"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(ClaimFileOutput { role: role as i32 }))
+    }
+
+    #[instrument]
+    async fn set_role(
+        &self,
+        request: Request<SetRoleInput>,
+    ) -> Result<Response<SetRoleOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let role = to_role(msg.role)?;
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        store::set_role(&mut redis_conn, &msg.file, &msg.user, role)
+            .instrument(info_span!("set_role"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(SetRoleOutput {}))
+    }
+
+    #[instrument]
+    async fn list_roles(
+        &self,
+        request: Request<ListRolesInput>,
+    ) -> Result<Response<ListRolesOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let roles = store::list_roles(&mut redis_conn, &msg.file)
+            .instrument(info_span!("list_roles"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(ListRolesOutput {
+            roles: roles
+                .into_iter()
+                .map(|(user, role)| UserRoleMsg {
+                    user,
+                    role: role as i32,
+                })
+                .collect(),
+        }))
+    }
+
+    #[instrument]
+    async fn delete_file(
+        &self,
+        request: Request<DeleteFileInput>,
+    ) -> Result<Response<DeleteFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        store::delete_file(&mut redis_conn, &msg.file)
+            .instrument(info_span!("delete_file"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(DeleteFileOutput {}))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let run_url = std::env::var("RUN_URL").unwrap().parse().unwrap();
+    let jaeger_url = std::env::var("JAEGER_URL").unwrap();
+    let redis_url = std::env::var("REDIS_URL").unwrap();
+    trace_lib::init_tracer(&jaeger_url, "acl")?;
+
+    let svc = acl_server::AclServer::new(AclService { redis_url });
+
+    println!("Running on {:?}", run_url);
+    Server::builder()
+        .add_service(svc)
+        .serve(run_url)
+        .await
+        .unwrap();
+    Ok(())
+}
@@ -0,0 +1,201 @@
+//! Each file's access control list is a hash of user to role.  A file with no entries hasn't been
+//! claimed by anybody yet.
+
+use super::*;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AclError {
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error("Invalid role {0} stored")]
+    InvalidRole(i32),
+    #[error("Redis error: {0:?}")]
+    DatabaseError(#[from] redis::RedisError),
+}
+
+impl Into<tonic::Status> for AclError {
+    fn into(self) -> tonic::Status {
+        let msg = format!("{}", self);
+        let code = match self {
+            AclError::InvalidInput(..) => tonic::Code::InvalidArgument,
+            AclError::InvalidRole(..) | AclError::DatabaseError(..) => tonic::Code::Internal,
+        };
+        tonic::Status::new(code, msg)
+    }
+}
+
+pub fn to_status<T: Into<AclError>>(err: T) -> tonic::Status {
+    let acl_error: AclError = err.into();
+    acl_error.into()
+}
+
+fn file_acl(file: &str) -> String {
+    format!("{}:acl", file)
+}
+
+fn to_role(role: i32) -> Result<Role, AclError> {
+    Role::from_i32(role).ok_or(AclError::InvalidRole(role))
+}
+
+/// Returns the user's role on the file, and whether anybody has a role on it.
+pub async fn get_role(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    user: &str,
+) -> Result<(Role, bool), AclError> {
+    let key = file_acl(file);
+    let (role, claimed): (Option<i32>, bool) = redis::pipe()
+        .hget(&key, user)
+        .exists(&key)
+        .query_async(conn)
+        .await?;
+    let role = match role {
+        Some(role) => to_role(role)?,
+        None => Role::None,
+    };
+    Ok((role, claimed))
+}
+
+/// Makes the user the owner if the file isn't claimed, and returns their role either way.
+pub async fn claim_file(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    user: &str,
+) -> Result<Role, AclError> {
+    let script = redis::Script::new(
+        r"
+        if redis.call('EXISTS', KEYS[1]) == 0 then
+            redis.call('HSET', KEYS[1], ARGV[1], ARGV[2])
+        end
+        return redis.call('HGET', KEYS[1], ARGV[1])
+        ",
+    );
+    let role: Option<i32> = script
+        .key(file_acl(file))
+        .arg(user)
+        .arg(Role::Owner as i32)
+        .invoke_async(conn)
+        .await?;
+    match role {
+        Some(role) => to_role(role),
+        None => Ok(Role::None),
+    }
+}
+
+pub async fn list_roles(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+) -> Result<Vec<(String, Role)>, AclError> {
+    let entries: Vec<(String, i32)> = conn.hgetall(file_acl(file)).await?;
+    let mut roles = Vec::new();
+    for (user, role) in entries {
+        roles.push((user, to_role(role)?));
+    }
+    Ok(roles)
+}
+
+/// A role of None removes the user.  Files always keep at least one owner.
+pub async fn set_role(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    user: &str,
+    role: Role,
+) -> Result<(), AclError> {
+    if role != Role::Owner {
+        let owners: Vec<String> = list_roles(conn, file)
+            .await?
+            .into_iter()
+            .filter(|(_, role)| *role == Role::Owner)
+            .map(|(user, _)| user)
+            .collect();
+        if owners.len() == 1 && owners[0] == user {
+            return Err(AclError::InvalidInput(format!(
+                "{} is the only owner of {}",
+                user, file
+            )));
+        }
+    }
+    let key = file_acl(file);
+    match role {
+        Role::None => conn.hdel(&key, user).await?,
+        _ => conn.hset(&key, user, role as i32).await?,
+    }
+    Ok(())
+}
+
+/// Removes everything stored for the file.
+pub async fn delete_file(conn: &mut MultiplexedConnection, file: &str) -> Result<(), AclError> {
+    redis_lib::delete_file(conn, file).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    pub async fn test_get_conn() -> MultiplexedConnection {
+        let _ = env_logger::Builder::new()
+            .filter_module("acl", log::LevelFilter::Trace)
+            .is_test(true)
+            .try_init();
+        let env_opt = std::env::var("REDIS_URL");
+        let redis_url = if let Ok(url) = env_opt {
+            url
+        } else {
+            String::from("redis://127.0.0.1:6379")
+        };
+        let client = redis::Client::open(redis_url).unwrap();
+        let (conn, fut) = client.get_multiplexed_async_connection().await.unwrap();
+        tokio::spawn(fut);
+        conn
+    }
+
+    #[tokio_macros::test]
+    async fn test_roles() {
+        let mut conn = test_get_conn().await;
+        let file = Uuid::new_v4().to_string();
+        let owner = Uuid::new_v4().to_string();
+        let other = Uuid::new_v4().to_string();
+
+        assert_eq!(
+            get_role(&mut conn, &file, &owner).await.unwrap(),
+            (Role::None, false)
+        );
+        assert_eq!(
+            claim_file(&mut conn, &file, &owner).await.unwrap(),
+            Role::Owner
+        );
+        assert_eq!(
+            claim_file(&mut conn, &file, &other).await.unwrap(),
+            Role::None
+        );
+        assert_eq!(
+            get_role(&mut conn, &file, &other).await.unwrap(),
+            (Role::None, true)
+        );
+
+        set_role(&mut conn, &file, &other, Role::Editor)
+            .await
+            .unwrap();
+        assert_eq!(
+            get_role(&mut conn, &file, &other).await.unwrap(),
+            (Role::Editor, true)
+        );
+        assert!(set_role(&mut conn, &file, &owner, Role::Viewer)
+            .await
+            .is_err());
+
+        set_role(&mut conn, &file, &other, Role::Owner)
+            .await
+            .unwrap();
+        set_role(&mut conn, &file, &owner, Role::None)
+            .await
+            .unwrap();
+        let roles = list_roles(&mut conn, &file).await.unwrap();
+        assert_eq!(roles, vec![(other, Role::Owner)]);
+    }
+}
@@ -13,6 +13,11 @@ fn main() {
                 "../proto/representation.proto",
                 "../proto/rep_cache.proto",
                 "../proto/submit.proto",
+                "../proto/dependencies.proto",
+                "../proto/spatial_index.proto",
+                "../proto/updates.proto",
+                "../proto/acl.proto",
+                "../proto/files.proto",
             ],
             &["../proto"],
         )
@@ -0,0 +1,57 @@
+//! Claims files that were created before acl existed, so they can be used again.  Nobody can
+//! read or write a file nobody has claimed, unless api and updates are run with
+//! `OPEN_UNCLAIMED=true` to keep them readable until this has been run.
+//! Usage: claim_files <files url> <acl url> [<file> <owner>]...
+//! Without any files, every file in the files service is claimed for the owner it was created
+//! with.  Files that are already claimed are left alone.
+mod acl {
+    tonic::include_proto!("acl");
+}
+
+mod files {
+    tonic::include_proto!("files");
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let usage = "Usage: claim_files <files url> <acl url> [<file> <owner>]...";
+    let files_url = args.next().ok_or(usage)?;
+    let acl_url = args.next().ok_or(usage)?;
+    let mut to_claim = Vec::new();
+    while let Some(file) = args.next() {
+        let owner = args.next().ok_or(usage)?;
+        to_claim.push((file, owner));
+    }
+    if to_claim.is_empty() {
+        let mut files_client = files::files_client::FilesClient::connect(files_url).await?;
+        let output = files_client
+            .list_files(files::ListFilesInput {
+                include_archived: true,
+            })
+            .await?
+            .into_inner();
+        for file in output.files {
+            if file.owner.is_empty() {
+                println!("{} has no owner recorded, skipping", file.id);
+            } else {
+                to_claim.push((file.id, file.owner));
+            }
+        }
+    }
+    let mut acl_client = acl::acl_client::AclClient::connect(acl_url).await?;
+    for (file, owner) in to_claim {
+        let output = acl_client
+            .claim_file(acl::ClaimFileInput {
+                file: file.clone(),
+                user: owner.clone(),
+            })
+            .await?
+            .into_inner();
+        match acl::Role::from_i32(output.role) {
+            Some(role) => println!("{} is {:?} on {}", owner, role, file),
+            None => println!("{} has an invalid role on {}", owner, file),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Exports a file as IFC through the api.
+//! Usage: export_ifc <api url> <file> <output path> [token]
+//! The token is only needed if the api has authentication turned on.
+pub mod geom {
+    tonic::include_proto!("geom");
+}
+
+pub mod object_state {
+    tonic::include_proto!("object_state");
+}
+
+pub mod representation {
+    tonic::include_proto!("representation");
+}
+
+pub mod acl {
+    tonic::include_proto!("acl");
+}
+
+mod api {
+    tonic::include_proto!("api");
+}
+
+use api::*;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let usage = "Usage: export_ifc <api url> <file> <output path> [token]";
+    let url = args.next().ok_or(usage)?;
+    let file = args.next().ok_or(usage)?;
+    let path = args.next().ok_or(usage)?;
+    let mut request = tonic::Request::new(ExportIfcInput { file });
+    if let Some(token) = args.next() {
+        request
+            .metadata_mut()
+            .insert("authorization", format!("Bearer {}", token).parse()?);
+    }
+    let mut client = api_client::ApiClient::connect(url).await?;
+    let response = client.export_ifc(request).await?;
+    std::fs::write(path, response.into_inner().ifc)?;
+    Ok(())
+}
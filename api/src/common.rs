@@ -56,6 +56,163 @@ pub async fn operations_client(
     Ok(ops_client)
 }
 
+pub async fn dependencies_client(
+    dependencies_url: String,
+) -> Result<dependencies::dependencies_client::DependenciesClient<Channel>, Status> {
+    let dep_client =
+        dependencies::dependencies_client::DependenciesClient::connect(dependencies_url)
+            .instrument(info_span!("dep_client::connect"))
+            .await
+            .map_err(unavailable)?;
+    Ok(dep_client)
+}
+
+pub async fn spatial_index_client(
+    spatial_index_url: String,
+) -> Result<spatial_index::spatial_index_client::SpatialIndexClient<Channel>, Status> {
+    let spatial_client =
+        spatial_index::spatial_index_client::SpatialIndexClient::connect(spatial_index_url)
+            .instrument(info_span!("spatial_client::connect"))
+            .await
+            .map_err(unavailable)?;
+    Ok(spatial_client)
+}
+
+pub async fn updates_client(
+    updates_url: String,
+) -> Result<updates::updates_client::UpdatesClient<Channel>, Status> {
+    let updates_client = updates::updates_client::UpdatesClient::connect(updates_url)
+        .instrument(info_span!("updates_client::connect"))
+        .await
+        .map_err(unavailable)?;
+    Ok(updates_client)
+}
+
+pub async fn acl_client(acl_url: String) -> Result<acl::acl_client::AclClient<Channel>, Status> {
+    let acl_client = acl::acl_client::AclClient::connect(acl_url)
+        .instrument(info_span!("acl_client::connect"))
+        .await
+        .map_err(unavailable)?;
+    Ok(acl_client)
+}
+
+pub async fn files_client(
+    files_url: String,
+) -> Result<files::files_client::FilesClient<Channel>, Status> {
+    let files_client = files::files_client::FilesClient::connect(files_url)
+        .instrument(info_span!("files_client::connect"))
+        .await
+        .map_err(unavailable)?;
+    Ok(files_client)
+}
+
+pub async fn to_file_api_msg(
+    obj_client: &mut objects::objects_client::ObjectsClient<Channel>,
+    file: files::FileMsg,
+) -> Result<api::FileApiMsg, Status> {
+    let resp = obj_client
+        .get_latest_offset(TracedRequest::new(objects::GetLatestOffsetInput {
+            file: file.id.clone(),
+        }))
+        .await;
+    let latest_offset = trace_response(resp)?.offset;
+    Ok(api::FileApiMsg {
+        id: file.id,
+        name: file.name,
+        owner: file.owner,
+        created: file.created,
+        archived: file.archived,
+        latest_offset,
+        thumbnail: file.thumbnail,
+    })
+}
+
+/// Fails unless the user has at least the required role on the file.  Files are claimed when
+/// they're created, and older files with the claim_files tool, so nobody can use a file nobody
+/// has claimed.  With open_unclaimed anybody can read one while they're being claimed.
+pub async fn check_role(
+    acl_url: String,
+    open_unclaimed: bool,
+    file: &str,
+    user: &str,
+    required: acl::Role,
+) -> Result<(), Status> {
+    let mut acl_client = acl_client(acl_url).await?;
+    let resp = acl_client
+        .get_role(TracedRequest::new(acl::GetRoleInput {
+            file: String::from(file),
+            user: String::from(user),
+        }))
+        .await;
+    let output = trace_response(resp)?;
+    if !output.claimed {
+        if open_unclaimed && required == acl::Role::Viewer {
+            return Ok(());
+        }
+        return Err(Status::permission_denied(format!(
+            "{} hasn't been claimed by anybody",
+            file
+        )));
+    }
+    if output.role < required as i32 {
+        return Err(Status::permission_denied(format!(
+            "{} needs to be at least {:?} on {}",
+            user, required, file
+        )));
+    }
+    Ok(())
+}
+
+/// Gets the representations of the file and of every symbol file it references, directly or
+/// through other symbol files.
+pub async fn file_and_symbol_reps(
+    rep_cache_client: &mut rep_cache::rep_cache_client::RepCacheClient<Channel>,
+    file: &str,
+) -> Result<gltf::FileReps, Status> {
+    let mut reps = gltf::FileReps::new();
+    let mut to_load = vec![String::from(file)];
+    while let Some(next) = to_load.pop() {
+        if reps.contains_key(&next) {
+            continue;
+        }
+        let resp = rep_cache_client
+            .get_file_representations(TracedRequest::new(rep_cache::GetFileRepresentationsInput {
+                file: next.clone(),
+            }))
+            .await;
+        let file_reps = trace_response(resp)?.reps;
+        for rep in &file_reps {
+            if let Some(representation::UpdateOutputMsg {
+                output: Some(representation::update_output_msg::Output::FileRef(sym_file)),
+                ..
+            }) = &rep.update
+            {
+                to_load.push(sym_file.clone());
+            }
+        }
+        reps.insert(next, file_reps);
+    }
+    Ok(reps)
+}
+
+pub fn to_hit_msgs(hits: Vec<spatial_index::HitMsg>) -> Vec<api::HitMsg> {
+    hits.into_iter()
+        .map(|hit| api::HitMsg {
+            obj_id: hit.obj_id,
+            distance: hit.distance,
+        })
+        .collect()
+}
+
+pub fn to_ops_custom_view(msg: api::CustomViewMsg) -> operations::CustomViewMsg {
+    operations::CustomViewMsg {
+        camera_pos: msg.camera_pos,
+        target: msg.target,
+        perspective: msg.perspective,
+        clip: msg.clip,
+    }
+}
+
 pub async fn get_objects(
     client: &mut objects_client::ObjectsClient<Channel>,
     file: &str,
@@ -116,6 +273,24 @@ pub async fn get_objects(
     Ok(objects)
 }
 
+/// Gets the latest version of every object in the file that hasn't been deleted.
+pub async fn get_latest_objects(
+    client: &mut objects_client::ObjectsClient<Channel>,
+    file: &str,
+) -> Result<Vec<ObjectMsg>, Status> {
+    let resp = client
+        .get_latest_object_list(TracedRequest::new(objects::GetLatestObjectListInput {
+            file: String::from(file),
+        }))
+        .await;
+    let mut stream = trace_response(resp)?;
+    let mut obj_ids = Vec::new();
+    while let Some(obj_id) = stream.next().await {
+        obj_ids.push(obj_id?.obj_id);
+    }
+    get_objects(client, file, obj_ids, i64::MAX, false).await
+}
+
 pub async fn submit_changes(
     client: &mut submit_changes_client::SubmitChangesClient<Channel>,
     file: String,
@@ -143,6 +318,29 @@ pub async fn submit_changes(
     }
 }
 
+///Undoing or redoing can end up with nothing to change if every object conflicted, in which case the prefix offset
+/// is handed back.
+pub async fn submit_if_changed(
+    client: &mut submit_changes_client::SubmitChangesClient<Channel>,
+    prefix: Prefix,
+    changes: Vec<ChangeMsg>,
+) -> Result<i64, Status> {
+    if changes.len() == 0 {
+        return Ok(prefix.offset);
+    }
+    submit_changes(client, prefix.file, prefix.user, prefix.offset, changes).await
+}
+
+pub fn to_conflict_msgs(conflicts: Vec<undo::ConflictMsg>) -> Vec<api::ConflictMsg> {
+    conflicts
+        .into_iter()
+        .map(|conflict| api::ConflictMsg {
+            obj_id: conflict.obj_id,
+            user: conflict.user,
+        })
+        .collect()
+}
+
 pub fn add(user: &str, obj: ObjectMsg) -> ChangeMsg {
     object_state::ChangeMsg {
         user: String::from(user),
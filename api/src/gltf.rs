@@ -0,0 +1,439 @@
+//! Writes a file's representations out as binary glTF 2.0, so models can be handed to visualization
+//! and VR tools.  The meshes of each symbol file are only written once, and every symbol instance
+//! gets its own nodes pointing at them.  Object metadata goes in each node's extras.
+
+use crate::representation::{update_output_msg::Output, InstanceDataMsg, MeshDataMsg};
+use crate::representation::{UpdateChangeMsg, UpdateOutputMsg};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// The representations of the exported file and of every symbol file it references, by file.
+pub type FileReps = HashMap<String, Vec<UpdateChangeMsg>>;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+const TRIANGLES: u32 = 4;
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_VERSION: u32 = 2;
+const JSON_CHUNK: u32 = 0x4E4F_534A;
+const BIN_CHUNK: u32 = 0x004E_4942;
+
+#[derive(Default)]
+struct Writer {
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    meshes: Vec<Value>,
+    nodes: Vec<Value>,
+    //By file and object, so symbol files shared between instances are only written once.
+    mesh_indices: HashMap<(String, String), Option<usize>>,
+}
+
+fn extras(meta_json: &str) -> Option<Value> {
+    serde_json::from_str(meta_json).ok()
+}
+
+fn symbol_file<'a>(reps: &'a FileReps, file: &str, sym_def: &str) -> Option<&'a String> {
+    reps.get(file)?
+        .iter()
+        .filter(|rep| rep.obj_id == sym_def)
+        .find_map(|rep| match &rep.update {
+            Some(UpdateOutputMsg {
+                output: Some(Output::FileRef(sym_file)),
+                ..
+            }) => Some(sym_file),
+            _ => None,
+        })
+}
+
+//GLB chunks and buffer views both have to start on 4 byte boundaries.
+fn pad(bytes: &mut Vec<u8>, fill: u8) {
+    let len = (bytes.len() + 3) & !3;
+    bytes.resize(len, fill);
+}
+
+impl Writer {
+    fn push_view(&mut self, bytes: &[u8], target: u32) -> usize {
+        pad(&mut self.buffer, 0);
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        self.buffer.extend_from_slice(bytes);
+        self.buffer_views.len() - 1
+    }
+
+    fn push_accessor(&mut self, accessor: Value) -> usize {
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn push_node(&mut self, node: Value) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn write_mesh(&mut self, obj_id: &str, mesh: &MeshDataMsg) -> Option<usize> {
+        let count = mesh.positions.len() / 3;
+        if count == 0
+            || mesh.indices.is_empty()
+            || mesh.indices.iter().any(|i| *i as usize >= count)
+        {
+            return None;
+        }
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        let mut positions = Vec::with_capacity(count * 12);
+        for (i, coord) in mesh.positions[..count * 3].iter().enumerate() {
+            let coord = *coord as f32;
+            min[i % 3] = min[i % 3].min(coord);
+            max[i % 3] = max[i % 3].max(coord);
+            positions.extend_from_slice(&coord.to_le_bytes());
+        }
+        let mut indices = Vec::with_capacity(mesh.indices.len() * 4);
+        for index in &mesh.indices {
+            indices.extend_from_slice(&(*index as u32).to_le_bytes());
+        }
+
+        let view = self.push_view(&positions, ARRAY_BUFFER);
+        let position_accessor = self.push_accessor(json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": count,
+            "type": "VEC3",
+            "min": min,
+            "max": max,
+        }));
+        let view = self.push_view(&indices, ELEMENT_ARRAY_BUFFER);
+        let index_accessor = self.push_accessor(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": mesh.indices.len(),
+            "type": "SCALAR",
+        }));
+        self.meshes.push(json!({
+            "name": obj_id,
+            "primitives": [{
+                "attributes": { "POSITION": position_accessor },
+                "indices": index_accessor,
+                "mode": TRIANGLES,
+            }],
+        }));
+        Some(self.meshes.len() - 1)
+    }
+
+    fn mesh_node(&mut self, file: &str, obj_id: &str, mesh: &MeshDataMsg) -> Option<usize> {
+        let key = (String::from(file), String::from(obj_id));
+        let mesh_index = match self.mesh_indices.get(&key) {
+            Some(mesh_index) => *mesh_index,
+            None => {
+                let mesh_index = self.write_mesh(obj_id, mesh);
+                self.mesh_indices.insert(key, mesh_index);
+                mesh_index
+            }
+        }?;
+        let mut node = json!({ "name": obj_id, "mesh": mesh_index });
+        if let Some(extras) = extras(&mesh.meta_json) {
+            node["extras"] = extras;
+        }
+        Some(self.push_node(node))
+    }
+
+    fn instance_node(
+        &mut self,
+        reps: &FileReps,
+        file: &str,
+        obj_id: &str,
+        instance: &InstanceDataMsg,
+        open_files: &mut Vec<String>,
+    ) -> usize {
+        let mut children = Vec::new();
+        if let Some(sym_file) = symbol_file(reps, file, &instance.source) {
+            //A symbol file that ends up instancing itself is cut off rather than followed forever.
+            if !open_files.contains(sym_file) {
+                children = self.file_nodes(reps, sym_file, open_files);
+            }
+        }
+        let mut node = json!({ "name": obj_id });
+        if instance.transform.len() == 16 {
+            let matrix: Vec<f32> = instance.transform.iter().map(|val| *val as f32).collect();
+            node["matrix"] = json!(matrix);
+        }
+        if !children.is_empty() {
+            node["children"] = json!(children);
+        }
+        if let Some(extras) = extras(&instance.meta_json) {
+            node["extras"] = extras;
+        }
+        self.push_node(node)
+    }
+
+    fn file_nodes(
+        &mut self,
+        reps: &FileReps,
+        file: &str,
+        open_files: &mut Vec<String>,
+    ) -> Vec<usize> {
+        let mut nodes = Vec::new();
+        open_files.push(String::from(file));
+        for rep in reps.get(file).into_iter().flatten() {
+            let output = rep
+                .update
+                .as_ref()
+                .and_then(|update| update.output.as_ref());
+            match output {
+                Some(Output::Mesh(mesh)) => {
+                    if let Some(node) = self.mesh_node(file, &rep.obj_id, mesh) {
+                        nodes.push(node);
+                    }
+                }
+                Some(Output::Instance(instance)) => {
+                    nodes.push(self.instance_node(reps, file, &rep.obj_id, instance, open_files));
+                }
+                _ => (),
+            }
+        }
+        open_files.pop();
+        nodes
+    }
+
+    fn into_glb(mut self, root: usize) -> Result<Vec<u8>, serde_json::Error> {
+        let mut gltf = json!({
+            "asset": { "version": "2.0", "generator": "kafka-cad" },
+            "scene": 0,
+            "scenes": [{ "nodes": [root] }],
+            "nodes": self.nodes,
+        });
+        if !self.meshes.is_empty() {
+            gltf["meshes"] = json!(self.meshes);
+            gltf["accessors"] = json!(self.accessors);
+            gltf["bufferViews"] = json!(self.buffer_views);
+            pad(&mut self.buffer, 0);
+            gltf["buffers"] = json!([{ "byteLength": self.buffer.len() }]);
+        }
+        let mut json_chunk = serde_json::to_vec(&gltf)?;
+        pad(&mut json_chunk, b' ');
+
+        let mut length = 12 + 8 + json_chunk.len();
+        if !self.buffer.is_empty() {
+            length += 8 + self.buffer.len();
+        }
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+        glb.extend_from_slice(&GLB_VERSION.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+        glb.extend_from_slice(&(json_chunk.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&JSON_CHUNK.to_le_bytes());
+        glb.extend_from_slice(&json_chunk);
+        if !self.buffer.is_empty() {
+            glb.extend_from_slice(&(self.buffer.len() as u32).to_le_bytes());
+            glb.extend_from_slice(&BIN_CHUNK.to_le_bytes());
+            glb.extend_from_slice(&self.buffer);
+        }
+        Ok(glb)
+    }
+}
+
+/// Returns the file as a .glb.  Objects are modelled Z up, so the root node rotates them into
+/// glTF's Y up.
+pub fn export(file: &str, reps: &FileReps) -> Result<Vec<u8>, serde_json::Error> {
+    let mut writer = Writer::default();
+    let children = writer.file_nodes(reps, file, &mut Vec::new());
+    let half_sqrt = std::f64::consts::FRAC_1_SQRT_2;
+    let root = writer.push_node(json!({
+        "name": file,
+        "rotation": [-half_sqrt, 0.0, 0.0, half_sqrt],
+        "children": children,
+    }));
+    writer.into_glb(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rep(file: &str, obj_id: &str, output: Output) -> UpdateChangeMsg {
+        UpdateChangeMsg {
+            file: String::from(file),
+            obj_id: String::from(obj_id),
+            update: Some(UpdateOutputMsg {
+                output: Some(output),
+                views: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn triangle() -> Output {
+        Output::Mesh(MeshDataMsg {
+            positions: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+            indices: vec![0, 1, 2],
+            meta_json: String::from(r#"{"type":"Mesh"}"#),
+        })
+    }
+
+    fn instance(source: &str, x: f64, meta_json: &str) -> Output {
+        let mut transform = vec![0.0; 16];
+        transform[0] = 1.0;
+        transform[5] = 1.0;
+        transform[10] = 1.0;
+        transform[12] = x;
+        transform[15] = 1.0;
+        Output::Instance(InstanceDataMsg {
+            transform,
+            source: String::from(source),
+            meta_json: String::from(meta_json),
+            ..Default::default()
+        })
+    }
+
+    fn read_u32(glb: &[u8], at: usize) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&glb[at..at + 4]);
+        u32::from_le_bytes(bytes)
+    }
+
+    //Checks the chunk layout, and returns the JSON and the binary buffer.
+    fn parse(glb: &[u8]) -> (Value, Vec<u8>) {
+        assert_eq!(read_u32(glb, 0), GLB_MAGIC);
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(read_u32(glb, 4), 2);
+        assert_eq!(read_u32(glb, 8) as usize, glb.len());
+
+        let json_len = read_u32(glb, 12) as usize;
+        assert_eq!(read_u32(glb, 16), JSON_CHUNK);
+        assert_eq!(&glb[16..20], b"JSON");
+        assert_eq!(json_len % 4, 0);
+        let json_chunk = &glb[20..20 + json_len];
+        let gltf: Value = serde_json::from_slice(json_chunk).unwrap();
+
+        let bin_start = 20 + json_len;
+        if bin_start == glb.len() {
+            return (gltf, Vec::new());
+        }
+        let bin_len = read_u32(glb, bin_start) as usize;
+        assert_eq!(read_u32(glb, bin_start + 4), BIN_CHUNK);
+        assert_eq!(&glb[bin_start + 4..bin_start + 7], b"BIN");
+        assert_eq!(bin_len % 4, 0);
+        assert_eq!(bin_start + 8 + bin_len, glb.len());
+        (gltf, glb[bin_start + 8..].to_vec())
+    }
+
+    fn node<'a>(gltf: &'a Value, name: &str) -> &'a Value {
+        gltf["nodes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|node| node["name"] == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_header_and_chunks() {
+        let mut reps = FileReps::new();
+        reps.insert(String::from("file"), vec![rep("file", "tri", triangle())]);
+        let glb = export("file", &reps).unwrap();
+        let (gltf, bin) = parse(&glb);
+
+        assert_eq!(gltf["asset"]["version"], "2.0");
+        assert_eq!(gltf["buffers"][0]["byteLength"], bin.len());
+        //Three float positions, then three int indices
+        assert_eq!(bin.len(), 36 + 12);
+        for view in gltf["bufferViews"].as_array().unwrap() {
+            assert_eq!(view["byteOffset"].as_u64().unwrap() % 4, 0);
+        }
+        assert_eq!(gltf["bufferViews"][0]["target"], ARRAY_BUFFER);
+        assert_eq!(gltf["bufferViews"][1]["target"], ELEMENT_ARRAY_BUFFER);
+        assert_eq!(gltf["accessors"][0]["componentType"], FLOAT);
+        assert_eq!(gltf["accessors"][0]["max"], json!([1.0, 1.0, 0.0]));
+        assert_eq!(gltf["accessors"][1]["componentType"], UNSIGNED_INT);
+        assert_eq!(read_u32(&bin, 36 + 8), 2);
+
+        let tri = node(&gltf, "tri");
+        assert_eq!(tri["mesh"], 0);
+        assert_eq!(tri["extras"]["type"], "Mesh");
+        let root = node(&gltf, "file");
+        assert_eq!(root["children"], json!([0]));
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let glb = export("file", &FileReps::new()).unwrap();
+        let (gltf, bin) = parse(&glb);
+        assert!(bin.is_empty());
+        assert!(gltf.get("buffers").is_none());
+        assert_eq!(gltf["nodes"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_symbol_instances() {
+        let mut reps = FileReps::new();
+        reps.insert(
+            String::from("file"),
+            vec![
+                rep("file", "def", Output::FileRef(String::from("sym"))),
+                rep("file", "first", instance("def", 2.0, r#"{"name":"first"}"#)),
+                rep(
+                    "file",
+                    "second",
+                    instance("def", 5.0, r#"{"name":"second"}"#),
+                ),
+            ],
+        );
+        reps.insert(String::from("sym"), vec![rep("sym", "tri", triangle())]);
+        let glb = export("file", &reps).unwrap();
+        let (gltf, bin) = parse(&glb);
+
+        //The symbol's mesh is written once and shared
+        assert_eq!(gltf["meshes"].as_array().unwrap().len(), 1);
+        assert_eq!(bin.len(), 36 + 12);
+        for (name, x) in &[("first", 2.0), ("second", 5.0)] {
+            let inst = node(&gltf, name);
+            assert_eq!(inst["extras"]["name"], *name);
+            let matrix = inst["matrix"].as_array().unwrap();
+            assert_eq!(matrix.len(), 16);
+            assert_eq!(matrix[12], *x);
+            assert_eq!(matrix[15], 1.0);
+            let children = inst["children"].as_array().unwrap();
+            assert_eq!(children.len(), 1);
+            let child = &gltf["nodes"][children[0].as_u64().unwrap() as usize];
+            assert_eq!(child["mesh"], 0);
+        }
+    }
+
+    #[test]
+    fn test_self_instance() {
+        let mut reps = FileReps::new();
+        reps.insert(
+            String::from("file"),
+            vec![
+                rep("file", "def", Output::FileRef(String::from("sym"))),
+                rep("file", "outer", instance("def", 0.0, "")),
+            ],
+        );
+        reps.insert(
+            String::from("sym"),
+            vec![
+                rep("sym", "tri", triangle()),
+                rep("sym", "self_def", Output::FileRef(String::from("sym"))),
+                rep("sym", "inner", instance("self_def", 1.0, "")),
+            ],
+        );
+        let glb = export("file", &reps).unwrap();
+        let (gltf, _) = parse(&glb);
+
+        let outer = node(&gltf, "outer");
+        assert_eq!(outer["children"].as_array().unwrap().len(), 2);
+        //The symbol is already being written, so the instance of it inside itself is left empty
+        let inner = node(&gltf, "inner");
+        assert!(inner.get("children").is_none());
+        assert!(inner.get("extras").is_none());
+        assert_eq!(inner["matrix"][12], 1.0);
+        assert_eq!(gltf["meshes"].as_array().unwrap().len(), 1);
+    }
+}
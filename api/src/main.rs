@@ -6,6 +6,7 @@ use tracing::*;
 use tracing_futures::Instrument;
 
 mod common;
+mod gltf;
 
 mod api {
     tonic::include_proto!("api");
@@ -44,6 +45,27 @@ mod objects {
     tonic::include_proto!("objects");
 }
 
+mod dependencies {
+    tonic::include_proto!("dependencies");
+}
+
+mod spatial_index {
+    tonic::include_proto!("spatial_index");
+}
+
+mod acl {
+    tonic::include_proto!("acl");
+}
+
+mod files {
+    tonic::include_proto!("files");
+}
+use acl::Role;
+
+mod updates {
+    tonic::include_proto!("updates");
+}
+
 fn unavailable<T: std::fmt::Debug>(err: T) -> Status {
     Status::unavailable(format!("Couldn't connect to child service: {:?}", err))
 }
@@ -76,6 +98,53 @@ struct ApiService {
     ops_url: String,
     submit_url: String,
     rep_cache_url: String,
+    dep_url: String,
+    spatial_url: String,
+    updates_url: String,
+    acl_url: String,
+    files_url: String,
+    open_unclaimed: bool,
+}
+
+impl ApiService {
+    /// Checks the authenticated caller has at least the required role on the file, and returns the
+    /// user to act as.  Without authentication there's nobody to check, so the user the request
+    /// claims to be is used as is.
+    async fn authorize(
+        &self,
+        caller: Option<String>,
+        file: &str,
+        user: String,
+        required: Role,
+    ) -> Result<String, Status> {
+        match caller {
+            Some(caller) => {
+                common::check_role(
+                    self.acl_url.clone(),
+                    self.open_unclaimed,
+                    file,
+                    &caller,
+                    required,
+                )
+                .await?;
+                Ok(caller)
+            }
+            None => Ok(user),
+        }
+    }
+
+    async fn authorized_prefix(
+        &self,
+        caller: Option<String>,
+        prefix_opt: Option<OpPrefixMsg>,
+        required: Role,
+    ) -> Result<Prefix, Status> {
+        let mut prefix = Prefix::new(prefix_opt)?;
+        prefix.user = self
+            .authorize(caller, &prefix.file, prefix.user, required)
+            .await?;
+        Ok(prefix)
+    }
 }
 
 #[tonic::async_trait]
@@ -85,15 +154,22 @@ impl api_server::Api for ApiService {
         &self,
         request: Request<BeginUndoEventInput>,
     ) -> Result<Response<BeginUndoEventOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
         let msg = request.into_inner();
+        let user = self
+            .authorize(caller, &msg.file, msg.user, Role::Editor)
+            .await?;
         let mut undo_client = common::undo_client(self.undo_url.clone()).await?;
         let req = TracedRequest::new(undo::BeginUndoEventInput {
             file: msg.file,
-            user: msg.user,
+            user,
+            label: msg.label,
         });
         let resp = undo_client.begin_undo_event(req).await;
-        trace_response(resp)?;
-        Ok(Response::new(BeginUndoEventOutput {}))
+        let output = trace_response(resp)?;
+        Ok(Response::new(BeginUndoEventOutput {
+            event: output.event,
+        }))
     }
 
     #[instrument]
@@ -101,31 +177,25 @@ impl api_server::Api for ApiService {
         &self,
         request: Request<UndoLatestInput>,
     ) -> Result<Response<UndoLatestOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
         let msg = request.into_inner();
         let mut undo_client = common::undo_client(self.undo_url.clone()).await?;
         let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
-        let prefix = Prefix::new(msg.prefix)?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
         let req = TracedRequest::new(undo::UndoLatestInput {
             file: prefix.file.clone(),
             user: prefix.user.clone(),
+            policy: msg.policy,
         });
         let resp = undo_client.undo_latest(req).await;
-        let changes = trace_response(resp)?;
-        let resp = submit_client
-            .submit_changes(TracedRequest::new(submit::SubmitChangesInput {
-                file: prefix.file,
-                user: prefix.user,
-                offset: prefix.offset,
-                changes: changes.changes,
-            }))
-            .await;
-        let mut output = trace_response(resp)?;
-        match output.offsets.pop() {
-            Some(offset) => Ok(Response::new(UndoLatestOutput { offset })),
-            None => Err(Status::out_of_range(
-                "No offsets received from submit service",
-            )),
-        }
+        let output = trace_response(resp)?;
+        let offset = common::submit_if_changed(&mut submit_client, prefix, output.changes).await?;
+        Ok(Response::new(UndoLatestOutput {
+            offset,
+            conflicts: common::to_conflict_msgs(output.conflicts),
+        }))
     }
 
     #[instrument]
@@ -133,31 +203,84 @@ impl api_server::Api for ApiService {
         &self,
         request: Request<RedoLatestInput>,
     ) -> Result<Response<RedoLatestOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
         let msg = request.into_inner();
         let mut undo_client = common::undo_client(self.undo_url.clone()).await?;
         let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
-        let prefix = Prefix::new(msg.prefix)?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
         let resp = undo_client
             .redo_latest(TracedRequest::new(undo::RedoLatestInput {
                 file: prefix.file.clone(),
                 user: prefix.user.clone(),
+                policy: msg.policy,
             }))
             .await;
-        let changes = trace_response(resp)?;
-        let req = TracedRequest::new(submit::SubmitChangesInput {
-            file: prefix.file,
-            user: prefix.user,
-            offset: prefix.offset,
-            changes: changes.changes,
-        });
-        let resp = submit_client.submit_changes(req).await;
-        let mut output = trace_response(resp)?;
-        match output.offsets.pop() {
-            Some(offset) => Ok(Response::new(RedoLatestOutput { offset })),
-            None => Err(Status::out_of_range(
-                "No offsets received from submit service",
-            )),
-        }
+        let output = trace_response(resp)?;
+        let offset = common::submit_if_changed(&mut submit_client, prefix, output.changes).await?;
+        Ok(Response::new(RedoLatestOutput {
+            offset,
+            conflicts: common::to_conflict_msgs(output.conflicts),
+        }))
+    }
+
+    #[instrument]
+    async fn undo_to(
+        &self,
+        request: Request<UndoToInput>,
+    ) -> Result<Response<UndoToOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let mut undo_client = common::undo_client(self.undo_url.clone()).await?;
+        let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
+        let resp = undo_client
+            .undo_to(TracedRequest::new(undo::UndoToInput {
+                file: prefix.file.clone(),
+                user: prefix.user.clone(),
+                event: msg.event,
+                policy: msg.policy,
+            }))
+            .await;
+        let output = trace_response(resp)?;
+        let offset = common::submit_if_changed(&mut submit_client, prefix, output.changes).await?;
+        Ok(Response::new(UndoToOutput {
+            offset,
+            conflicts: common::to_conflict_msgs(output.conflicts),
+        }))
+    }
+
+    #[instrument]
+    async fn list_undo_history(
+        &self,
+        request: Request<ListUndoHistoryInput>,
+    ) -> Result<Response<ListUndoHistoryOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let user = self
+            .authorize(caller, &msg.file, msg.user, Role::Viewer)
+            .await?;
+        let mut undo_client = common::undo_client(self.undo_url.clone()).await?;
+        let resp = undo_client
+            .list_undo_history(TracedRequest::new(undo::ListUndoHistoryInput {
+                file: msg.file,
+                user,
+            }))
+            .await;
+        let output = trace_response(resp)?;
+        let to_api = |event: undo::UndoEventMsg| UndoEventMsg {
+            event: event.event,
+            label: event.label,
+            timestamp: event.timestamp,
+            obj_ids: event.obj_ids,
+        };
+        Ok(Response::new(ListUndoHistoryOutput {
+            undo_events: output.undo_events.into_iter().map(to_api).collect(),
+            redo_events: output.redo_events.into_iter().map(to_api).collect(),
+        }))
     }
 
     type OpenFileStream = tokio::sync::mpsc::Receiver<Result<OpenFileOutput, Status>>;
@@ -167,7 +290,10 @@ impl api_server::Api for ApiService {
         &self,
         request: Request<OpenFileInput>,
     ) -> Result<Response<Self::OpenFileStream>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
         let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Viewer)
+            .await?;
         let mut rep_cache_client = common::rep_cache_client(self.rep_cache_url.clone()).await?;
         let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
         let resp = obj_client
@@ -203,15 +329,54 @@ impl api_server::Api for ApiService {
         Ok(Response::new(rx))
     }
 
+    type SubscribeFileStream =
+        tokio::sync::mpsc::Receiver<Result<representation::UpdateChangeMsg, Status>>;
+
+    #[instrument]
+    async fn subscribe_file(
+        &self,
+        request: Request<SubscribeFileInput>,
+    ) -> Result<Response<Self::SubscribeFileStream>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let user = self
+            .authorize(caller, &msg.file, msg.user, Role::Viewer)
+            .await?;
+        let mut updates_client = common::updates_client(self.updates_url.clone()).await?;
+        let resp = updates_client
+            .subscribe_file(TracedRequest::new(updates::SubscribeFileInput {
+                file: msg.file,
+                user,
+                replay: msg.replay,
+                since_offset: msg.since_offset,
+            }))
+            .await;
+        let mut stream = trace_response(resp)?;
+        let (mut tx, rx) = tokio::sync::mpsc::channel(100);
+        tokio::spawn(async move {
+            while let Some(rep) = stream.next().await {
+                // Dropping the stream from updates when the client goes away ends the subscription
+                // there too.
+                if tx.send(rep).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(rx))
+    }
+
     #[instrument]
     async fn create_walls(
         &self,
         request: Request<CreateWallsInput>,
     ) -> Result<Response<CreateWallsOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
         let msg = request.into_inner();
         let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
         let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
-        let prefix = Prefix::new(msg.prefix)?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
         let mut walls = Vec::new();
         for wall in msg.walls {
             walls.push(operations::WallMsg {
@@ -257,11 +422,14 @@ impl api_server::Api for ApiService {
         &self,
         request: Request<MoveObjectsInput>,
     ) -> Result<Response<MoveObjectsOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
         let msg = request.into_inner();
         let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
         let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
         let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
-        let prefix = Prefix::new(msg.prefix)?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
 
         let objects = common::get_objects(
             &mut obj_client,
@@ -298,20 +466,45 @@ impl api_server::Api for ApiService {
         &self,
         request: Request<JoinObjectsAtPointInput>,
     ) -> Result<Response<JoinObjectsAtPointOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
         let msg = request.into_inner();
         let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
         let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
         let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
-        let prefix = Prefix::new(msg.prefix)?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
 
-        let mut objects = common::get_objects(
-            &mut obj_client,
-            &prefix.file,
-            vec![msg.first_id, msg.second_id],
-            prefix.offset,
-            true,
-        )
-        .await?;
+        let mut ids = Vec::new();
+        for id in vec![msg.first_id, msg.second_id] {
+            if !id.is_empty() {
+                ids.push(id);
+            }
+        }
+        if ids.len() < 2 {
+            let mut spatial_client = common::spatial_index_client(self.spatial_url.clone()).await?;
+            let resp = spatial_client
+                .nearest(TracedRequest::new(spatial_index::NearestInput {
+                    file: prefix.file.clone(),
+                    offset: prefix.offset,
+                    point: msg.guess.clone(),
+                    count: 3,
+                }))
+                .instrument(info_span!("nearest"))
+                .await;
+            let output = trace_response(resp)?;
+            for hit in output.hits {
+                if ids.len() < 2 && !ids.contains(&hit.obj_id) {
+                    ids.push(hit.obj_id);
+                }
+            }
+            if ids.len() < 2 {
+                return Err(Status::not_found("No objects to join near the guess"));
+            }
+        }
+
+        let mut objects =
+            common::get_objects(&mut obj_client, &prefix.file, ids, prefix.offset, true).await?;
 
         let second_opt = objects.pop();
         let first_opt = objects.pop();
@@ -350,14 +543,77 @@ impl api_server::Api for ApiService {
         &self,
         request: Request<DeleteObjectsInput>,
     ) -> Result<Response<DeleteObjectsOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
         let msg = request.into_inner();
+        let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
+        let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
+        let mut dep_client = common::dependencies_client(self.dep_url.clone()).await?;
         let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
-        let prefix = Prefix::new(msg.prefix)?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
+
+        let mut deleted = msg.obj_ids.clone();
+        let mut modified: Vec<object_state::ObjectMsg> = Vec::new();
+        //Cascaded deletes can have dependents of their own, so keep going until nothing new gets deleted
+        let mut pending = msg.obj_ids;
+        while pending.len() > 0 {
+            let resp = dep_client
+                .get_object_subscribers(TracedRequest::new(
+                    dependencies::GetObjectSubscribersInput {
+                        file: prefix.file.clone(),
+                        offset: prefix.offset,
+                        obj_ids: pending,
+                        transitive: false,
+                    },
+                ))
+                .await;
+            let references = trace_response(resp)?.references;
+            let mut dep_ids = Vec::new();
+            for refer in references {
+                if let Some(owner) = refer.owner {
+                    if !deleted.contains(&owner.id) && !dep_ids.contains(&owner.id) {
+                        dep_ids.push(owner.id);
+                    }
+                }
+            }
+            if dep_ids.len() == 0 {
+                break;
+            }
+            //Dependents already modified by an earlier pass keep those changes
+            let mut dependents = Vec::new();
+            let mut to_get = Vec::new();
+            for dep_id in dep_ids {
+                match modified.iter().position(|obj| obj.id == dep_id) {
+                    Some(index) => dependents.push(modified.remove(index)),
+                    None => to_get.push(dep_id),
+                }
+            }
+            dependents.extend(
+                common::get_objects(&mut obj_client, &prefix.file, to_get, prefix.offset, false)
+                    .await?,
+            );
+            let resp = ops_client
+                .delete_references(TracedRequest::new(operations::DeleteReferencesInput {
+                    obj_ids: deleted.clone(),
+                    dependents,
+                    cascade: msg.cascade,
+                }))
+                .instrument(info_span!("delete_references"))
+                .await;
+            let output = trace_response(resp)?;
+            modified.extend(output.modified);
+            deleted.extend(output.cascaded.iter().cloned());
+            pending = output.cascaded;
+        }
 
         let mut changes = Vec::new();
-        for obj_id in msg.obj_ids {
+        for obj_id in deleted {
             changes.push(common::delete(&prefix.user, obj_id));
         }
+        for obj in modified {
+            changes.push(common::modify(&prefix.user, obj));
+        }
         let offset = common::submit_changes(
             &mut submit_client,
             prefix.file,
@@ -376,8 +632,11 @@ impl api_server::Api for ApiService {
     ) -> Result<Response<CreateSheetOutput>, Status> {
         let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
         let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
+        let caller = auth_lib::authenticated_user(&request);
         let msg = request.into_inner();
-        let prefix = Prefix::new(msg.prefix)?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
         let ops_sheet = operations::CreateSheetInput {
             name: msg.name,
             print_size: msg.print_size,
@@ -418,10 +677,13 @@ impl api_server::Api for ApiService {
         &self,
         request: Request<CreateViewportInput>,
     ) -> Result<Response<CreateViewportOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
         let msg = request.into_inner();
         let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
         let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
-        let prefix = Prefix::new(msg.prefix)?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
         let view_type = match msg.view_type {
             Some(create_viewport_input::ViewType::Top(msg)) => {
                 operations::create_viewport_input::ViewType::Top(msg)
@@ -442,10 +704,10 @@ impl api_server::Api for ApiService {
                 operations::create_viewport_input::ViewType::Bottom(msg)
             }
             Some(create_viewport_input::ViewType::Custom(msg)) => {
-                operations::create_viewport_input::ViewType::Custom(operations::CustomViewMsg {
-                    camera_pos: msg.camera_pos,
-                    target: msg.target,
-                })
+                operations::create_viewport_input::ViewType::Custom(common::to_ops_custom_view(msg))
+            }
+            Some(create_viewport_input::ViewType::SectionId(id)) => {
+                operations::create_viewport_input::ViewType::SectionId(id)
             }
             None => return Err(tonic::Status::invalid_argument("No view type passed in")),
         };
@@ -454,6 +716,9 @@ impl api_server::Api for ApiService {
             view_type: Some(view_type),
             origin: msg.origin,
             scale: msg.scale,
+            size: msg.size,
+            hidden_lines: msg.hidden_lines,
+            center: msg.center,
         };
         let resp = ops_client
             .create_viewport(TracedRequest::new(ops_viewport))
@@ -487,6 +752,834 @@ impl api_server::Api for ApiService {
             )),
         }
     }
+
+    #[instrument]
+    async fn create_section_cut(
+        &self,
+        request: Request<CreateSectionCutInput>,
+    ) -> Result<Response<CreateSectionCutOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
+        let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
+        let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
+        let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
+        let objects = common::get_latest_objects(&mut obj_client, &prefix.file).await?;
+        let resp = ops_client
+            .create_section_cut(TracedRequest::new(operations::CreateSectionCutInput {
+                first_pt: msg.first_pt,
+                second_pt: msg.second_pt,
+                depth: msg.depth,
+                direction: msg.direction,
+                objects,
+            }))
+            .await;
+        let object = trace_response(resp)?;
+        match object.section {
+            Some(obj_msg) => {
+                let id = obj_msg.id.clone();
+                let change = common::add(&prefix.user, obj_msg);
+                let offset = common::submit_changes(
+                    &mut submit_client,
+                    prefix.file,
+                    prefix.user,
+                    prefix.offset,
+                    vec![change],
+                )
+                .await?;
+                Ok(Response::new(CreateSectionCutOutput {
+                    section_id: id,
+                    offset,
+                }))
+            }
+            None => Err(Status::not_found(
+                "No section returned from operations service",
+            )),
+        }
+    }
+
+    #[instrument]
+    async fn create_dimension(
+        &self,
+        request: Request<CreateDimensionInput>,
+    ) -> Result<Response<CreateDimensionOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
+        let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
+        let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
+        let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
+        let mut objects = common::get_objects(
+            &mut obj_client,
+            &prefix.file,
+            vec![msg.first_id, msg.second_id],
+            prefix.offset,
+            true,
+        )
+        .await?;
+        let second_obj = objects.pop();
+        let first_obj = objects.pop();
+        let resp = ops_client
+            .create_dimension(TracedRequest::new(operations::CreateDimensionInput {
+                first_obj,
+                first_index: msg.first_index,
+                second_obj,
+                second_index: msg.second_index,
+                offset: msg.offset,
+                text_style: msg.text_style,
+            }))
+            .await;
+        let object = trace_response(resp)?;
+        match object.dimension {
+            Some(obj_msg) => {
+                let id = obj_msg.id.clone();
+                let change = common::add(&prefix.user, obj_msg);
+                let offset = common::submit_changes(
+                    &mut submit_client,
+                    prefix.file,
+                    prefix.user,
+                    prefix.offset,
+                    vec![change],
+                )
+                .await?;
+                Ok(Response::new(CreateDimensionOutput {
+                    dimension_id: id,
+                    offset,
+                }))
+            }
+            None => Err(Status::not_found(
+                "No dimension returned from operations service",
+            )),
+        }
+    }
+
+    #[instrument]
+    async fn create_text_note(
+        &self,
+        request: Request<CreateTextNoteInput>,
+    ) -> Result<Response<CreateTextNoteOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
+        let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
+        let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
+        let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
+        let leader_target = if msg.leader_id.is_empty() {
+            None
+        } else {
+            common::get_objects(
+                &mut obj_client,
+                &prefix.file,
+                vec![msg.leader_id],
+                prefix.offset,
+                true,
+            )
+            .await?
+            .pop()
+        };
+        let resp = ops_client
+            .create_text_note(TracedRequest::new(operations::CreateTextNoteInput {
+                text: msg.text,
+                leader_target,
+                leader_index: msg.leader_index,
+            }))
+            .await;
+        let object = trace_response(resp)?;
+        match object.note {
+            Some(obj_msg) => {
+                let id = obj_msg.id.clone();
+                let change = common::add(&prefix.user, obj_msg);
+                let offset = common::submit_changes(
+                    &mut submit_client,
+                    prefix.file,
+                    prefix.user,
+                    prefix.offset,
+                    vec![change],
+                )
+                .await?;
+                Ok(Response::new(CreateTextNoteOutput {
+                    note_id: id,
+                    offset,
+                }))
+            }
+            None => Err(Status::not_found(
+                "No note returned from operations service",
+            )),
+        }
+    }
+
+    #[instrument]
+    async fn get_subscribers(
+        &self,
+        request: Request<GetSubscribersInput>,
+    ) -> Result<Response<GetSubscribersOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let mut dep_client = common::dependencies_client(self.dep_url.clone()).await?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Viewer)
+            .await?;
+        let resp = dep_client
+            .get_subscribers(TracedRequest::new(dependencies::GetSubscribersInput {
+                file: prefix.file,
+                offset: prefix.offset,
+                ids: msg.ids,
+                transitive: msg.transitive,
+            }))
+            .await;
+        let output = trace_response(resp)?;
+        Ok(Response::new(GetSubscribersOutput {
+            references: output.references,
+        }))
+    }
+
+    #[instrument]
+    async fn get_object_subscribers(
+        &self,
+        request: Request<GetObjectSubscribersInput>,
+    ) -> Result<Response<GetObjectSubscribersOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let mut dep_client = common::dependencies_client(self.dep_url.clone()).await?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Viewer)
+            .await?;
+        let resp = dep_client
+            .get_object_subscribers(TracedRequest::new(
+                dependencies::GetObjectSubscribersInput {
+                    file: prefix.file,
+                    offset: prefix.offset,
+                    obj_ids: msg.obj_ids,
+                    transitive: msg.transitive,
+                },
+            ))
+            .await;
+        let output = trace_response(resp)?;
+        Ok(Response::new(GetObjectSubscribersOutput {
+            references: output.references,
+        }))
+    }
+
+    #[instrument]
+    async fn get_publishers(
+        &self,
+        request: Request<GetPublishersInput>,
+    ) -> Result<Response<GetPublishersOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let mut dep_client = common::dependencies_client(self.dep_url.clone()).await?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Viewer)
+            .await?;
+        let resp = dep_client
+            .get_publishers(TracedRequest::new(dependencies::GetPublishersInput {
+                file: prefix.file,
+                offset: prefix.offset,
+                obj_ids: msg.obj_ids,
+            }))
+            .await;
+        let output = trace_response(resp)?;
+        Ok(Response::new(GetPublishersOutput {
+            references: output.references,
+        }))
+    }
+
+    #[instrument]
+    async fn pick(&self, request: Request<PickInput>) -> Result<Response<PickOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let mut spatial_client = common::spatial_index_client(self.spatial_url.clone()).await?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Viewer)
+            .await?;
+        let resp = spatial_client
+            .query_ray(TracedRequest::new(spatial_index::QueryRayInput {
+                file: prefix.file,
+                offset: prefix.offset,
+                origin: msg.origin,
+                direction: msg.direction,
+            }))
+            .await;
+        let output = trace_response(resp)?;
+        Ok(Response::new(PickOutput {
+            hits: common::to_hit_msgs(output.hits),
+        }))
+    }
+
+    #[instrument]
+    async fn snap(&self, request: Request<SnapInput>) -> Result<Response<SnapOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let mut spatial_client = common::spatial_index_client(self.spatial_url.clone()).await?;
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Viewer)
+            .await?;
+        let resp = spatial_client
+            .nearest(TracedRequest::new(spatial_index::NearestInput {
+                file: prefix.file,
+                offset: prefix.offset,
+                point: msg.point,
+                count: msg.count,
+            }))
+            .await;
+        let output = trace_response(resp)?;
+        Ok(Response::new(SnapOutput {
+            hits: common::to_hit_msgs(output.hits),
+        }))
+    }
+
+    #[instrument]
+    async fn set_file_role(
+        &self,
+        request: Request<SetFileRoleInput>,
+    ) -> Result<Response<SetFileRoleOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Owner)
+            .await?;
+        let mut acl_client = common::acl_client(self.acl_url.clone()).await?;
+        let resp = acl_client
+            .set_role(TracedRequest::new(acl::SetRoleInput {
+                file: msg.file,
+                user: msg.user,
+                role: msg.role,
+            }))
+            .await;
+        trace_response(resp)?;
+        Ok(Response::new(SetFileRoleOutput {}))
+    }
+
+    #[instrument]
+    async fn list_file_roles(
+        &self,
+        request: Request<ListFileRolesInput>,
+    ) -> Result<Response<ListFileRolesOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Viewer)
+            .await?;
+        let mut acl_client = common::acl_client(self.acl_url.clone()).await?;
+        let resp = acl_client
+            .list_roles(TracedRequest::new(acl::ListRolesInput { file: msg.file }))
+            .await;
+        let output = trace_response(resp)?;
+        Ok(Response::new(ListFileRolesOutput {
+            roles: output.roles,
+        }))
+    }
+
+    #[instrument]
+    async fn create_file(
+        &self,
+        request: Request<CreateFileInput>,
+    ) -> Result<Response<CreateFileOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let owner = caller.unwrap_or(msg.owner);
+        let mut files_client = common::files_client(self.files_url.clone()).await?;
+        let mut acl_client = common::acl_client(self.acl_url.clone()).await?;
+        let resp = files_client
+            .create_file(TracedRequest::new(files::CreateFileInput {
+                name: msg.name,
+                owner: owner.clone(),
+                id: String::new(),
+            }))
+            .await;
+        let file = trace_response(resp)?
+            .file
+            .ok_or_else(|| Status::internal("No file created"))?;
+        let resp = acl_client
+            .claim_file(TracedRequest::new(acl::ClaimFileInput {
+                file: file.id.clone(),
+                user: owner,
+            }))
+            .await;
+        trace_response(resp)?;
+        let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
+        let file = common::to_file_api_msg(&mut obj_client, file).await?;
+        Ok(Response::new(CreateFileOutput { file: Some(file) }))
+    }
+
+    #[instrument]
+    async fn rename_file(
+        &self,
+        request: Request<RenameFileInput>,
+    ) -> Result<Response<RenameFileOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Editor)
+            .await?;
+        let mut files_client = common::files_client(self.files_url.clone()).await?;
+        let resp = files_client
+            .rename_file(TracedRequest::new(files::RenameFileInput {
+                id: msg.file,
+                name: msg.name,
+            }))
+            .await;
+        trace_response(resp)?;
+        Ok(Response::new(RenameFileOutput {}))
+    }
+
+    #[instrument]
+    async fn list_files(
+        &self,
+        request: Request<ListFilesInput>,
+    ) -> Result<Response<ListFilesOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let mut files_client = common::files_client(self.files_url.clone()).await?;
+        let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
+        let resp = files_client
+            .list_files(TracedRequest::new(files::ListFilesInput {
+                include_archived: msg.include_archived,
+            }))
+            .await;
+        let mut files = Vec::new();
+        for file in trace_response(resp)?.files {
+            if let Some(caller) = &caller {
+                let allowed = common::check_role(
+                    self.acl_url.clone(),
+                    self.open_unclaimed,
+                    &file.id,
+                    caller,
+                    Role::Viewer,
+                )
+                .await;
+                match allowed {
+                    Ok(()) => (),
+                    Err(e) if e.code() == tonic::Code::PermissionDenied => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+            files.push(common::to_file_api_msg(&mut obj_client, file).await?);
+        }
+        Ok(Response::new(ListFilesOutput { files }))
+    }
+
+    #[instrument]
+    async fn archive_file(
+        &self,
+        request: Request<ArchiveFileInput>,
+    ) -> Result<Response<ArchiveFileOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Owner)
+            .await?;
+        let mut files_client = common::files_client(self.files_url.clone()).await?;
+        let resp = files_client
+            .archive_file(TracedRequest::new(files::ArchiveFileInput {
+                id: msg.file,
+                archived: msg.archived,
+            }))
+            .await;
+        trace_response(resp)?;
+        Ok(Response::new(ArchiveFileOutput {}))
+    }
+
+    #[instrument]
+    async fn set_file_thumbnail(
+        &self,
+        request: Request<SetFileThumbnailInput>,
+    ) -> Result<Response<SetFileThumbnailOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Editor)
+            .await?;
+        let mut files_client = common::files_client(self.files_url.clone()).await?;
+        let resp = files_client
+            .set_thumbnail(TracedRequest::new(files::SetThumbnailInput {
+                id: msg.file,
+                thumbnail: msg.thumbnail,
+            }))
+            .await;
+        trace_response(resp)?;
+        Ok(Response::new(SetFileThumbnailOutput {}))
+    }
+
+    #[instrument]
+    async fn delete_file(
+        &self,
+        request: Request<DeleteFileInput>,
+    ) -> Result<Response<DeleteFileOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Owner)
+            .await?;
+        let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
+        let resp = obj_client
+            .delete_file(TracedRequest::new(objects::DeleteFileInput {
+                file: msg.file.clone(),
+            }))
+            .await;
+        trace_response(resp)?;
+        let mut dep_client = common::dependencies_client(self.dep_url.clone()).await?;
+        let resp = dep_client
+            .delete_file(TracedRequest::new(dependencies::DeleteFileInput {
+                file: msg.file.clone(),
+            }))
+            .await;
+        trace_response(resp)?;
+        let mut undo_client = common::undo_client(self.undo_url.clone()).await?;
+        let resp = undo_client
+            .delete_file(TracedRequest::new(undo::DeleteFileInput {
+                file: msg.file.clone(),
+            }))
+            .await;
+        trace_response(resp)?;
+        let mut rep_cache_client = common::rep_cache_client(self.rep_cache_url.clone()).await?;
+        let resp = rep_cache_client
+            .delete_file(TracedRequest::new(rep_cache::DeleteFileInput {
+                file: msg.file.clone(),
+            }))
+            .await;
+        trace_response(resp)?;
+        let mut spatial_client = common::spatial_index_client(self.spatial_url.clone()).await?;
+        let resp = spatial_client
+            .delete_file(TracedRequest::new(spatial_index::DeleteFileInput {
+                file: msg.file.clone(),
+            }))
+            .await;
+        trace_response(resp)?;
+        // The metadata and access go last, so a failure part way through leaves the file listed
+        // and the owner can retry.
+        let mut files_client = common::files_client(self.files_url.clone()).await?;
+        let resp = files_client
+            .delete_file(TracedRequest::new(files::DeleteFileInput {
+                id: msg.file.clone(),
+            }))
+            .await;
+        trace_response(resp)?;
+        let mut acl_client = common::acl_client(self.acl_url.clone()).await?;
+        let resp = acl_client
+            .delete_file(TracedRequest::new(acl::DeleteFileInput { file: msg.file }))
+            .await;
+        trace_response(resp)?;
+        Ok(Response::new(DeleteFileOutput {}))
+    }
+
+    #[instrument]
+    async fn export_gltf(
+        &self,
+        request: Request<ExportGltfInput>,
+    ) -> Result<Response<ExportGltfOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Viewer)
+            .await?;
+        let mut rep_cache_client = common::rep_cache_client(self.rep_cache_url.clone()).await?;
+        let reps = common::file_and_symbol_reps(&mut rep_cache_client, &msg.file).await?;
+        let glb = gltf::export(&msg.file, &reps)
+            .map_err(|e| Status::internal(format!("Couldn't write glTF: {}", e)))?;
+        Ok(Response::new(ExportGltfOutput { glb }))
+    }
+
+    #[instrument]
+    async fn export_ifc(
+        &self,
+        request: Request<ExportIfcInput>,
+    ) -> Result<Response<ExportIfcOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Viewer)
+            .await?;
+        let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
+        let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
+        let mut rep_cache_client = common::rep_cache_client(self.rep_cache_url.clone()).await?;
+        let objects = common::get_latest_objects(&mut obj_client, &msg.file).await?;
+        // The representations are only used to find the symbol files, through their definitions.
+        let reps = common::file_and_symbol_reps(&mut rep_cache_client, &msg.file).await?;
+        let mut symbol_files = Vec::new();
+        for sym_file in reps.keys().filter(|sym_file| **sym_file != msg.file) {
+            symbol_files.push(operations::SymbolFileMsg {
+                file: sym_file.clone(),
+                name: String::new(),
+                objects: common::get_latest_objects(&mut obj_client, sym_file).await?,
+            });
+        }
+        let resp = ops_client
+            .export_ifc(TracedRequest::new(operations::ExportIfcInput {
+                name: msg.file,
+                objects,
+                symbol_files,
+            }))
+            .await;
+        let ifc = trace_response(resp)?.ifc;
+        Ok(Response::new(ExportIfcOutput { ifc }))
+    }
+
+    #[instrument]
+    async fn export_dxf(
+        &self,
+        request: Request<ExportDxfInput>,
+    ) -> Result<Response<ExportDxfOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Viewer)
+            .await?;
+        let target = match msg.target {
+            Some(export_dxf_input::Target::SheetId(sheet_id)) => {
+                operations::export_dxf_input::Target::SheetId(sheet_id)
+            }
+            Some(export_dxf_input::Target::Top(msg)) => {
+                operations::export_dxf_input::Target::Top(msg)
+            }
+            Some(export_dxf_input::Target::Front(msg)) => {
+                operations::export_dxf_input::Target::Front(msg)
+            }
+            Some(export_dxf_input::Target::Left(msg)) => {
+                operations::export_dxf_input::Target::Left(msg)
+            }
+            Some(export_dxf_input::Target::Right(msg)) => {
+                operations::export_dxf_input::Target::Right(msg)
+            }
+            Some(export_dxf_input::Target::Back(msg)) => {
+                operations::export_dxf_input::Target::Back(msg)
+            }
+            Some(export_dxf_input::Target::Bottom(msg)) => {
+                operations::export_dxf_input::Target::Bottom(msg)
+            }
+            Some(export_dxf_input::Target::Custom(msg)) => {
+                operations::export_dxf_input::Target::Custom(common::to_ops_custom_view(msg))
+            }
+            Some(export_dxf_input::Target::SectionId(id)) => {
+                operations::export_dxf_input::Target::SectionId(id)
+            }
+            None => return Err(Status::invalid_argument("No view or sheet to export")),
+        };
+        let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
+        let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
+        let objects = common::get_latest_objects(&mut obj_client, &msg.file).await?;
+        let resp = ops_client
+            .export_dxf(TracedRequest::new(operations::ExportDxfInput {
+                objects,
+                target: Some(target),
+                hidden_lines: msg.hidden_lines,
+            }))
+            .await;
+        let dxf = trace_response(resp)?.dxf;
+        Ok(Response::new(ExportDxfOutput { dxf }))
+    }
+
+    #[instrument]
+    async fn render_sheet(
+        &self,
+        request: Request<RenderSheetInput>,
+    ) -> Result<Response<RenderSheetOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Viewer)
+            .await?;
+        let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
+        let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
+        let objects = common::get_latest_objects(&mut obj_client, &msg.file).await?;
+        let resp = ops_client
+            .render_sheet(TracedRequest::new(operations::RenderSheetInput {
+                objects,
+                sheet_id: msg.sheet_id,
+            }))
+            .await;
+        let output = trace_response(resp)?;
+        Ok(Response::new(RenderSheetOutput {
+            svg: output.svg,
+            pdf: output.pdf,
+        }))
+    }
+
+    #[instrument]
+    async fn get_viewport_drawing(
+        &self,
+        request: Request<GetViewportDrawingInput>,
+    ) -> Result<Response<GetViewportDrawingOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        self.authorize(caller, &msg.file, String::new(), Role::Viewer)
+            .await?;
+        let mut obj_client = common::objects_client(self.obj_url.clone()).await?;
+        let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
+        let objects = common::get_latest_objects(&mut obj_client, &msg.file).await?;
+        let resp = ops_client
+            .compose_viewport(TracedRequest::new(operations::ComposeViewportInput {
+                objects,
+                viewport_id: msg.viewport_id,
+            }))
+            .await;
+        let output = trace_response(resp)?;
+        Ok(Response::new(GetViewportDrawingOutput {
+            drawing: output.drawing,
+        }))
+    }
+
+    #[instrument]
+    async fn import_ifc(
+        &self,
+        request: Request<ImportIfcInput>,
+    ) -> Result<Response<ImportIfcOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
+        let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
+        let resp = ops_client
+            .import_ifc(TracedRequest::new(operations::ImportIfcInput {
+                ifc: msg.ifc,
+            }))
+            .await;
+        let import = trace_response(resp)?;
+        let to_counts = |counts: Vec<operations::EntityCountMsg>| {
+            counts
+                .into_iter()
+                .map(|count| EntityCountApiMsg {
+                    entity_type: count.entity_type,
+                    count: count.count,
+                })
+                .collect()
+        };
+        let as_symbols = to_counts(import.as_symbols);
+        let skipped = to_counts(import.skipped);
+        if import.objects.is_empty() {
+            return Ok(Response::new(ImportIfcOutput {
+                obj_ids: Vec::new(),
+                offset: prefix.offset,
+                event: String::new(),
+                as_symbols,
+                skipped,
+            }));
+        }
+
+        //The symbol definitions point at their symbol files, so those are made and filled first.
+        let mut files_client = common::files_client(self.files_url.clone()).await?;
+        let mut acl_client = common::acl_client(self.acl_url.clone()).await?;
+        let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
+        for symbol_file in import.symbol_files {
+            let resp = files_client
+                .create_file(TracedRequest::new(files::CreateFileInput {
+                    name: symbol_file.name,
+                    owner: prefix.user.clone(),
+                    id: symbol_file.file.clone(),
+                }))
+                .await;
+            trace_response(resp)?;
+            let resp = acl_client
+                .claim_file(TracedRequest::new(acl::ClaimFileInput {
+                    file: symbol_file.file.clone(),
+                    user: prefix.user.clone(),
+                }))
+                .await;
+            trace_response(resp)?;
+            let changes = symbol_file
+                .objects
+                .into_iter()
+                .map(|obj| common::add(&prefix.user, obj))
+                .collect();
+            common::submit_changes(
+                &mut submit_client,
+                symbol_file.file,
+                prefix.user.clone(),
+                0,
+                changes,
+            )
+            .await?;
+        }
+
+        //Everything goes in one event, so the whole import can be undone at once.
+        let mut undo_client = common::undo_client(self.undo_url.clone()).await?;
+        let resp = undo_client
+            .begin_undo_event(TracedRequest::new(undo::BeginUndoEventInput {
+                file: prefix.file.clone(),
+                user: prefix.user.clone(),
+                label: String::from("Import IFC"),
+            }))
+            .await;
+        let event = trace_response(resp)?.event;
+        let mut changes = Vec::new();
+        let mut obj_ids = Vec::new();
+        for obj in import.objects {
+            obj_ids.push(obj.id.clone());
+            changes.push(common::add(&prefix.user, obj));
+        }
+        let submitted = common::submit_changes(
+            &mut submit_client,
+            prefix.file.clone(),
+            prefix.user.clone(),
+            prefix.offset,
+            changes,
+        )
+        .await;
+        let offset = match submitted {
+            Ok(offset) => offset,
+            Err(status) => {
+                //Otherwise the empty event would sit on top of the user's undo stack
+                let resp = undo_client
+                    .cancel_undo_event(TracedRequest::new(undo::CancelUndoEventInput {
+                        file: prefix.file,
+                        user: prefix.user,
+                        event,
+                    }))
+                    .await;
+                trace_response(resp)?;
+                return Err(status);
+            }
+        };
+        Ok(Response::new(ImportIfcOutput {
+            obj_ids,
+            offset,
+            event,
+            as_symbols,
+            skipped,
+        }))
+    }
+
+    #[instrument]
+    async fn import_dxf_underlay(
+        &self,
+        request: Request<ImportDxfUnderlayInput>,
+    ) -> Result<Response<ImportDxfUnderlayOutput>, Status> {
+        let caller = auth_lib::authenticated_user(&request);
+        let msg = request.into_inner();
+        let prefix = self
+            .authorized_prefix(caller, msg.prefix, Role::Editor)
+            .await?;
+        let mut ops_client = common::operations_client(self.ops_url.clone()).await?;
+        let resp = ops_client
+            .import_dxf_underlay(TracedRequest::new(operations::ImportDxfUnderlayInput {
+                name: msg.name,
+                dxf: msg.dxf,
+            }))
+            .await;
+        let import = trace_response(resp)?;
+        let underlay = import
+            .underlay
+            .ok_or_else(|| Status::not_found("No underlay returned from operations service"))?;
+        let obj_id = underlay.id.clone();
+        let mut submit_client = common::submit_client(self.submit_url.clone()).await?;
+        let offset = common::submit_changes(
+            &mut submit_client,
+            prefix.file,
+            prefix.user.clone(),
+            prefix.offset,
+            vec![common::add(&prefix.user, underlay)],
+        )
+        .await?;
+        Ok(Response::new(ImportDxfUnderlayOutput {
+            obj_id,
+            offset,
+            skipped: import
+                .skipped
+                .into_iter()
+                .map(|count| EntityCountApiMsg {
+                    entity_type: count.entity_type,
+                    count: count.count,
+                })
+                .collect(),
+        }))
+    }
 }
 
 #[tokio::main]
@@ -498,14 +1591,36 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let ops_url = std::env::var("OPS_URL").unwrap().parse().unwrap();
     let submit_url = std::env::var("SUBMIT_URL").unwrap().parse().unwrap();
     let rep_cache_url = std::env::var("REP_CACHE_URL").unwrap().parse().unwrap();
+    let dep_url = std::env::var("DEPENDENCIES_URL").unwrap().parse().unwrap();
+    let spatial_url = std::env::var("SPATIAL_INDEX_URL").unwrap().parse().unwrap();
+    let updates_url = std::env::var("UPDATES_URL").unwrap().parse().unwrap();
+    let acl_url = std::env::var("ACL_URL").unwrap().parse().unwrap();
+    let files_url = std::env::var("FILES_URL").unwrap().parse().unwrap();
+    let open_unclaimed = match std::env::var("OPEN_UNCLAIMED") {
+        Ok(open) => open.parse().unwrap(),
+        Err(..) => false,
+    };
+    let verifier = auth_lib::Verifier::from_env()?;
+    if verifier.is_none() {
+        println!("No JWT_KEY set, authentication and access control are off");
+    }
     trace_lib::init_tracer(&jaeger_url, "api")?;
-    let svc = api_server::ApiServer::new(ApiService {
-        undo_url,
-        obj_url,
-        ops_url,
-        submit_url,
-        rep_cache_url,
-    });
+    let svc = api_server::ApiServer::with_interceptor(
+        ApiService {
+            undo_url,
+            obj_url,
+            ops_url,
+            submit_url,
+            rep_cache_url,
+            dep_url,
+            spatial_url,
+            updates_url,
+            acl_url,
+            files_url,
+            open_unclaimed,
+        },
+        auth_lib::interceptor(verifier),
+    );
     println!("Running on {:?}", run_url);
     Server::builder()
         .add_service(svc)
@@ -0,0 +1,203 @@
+//! Verifies the signed JWTs clients authenticate with.  The token's subject is the user, so once a
+//! request is verified the user in it can't be set to anybody else.
+
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Metadata key the interceptor puts the verified user under.
+pub const USER_KEY: &str = "x-user";
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("No token given")]
+    MissingToken,
+    #[error("Invalid token: {0}")]
+    TokenError(#[from] jsonwebtoken::errors::Error),
+    #[error("Invalid key configuration: {0}")]
+    ConfigError(String),
+}
+
+impl Into<tonic::Status> for AuthError {
+    fn into(self) -> tonic::Status {
+        let msg = format!("{}", self);
+        let code = match self {
+            AuthError::MissingToken | AuthError::TokenError(..) => tonic::Code::Unauthenticated,
+            AuthError::ConfigError(..) => tonic::Code::Internal,
+        };
+        tonic::Status::new(code, msg)
+    }
+}
+
+pub fn to_status<T: Into<AuthError>>(err: T) -> tonic::Status {
+    let auth_error: AuthError = err.into();
+    auth_error.into()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: usize,
+}
+
+#[derive(Clone)]
+pub struct Verifier {
+    key: DecodingKey<'static>,
+    validation: Validation,
+}
+
+impl Verifier {
+    /// HS* algorithms take the shared secret as the key, RS* and ES* take a PEM encoded public key.
+    pub fn new(key: &str, algorithm: Algorithm) -> Result<Verifier, AuthError> {
+        let key = match algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                DecodingKey::from_secret(key.as_bytes()).into_static()
+            }
+            Algorithm::ES256 | Algorithm::ES384 => {
+                DecodingKey::from_ec_pem(key.as_bytes())?.into_static()
+            }
+            _ => DecodingKey::from_rsa_pem(key.as_bytes())?.into_static(),
+        };
+        Ok(Verifier {
+            key,
+            validation: Validation::new(algorithm),
+        })
+    }
+
+    /// Reads the key from JWT_KEY and the algorithm from JWT_ALGORITHM, which defaults to HS256.
+    /// Returns None if no key is configured, in which case authentication is turned off.
+    pub fn from_env() -> Result<Option<Verifier>, AuthError> {
+        let key = match std::env::var("JWT_KEY") {
+            Ok(key) if !key.is_empty() => key,
+            _ => return Ok(None),
+        };
+        let algorithm = match std::env::var("JWT_ALGORITHM") {
+            Ok(algorithm) => algorithm
+                .parse()
+                .map_err(|_| AuthError::ConfigError(format!("Unknown algorithm {}", algorithm)))?,
+            Err(..) => Algorithm::HS256,
+        };
+        Ok(Some(Verifier::new(&key, algorithm)?))
+    }
+
+    /// Returns the user the token was issued to.  A leading "Bearer " is ignored.
+    pub fn verify(&self, token: &str) -> Result<String, AuthError> {
+        let token = token.trim_start_matches("Bearer ").trim();
+        if token.is_empty() {
+            return Err(AuthError::MissingToken);
+        }
+        let data = decode::<Claims>(token, &self.key, &self.validation)?;
+        Ok(data.claims.sub)
+    }
+}
+
+/// Returns an interceptor that rejects requests without a valid token in their authorization
+/// metadata, and puts the verified user under USER_KEY.  Any USER_KEY the client sent is removed,
+/// and with no verifier that's all it does.
+pub fn interceptor(
+    verifier: Option<Verifier>,
+) -> impl Fn(tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status> + Send + Sync + 'static
+{
+    move |mut request: tonic::Request<()>| {
+        request.metadata_mut().remove(USER_KEY);
+        if let Some(verifier) = &verifier {
+            let token = request
+                .metadata()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok())
+                .ok_or(AuthError::MissingToken)
+                .map_err(to_status)?;
+            let user = verifier.verify(token).map_err(to_status)?;
+            let value = tonic::metadata::MetadataValue::from_str(&user)
+                .map_err(|_| tonic::Status::unauthenticated("Invalid user in token"))?;
+            request.metadata_mut().insert(USER_KEY, value);
+        }
+        Ok(request)
+    }
+}
+
+/// The key is left out so it doesn't end up in logs.
+impl std::fmt::Debug for Verifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Verifier")
+            .field("algorithms", &self.validation.algorithms)
+            .finish()
+    }
+}
+
+/// Returns the user the interceptor verified, or None if authentication is turned off.
+pub fn authenticated_user<T>(request: &tonic::Request<T>) -> Option<String> {
+    request
+        .metadata()
+        .get(USER_KEY)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    fn token(secret: &str, sub: &str, exp: usize) -> String {
+        let claims = Claims {
+            sub: String::from(sub),
+            exp,
+        };
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    const FAR_FUTURE: usize = 4_000_000_000;
+
+    #[test]
+    fn test_verify() {
+        let verifier = Verifier::new("secret", Algorithm::HS256).unwrap();
+        let valid = token("secret", "user_1", FAR_FUTURE);
+        assert_eq!(verifier.verify(&valid).unwrap(), "user_1");
+        assert_eq!(
+            verifier.verify(&format!("Bearer {}", valid)).unwrap(),
+            "user_1"
+        );
+        assert!(verifier
+            .verify(&token("other", "user_1", FAR_FUTURE))
+            .is_err());
+        assert!(verifier.verify(&token("secret", "user_1", 1)).is_err());
+        assert!(verifier.verify("").is_err());
+    }
+
+    #[test]
+    fn test_interceptor() {
+        let verifier = Verifier::new("secret", Algorithm::HS256).unwrap();
+        let intercept = interceptor(Some(verifier));
+
+        let mut request = tonic::Request::new(());
+        request
+            .metadata_mut()
+            .insert(USER_KEY, "someone_else".parse().unwrap());
+        assert!(intercept(request).is_err());
+
+        let mut request = tonic::Request::new(());
+        let bearer = format!("Bearer {}", token("secret", "user_1", FAR_FUTURE));
+        request
+            .metadata_mut()
+            .insert("authorization", bearer.parse().unwrap());
+        request
+            .metadata_mut()
+            .insert(USER_KEY, "someone_else".parse().unwrap());
+        let request = intercept(request).unwrap();
+        assert_eq!(authenticated_user(&request), Some(String::from("user_1")));
+
+        let intercept = interceptor(None);
+        let mut request = tonic::Request::new(());
+        request
+            .metadata_mut()
+            .insert(USER_KEY, "someone_else".parse().unwrap());
+        let request = intercept(request).unwrap();
+        assert_eq!(authenticated_user(&request), None);
+    }
+}
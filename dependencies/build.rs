@@ -1,6 +1,6 @@
 fn main() {
     tonic_build::configure()
-        .build_client(false)
+        .build_client(true)
         .build_server(true)
         .compile(
             &[
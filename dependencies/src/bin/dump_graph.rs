@@ -0,0 +1,43 @@
+//! Prints the dependency graph of a file for debugging.
+//! Usage: dump_graph <dependencies url> <file> [offset] [dot|json]
+//! The offset defaults to the latest, and the format defaults to DOT, so the output can be piped straight into `dot -Tsvg`.
+pub mod geom {
+    tonic::include_proto!("geom");
+}
+
+pub mod object_state {
+    tonic::include_proto!("object_state");
+}
+
+mod dependencies {
+    tonic::include_proto!("dependencies");
+}
+
+use dependencies::*;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let usage = "Usage: dump_graph <dependencies url> <file> [offset] [dot|json]";
+    let url = args.next().ok_or(usage)?;
+    let file = args.next().ok_or(usage)?;
+    let offset = match args.next() {
+        Some(offset) => offset.parse()?,
+        None => std::i64::MAX,
+    };
+    let format = match args.next().as_deref() {
+        None | Some("dot") => dump_graph_input::GraphFormat::Dot,
+        Some("json") => dump_graph_input::GraphFormat::Json,
+        Some(other) => return Err(format!("Unknown format {}.  {}", other, usage).into()),
+    };
+    let mut client = dependencies_client::DependenciesClient::connect(url).await?;
+    let response = client
+        .dump_graph(tonic::Request::new(DumpGraphInput {
+            file,
+            offset,
+            format: format as i32,
+        }))
+        .await?;
+    println!("{}", response.into_inner().graph);
+    Ok(())
+}
@@ -7,6 +7,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, VecDeque};
 use thiserror::Error;
 
+use crate::graph::*;
+
 pub mod geom {
     tonic::include_proto!("geom");
 }
@@ -56,6 +58,8 @@ pub enum DepError {
     DatabaseError(#[from] redis::RedisError),
     #[error("Bincode error: {0}")]
     BincodeError(#[from] bincode::Error),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
 }
 
 impl Into<tonic::Status> for DepError {
@@ -64,6 +68,7 @@ impl Into<tonic::Status> for DepError {
         let code = match self {
             DepError::DatabaseError(..)
             | DepError::BincodeError(..)
+            | DepError::JsonError(..)
             | DepError::ProstEncodeError(..)
             | DepError::ProstDecodeError(..) => tonic::Code::Internal,
         };
@@ -78,7 +83,7 @@ pub fn to_status<T: Into<DepError>>(err: T) -> tonic::Status {
 
 ///Anti-corruption layer.  Also, RefIdMsg doesn't implement Hash by default.
 #[derive(Debug, Clone, Hash, Serialize, Deserialize, PartialEq, Eq)]
-struct RefID {
+pub(crate) struct RefID {
     id: String,
     ref_type: i32,
     index: u64,
@@ -123,35 +128,153 @@ fn ref_id_subscribers(file: &str, ref_id: &RefID) -> String {
     format!("{}:{:?}:subs", file, ref_id)
 }
 
+///The versions of an object's references, latest first.
 fn obj_refs(file: &str, obj: &str) -> String {
+    format!("{}:{}:refs", file, obj)
+}
+
+///Where an object's latest references used to be kept as a single value.  They're moved into obj_refs the next time
+/// the object's references are stored, and read from here until then.
+fn legacy_obj_refs(file: &str, obj: &str) -> String {
     format!("{}:{}:deps", file, obj)
 }
 
+///The set of RefIDs on an object that have a subscriber set stored for them.
+fn obj_publishers(file: &str, obj: &str) -> String {
+    format!("{}:{}:pubs", file, obj)
+}
+
+///The type name of an object, taken from its serialized data.
+fn obj_type(file: &str, obj: &str) -> String {
+    format!("{}:{}:type", file, obj)
+}
+
+///Every object that is currently in the file.
+fn file_objs(file: &str) -> String {
+    format!("{}:objs", file)
+}
+
+///Every object of a type that is currently in the file.
+fn type_objs(file: &str, type_name: &str) -> String {
+    format!("{}:types:{}", file, type_name)
+}
+
+///How many changes have been applied to the file.  Offsets are shared with other files, so this is what history is
+/// counted in when cleaning up.
+fn file_changes(file: &str) -> String {
+    format!("{}:changes", file)
+}
+
+///RefIDs whose latest subscriber set is empty, scored by the change that emptied it.
+fn emptied_subs(file: &str) -> String {
+    format!("{}:emptied", file)
+}
+
+///Deleted objects, scored by the change that deleted them.
+fn deleted_objs(file: &str) -> String {
+    format!("{}:deleted", file)
+}
+
+impl RefID {
+    fn label(&self) -> String {
+        match ref_id_msg::RefType::from_i32(self.ref_type) {
+            Some(ref_type) => format!("{}>{:?}>{}", self.id, ref_type, self.index),
+            None => format!("{}>{}>{}", self.id, self.ref_type, self.index),
+        }
+    }
+}
+
+///Object data is internally tagged by typetag, so it starts with a "type" entry holding the type name.  Bincode lays
+/// that out as the map length followed by the key and the name, so the name can be read without knowing the object
+/// definitions.
+fn get_type_name(obj_data: &[u8]) -> Option<String> {
+    match bincode::deserialize::<(u64, String, String)>(obj_data) {
+        Ok((_, tag, type_name)) if tag == "type" => Some(type_name),
+        _ => None,
+    }
+}
+
+async fn store_obj_info(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    object: &ObjectMsg,
+) -> Result<(), DepError> {
+    conn.sadd(file_objs(file), &object.id).await?;
+    conn.zrem(deleted_objs(file), &object.id).await?;
+    if let Some(type_name) = get_type_name(&object.obj_data) {
+        conn.sadd(type_objs(file, &type_name), &object.id).await?;
+        conn.set(obj_type(file, &object.id), type_name).await?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Subscribers {
     offset: i64,
     subs: HashSet<RefID>,
 }
 
+///How many versions of each subscriber set and object reference list are kept when none is configured.
+pub const DEFAULT_HISTORY: usize = 5;
+
+async fn get_change_count(conn: &mut MultiplexedConnection, file: &str) -> Result<i64, DepError> {
+    let count: Option<i64> = conn.get(file_changes(file)).await?;
+    Ok(count.unwrap_or(0))
+}
+
+///Empty sets are stored like any other version, so offsets before the last subscriber let go still see it.  They're
+/// noted in emptied_subs so the list can be removed once that's out of the history, see purge_tombstones.
 async fn update_ref_id_subscribers(
     conn: &mut MultiplexedConnection,
     file: &str,
     ref_id: &RefID,
     offset: i64,
     subs: HashSet<RefID>,
+    history: usize,
 ) -> Result<(), DepError> {
     debug!(
         "Updating subs {:?} for ref ID {:?} in file {}",
         subs, ref_id, file
     );
     let ref_id_subscribers = ref_id_subscribers(file, ref_id);
+    let obj_publishers = obj_publishers(file, &ref_id.id);
+    if subs.is_empty() {
+        let change = get_change_count(conn, file).await?;
+        conn.zadd(emptied_subs(file), bincode::serialize(ref_id)?, change)
+            .await?;
+    } else {
+        conn.zrem(emptied_subs(file), bincode::serialize(ref_id)?)
+            .await?;
+    }
     let entry = Subscribers { offset, subs };
     let serialized_subs = bincode::serialize(&entry)?;
     //Push to the left so the latest is first in the list
     conn.lpush(&ref_id_subscribers, serialized_subs).await?;
+    conn.ltrim(&ref_id_subscribers, 0, history.max(1) as isize - 1)
+        .await?;
+    conn.sadd(&obj_publishers, bincode::serialize(ref_id)?)
+        .await?;
     Ok(())
 }
 
+async fn get_obj_publishers(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    obj_id: &str,
+) -> Result<Vec<RefID>, DepError> {
+    debug!(
+        "Getting published ref IDs for object {} in file {}",
+        obj_id, file
+    );
+    let obj_publishers = obj_publishers(file, obj_id);
+    let serialized: Vec<Vec<u8>> = conn.smembers(&obj_publishers).await?;
+    let mut results = Vec::new();
+    for raw_bytes in serialized {
+        results.push(bincode::deserialize(&raw_bytes)?);
+    }
+    Ok(results)
+}
+
 async fn get_ref_id_subs(
     conn: &mut MultiplexedConnection,
     file: &str,
@@ -160,9 +283,9 @@ async fn get_ref_id_subs(
 ) -> Result<HashSet<RefID>, DepError> {
     debug!("Getting subs for ref ID {:?} in file {}", ref_id, file);
     let ref_id_subscribers = ref_id_subscribers(file, ref_id);
-    let cache_length: u64 = conn.llen(&ref_id_subscribers).await?;
-    for i in 0isize..cache_length as isize {
-        let raw_bytes: Vec<u8> = conn.lindex(&ref_id_subscribers, i).await?;
+    //The list is trimmed to the retained history, so pulling it in one go is cheap
+    let entries: Vec<Vec<u8>> = conn.lrange(&ref_id_subscribers, 0, -1).await?;
+    for raw_bytes in entries {
         let entry: Subscribers = bincode::deserialize(&raw_bytes)?;
         if entry.offset <= before_or_equal {
             return Ok(entry.subs);
@@ -177,12 +300,25 @@ struct ObjRefs {
     serialized_refs: Vec<u8>,
 }
 
+async fn get_legacy_obj_refs(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    obj_id: &str,
+) -> Result<Option<ObjRefs>, DepError> {
+    let raw: Option<Vec<u8>> = conn.get(legacy_obj_refs(file, obj_id)).await?;
+    match raw {
+        Some(raw_bytes) => Ok(Some(bincode::deserialize(&raw_bytes)?)),
+        None => Ok(None),
+    }
+}
+
 async fn store_obj_refs(
     conn: &mut MultiplexedConnection,
     file: &str,
     obj_id: &str,
     refs: &DependenciesMsg,
     offset: i64,
+    history: usize,
 ) -> Result<(), DepError> {
     debug!("Storing refs for object {} from file {}", obj_id, file);
     let obj_refs = obj_refs(file, obj_id);
@@ -192,7 +328,13 @@ async fn store_obj_refs(
         offset,
         serialized_refs,
     };
-    conn.set(&obj_refs, bincode::serialize(&refs)?).await?;
+    if let Some(legacy) = get_legacy_obj_refs(conn, file, obj_id).await? {
+        conn.rpush(&obj_refs, bincode::serialize(&legacy)?).await?;
+        conn.del(legacy_obj_refs(file, obj_id)).await?;
+    }
+    //Push to the left so the latest is first in the list, same as the subscriber sets
+    conn.lpush(&obj_refs, bincode::serialize(&refs)?).await?;
+    conn.ltrim(&obj_refs, 0, history.max(1) as isize - 1).await?;
     Ok(())
 }
 
@@ -203,10 +345,13 @@ async fn get_obj_refs(
 ) -> Result<Option<(i64, DependenciesMsg)>, DepError> {
     debug!("Getting refs for object {} from file {}", obj_id, file);
     let obj_refs = obj_refs(file, obj_id);
-    let refs: Option<Vec<u8>> = conn.get(&obj_refs).await?;
-    match refs {
-        Some(raw_bytes) => {
-            let obj_refs: ObjRefs = bincode::deserialize(&raw_bytes)?;
+    let refs: Option<Vec<u8>> = conn.lindex(&obj_refs, 0).await?;
+    let latest = match refs {
+        Some(raw_bytes) => Some(bincode::deserialize::<ObjRefs>(&raw_bytes)?),
+        None => get_legacy_obj_refs(conn, file, obj_id).await?,
+    };
+    match latest {
+        Some(obj_refs) => {
             let deserialized = DependenciesMsg::decode(obj_refs.serialized_refs.as_ref())?;
             Ok(Some((obj_refs.offset, deserialized)))
         }
@@ -214,6 +359,34 @@ async fn get_obj_refs(
     }
 }
 
+async fn get_obj_refs_at(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    obj_id: &str,
+    before_or_equal: i64,
+) -> Result<Option<DependenciesMsg>, DepError> {
+    debug!(
+        "Getting refs for object {} from file {} at offset {}",
+        obj_id, file, before_or_equal
+    );
+    let obj_refs = obj_refs(file, obj_id);
+    let entries: Vec<Vec<u8>> = conn.lrange(&obj_refs, 0, -1).await?;
+    let mut versions = Vec::new();
+    for raw_bytes in entries {
+        versions.push(bincode::deserialize::<ObjRefs>(&raw_bytes)?);
+    }
+    if versions.is_empty() {
+        versions.extend(get_legacy_obj_refs(conn, file, obj_id).await?);
+    }
+    for entry in versions {
+        if entry.offset <= before_or_equal {
+            let deserialized = DependenciesMsg::decode(entry.serialized_refs.as_ref())?;
+            return Ok(Some(deserialized));
+        }
+    }
+    Ok(None)
+}
+
 pub enum DepChange {
     Add,
     Modify,
@@ -297,6 +470,7 @@ async fn add_deps(
     obj_id: &str,
     deps: &DependenciesMsg,
     offset: i64,
+    history: usize,
 ) -> Result<(), DepError> {
     debug!(
         "Adding dependencies {:#?} for object {} from file {}",
@@ -309,9 +483,9 @@ async fn add_deps(
                 .await?;
         }
     }
-    store_obj_refs(conn, file, obj_id, deps, offset).await?;
+    store_obj_refs(conn, file, obj_id, deps, offset, history).await?;
     for (ref_id, subs) in changed_subs {
-        update_ref_id_subscribers(conn, file, &ref_id, offset, subs).await?;
+        update_ref_id_subscribers(conn, file, &ref_id, offset, subs, history).await?;
     }
     Ok(())
 }
@@ -322,6 +496,7 @@ async fn modify_deps(
     obj_id: &str,
     deps: &DependenciesMsg,
     offset: i64,
+    history: usize,
 ) -> Result<(), DepError> {
     debug!(
         "Modifying dependencies {:?} for object {} from file {}",
@@ -361,24 +536,34 @@ async fn modify_deps(
             }
         }
     }
-    store_obj_refs(conn, file, &obj_id, &deps, offset).await?;
+    store_obj_refs(conn, file, &obj_id, &deps, offset, history).await?;
     for (ref_id, subs) in changed_subs {
-        update_ref_id_subscribers(conn, file, &ref_id, offset, subs).await?;
+        update_ref_id_subscribers(conn, file, &ref_id, offset, subs, history).await?;
     }
     Ok(())
 }
 
+///Removes the object from the subscriber sets of everything it referenced, and stores an empty version of its
+/// references.  Its keys stay readable at earlier offsets until purge_tombstones removes them.
 async fn delete_deps(
     conn: &mut MultiplexedConnection,
     file: &str,
     obj_id: &str,
     deps: &DependenciesMsg,
     offset: i64,
+    history: usize,
 ) -> Result<(), DepError> {
     debug!(
         "Deleting dependencies {:?} for object {} from file {}",
         deps, obj_id, file
     );
+    conn.srem(file_objs(file), obj_id).await?;
+    let type_name: Option<String> = conn.get(obj_type(file, obj_id)).await?;
+    if let Some(type_name) = type_name {
+        conn.srem(type_objs(file, &type_name), obj_id).await?;
+    }
+    let change = get_change_count(conn, file).await?;
+    conn.zadd(deleted_objs(file), obj_id, change).await?;
     let mut changed_subs = HashMap::new();
     for refer_opt in &deps.references {
         if let Some(refer) = &refer_opt.reference {
@@ -393,19 +578,71 @@ async fn delete_deps(
             .await?;
         }
     }
-    let mut delete_deps = DependenciesMsg {
-        references: Vec::with_capacity(deps.references.len()),
-    };
-    delete_deps
-        .references
-        .resize_with(deps.references.len(), || OptionReferenceMsg {
-            reference: None,
-        });
-    store_obj_refs(conn, file, obj_id, &delete_deps, offset).await?;
+    store_obj_refs(
+        conn,
+        file,
+        obj_id,
+        &DependenciesMsg::default(),
+        offset,
+        history,
+    )
+    .await?;
     for (ref_id, subs) in changed_subs {
-        update_ref_id_subscribers(conn, file, &ref_id, offset, subs).await?;
+        update_ref_id_subscribers(conn, file, &ref_id, offset, subs, history).await?;
     }
+    Ok(())
+}
 
+///Removes the subscriber sets that were emptied, and all the keys of objects that were deleted, at least history
+/// changes ago.  Every offset since then sees them empty, and anything before is out of the history anyway.  Sets that got
+/// subscribers again, and objects that were added back, were taken off the lists when that happened.
+async fn purge_tombstones(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    before_or_equal: i64,
+) -> Result<(), DepError> {
+    let emptied: Vec<Vec<u8>> = conn
+        .zrangebyscore(emptied_subs(file), "-inf", before_or_equal)
+        .await?;
+    for raw_bytes in emptied {
+        let ref_id: RefID = bincode::deserialize(&raw_bytes)?;
+        debug!("Purging subs for ref ID {:?} in file {}", ref_id, file);
+        conn.del(ref_id_subscribers(file, &ref_id)).await?;
+        conn.srem(obj_publishers(file, &ref_id.id), raw_bytes.as_slice())
+            .await?;
+        conn.zrem(emptied_subs(file), raw_bytes.as_slice()).await?;
+    }
+    let deleted: Vec<String> = conn
+        .zrangebyscore(deleted_objs(file), "-inf", before_or_equal)
+        .await?;
+    for obj_id in deleted {
+        debug!("Purging keys for object {} in file {}", obj_id, file);
+        let type_name: Option<String> = conn.get(obj_type(file, &obj_id)).await?;
+        let publishers: Vec<Vec<u8>> = conn.smembers(obj_publishers(file, &obj_id)).await?;
+        //Everything since the object was deleted sees it without subscribers, so its sets go along with it
+        let mut keys = vec![
+            obj_refs(file, &obj_id),
+            legacy_obj_refs(file, &obj_id),
+            obj_publishers(file, &obj_id),
+            obj_type(file, &obj_id),
+        ];
+        for raw_bytes in &publishers {
+            let ref_id: RefID = bincode::deserialize(raw_bytes)?;
+            keys.push(ref_id_subscribers(file, &ref_id));
+        }
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(keys).ignore();
+        if let Some(type_name) = type_name {
+            pipe.srem(type_objs(file, &type_name), &obj_id).ignore();
+        }
+        if !publishers.is_empty() {
+            pipe.zrem(emptied_subs(file), publishers).ignore();
+        }
+        pipe.zrem(deleted_objs(file), &obj_id)
+            .ignore()
+            .query_async::<_, ()>(conn)
+            .await?;
+    }
     Ok(())
 }
 
@@ -414,38 +651,48 @@ async fn update_deps_inner(
     file: &str,
     offset: i64,
     change: ChangeMsg,
+    history: usize,
 ) -> Result<(), DepError> {
     if let Some(change_type) = change.change_type {
         match change_type {
             change_msg::ChangeType::Add(object) => {
+                store_obj_info(conn, file, &object).await?;
                 if let Some(deps) = object.dependencies {
-                    add_deps(conn, file, &object.id, &deps, offset).await?;
+                    add_deps(conn, file, &object.id, &deps, offset, history).await?;
                 }
             }
             change_msg::ChangeType::Modify(object) => {
+                store_obj_info(conn, file, &object).await?;
                 if let Some(deps) = object.dependencies {
-                    modify_deps(conn, file, &object.id, &deps, offset).await?;
+                    modify_deps(conn, file, &object.id, &deps, offset, history).await?;
                 }
             }
             change_msg::ChangeType::Delete(msg) => {
-                let prev_obj_refs = get_obj_refs(conn, file, &msg.id).await?;
-                if let Some((_, deps)) = prev_obj_refs {
-                    delete_deps(conn, file, &msg.id, &deps, offset).await?;
-                }
+                let deps = match get_obj_refs(conn, file, &msg.id).await? {
+                    Some((_, deps)) => deps,
+                    None => DependenciesMsg::default(),
+                };
+                delete_deps(conn, file, &msg.id, &deps, offset, history).await?;
             }
         }
     }
     Ok(())
 }
 
+///Applies a change to the graph.  Only the latest history versions of each subscriber set and object reference
+/// list are kept, so queries at offsets older than that see an empty graph.  Sets left empty and deleted objects are
+/// removed entirely once history more changes have been applied to the file.
 pub async fn update_deps(
     conn: &mut MultiplexedConnection,
     file: &str,
     offset: i64,
     change: &[u8],
+    history: usize,
 ) -> Result<(), DepError> {
     let change_msg = ChangeMsg::decode(change)?;
-    update_deps_inner(conn, file, offset, change_msg).await?;
+    let count: i64 = conn.incr(file_changes(file), 1).await?;
+    update_deps_inner(conn, file, offset, change_msg, history).await?;
+    purge_tombstones(conn, file, count - history.max(1) as i64).await?;
     Ok(())
 }
 
@@ -479,6 +726,113 @@ async fn breadth_first_search(
     Ok(result)
 }
 
+async fn direct_subscribers(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    offset: i64,
+    ref_id: RefID,
+) -> Result<IndexSet<Reference>, DepError> {
+    let mut result = IndexSet::new();
+    let sub_set = get_ref_id_subs(conn, file, &ref_id, offset).await?;
+    for sub in sub_set {
+        result.insert(Reference {
+            owner: sub,
+            other: ref_id.clone(),
+        });
+    }
+    Ok(result)
+}
+
+async fn get_subscribers_inner(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    offset: i64,
+    ref_ids: Vec<RefID>,
+    transitive: bool,
+) -> Result<IndexSet<Reference>, DepError> {
+    let mut result_set = IndexSet::new();
+    for ref_id in ref_ids {
+        let set = if transitive {
+            breadth_first_search(conn, file, offset, ref_id).await?
+        } else {
+            direct_subscribers(conn, file, offset, ref_id).await?
+        };
+        result_set.extend(set);
+    }
+    Ok(result_set)
+}
+
+///Returns the references subscribed to each of ref_ids at the given offset.  If transitive is set,
+/// subscribers of subscribers are followed until the graph is exhausted.
+pub async fn get_subscribers(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    offset: i64,
+    ref_ids: &Vec<RefIdMsg>,
+    transitive: bool,
+) -> Result<Vec<ReferenceMsg>, DepError> {
+    let ref_ids = ref_ids.iter().map(RefID::from).collect();
+    let result_set = get_subscribers_inner(conn, file, offset, ref_ids, transitive).await?;
+    Ok(result_set.into_iter().map(ReferenceMsg::from).collect())
+}
+
+///Returns the references from other objects subscribed to anything published by obj_ids at the given offset.
+/// References between the queried objects themselves are left out.
+pub async fn get_object_subscribers(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    offset: i64,
+    obj_ids: &Vec<String>,
+    transitive: bool,
+) -> Result<Vec<ReferenceMsg>, DepError> {
+    let mut ref_ids = Vec::new();
+    for obj_id in obj_ids {
+        ref_ids.extend(get_obj_publishers(conn, file, obj_id).await?);
+    }
+    let result_set = get_subscribers_inner(conn, file, offset, ref_ids, transitive).await?;
+    let mut results = Vec::new();
+    for refer in result_set {
+        if !obj_ids.contains(&refer.owner.id) {
+            results.push(ReferenceMsg::from(refer));
+        }
+    }
+    Ok(results)
+}
+
+///Returns the references each of obj_ids holds to other objects at the given offset.
+pub async fn get_publishers(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    offset: i64,
+    obj_ids: &Vec<String>,
+) -> Result<Vec<ReferenceMsg>, DepError> {
+    let mut results = Vec::new();
+    for obj_id in obj_ids {
+        if let Some(deps) = get_obj_refs_at(conn, file, obj_id, offset).await? {
+            for refer_opt in deps.references {
+                if let Some(refer) = refer_opt.reference {
+                    if let Some(other) = &refer.other {
+                        if other.id != *obj_id {
+                            results.push(refer);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(results)
+}
+
+///Returns the objects of the type that are currently in the file.
+pub async fn get_objects_of_type(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    type_name: &str,
+) -> Result<Vec<String>, DepError> {
+    let obj_ids = conn.smembers(type_objs(file, type_name)).await?;
+    Ok(obj_ids)
+}
+
 pub async fn get_all_deps(
     conn: &mut MultiplexedConnection,
     file: &str,
@@ -497,3 +851,83 @@ pub async fn get_all_deps(
     }
     Ok(results)
 }
+
+async fn add_graph_node(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    ref_id: &RefID,
+    added: &mut HashSet<RefID>,
+    obj_types: &mut HashMap<String, String>,
+    graph: &mut DepGraph,
+) -> Result<String, DepError> {
+    let label = ref_id.label();
+    if added.insert(ref_id.clone()) {
+        if !obj_types.contains_key(&ref_id.id) {
+            let type_name: Option<String> = conn.get(obj_type(file, &ref_id.id)).await?;
+            obj_types.insert(ref_id.id.clone(), type_name.unwrap_or_default());
+        }
+        graph.nodes.push(GraphNode {
+            label: label.clone(),
+            obj_id: ref_id.id.clone(),
+            obj_type: obj_types[&ref_id.id].clone(),
+        });
+    }
+    Ok(label)
+}
+
+///Builds the whole subscriber graph of the file at the given offset, including objects deleted since.  Only RefIDs
+/// with at least one subscriber, and those subscribers, show up as nodes.  Meant for debugging, as it walks every
+/// object in the file.
+pub async fn get_graph(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    offset: i64,
+) -> Result<DepGraph, DepError> {
+    let mut graph = DepGraph {
+        file: String::from(file),
+        offset,
+        ..Default::default()
+    };
+    //Deleted objects are kept until they're out of the history, so between them they hold everything that was
+    // in the file at any offset still kept.  Those added later have no subscribers yet at the offset.
+    let mut obj_ids: Vec<String> = conn.smembers(file_objs(file)).await?;
+    let deleted: Vec<String> = conn.zrangebyscore(deleted_objs(file), "-inf", "+inf").await?;
+    obj_ids.extend(deleted);
+    obj_ids.sort();
+    obj_ids.dedup();
+    let mut added = HashSet::new();
+    let mut obj_types = HashMap::new();
+    for obj_id in obj_ids {
+        let mut publishers = get_obj_publishers(conn, file, &obj_id).await?;
+        publishers.sort_by_key(|ref_id| (ref_id.ref_type, ref_id.index));
+        for publisher in publishers {
+            let mut subs: Vec<RefID> = get_ref_id_subs(conn, file, &publisher, offset)
+                .await?
+                .into_iter()
+                .collect();
+            if subs.is_empty() {
+                continue;
+            }
+            subs.sort_by(|a, b| (&a.id, a.ref_type, a.index).cmp(&(&b.id, b.ref_type, b.index)));
+            let pub_label =
+                add_graph_node(conn, file, &publisher, &mut added, &mut obj_types, &mut graph)
+                    .await?;
+            for sub in subs {
+                let sub_label =
+                    add_graph_node(conn, file, &sub, &mut added, &mut obj_types, &mut graph)
+                        .await?;
+                graph.edges.push(GraphEdge {
+                    publisher: pub_label.clone(),
+                    subscriber: sub_label,
+                });
+            }
+        }
+    }
+    Ok(graph)
+}
+
+/// Removes everything stored for the file.
+pub async fn delete_file(conn: &mut MultiplexedConnection, file: &str) -> Result<(), DepError> {
+    redis_lib::delete_file(conn, file).await?;
+    Ok(())
+}
@@ -0,0 +1,138 @@
+//! Debug views of the dependency graph.  Nodes are RefIDs labeled as `id>RefType>index`, and edges point from a publisher
+//! to the subscriber that gets notified when it changes.
+use serde::Serialize;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct GraphNode {
+    pub label: String,
+    pub obj_id: String,
+    ///Taken from the object's serialized data.  Empty if the object was never seen with data.
+    pub obj_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct GraphEdge {
+    pub publisher: String,
+    pub subscriber: String,
+}
+
+#[derive(Debug, Default, Serialize, PartialEq)]
+pub struct DepGraph {
+    pub file: String,
+    pub offset: i64,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+fn escape(input: &str) -> String {
+    input.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl DepGraph {
+    ///Renders the graph in Graphviz DOT format, with the RefIDs of each object grouped into a cluster.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph \"{}@{}\" {{", escape(&self.file), self.offset).unwrap();
+        writeln!(out, "    rankdir=LR;").unwrap();
+        let mut clusters: Vec<(&str, &str, Vec<&GraphNode>)> = Vec::new();
+        for node in &self.nodes {
+            match clusters.iter_mut().find(|(id, _, _)| *id == node.obj_id) {
+                Some((_, _, nodes)) => nodes.push(node),
+                None => clusters.push((&node.obj_id, &node.obj_type, vec![node])),
+            }
+        }
+        for (index, (obj_id, obj_type, nodes)) in clusters.iter().enumerate() {
+            writeln!(out, "    subgraph cluster_{} {{", index).unwrap();
+            writeln!(out, "        label=\"{}\\n{}\";", escape(obj_type), escape(obj_id)).unwrap();
+            for node in nodes {
+                writeln!(out, "        \"{}\";", escape(&node.label)).unwrap();
+            }
+            writeln!(out, "    }}").unwrap();
+        }
+        for edge in &self.edges {
+            writeln!(
+                out,
+                "    \"{}\" -> \"{}\";",
+                escape(&edge.publisher),
+                escape(&edge.subscriber)
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_graph() -> DepGraph {
+        DepGraph {
+            file: String::from("file"),
+            offset: 4,
+            nodes: vec![
+                GraphNode {
+                    label: String::from("a>ProfilePoint>0"),
+                    obj_id: String::from("a"),
+                    obj_type: String::from("Wall"),
+                },
+                GraphNode {
+                    label: String::from("b>ProfileLine>0"),
+                    obj_id: String::from("b"),
+                    obj_type: String::from("Door"),
+                },
+                GraphNode {
+                    label: String::from("a>ProfileLine>0"),
+                    obj_id: String::from("a"),
+                    obj_type: String::from("Wall"),
+                },
+            ],
+            edges: vec![
+                GraphEdge {
+                    publisher: String::from("a>ProfilePoint>0"),
+                    subscriber: String::from("a>ProfileLine>0"),
+                },
+                GraphEdge {
+                    publisher: String::from("a>ProfileLine>0"),
+                    subscriber: String::from("b>ProfileLine>0"),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = test_graph().to_dot();
+        let answer = r#"digraph "file@4" {
+    rankdir=LR;
+    subgraph cluster_0 {
+        label="Wall\na";
+        "a>ProfilePoint>0";
+        "a>ProfileLine>0";
+    }
+    subgraph cluster_1 {
+        label="Door\nb";
+        "b>ProfileLine>0";
+    }
+    "a>ProfilePoint>0" -> "a>ProfileLine>0";
+    "a>ProfileLine>0" -> "b>ProfileLine>0";
+}
+"#;
+        assert_eq!(dot, answer);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json = test_graph().to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["offset"], 4);
+        assert_eq!(value["nodes"][1]["obj_type"], "Door");
+        assert_eq!(value["edges"][1]["subscriber"], "b>ProfileLine>0");
+    }
+}
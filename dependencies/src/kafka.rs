@@ -24,6 +24,7 @@ pub enum UpdateError {
 async fn handle_message<M: Message>(
     redis_conn: &mut redis::aio::MultiplexedConnection,
     m: &M,
+    history: usize,
 ) -> Result<(), UpdateError> {
     let partition = m.partition();
     let offset = m.offset();
@@ -34,7 +35,7 @@ async fn handle_message<M: Message>(
         .key()
         .ok_or(UpdateError::FileError { partition, offset })?;
     let file = std::str::from_utf8(file_bytes)?;
-    crate::cache::update_deps(redis_conn, &file, offset, bytes)
+    crate::cache::update_deps(redis_conn, &file, offset, bytes, history)
         .instrument(info_span!("update_deps"))
         .await?;
     Ok(())
@@ -45,6 +46,7 @@ async fn handle_stream(
     brokers: &str,
     group_id: &str,
     topic: &str,
+    history: usize,
 ) -> Result<(), UpdateError> {
     let consumer: StreamConsumer<rdkafka::consumer::DefaultConsumerContext> = ClientConfig::new()
         .set("group.id", group_id)
@@ -65,7 +67,7 @@ async fn handle_stream(
         match crate::get_redis_conn(redis_url).await {
             Ok(mut redis_conn) => match message {
                 Ok(m) => {
-                    if let Err(e) = handle_message(&mut redis_conn, &m)
+                    if let Err(e) = handle_message(&mut redis_conn, &m, history)
                         .instrument(info_span!("handle_message"))
                         .await
                     {
@@ -95,9 +97,15 @@ async fn handle_stream(
     Ok(())
 }
 
-pub async fn update_cache(redis_url: String, brokers: String, group_id: String, topic: String) {
+pub async fn update_cache(
+    redis_url: String,
+    brokers: String,
+    group_id: String,
+    topic: String,
+    history: usize,
+) {
     std::thread::sleep(std::time::Duration::from_secs(30));
-    if let Err(e) = handle_stream(&redis_url, &brokers, &group_id, &topic).await {
+    if let Err(e) = handle_stream(&redis_url, &brokers, &group_id, &topic, history).await {
         error!("{}", e);
     }
 }
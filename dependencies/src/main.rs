@@ -5,6 +5,7 @@ use tracing::*;
 use tracing_futures::Instrument;
 
 mod cache;
+mod graph;
 mod kafka;
 #[cfg(test)]
 mod tests;
@@ -52,6 +53,120 @@ impl dependencies_server::Dependencies for DepsService {
             .map_err(to_status)?;
         Ok(Response::new(GetAllDependenciesOutput { references }))
     }
+
+    #[instrument]
+    async fn get_subscribers(
+        &self,
+        request: Request<GetSubscribersInput>,
+    ) -> Result<Response<GetSubscribersOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        info!("Get subscribers: {:?}", msg);
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let references = cache::get_subscribers(
+            &mut redis_conn,
+            &msg.file,
+            msg.offset,
+            &msg.ids,
+            msg.transitive,
+        )
+        .instrument(info_span!("get_subscribers"))
+        .await
+        .map_err(to_status)?;
+        Ok(Response::new(GetSubscribersOutput { references }))
+    }
+
+    #[instrument]
+    async fn get_object_subscribers(
+        &self,
+        request: Request<GetObjectSubscribersInput>,
+    ) -> Result<Response<GetObjectSubscribersOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        info!("Get object subscribers: {:?}", msg);
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let references = cache::get_object_subscribers(
+            &mut redis_conn,
+            &msg.file,
+            msg.offset,
+            &msg.obj_ids,
+            msg.transitive,
+        )
+        .instrument(info_span!("get_object_subscribers"))
+        .await
+        .map_err(to_status)?;
+        Ok(Response::new(GetObjectSubscribersOutput { references }))
+    }
+
+    #[instrument]
+    async fn get_publishers(
+        &self,
+        request: Request<GetPublishersInput>,
+    ) -> Result<Response<GetPublishersOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        info!("Get publishers: {:?}", msg);
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let references =
+            cache::get_publishers(&mut redis_conn, &msg.file, msg.offset, &msg.obj_ids)
+                .instrument(info_span!("get_publishers"))
+                .await
+                .map_err(to_status)?;
+        Ok(Response::new(GetPublishersOutput { references }))
+    }
+
+    #[instrument]
+    async fn get_objects_of_type(
+        &self,
+        request: Request<GetObjectsOfTypeInput>,
+    ) -> Result<Response<GetObjectsOfTypeOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        info!("Get objects of type: {:?}", msg);
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let obj_ids = cache::get_objects_of_type(&mut redis_conn, &msg.file, &msg.type_name)
+            .instrument(info_span!("get_objects_of_type"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(GetObjectsOfTypeOutput { obj_ids }))
+    }
+
+    #[instrument]
+    async fn dump_graph(
+        &self,
+        request: Request<DumpGraphInput>,
+    ) -> Result<Response<DumpGraphOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        info!("Dump graph: {:?}", msg);
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let dep_graph = cache::get_graph(&mut redis_conn, &msg.file, msg.offset)
+            .instrument(info_span!("get_graph"))
+            .await
+            .map_err(to_status)?;
+        let graph = match dump_graph_input::GraphFormat::from_i32(msg.format) {
+            Some(dump_graph_input::GraphFormat::Json) => {
+                dep_graph.to_json().map_err(to_status)?
+            }
+            _ => dep_graph.to_dot(),
+        };
+        Ok(Response::new(DumpGraphOutput { graph }))
+    }
+
+    #[instrument]
+    async fn delete_file(
+        &self,
+        request: Request<DeleteFileInput>,
+    ) -> Result<Response<DeleteFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        cache::delete_file(&mut redis_conn, &msg.file)
+            .instrument(info_span!("delete_file"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(DeleteFileOutput {}))
+    }
 }
 
 #[tokio::main]
@@ -62,8 +177,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let broker = std::env::var("BROKER").unwrap();
     let group = std::env::var("GROUP").unwrap();
     let topic = std::env::var("TOPIC").unwrap();
+    let history = match std::env::var("HISTORY") {
+        Ok(history) => history.parse()?,
+        Err(..) => DEFAULT_HISTORY,
+    };
     init_tracer(&jaeger_url, "dependencies")?;
-    tokio::spawn(update_cache(redis_url.clone(), broker, group, topic));
+    tokio::spawn(update_cache(
+        redis_url.clone(),
+        broker,
+        group,
+        topic,
+        history,
+    ));
 
     let svc = dependencies_server::DependenciesServer::new(DepsService { redis_url });
 
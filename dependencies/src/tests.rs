@@ -2,9 +2,11 @@
 //! When that working set is submitted, we serialize each dep set and attach it as a blob mapped to the publisher's RefIdMsg in the history.  The history then stores
 //! a hash of GeometryIDs to Vec<RefIdMsg>.
 use super::*;
+use crate::graph::*;
 use change_msg::ChangeType;
 use prost::Message;
 use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
 use ref_id_msg::RefType;
 use std::collections::HashSet;
 use uuid::Uuid;
@@ -162,9 +164,9 @@ async fn test_deps_simple() {
         ],
     );
 
-    update_deps(&mut conn, &file, 0, &obj_0).await.unwrap();
+    update_deps(&mut conn, &file, 0, &obj_0, DEFAULT_HISTORY).await.unwrap();
 
-    update_deps(&mut conn, &file, 1, &obj_1).await.unwrap();
+    update_deps(&mut conn, &file, 1, &obj_1, DEFAULT_HISTORY).await.unwrap();
 
     let results = get_all_deps(&mut conn, &file, 1, &vec![obj_0_pt_1.clone()])
         .await
@@ -212,8 +214,8 @@ async fn test_deps_versioned() {
         vec![opt_ref_msg(&obj_1_pt_1, &obj_0_pt_0)],
     );
 
-    update_deps(&mut conn, &file, 0, &obj_0).await.unwrap();
-    update_deps(&mut conn, &file, 1, &obj_1).await.unwrap();
+    update_deps(&mut conn, &file, 0, &obj_0, DEFAULT_HISTORY).await.unwrap();
+    update_deps(&mut conn, &file, 1, &obj_1, DEFAULT_HISTORY).await.unwrap();
     let obj_1 = modify_change_msg(
         obj_1_id.clone(),
         vec![
@@ -221,7 +223,7 @@ async fn test_deps_versioned() {
             opt_ref_msg(&obj_1_pt_0, &obj_1_pt_1),
         ],
     );
-    update_deps(&mut conn, &file, 2, &obj_1).await.unwrap();
+    update_deps(&mut conn, &file, 2, &obj_1, DEFAULT_HISTORY).await.unwrap();
 
     let results = get_all_deps(&mut conn, &file, 2, &vec![obj_0_pt_0.clone()])
         .await
@@ -237,7 +239,7 @@ async fn test_deps_versioned() {
     ));
 
     let obj_0 = delete_change_msg(obj_0_id.clone());
-    update_deps(&mut conn, &file, 3, &obj_0).await.unwrap();
+    update_deps(&mut conn, &file, 3, &obj_0, DEFAULT_HISTORY).await.unwrap();
 
     let results = get_all_deps(&mut conn, &file, 3, &vec![obj_0_pt_0.clone()])
         .await
@@ -319,11 +321,11 @@ async fn test_get_all_deps() {
             opt_ref_msg(&window_pt_1, &wall_1_line_0),
         ],
     );
-    update_deps(&mut conn, &file, 0, &wall_0).await.unwrap();
-    update_deps(&mut conn, &file, 1, &wall_1).await.unwrap();
-    update_deps(&mut conn, &file, 2, &dim_0).await.unwrap();
-    update_deps(&mut conn, &file, 3, &dim_1).await.unwrap();
-    update_deps(&mut conn, &file, 4, &window).await.unwrap();
+    update_deps(&mut conn, &file, 0, &wall_0, DEFAULT_HISTORY).await.unwrap();
+    update_deps(&mut conn, &file, 1, &wall_1, DEFAULT_HISTORY).await.unwrap();
+    update_deps(&mut conn, &file, 2, &dim_0, DEFAULT_HISTORY).await.unwrap();
+    update_deps(&mut conn, &file, 3, &dim_1, DEFAULT_HISTORY).await.unwrap();
+    update_deps(&mut conn, &file, 4, &window, DEFAULT_HISTORY).await.unwrap();
 
     let results = get_all_deps(&mut conn, &file, 4, &vec![wall_0_pt_1.clone()])
         .await
@@ -365,3 +367,385 @@ async fn test_get_all_deps() {
     log::info!("Got results: {:#?}", results);
     assert!(equals(results, vec![]));
 }
+
+#[tokio_macros::test]
+async fn test_subscribers_and_publishers() {
+    let file = Uuid::new_v4().to_string();
+    let mut conn = test_get_conn().await;
+
+    let wall_id = Uuid::new_v4().to_string();
+    let wall_pt_0 = ref_id_msg(wall_id.clone(), RefType::ProfilePoint, 0);
+    let wall_line_0 = ref_id_msg(wall_id.clone(), RefType::ProfileLine, 0);
+    let door_id = Uuid::new_v4().to_string();
+    let door_line_0 = ref_id_msg(door_id.clone(), RefType::ProfileLine, 0);
+    let door_pt_0 = ref_id_msg(door_id.clone(), RefType::ProfilePoint, 0);
+    let dim_id = Uuid::new_v4().to_string();
+    let dim_pt_0 = ref_id_msg(dim_id.clone(), RefType::ProfilePoint, 0);
+
+    log::trace!("wall: {:?}", wall_id);
+    log::trace!("door: {:?}", door_id);
+    log::trace!("dim: {:?}", dim_id);
+
+    let wall = add_change_msg(wall_id.clone(), vec![opt_ref_msg(&wall_line_0, &wall_pt_0)]);
+    let door = add_change_msg(
+        door_id.clone(),
+        vec![
+            opt_ref_msg(&door_line_0, &wall_line_0),
+            opt_ref_msg(&door_pt_0, &door_line_0),
+        ],
+    );
+    let dim = add_change_msg(dim_id.clone(), vec![opt_ref_msg(&dim_pt_0, &door_pt_0)]);
+    update_deps(&mut conn, &file, 0, &wall, DEFAULT_HISTORY).await.unwrap();
+    update_deps(&mut conn, &file, 1, &door, DEFAULT_HISTORY).await.unwrap();
+    update_deps(&mut conn, &file, 2, &dim, DEFAULT_HISTORY).await.unwrap();
+
+    let results = get_subscribers(&mut conn, &file, 2, &vec![wall_line_0.clone()], false)
+        .await
+        .unwrap();
+    assert!(equals(
+        results,
+        vec![set![ref_msg_bin(&door_line_0, &wall_line_0)]]
+    ));
+
+    let results = get_subscribers(&mut conn, &file, 2, &vec![wall_line_0.clone()], true)
+        .await
+        .unwrap();
+    assert!(equals(
+        results,
+        vec![
+            set![ref_msg_bin(&door_line_0, &wall_line_0)],
+            set![ref_msg_bin(&door_pt_0, &door_line_0)],
+            set![ref_msg_bin(&dim_pt_0, &door_pt_0)]
+        ]
+    ));
+
+    //The wall's own line subscribing to its point shouldn't show up
+    let results = get_object_subscribers(&mut conn, &file, 2, &vec![wall_id.clone()], false)
+        .await
+        .unwrap();
+    assert!(equals(
+        results,
+        vec![set![ref_msg_bin(&door_line_0, &wall_line_0)]]
+    ));
+
+    let results = get_object_subscribers(&mut conn, &file, 1, &vec![door_id.clone()], true)
+        .await
+        .unwrap();
+    assert!(results.is_empty());
+
+    let results = get_publishers(&mut conn, &file, 2, &vec![door_id.clone()])
+        .await
+        .unwrap();
+    assert!(equals(
+        results,
+        vec![set![ref_msg_bin(&door_line_0, &wall_line_0)]]
+    ));
+
+    let door = modify_change_msg(
+        door_id.clone(),
+        vec![
+            OptionReferenceMsg { reference: None },
+            opt_ref_msg(&door_pt_0, &door_line_0),
+        ],
+    );
+    update_deps(&mut conn, &file, 3, &door, DEFAULT_HISTORY).await.unwrap();
+
+    let results = get_publishers(&mut conn, &file, 3, &vec![door_id.clone()])
+        .await
+        .unwrap();
+    assert!(results.is_empty());
+
+    let results = get_publishers(&mut conn, &file, 2, &vec![door_id.clone()])
+        .await
+        .unwrap();
+    assert!(equals(
+        results,
+        vec![set![ref_msg_bin(&door_line_0, &wall_line_0)]]
+    ));
+}
+
+#[tokio_macros::test]
+async fn test_get_graph() {
+    let file = Uuid::new_v4().to_string();
+    let mut conn = test_get_conn().await;
+
+    let wall_id = Uuid::new_v4().to_string();
+    let wall_line_0 = ref_id_msg(wall_id.clone(), RefType::ProfileLine, 0);
+    let door_id = Uuid::new_v4().to_string();
+    let door_line_0 = ref_id_msg(door_id.clone(), RefType::ProfileLine, 0);
+
+    //Same layout bincode gives a typetag object, which is all the graph reads
+    let wall = ChangeMsg {
+        user: "Doesn't matter".to_string(),
+        change_type: Some(ChangeType::Add(ObjectMsg {
+            id: wall_id.clone(),
+            dependencies: None,
+            obj_data: bincode::serialize(&(2u64, "type", "Wall")).unwrap(),
+        })),
+        change_source: Some(change_msg::ChangeSource::UserAction(EmptyMsg {})),
+    };
+    let mut wall_bytes = Vec::new();
+    wall.encode(&mut wall_bytes).unwrap();
+    let door = add_change_msg(door_id.clone(), vec![opt_ref_msg(&door_line_0, &wall_line_0)]);
+    update_deps(&mut conn, &file, 0, &wall_bytes, DEFAULT_HISTORY).await.unwrap();
+    update_deps(&mut conn, &file, 1, &door, DEFAULT_HISTORY).await.unwrap();
+
+    let graph = get_graph(&mut conn, &file, 0).await.unwrap();
+    assert!(graph.nodes.is_empty());
+    assert!(graph.edges.is_empty());
+
+    let graph = get_graph(&mut conn, &file, 1).await.unwrap();
+    let wall_label = format!("{}>ProfileLine>0", wall_id);
+    let door_label = format!("{}>ProfileLine>0", door_id);
+    assert_eq!(
+        graph.nodes,
+        vec![
+            GraphNode {
+                label: wall_label.clone(),
+                obj_id: wall_id.clone(),
+                obj_type: String::from("Wall"),
+            },
+            GraphNode {
+                label: door_label.clone(),
+                obj_id: door_id.clone(),
+                obj_type: String::new(),
+            },
+        ]
+    );
+    assert_eq!(
+        graph.edges,
+        vec![GraphEdge {
+            publisher: wall_label.clone(),
+            subscriber: door_label.clone(),
+        }]
+    );
+
+    //The door is still in the graph at offsets from before it was deleted
+    let delete = delete_change_msg(door_id.clone());
+    update_deps(&mut conn, &file, 2, &delete, DEFAULT_HISTORY).await.unwrap();
+    let graph = get_graph(&mut conn, &file, 1).await.unwrap();
+    assert_eq!(
+        graph.edges,
+        vec![GraphEdge {
+            publisher: wall_label,
+            subscriber: door_label,
+        }]
+    );
+    let graph = get_graph(&mut conn, &file, 2).await.unwrap();
+    assert!(graph.edges.is_empty());
+}
+
+#[tokio_macros::test]
+async fn test_get_objects_of_type() {
+    let file = Uuid::new_v4().to_string();
+    let mut conn = test_get_conn().await;
+
+    let typed = |id: &String, type_name: &str| {
+        let msg = ChangeMsg {
+            user: "Doesn't matter".to_string(),
+            change_type: Some(ChangeType::Add(ObjectMsg {
+                id: id.clone(),
+                dependencies: None,
+                obj_data: bincode::serialize(&(2u64, "type", type_name)).unwrap(),
+            })),
+            change_source: Some(change_msg::ChangeSource::UserAction(EmptyMsg {})),
+        };
+        let mut bytes = Vec::new();
+        msg.encode(&mut bytes).unwrap();
+        bytes
+    };
+    let wall_id = Uuid::new_v4().to_string();
+    let section_id = Uuid::new_v4().to_string();
+    update_deps(&mut conn, &file, 0, &typed(&wall_id, "Wall"), DEFAULT_HISTORY)
+        .await
+        .unwrap();
+    update_deps(&mut conn, &file, 1, &typed(&section_id, "SectionCut"), DEFAULT_HISTORY)
+        .await
+        .unwrap();
+
+    let sections = get_objects_of_type(&mut conn, &file, "SectionCut")
+        .await
+        .unwrap();
+    assert_eq!(sections, vec![section_id.clone()]);
+    let walls = get_objects_of_type(&mut conn, &file, "Wall").await.unwrap();
+    assert_eq!(walls, vec![wall_id]);
+
+    let delete = delete_change_msg(section_id);
+    update_deps(&mut conn, &file, 2, &delete, DEFAULT_HISTORY)
+        .await
+        .unwrap();
+    let sections = get_objects_of_type(&mut conn, &file, "SectionCut")
+        .await
+        .unwrap();
+    assert!(sections.is_empty());
+}
+
+#[tokio_macros::test]
+async fn test_history_and_cleanup() {
+    let file = Uuid::new_v4().to_string();
+    let mut conn = test_get_conn().await;
+
+    let wall_id = Uuid::new_v4().to_string();
+    let wall_pt_0 = ref_id_msg(wall_id.clone(), RefType::ProfilePoint, 0);
+    let wall_line_0 = ref_id_msg(wall_id.clone(), RefType::ProfileLine, 0);
+    let door_id = Uuid::new_v4().to_string();
+    let door_line_0 = ref_id_msg(door_id.clone(), RefType::ProfileLine, 0);
+
+    let wall = ChangeMsg {
+        user: "Doesn't matter".to_string(),
+        change_type: Some(ChangeType::Add(ObjectMsg {
+            id: wall_id.clone(),
+            dependencies: Some(DependenciesMsg {
+                references: vec![opt_ref_msg(&wall_line_0, &wall_pt_0)],
+            }),
+            obj_data: bincode::serialize(&(2u64, "type", "Wall")).unwrap(),
+        })),
+        change_source: Some(change_msg::ChangeSource::UserAction(EmptyMsg {})),
+    };
+    let mut wall_bytes = Vec::new();
+    wall.encode(&mut wall_bytes).unwrap();
+    update_deps(&mut conn, &file, 0, &wall_bytes, 2).await.unwrap();
+    let door = add_change_msg(door_id.clone(), vec![opt_ref_msg(&door_line_0, &wall_line_0)]);
+    update_deps(&mut conn, &file, 1, &door, 2).await.unwrap();
+    let exists: bool = conn
+        .exists(format!("{}:{}:type", file, wall_id))
+        .await
+        .unwrap();
+    assert!(exists);
+    let door = modify_change_msg(door_id.clone(), vec![opt_ref_msg(&door_line_0, &wall_line_0)]);
+    update_deps(&mut conn, &file, 2, &door, 2).await.unwrap();
+    update_deps(&mut conn, &file, 3, &door, 2).await.unwrap();
+
+    let subs_key = format!("{}:{:?}:subs", file, RefID::from(&wall_line_0));
+    let length: u64 = conn.llen(&subs_key).await.unwrap();
+    assert_eq!(length, 2);
+    let deps_key = format!("{}:{}:refs", file, door_id);
+    let length: u64 = conn.llen(&deps_key).await.unwrap();
+    assert_eq!(length, 2);
+
+    //Offset 1 fell out of the retained history
+    let results = get_subscribers(&mut conn, &file, 1, &vec![wall_line_0.clone()], false)
+        .await
+        .unwrap();
+    assert!(results.is_empty());
+    let results = get_subscribers(&mut conn, &file, 3, &vec![wall_line_0.clone()], false)
+        .await
+        .unwrap();
+    assert!(equals(
+        results,
+        vec![set![ref_msg_bin(&door_line_0, &wall_line_0)]]
+    ));
+
+    //Deleted objects are still there at earlier offsets
+    let wall = delete_change_msg(wall_id.clone());
+    update_deps(&mut conn, &file, 4, &wall, 2).await.unwrap();
+    let results = get_subscribers(&mut conn, &file, 3, &vec![wall_pt_0.clone()], false)
+        .await
+        .unwrap();
+    assert!(equals(
+        results,
+        vec![set![ref_msg_bin(&wall_line_0, &wall_pt_0)]]
+    ));
+    let results = get_subscribers(&mut conn, &file, 4, &vec![wall_pt_0.clone()], false)
+        .await
+        .unwrap();
+    assert!(results.is_empty());
+
+    //Until history more changes have gone by.  The door's deletion is still kept, so its keys stay too.
+    let door = delete_change_msg(door_id.clone());
+    update_deps(&mut conn, &file, 5, &door, 2).await.unwrap();
+    let other_id = Uuid::new_v4().to_string();
+    let other = add_change_msg(other_id.clone(), Vec::new());
+    update_deps(&mut conn, &file, 6, &other, 2).await.unwrap();
+    for key in &[
+        format!("{}:{:?}:subs", file, RefID::from(&wall_pt_0)),
+        subs_key.clone(),
+        format!("{}:{}:refs", file, wall_id),
+        format!("{}:{}:pubs", file, wall_id),
+        format!("{}:{}:type", file, wall_id),
+    ] {
+        let exists: bool = conn.exists(key).await.unwrap();
+        assert!(!exists, "{} should have been purged with the wall", key);
+    }
+    let walls: Vec<String> = conn
+        .smembers(format!("{}:types:Wall", file))
+        .await
+        .unwrap();
+    assert!(!walls.contains(&wall_id));
+    let exists: bool = conn.exists(&deps_key).await.unwrap();
+    assert!(exists);
+
+    let other = delete_change_msg(other_id.clone());
+    update_deps(&mut conn, &file, 7, &other, 2).await.unwrap();
+    for key in vec![
+        subs_key,
+        deps_key,
+        format!("{}:{}:pubs", file, wall_id),
+        format!("{}:{}:pubs", file, door_id),
+        format!("{}:objs", file),
+    ] {
+        let exists: bool = conn.exists(&key).await.unwrap();
+        assert!(!exists, "{} should have been cleaned up", key);
+    }
+}
+
+#[tokio_macros::test]
+async fn test_legacy_obj_refs() {
+    let file = Uuid::new_v4().to_string();
+    let mut conn = test_get_conn().await;
+
+    let wall_id = Uuid::new_v4().to_string();
+    let wall_line_0 = ref_id_msg(wall_id.clone(), RefType::ProfileLine, 0);
+    let door_id = Uuid::new_v4().to_string();
+    let door_line_0 = ref_id_msg(door_id.clone(), RefType::ProfileLine, 0);
+
+    //References used to be stored as a single value, laid out the same as ObjRefs
+    let mut serialized_refs = Vec::new();
+    DependenciesMsg {
+        references: vec![opt_ref_msg(&door_line_0, &wall_line_0)],
+    }
+    .encode(&mut serialized_refs)
+    .unwrap();
+    let legacy_key = format!("{}:{}:deps", file, door_id);
+    conn.set::<_, _, ()>(
+        &legacy_key,
+        bincode::serialize(&(0i64, serialized_refs)).unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let results = get_publishers(&mut conn, &file, 0, &vec![door_id.clone()])
+        .await
+        .unwrap();
+    assert!(equals(
+        results,
+        vec![set![ref_msg_bin(&door_line_0, &wall_line_0)]]
+    ));
+
+    let door = modify_change_msg(
+        door_id.clone(),
+        vec![OptionReferenceMsg { reference: None }],
+    );
+    update_deps(&mut conn, &file, 1, &door, DEFAULT_HISTORY)
+        .await
+        .unwrap();
+    let exists: bool = conn.exists(&legacy_key).await.unwrap();
+    assert!(!exists);
+    let length: u64 = conn
+        .llen(format!("{}:{}:refs", file, door_id))
+        .await
+        .unwrap();
+    assert_eq!(length, 2);
+
+    let results = get_publishers(&mut conn, &file, 1, &vec![door_id.clone()])
+        .await
+        .unwrap();
+    assert!(results.is_empty());
+    let results = get_publishers(&mut conn, &file, 0, &vec![door_id.clone()])
+        .await
+        .unwrap();
+    assert!(equals(
+        results,
+        vec![set![ref_msg_bin(&door_line_0, &wall_line_0)]]
+    ));
+}
@@ -0,0 +1,158 @@
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use trace_lib::*;
+use tracing::*;
+use tracing_futures::Instrument;
+
+mod store;
+use store::*;
+
+mod files {
+    tonic::include_proto!("files");
+}
+use files::*;
+
+#[instrument]
+async fn get_redis_conn(url: &str) -> Result<redis::aio::MultiplexedConnection, tonic::Status> {
+    let client =
+        redis::Client::open(url).map_err(|e| tonic::Status::unavailable(format!("{:?}", e)))?;
+    match client.get_multiplexed_async_connection().await {
+        Ok((redis_conn, fut)) => {
+            tokio::spawn(fut);
+            Ok(redis_conn)
+        }
+        Err(e) => Err(tonic::Status::unavailable(format!("{:?}", e))),
+    }
+}
+
+#[derive(Debug)]
+struct FilesService {
+    redis_url: String,
+}
+
+#[tonic::async_trait]
+impl files_server::Files for FilesService {
+    #[instrument]
+    async fn create_file(
+        &self,
+        request: Request<CreateFileInput>,
+    ) -> Result<Response<CreateFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let id = Some(msg.id.as_str()).filter(|id| !id.is_empty());
+        let file = store::create_file(&mut redis_conn, id, &msg.name, &msg.owner)
+            .instrument(info_span!("create_file"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(CreateFileOutput { file: Some(file) }))
+    }
+
+    #[instrument]
+    async fn get_file(
+        &self,
+        request: Request<GetFileInput>,
+    ) -> Result<Response<GetFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let file = store::get_file(&mut redis_conn, &msg.id)
+            .instrument(info_span!("get_file"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(GetFileOutput { file: Some(file) }))
+    }
+
+    #[instrument]
+    async fn rename_file(
+        &self,
+        request: Request<RenameFileInput>,
+    ) -> Result<Response<RenameFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        store::rename_file(&mut redis_conn, &msg.id, &msg.name)
+            .instrument(info_span!("rename_file"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(RenameFileOutput {}))
+    }
+
+    #[instrument]
+    async fn list_files(
+        &self,
+        request: Request<ListFilesInput>,
+    ) -> Result<Response<ListFilesOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let files = store::list_files(&mut redis_conn, msg.include_archived)
+            .instrument(info_span!("list_files"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(ListFilesOutput { files }))
+    }
+
+    #[instrument]
+    async fn archive_file(
+        &self,
+        request: Request<ArchiveFileInput>,
+    ) -> Result<Response<ArchiveFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        store::archive_file(&mut redis_conn, &msg.id, msg.archived)
+            .instrument(info_span!("archive_file"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(ArchiveFileOutput {}))
+    }
+
+    #[instrument]
+    async fn set_thumbnail(
+        &self,
+        request: Request<SetThumbnailInput>,
+    ) -> Result<Response<SetThumbnailOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        store::set_thumbnail(&mut redis_conn, &msg.id, &msg.thumbnail)
+            .instrument(info_span!("set_thumbnail"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(SetThumbnailOutput {}))
+    }
+
+    #[instrument]
+    async fn delete_file(
+        &self,
+        request: Request<DeleteFileInput>,
+    ) -> Result<Response<DeleteFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        store::delete_file(&mut redis_conn, &msg.id)
+            .instrument(info_span!("delete_file"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(DeleteFileOutput {}))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let run_url = std::env::var("RUN_URL").unwrap().parse().unwrap();
+    let jaeger_url = std::env::var("JAEGER_URL").unwrap();
+    let redis_url = std::env::var("REDIS_URL").unwrap();
+    trace_lib::init_tracer(&jaeger_url, "files")?;
+
+    let svc = files_server::FilesServer::new(FilesService { redis_url });
+
+    println!("Running on {:?}", run_url);
+    Server::builder()
+        .add_service(svc)
+        .serve(run_url)
+        .await
+        .unwrap();
+    Ok(())
+}
@@ -0,0 +1,250 @@
+//! Every file's metadata is a hash, and the IDs of all files are kept in a set so they can be
+//! listed.
+
+use super::*;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum FilesError {
+    #[error("File {0} not found")]
+    FileNotFound(String),
+    #[error("File {0} already exists")]
+    FileExists(String),
+    #[error("Invalid metadata stored for file {0}")]
+    InvalidMetadata(String),
+    #[error("Redis error: {0:?}")]
+    DatabaseError(#[from] redis::RedisError),
+}
+
+impl Into<tonic::Status> for FilesError {
+    fn into(self) -> tonic::Status {
+        let msg = format!("{}", self);
+        let code = match self {
+            FilesError::FileNotFound(..) => tonic::Code::NotFound,
+            FilesError::FileExists(..) => tonic::Code::AlreadyExists,
+            FilesError::InvalidMetadata(..) | FilesError::DatabaseError(..) => {
+                tonic::Code::Internal
+            }
+        };
+        tonic::Status::new(code, msg)
+    }
+}
+
+pub fn to_status<T: Into<FilesError>>(err: T) -> tonic::Status {
+    let files_error: FilesError = err.into();
+    files_error.into()
+}
+
+const ALL_FILES: &str = "files";
+
+fn file_meta(id: &str) -> String {
+    format!("{}:meta", id)
+}
+
+fn to_string(id: &str, bytes: Option<Vec<u8>>) -> Result<String, FilesError> {
+    String::from_utf8(bytes.unwrap_or_default())
+        .map_err(|_| FilesError::InvalidMetadata(String::from(id)))
+}
+
+async fn check_exists(conn: &mut MultiplexedConnection, id: &str) -> Result<(), FilesError> {
+    let exists: bool = conn.exists(file_meta(id)).await?;
+    if !exists {
+        return Err(FilesError::FileNotFound(String::from(id)));
+    }
+    Ok(())
+}
+
+///A new ID is made up unless one is given, which mustn't belong to a file already.
+pub async fn create_file(
+    conn: &mut MultiplexedConnection,
+    id: Option<&str>,
+    name: &str,
+    owner: &str,
+) -> Result<FileMsg, FilesError> {
+    let id = match id {
+        Some(id) => {
+            let exists: bool = conn.exists(file_meta(id)).await?;
+            if exists {
+                return Err(FilesError::FileExists(String::from(id)));
+            }
+            String::from(id)
+        }
+        None => Uuid::new_v4().to_string(),
+    };
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs() as i64)
+        .unwrap_or_default();
+    redis::pipe()
+        .atomic()
+        .hset_multiple(
+            file_meta(&id),
+            &[
+                ("name", name.as_bytes()),
+                ("owner", owner.as_bytes()),
+                ("created", created.to_string().as_bytes()),
+                ("archived", b"0"),
+                ("thumbnail", b""),
+            ],
+        )
+        .sadd(ALL_FILES, &id)
+        .query_async::<_, ()>(conn)
+        .await?;
+    Ok(FileMsg {
+        id,
+        name: String::from(name),
+        owner: String::from(owner),
+        created,
+        archived: false,
+        thumbnail: Vec::new(),
+    })
+}
+
+pub async fn get_file(conn: &mut MultiplexedConnection, id: &str) -> Result<FileMsg, FilesError> {
+    let mut fields: HashMap<String, Vec<u8>> = conn.hgetall(file_meta(id)).await?;
+    if fields.is_empty() {
+        return Err(FilesError::FileNotFound(String::from(id)));
+    }
+    let created = to_string(id, fields.remove("created"))?
+        .parse()
+        .map_err(|_| FilesError::InvalidMetadata(String::from(id)))?;
+    Ok(FileMsg {
+        id: String::from(id),
+        name: to_string(id, fields.remove("name"))?,
+        owner: to_string(id, fields.remove("owner"))?,
+        created,
+        archived: fields.remove("archived").as_deref() == Some(b"1"),
+        thumbnail: fields.remove("thumbnail").unwrap_or_default(),
+    })
+}
+
+pub async fn rename_file(
+    conn: &mut MultiplexedConnection,
+    id: &str,
+    name: &str,
+) -> Result<(), FilesError> {
+    check_exists(conn, id).await?;
+    conn.hset::<_, _, _, ()>(file_meta(id), "name", name)
+        .await?;
+    Ok(())
+}
+
+pub async fn list_files(
+    conn: &mut MultiplexedConnection,
+    include_archived: bool,
+) -> Result<Vec<FileMsg>, FilesError> {
+    let ids: Vec<String> = conn.smembers(ALL_FILES).await?;
+    let mut files = Vec::new();
+    for id in ids {
+        match get_file(conn, &id).await {
+            Ok(file) => {
+                if include_archived || !file.archived {
+                    files.push(file);
+                }
+            }
+            // Deleted since the set was read.
+            Err(FilesError::FileNotFound(..)) => (),
+            Err(e) => return Err(e),
+        }
+    }
+    files.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.id.cmp(&b.id)));
+    Ok(files)
+}
+
+pub async fn archive_file(
+    conn: &mut MultiplexedConnection,
+    id: &str,
+    archived: bool,
+) -> Result<(), FilesError> {
+    check_exists(conn, id).await?;
+    let value = if archived { "1" } else { "0" };
+    conn.hset::<_, _, _, ()>(file_meta(id), "archived", value)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_thumbnail(
+    conn: &mut MultiplexedConnection,
+    id: &str,
+    thumbnail: &[u8],
+) -> Result<(), FilesError> {
+    check_exists(conn, id).await?;
+    conn.hset::<_, _, _, ()>(file_meta(id), "thumbnail", thumbnail)
+        .await?;
+    Ok(())
+}
+
+/// Deleting a file that's already gone does nothing, so a delete that failed part way through can
+/// be run again.
+pub async fn delete_file(conn: &mut MultiplexedConnection, id: &str) -> Result<(), FilesError> {
+    redis::pipe()
+        .atomic()
+        .del(file_meta(id))
+        .srem(ALL_FILES, id)
+        .query_async::<_, ()>(conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub async fn test_get_conn() -> MultiplexedConnection {
+        let _ = env_logger::Builder::new()
+            .filter_module("files", log::LevelFilter::Trace)
+            .is_test(true)
+            .try_init();
+        let env_opt = std::env::var("REDIS_URL");
+        let redis_url = if let Ok(url) = env_opt {
+            url
+        } else {
+            String::from("redis://127.0.0.1:6379")
+        };
+        let client = redis::Client::open(redis_url).unwrap();
+        let (conn, fut) = client.get_multiplexed_async_connection().await.unwrap();
+        tokio::spawn(fut);
+        conn
+    }
+
+    #[tokio_macros::test]
+    async fn test_files() {
+        let mut conn = test_get_conn().await;
+        let owner = Uuid::new_v4().to_string();
+        let file = create_file(&mut conn, None, "Plan", &owner).await.unwrap();
+        assert_eq!(get_file(&mut conn, &file.id).await.unwrap(), file);
+        assert!(create_file(&mut conn, Some(&file.id), "Again", &owner)
+            .await
+            .is_err());
+        let symbol_id = Uuid::new_v4().to_string();
+        let symbol = create_file(&mut conn, Some(&symbol_id), "Chair", &owner)
+            .await
+            .unwrap();
+        assert_eq!(symbol.id, symbol_id);
+        delete_file(&mut conn, &symbol.id).await.unwrap();
+
+        rename_file(&mut conn, &file.id, "Site plan").await.unwrap();
+        set_thumbnail(&mut conn, &file.id, &[1, 2, 3])
+            .await
+            .unwrap();
+        let renamed = get_file(&mut conn, &file.id).await.unwrap();
+        assert_eq!(renamed.name, "Site plan");
+        assert_eq!(renamed.thumbnail, vec![1, 2, 3]);
+
+        archive_file(&mut conn, &file.id, true).await.unwrap();
+        let listed = list_files(&mut conn, false).await.unwrap();
+        assert!(listed.iter().all(|listed| listed.id != file.id));
+        let listed = list_files(&mut conn, true).await.unwrap();
+        assert!(listed.iter().any(|listed| listed.id == file.id));
+
+        delete_file(&mut conn, &file.id).await.unwrap();
+        delete_file(&mut conn, &file.id).await.unwrap();
+        assert!(get_file(&mut conn, &file.id).await.is_err());
+        assert!(rename_file(&mut conn, &file.id, "Gone").await.is_err());
+    }
+}
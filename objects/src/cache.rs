@@ -225,13 +225,54 @@ pub async fn get_objects(
     Ok(results)
 }
 
+///The users who changed the object after the offset, oldest first.
+async fn get_changed_by(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+    offset: i64,
+    key: &str,
+) -> Result<Vec<String>, ObjError> {
+    let obj_cache = obj_cache(file, key);
+    let cache_length: isize = conn.llen(&obj_cache).await?;
+    let mut users = Vec::new();
+    for i in 0isize..cache_length {
+        let serialized: Vec<u8> = conn.lindex(&obj_cache, i).await?;
+        let entry: ObjEntry = bincode::deserialize(&serialized)?;
+        if entry.offset <= offset {
+            break;
+        }
+        users.push(ChangeMsg::decode(entry.object.as_ref())?.user);
+    }
+    users.reverse();
+    Ok(users)
+}
+
+pub async fn get_all_changed_by(
+    conn: &mut MultiplexedConnection,
+    input: &GetChangedByInput,
+) -> Result<Vec<ChangedByMsg>, ObjError> {
+    debug!("get_all_changed_by input: {:?}", input);
+    let mut results = Vec::new();
+    for entry in &input.obj_ids {
+        let users = get_changed_by(conn, &input.file, entry.offset, &entry.obj_id).await?;
+        results.push(ChangedByMsg { users });
+    }
+    Ok(results)
+}
+
 pub async fn get_latest_offset(
     conn: &mut MultiplexedConnection,
     input: &GetLatestOffsetInput,
 ) -> Result<i64, ObjError> {
     let file_offset = file_offset(&input.file);
-    let offset: i64 = conn.get(file_offset).await?;
-    Ok(offset)
+    let offset: Option<i64> = conn.get(file_offset).await?;
+    Ok(offset.unwrap_or(-1))
+}
+
+/// Removes everything stored for the file.
+pub async fn delete_file(conn: &mut MultiplexedConnection, file: &str) -> Result<(), ObjError> {
+    redis_lib::delete_file(conn, file).await?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -331,6 +372,15 @@ mod tests {
         assert!(get_object(&mut conn, &file, offset_1 - 1, &id)
             .await
             .is_err());
+
+        assert_eq!(
+            get_changed_by(&mut conn, &file, offset_1, &id).await.unwrap(),
+            vec![user.clone(), user.clone()]
+        );
+        assert!(get_changed_by(&mut conn, &file, offset_3, &id)
+            .await
+            .unwrap()
+            .is_empty());
     }
 
     #[tokio_macros::test]
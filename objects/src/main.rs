@@ -61,6 +61,21 @@ impl objects_server::Objects for RepCacheService {
         Ok(Response::new(GetLatestOffsetOutput { offset }))
     }
 
+    #[instrument]
+    async fn get_changed_by(
+        &self,
+        request: Request<GetChangedByInput>,
+    ) -> Result<Response<GetChangedByOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let objects = cache::get_all_changed_by(&mut redis_conn, msg)
+            .instrument(info_span!("cache::get_all_changed_by"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(GetChangedByOutput { objects }))
+    }
+
     type GetLatestObjectListStream = mpsc::Receiver<Result<GetLatestObjectListOutput, Status>>;
 
     async fn get_latest_object_list(
@@ -89,6 +104,21 @@ impl objects_server::Objects for RepCacheService {
         });
         Ok(Response::new(rx))
     }
+
+    #[instrument]
+    async fn delete_file(
+        &self,
+        request: Request<DeleteFileInput>,
+    ) -> Result<Response<DeleteFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        cache::delete_file(&mut redis_conn, &msg.file)
+            .instrument(info_span!("cache::delete_file"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(DeleteFileOutput {}))
+    }
 }
 
 #[tokio::main]
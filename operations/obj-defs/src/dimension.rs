@@ -0,0 +1,401 @@
+use crate::*;
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+///A linear dimension measuring the distance in plan between two points, usually the ends of walls
+/// or doors.  Its ends are ProfilePoints that can reference the points they measure, so it
+/// re-measures when they move.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Dimension {
+    id: ObjID,
+    pub first_pt: UpdatableInfo<Point3f>,
+    pub second_pt: UpdatableInfo<Point3f>,
+    ///How far the dimension line is from the measured points, to the left going from the first to
+    /// the second.  Negative offsets put it on the right.
+    pub offset: WorldCoord,
+    pub text_style: TextStyle,
+}
+
+impl Dimension {
+    pub fn new(
+        first: Point3f,
+        second: Point3f,
+        offset: WorldCoord,
+        text_style: TextStyle,
+    ) -> Dimension {
+        let id = ObjID::new_v4();
+        Dimension {
+            id,
+            first_pt: UpdatableInfo::new(first),
+            second_pt: UpdatableInfo::new(second),
+            offset,
+            text_style,
+        }
+    }
+
+    pub fn get_length(&self) -> WorldCoord {
+        (x_y(&self.second_pt.info) - x_y(&self.first_pt.info)).magnitude()
+    }
+
+    ///The measured length in millimeters
+    pub fn get_text(&self) -> String {
+        format!("{:.0}", self.get_length() * 1000.0)
+    }
+
+    ///Extension lines from the measured points out past the dimension line, with arrows at the
+    /// ends of the dimension line and the length written above it.  Everything is sized from the
+    /// text height, so it all scales together.
+    fn get_plan(&self) -> DrawingData {
+        let first = x_y(&self.first_pt.info);
+        let second = x_y(&self.second_pt.info);
+        let height = self.text_style.height;
+        let length = (second - first).magnitude();
+        if length < f64::EPSILON {
+            return DrawingData {
+                elements: Vec::new(),
+            };
+        }
+        let dir = (second - first) / length;
+        let left = Vector2f::new(-dir.y, dir.x);
+        let side = if self.offset < 0.0 { -left } else { left };
+        let line_first = first + left * self.offset;
+        let line_second = second + left * self.offset;
+        let mut elements = Vec::new();
+        for (pt, on_line) in &[(first, line_first), (second, line_second)] {
+            let gap = if self.offset.abs() > height / 2.0 {
+                side * (height / 2.0)
+            } else {
+                side * 0.0
+            };
+            elements.push(DrawElement2D::new_annotation(Element2D::Line(Line2D::new(
+                pt + gap,
+                on_line + side * (height / 2.0),
+            ))));
+        }
+        elements.push(DrawElement2D::new_annotation(Element2D::Line(Line2D::new(
+            line_first,
+            line_second,
+        ))));
+        let arrow_length = height.min(length / 3.0);
+        elements.push(arrow(line_first, dir, arrow_length));
+        elements.push(arrow(line_second, -dir, arrow_length));
+        //Text reads left to right or bottom to top, and sits above the line whichever way that is
+        let (text_dir, rotation) =
+            if dir.x < -f64::EPSILON || (dir.x.abs() <= f64::EPSILON && dir.y < 0.0) {
+                (-dir, (-dir.y).atan2(-dir.x))
+            } else {
+                (dir, dir.y.atan2(dir.x))
+            };
+        let up = Vector2f::new(-text_dir.y, text_dir.x);
+        let middle = line_first + (line_second - line_first) / 2.0;
+        elements.push(DrawElement2D::new_annotation(Element2D::Text(Text2D::new(
+            self.get_text(),
+            middle + up * (height / 2.0),
+            &self.text_style,
+            TextAlignment::Center,
+            radians(rotation),
+        ))));
+        DrawingData { elements }
+    }
+}
+
+#[async_trait::async_trait]
+#[typetag::serde]
+impl Data for Dimension {
+    fn get_id(&self) -> &ObjID {
+        &self.id
+    }
+
+    fn reset_id(&mut self) {
+        self.id = ObjID::new_v4();
+    }
+
+    async fn update(&self, _conn: &mut dyn GeomKernel) -> Result<UpdateOutput, ObjError> {
+        Ok(UpdateOutput::Other {
+            data: json! ({
+                "type": "Dimension",
+                "obj": {
+                    "First": self.first_pt.info,
+                    "Second": self.second_pt.info,
+                    "Offset": self.offset,
+                    "Length": self.get_length(),
+                    "Text": self.get_text()
+                }
+            }),
+        })
+    }
+
+    fn get_result(&self, ref_type: RefType, index: ResultInd) -> Option<RefResult> {
+        match ref_type {
+            RefType::Drawable => Some(RefResult::Empty),
+            RefType::Existence => Some(RefResult::Empty),
+            RefType::ProfilePoint => match index {
+                0 => Some(self.first_pt.get_result()),
+                1 => Some(self.second_pt.get_result()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn get_results_for_type(&self, ref_type: RefType) -> Vec<RefResult> {
+        match ref_type {
+            RefType::Drawable => vec![RefResult::Empty],
+            RefType::Existence => vec![RefResult::Empty],
+            RefType::ProfilePoint => vec![self.first_pt.get_result(), self.second_pt.get_result()],
+            _ => Vec::new(),
+        }
+    }
+
+    fn get_num_results_for_type(&self, ref_type: RefType) -> usize {
+        match ref_type {
+            RefType::Drawable => 1,
+            RefType::Existence => 1,
+            RefType::ProfilePoint => 2,
+            _ => 0,
+        }
+    }
+
+    fn clear_refs(&mut self) {
+        self.first_pt.refer = None;
+        self.second_pt.refer = None;
+    }
+
+    fn get_refs(&self) -> Vec<Option<Reference>> {
+        let self_pt_0 = RefID::new(self.id, RefType::ProfilePoint, 0);
+        let self_pt_1 = RefID::new(self.id, RefType::ProfilePoint, 1);
+        vec![
+            self.first_pt
+                .refer
+                .map(|other| Reference::new(self_pt_0, other)),
+            self.second_pt
+                .refer
+                .map(|other| Reference::new(self_pt_1, other)),
+        ]
+    }
+
+    fn get_available_refs_for_type(&self, ref_type: RefType) -> Vec<ResultInd> {
+        let mut results = Vec::new();
+        if let RefType::ProfilePoint = ref_type {
+            if self.first_pt.refer.is_none() {
+                results.push(0);
+            }
+            if self.second_pt.refer.is_none() {
+                results.push(1);
+            }
+        }
+        results
+    }
+
+    fn set_ref(
+        &mut self,
+        ref_type: RefType,
+        index: ResultInd,
+        result: RefResult,
+        other_ref: RefID,
+        _extra: &Option<RefResult>,
+    ) {
+        if let RefType::ProfilePoint = ref_type {
+            match index {
+                0 => self.first_pt.set_reference(result, other_ref),
+                1 => self.second_pt.set_reference(result, other_ref),
+                _ => (),
+            }
+        }
+    }
+
+    fn delete_ref(&mut self, ref_type: RefType, index: ResultInd) {
+        if let RefType::ProfilePoint = ref_type {
+            match index {
+                0 => self.first_pt.refer = None,
+                1 => self.second_pt.refer = None,
+                _ => (),
+            }
+        }
+    }
+
+    fn set_associated_result_for_type(
+        &mut self,
+        ref_type: RefType,
+        index: ResultInd,
+        result: Option<RefResult>,
+    ) {
+        if let RefType::ProfilePoint = ref_type {
+            match index {
+                0 => self.first_pt.update(result),
+                1 => self.second_pt.update(result),
+                _ => (),
+            }
+        }
+    }
+
+    fn data_clone(&self) -> DataBox {
+        Box::new(self.clone())
+    }
+
+    fn as_drawing_views(&self) -> Option<&dyn DrawingViews> {
+        Some(self)
+    }
+}
+
+impl DrawingViews for Dimension {
+    fn get_top(&self) -> DrawingData {
+        self.get_plan()
+    }
+
+    fn get_front(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_left(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_right(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_back(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_bottom(&self) -> DrawingData {
+        self.get_plan()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dimension(first: (f64, f64), second: (f64, f64), offset: WorldCoord) -> Dimension {
+        Dimension::new(
+            Point3f::new(first.0, first.1, 0.0),
+            Point3f::new(second.0, second.1, 0.0),
+            offset,
+            TextStyle::new(String::from("Arial"), 0.25),
+        )
+    }
+
+    fn line_elements(data: &DrawingData) -> Vec<Line2D> {
+        data.elements
+            .iter()
+            .filter_map(|element| match &element.element {
+                Element2D::Line(line) => Some(*line),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn arrow_tips(data: &DrawingData) -> Vec<Point2f> {
+        data.elements
+            .iter()
+            .filter_map(|element| match &element.element {
+                Element2D::Poly(poly) => Some(poly.pts[0]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn text_element(data: &DrawingData) -> Text2D {
+        data.elements
+            .iter()
+            .find_map(|element| match &element.element {
+                Element2D::Text(text) => Some(text.clone()),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn assert_close(actual: Point2f, expected: (f64, f64)) {
+        assert!(
+            (actual - Point2f::new(expected.0, expected.1)).magnitude() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_plan() {
+        let dim = dimension((0.0, 0.0), (4.0, 0.0), 1.0);
+        assert_eq!(dim.get_text(), "4000");
+        let top = dim.get_top();
+        assert_eq!(top.elements.len(), 6);
+
+        //Extension lines start a little off the measured points and run just past the dimension
+        // line, which is offset to the left
+        let lines = line_elements(&top);
+        assert_eq!(lines.len(), 3);
+        assert_close(lines[0].first, (0.0, 0.125));
+        assert_close(lines[0].second, (0.0, 1.125));
+        assert_close(lines[1].first, (4.0, 0.125));
+        assert_close(lines[1].second, (4.0, 1.125));
+        assert_close(lines[2].first, (0.0, 1.0));
+        assert_close(lines[2].second, (4.0, 1.0));
+
+        //Arrows point out at both ends of the dimension line
+        let tips = arrow_tips(&top);
+        assert_eq!(tips.len(), 2);
+        assert_close(tips[0], (0.0, 1.0));
+        assert_close(tips[1], (4.0, 1.0));
+        assert!(top.elements.iter().all(|element| match &element.element {
+            Element2D::Poly(poly) => poly.pts[1].x > 0.0 && poly.pts[1].x < 4.0,
+            _ => true,
+        }));
+
+        let text = text_element(&top);
+        assert_eq!(text.text, "4000");
+        assert_close(text.position, (2.0, 1.125));
+        assert_eq!(text.alignment, TextAlignment::Center);
+        assert!(text.rotation.0.abs() < 1e-9);
+        assert_eq!(dim.get_bottom(), top);
+        assert!(dim.get_front().elements.is_empty());
+    }
+
+    #[test]
+    fn test_plan_reversed() {
+        //Going right to left, left is down, and the text still reads left to right above the line
+        let dim = dimension((4.0, 0.0), (0.0, 0.0), 1.0);
+        let top = dim.get_top();
+        let lines = line_elements(&top);
+        assert_close(lines[2].first, (4.0, -1.0));
+        assert_close(lines[2].second, (0.0, -1.0));
+        let text = text_element(&top);
+        assert_close(text.position, (2.0, -0.875));
+        assert!(text.rotation.0.abs() < 1e-9);
+
+        //Negative offsets go to the right instead, with the extension lines still running past
+        let dim = dimension((0.0, 0.0), (0.0, 3.0), -0.5);
+        let top = dim.get_top();
+        let lines = line_elements(&top);
+        assert_close(lines[0].first, (0.125, 0.0));
+        assert_close(lines[0].second, (0.625, 0.0));
+        assert_close(lines[2].first, (0.5, 0.0));
+        let text = text_element(&top);
+        assert_eq!(text.text, "3000");
+        assert!((text.rotation.0 - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+
+        //Close enough that the extension lines would start past the dimension line
+        let dim = dimension((0.0, 0.0), (1.0, 0.0), 0.1);
+        let lines = line_elements(&dim.get_top());
+        assert_close(lines[0].first, (0.0, 0.0));
+        assert_close(lines[0].second, (0.0, 0.225));
+    }
+
+    #[test]
+    fn test_zero_length() {
+        let dim = dimension((1.0, 1.0), (1.0, 1.0), 1.0);
+        assert_eq!(dim.get_text(), "0");
+        assert!(dim.get_top().elements.is_empty());
+    }
+}
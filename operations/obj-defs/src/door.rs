@@ -226,6 +226,13 @@ impl Data for Door {
         }
     }
 
+    fn deletes_with_ref(&self, ref_type: RefType, index: ResultInd) -> bool {
+        match ref_type {
+            RefType::ProfileLine => index == 0,
+            _ => false,
+        }
+    }
+
     fn set_associated_result_for_type(
         &mut self,
         ref_type: RefType,
@@ -423,4 +430,18 @@ impl DrawingViews for Door {
         ];
         DrawingData { elements }
     }
+
+    ///Only the door itself.  Its host wall draws the opening it sits in, along with the wall's
+    /// other edges.
+    fn get_projection(&self, projection: &Projection) -> DrawingData {
+        let (first, second, third, fourth) =
+            offset_line(&self.dir.line.pt_1, &self.dir.line.pt_2, self.width);
+        projection.project_edges(&prism_edges(&[first, second, third, fourth], self.height))
+    }
+
+    fn get_faces(&self) -> Vec<Vec<Point3f>> {
+        let (first, second, third, fourth) =
+            offset_line(&self.dir.line.pt_1, &self.dir.line.pt_2, self.width);
+        prism_faces(&[first, second, third, fourth], self.height)
+    }
 }
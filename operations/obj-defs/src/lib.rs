@@ -1,18 +1,28 @@
 pub use obj_traits::*;
 
+mod dimension;
 mod door;
 mod geom_kernel;
+mod mesh;
+mod section_cut;
 mod sheet;
 mod symbol_def;
 mod symbol_instance;
+mod text_note;
+mod underlay;
 mod viewport;
 mod visibility_group;
 mod wall;
+pub use dimension::Dimension;
 pub use door::Door;
 pub use geom_kernel::{new_geom_conn, GeomConn};
+pub use mesh::Mesh;
+pub use section_cut::{SectionCut, SectionDirection};
 pub use sheet::Sheet;
 pub use symbol_def::SymbolDef;
 pub use symbol_instance::SymbolInstance;
+pub use text_note::TextNote;
+pub use underlay::Underlay;
 pub use viewport::*;
 pub use visibility_group::VisibilityGroup;
 pub use wall::Wall;
@@ -0,0 +1,126 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+///Triangles brought in from another program, which there's no native object for.  Imported symbol
+/// files hold these, so instances of the symbol have something to show.  Nothing can reference
+/// anything inside one except its bounding box.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Mesh {
+    id: ObjID,
+    triangles: Vec<[Point3f; 3]>,
+}
+
+impl Mesh {
+    pub fn new(triangles: Vec<[Point3f; 3]>) -> Mesh {
+        let id = ObjID::new_v4();
+        Mesh { id, triangles }
+    }
+
+    pub fn get_triangles(&self) -> &Vec<[Point3f; 3]> {
+        &self.triangles
+    }
+}
+
+#[async_trait::async_trait]
+#[typetag::serde]
+impl Data for Mesh {
+    fn get_id(&self) -> &ObjID {
+        &self.id
+    }
+
+    fn reset_id(&mut self) {
+        self.id = ObjID::new_v4();
+    }
+
+    async fn update(&self, _conn: &mut dyn GeomKernel) -> Result<UpdateOutput, ObjError> {
+        let mut data = MeshData {
+            positions: Vec::new(),
+            indices: Vec::new(),
+            metadata: Some(json! ({
+                "type": "Mesh",
+                "traits": ["Position"],
+            })),
+        };
+        for triangle in &self.triangles {
+            for pt in triangle {
+                data.indices.push(data.indices.len() as u64);
+                data.push_pt(*pt);
+            }
+        }
+        Ok(UpdateOutput::Mesh { data })
+    }
+
+    fn get_result(&self, ref_type: RefType, result: ResultInd) -> Option<RefResult> {
+        match ref_type {
+            RefType::Drawable => Some(RefResult::Empty),
+            RefType::Existence => Some(RefResult::Empty),
+            RefType::AxisAlignedBoundBox => match result {
+                0 => Some(self.get_axis_aligned_bounding_box().as_result()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn get_results_for_type(&self, ref_type: RefType) -> Vec<RefResult> {
+        match ref_type {
+            RefType::Drawable => vec![RefResult::Empty],
+            RefType::Existence => vec![RefResult::Empty],
+            RefType::AxisAlignedBoundBox => {
+                vec![self.get_axis_aligned_bounding_box().as_result()]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn get_num_results_for_type(&self, ref_type: RefType) -> usize {
+        match ref_type {
+            RefType::Drawable => 1,
+            RefType::Existence => 1,
+            RefType::AxisAlignedBoundBox => 1,
+            _ => 0,
+        }
+    }
+
+    fn as_position(&self) -> Option<&dyn Position> {
+        Some(self)
+    }
+    fn as_position_mut(&mut self) -> Option<&mut dyn Position> {
+        Some(self)
+    }
+
+    fn data_clone(&self) -> DataBox {
+        Box::new(self.clone())
+    }
+}
+
+impl Position for Mesh {
+    fn move_obj(&mut self, delta: &Vector3f) {
+        for triangle in &mut self.triangles {
+            for pt in triangle.iter_mut() {
+                *pt += *delta;
+            }
+        }
+    }
+
+    fn get_axis_aligned_bounding_box(&self) -> Cube {
+        let mut pts = self.triangles.iter().flat_map(|triangle| triangle.iter());
+        let mut bbox = match pts.next() {
+            Some(first) => Cube {
+                bottom_left: *first,
+                top_right: *first,
+            },
+            None => return Cube::default(),
+        };
+        for pt in pts {
+            bbox.bottom_left.x = bbox.bottom_left.x.min(pt.x);
+            bbox.bottom_left.y = bbox.bottom_left.y.min(pt.y);
+            bbox.bottom_left.z = bbox.bottom_left.z.min(pt.z);
+            bbox.top_right.x = bbox.top_right.x.max(pt.x);
+            bbox.top_right.y = bbox.top_right.y.max(pt.y);
+            bbox.top_right.z = bbox.top_right.z.max(pt.z);
+        }
+        bbox
+    }
+}
@@ -0,0 +1,312 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+///Which side of the cut line a section looks toward, going from its first point to its second.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SectionDirection {
+    Left,
+    Right,
+}
+
+///A building section, cut straight down along a line in plan and looking toward one side of it as
+/// far as its depth.  It subscribes to the bounding boxes of the objects it cuts through, so it
+/// updates whenever one of them changes.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SectionCut {
+    id: ObjID,
+    pub first_pt: Point3f,
+    pub second_pt: Point3f,
+    pub depth: WorldCoord,
+    pub direction: SectionDirection,
+    cut_objs: Vec<Option<UpdatableInfo<Cube>>>,
+}
+
+impl SectionCut {
+    pub fn new(
+        first_pt: Point3f,
+        second_pt: Point3f,
+        depth: WorldCoord,
+        direction: SectionDirection,
+    ) -> SectionCut {
+        let id = ObjID::new_v4();
+        SectionCut {
+            id,
+            first_pt,
+            second_pt,
+            depth,
+            direction,
+            cut_objs: Vec::new(),
+        }
+    }
+
+    ///The direction the section looks in
+    pub fn get_forward(&self) -> Vector3f {
+        let right = get_perp_2d(&self.first_pt, &self.second_pt);
+        match self.direction {
+            SectionDirection::Left => -right,
+            SectionDirection::Right => right,
+        }
+    }
+
+    ///The vertical plane along the cut line, facing away from the direction the section looks in
+    pub fn get_cut_plane(&self) -> Plane {
+        let up = Vector3f::unit_z();
+        match self.direction {
+            SectionDirection::Left => Plane::new(self.first_pt, self.second_pt, self.first_pt + up),
+            SectionDirection::Right => {
+                Plane::new(self.second_pt, self.first_pt, self.second_pt + up)
+            }
+        }
+    }
+
+    ///An axonometric view from the middle of the cut line, with everything in front of it cut away.
+    pub fn get_projection(&self) -> Projection {
+        let target = self.first_pt + (self.second_pt - self.first_pt) / 2.0;
+        Projection {
+            camera_pos: target - self.get_forward(),
+            target,
+            perspective: false,
+            clip: Some(self.get_cut_plane()),
+            depth: Some(self.depth),
+        }
+    }
+
+    ///Whether the cut line passes through the box in plan
+    pub fn crosses(&self, bbox: &Cube) -> bool {
+        let dir = self.second_pt - self.first_pt;
+        let (mut start, mut end) = (0.0, 1.0);
+        let axes = [
+            (self.first_pt.x, dir.x, bbox.bottom_left.x, bbox.top_right.x),
+            (self.first_pt.y, dir.y, bbox.bottom_left.y, bbox.top_right.y),
+        ];
+        for (origin, delta, min, max) in axes.iter() {
+            if *delta == 0.0 {
+                if origin < min || origin > max {
+                    return false;
+                }
+                continue;
+            }
+            let first = (min - origin) / delta;
+            let second = (max - origin) / delta;
+            start = f64::max(start, first.min(second));
+            end = f64::min(end, first.max(second));
+        }
+        start <= end
+    }
+
+    ///The objects the section cuts through
+    pub fn get_cut_objs(&self) -> Vec<ObjID> {
+        self.cut_objs
+            .iter()
+            .filter_map(|cut_opt| cut_opt.as_ref().and_then(|cut| cut.refer))
+            .map(|refer| refer.id)
+            .collect()
+    }
+
+    ///Where the object's bounding box is kept in the section's references
+    pub fn get_cut_index(&self, obj: &ObjID) -> Option<ResultInd> {
+        self.cut_objs.iter().position(|cut_opt| match cut_opt {
+            Some(UpdatableInfo {
+                refer: Some(refer), ..
+            }) => refer.id == *obj,
+            _ => false,
+        })
+    }
+
+    ///Empties the slots of the objects whose latest bounding boxes the cut line no longer passes
+    /// through.  Returns whether there were any.
+    pub fn drop_uncut(&mut self) -> bool {
+        let mut dropped = false;
+        for index in 0..self.cut_objs.len() {
+            let uncut = match &self.cut_objs[index] {
+                Some(cut) => cut.refer.is_some() && !self.crosses(&cut.info),
+                None => false,
+            };
+            if uncut {
+                self.cut_objs[index] = None;
+                dropped = true;
+            }
+        }
+        dropped
+    }
+
+    ///A heavy line along the cut with a short tick at each end pointing the way the section looks.
+    fn get_plan(&self) -> DrawingData {
+        let forward = self.get_forward() * SECTION_MARK_LENGTH;
+        let mark = |first: Point3f, second: Point3f| DrawElement2D {
+            element: Element2D::Line(Line2D::new(x_y(&first), x_y(&second))),
+            line_thickness: 2.0,
+            line_color: RGBA::default(),
+            line_type: LineType::Solid,
+            fill_type: FillType::default(),
+        };
+        DrawingData {
+            elements: vec![
+                mark(self.first_pt, self.second_pt),
+                mark(self.first_pt, self.first_pt + forward),
+                mark(self.second_pt, self.second_pt + forward),
+            ],
+        }
+    }
+}
+
+//How long the ticks at the ends of a section's plan mark are, in meters.
+const SECTION_MARK_LENGTH: WorldCoord = 0.5;
+
+#[async_trait::async_trait]
+#[typetag::serde]
+impl Data for SectionCut {
+    fn get_id(&self) -> &ObjID {
+        &self.id
+    }
+
+    fn reset_id(&mut self) {
+        self.id = ObjID::new_v4();
+    }
+
+    async fn update(&self, _conn: &mut dyn GeomKernel) -> Result<UpdateOutput, ObjError> {
+        Ok(UpdateOutput::Other {
+            data: json! ({
+                "type": "SectionCut",
+                "obj": {
+                    "First": self.first_pt,
+                    "Second": self.second_pt,
+                    "Depth": self.depth,
+                    "Direction": self.direction,
+                    "Cuts": self.get_cut_objs().len()
+                }
+            }),
+        })
+    }
+
+    fn get_result(&self, ref_type: RefType, index: ResultInd) -> Option<RefResult> {
+        match ref_type {
+            RefType::Drawable => Some(RefResult::Empty),
+            RefType::Existence => Some(RefResult::Empty),
+            RefType::ProfileLine => match index {
+                0 => Some(Line::new(self.first_pt, self.second_pt).as_result()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn get_results_for_type(&self, ref_type: RefType) -> Vec<RefResult> {
+        match ref_type {
+            RefType::Drawable => vec![RefResult::Empty],
+            RefType::Existence => vec![RefResult::Empty],
+            RefType::ProfileLine => vec![Line::new(self.first_pt, self.second_pt).as_result()],
+            _ => Vec::new(),
+        }
+    }
+
+    fn get_num_results_for_type(&self, ref_type: RefType) -> usize {
+        match ref_type {
+            RefType::Drawable => 1,
+            RefType::Existence => 1,
+            RefType::ProfileLine => 1,
+            _ => 0,
+        }
+    }
+
+    fn clear_refs(&mut self) {
+        self.cut_objs.clear();
+    }
+
+    fn get_refs(&self) -> Vec<Option<Reference>> {
+        self.cut_objs
+            .iter()
+            .enumerate()
+            .map(|(index, cut_opt)| {
+                let refer = cut_opt.as_ref()?.refer?;
+                let owner = RefID::new(self.id, RefType::AxisAlignedBoundBox, index);
+                Some(Reference::new(owner, refer))
+            })
+            .collect()
+    }
+
+    fn add_ref(
+        &mut self,
+        ref_type: RefType,
+        result: RefResult,
+        other_ref: RefID,
+        _extra: &Option<RefResult>,
+    ) -> bool {
+        if let (RefType::AxisAlignedBoundBox, RefResult::Cube(bbox)) = (ref_type, &result) {
+            let mut cut = UpdatableInfo::new(*bbox);
+            cut.set_reference(result, other_ref);
+            self.cut_objs.push(Some(cut));
+            true
+        } else {
+            false
+        }
+    }
+
+    ///Like visibility groups, the slot is emptied rather than removed so the other refs stay put.
+    fn delete_ref(&mut self, ref_type: RefType, index: ResultInd) {
+        if let RefType::AxisAlignedBoundBox = ref_type {
+            if let Some(cut_opt) = self.cut_objs.get_mut(index) {
+                *cut_opt = None;
+            }
+        }
+    }
+
+    fn set_associated_result_for_type(
+        &mut self,
+        ref_type: RefType,
+        index: ResultInd,
+        result: Option<RefResult>,
+    ) {
+        if let RefType::AxisAlignedBoundBox = ref_type {
+            if let Some(cut_opt) = self.cut_objs.get_mut(index) {
+                match (cut_opt.as_mut(), result) {
+                    (Some(cut), Some(result)) => cut.update(Some(result)),
+                    _ => *cut_opt = None,
+                }
+            }
+        }
+    }
+
+    fn data_clone(&self) -> DataBox {
+        Box::new(self.clone())
+    }
+
+    fn as_drawing_views(&self) -> Option<&dyn DrawingViews> {
+        Some(self)
+    }
+}
+
+impl DrawingViews for SectionCut {
+    fn get_top(&self) -> DrawingData {
+        self.get_plan()
+    }
+
+    fn get_front(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_left(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_right(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_back(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_bottom(&self) -> DrawingData {
+        self.get_plan()
+    }
+}
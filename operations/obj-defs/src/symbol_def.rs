@@ -26,6 +26,10 @@ impl SymbolDef {
         }
     }
 
+    pub fn get_sym_file(&self) -> &FileID {
+        &self.sym_file
+    }
+
     pub fn set_bbox(&mut self, sym_file: FileID, change: ChangeID, bbox: Cube) {
         self.sym_file = sym_file;
         self.change = change;
@@ -10,7 +10,7 @@ use serde_json::json;
 pub struct SymbolInstance {
     id: ObjID,
     bbox: UpdatableInfo<Cube>,
-    transform: TransMat,
+    pub transform: TransMat,
 }
 
 impl SymbolInstance {
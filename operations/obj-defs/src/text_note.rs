@@ -0,0 +1,355 @@
+use crate::*;
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+///A note written in plan, optionally with a leader pointing at something.  The leader's tip is a
+/// ProfilePoint that can reference a point on another object, so it follows that object around.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextNote {
+    id: ObjID,
+    pub text: String,
+    pub position: Point3f,
+    pub text_style: TextStyle,
+    pub alignment: TextAlignment,
+    pub rotation: Radians,
+    pub leader: Option<UpdatableInfo<Point3f>>,
+}
+
+impl TextNote {
+    pub fn new(
+        text: String,
+        position: Point3f,
+        text_style: TextStyle,
+        alignment: TextAlignment,
+        rotation: Radians,
+    ) -> TextNote {
+        let id = ObjID::new_v4();
+        TextNote {
+            id,
+            text,
+            position,
+            text_style,
+            alignment,
+            rotation,
+            leader: None,
+        }
+    }
+
+    ///Points the leader at a point, which it follows from then on if it's a result on another object.
+    pub fn set_leader(&mut self, tip: Point3f, other_ref: Option<RefID>) {
+        let mut leader = UpdatableInfo::new(tip);
+        if let Some(other_ref) = other_ref {
+            leader.set_reference(tip.as_result(), other_ref);
+        }
+        self.leader = Some(leader);
+    }
+
+    ///The text, with a line from the leader's tip to its position and an arrow at the tip.
+    fn get_plan(&self) -> DrawingData {
+        let position = x_y(&self.position);
+        let mut elements = Vec::new();
+        if let Some(leader) = &self.leader {
+            let tip = x_y(&leader.info);
+            let length = (position - tip).magnitude();
+            if length > f64::EPSILON {
+                let dir = (position - tip) / length;
+                let arrow_length = self.text_style.height.min(length / 3.0);
+                elements.push(DrawElement2D::new_annotation(Element2D::Line(Line2D::new(
+                    tip, position,
+                ))));
+                elements.push(arrow(tip, dir, arrow_length));
+            }
+        }
+        elements.push(DrawElement2D::new_annotation(Element2D::Text(Text2D::new(
+            self.text.clone(),
+            position,
+            &self.text_style,
+            self.alignment,
+            self.rotation,
+        ))));
+        DrawingData { elements }
+    }
+}
+
+#[async_trait::async_trait]
+#[typetag::serde]
+impl Data for TextNote {
+    fn get_id(&self) -> &ObjID {
+        &self.id
+    }
+
+    fn reset_id(&mut self) {
+        self.id = ObjID::new_v4();
+    }
+
+    async fn update(&self, _conn: &mut dyn GeomKernel) -> Result<UpdateOutput, ObjError> {
+        Ok(UpdateOutput::Other {
+            data: json! ({
+                "type": "TextNote",
+                "obj": {
+                    "Text": self.text,
+                    "Position": self.position,
+                    "Leader": self.leader.as_ref().map(|leader| leader.info)
+                }
+            }),
+        })
+    }
+
+    fn get_result(&self, ref_type: RefType, index: ResultInd) -> Option<RefResult> {
+        match ref_type {
+            RefType::Drawable => Some(RefResult::Empty),
+            RefType::Existence => Some(RefResult::Empty),
+            RefType::AxisAlignedBoundBox => match index {
+                0 => Some(self.get_axis_aligned_bounding_box().as_result()),
+                _ => None,
+            },
+            RefType::ProfilePoint => match (index, &self.leader) {
+                (0, Some(leader)) => Some(leader.get_result()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn get_results_for_type(&self, ref_type: RefType) -> Vec<RefResult> {
+        match ref_type {
+            RefType::Drawable => vec![RefResult::Empty],
+            RefType::Existence => vec![RefResult::Empty],
+            RefType::AxisAlignedBoundBox => vec![self.get_axis_aligned_bounding_box().as_result()],
+            RefType::ProfilePoint => self
+                .leader
+                .iter()
+                .map(|leader| leader.get_result())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn get_num_results_for_type(&self, ref_type: RefType) -> usize {
+        match ref_type {
+            RefType::Drawable => 1,
+            RefType::Existence => 1,
+            RefType::AxisAlignedBoundBox => 1,
+            RefType::ProfilePoint => self.leader.iter().count(),
+            _ => 0,
+        }
+    }
+
+    fn clear_refs(&mut self) {
+        if let Some(leader) = &mut self.leader {
+            leader.refer = None;
+        }
+    }
+
+    fn get_refs(&self) -> Vec<Option<Reference>> {
+        let self_pt = RefID::new(self.id, RefType::ProfilePoint, 0);
+        vec![self
+            .leader
+            .as_ref()
+            .and_then(|leader| leader.refer)
+            .map(|other| Reference::new(self_pt, other))]
+    }
+
+    fn get_available_refs_for_type(&self, ref_type: RefType) -> Vec<ResultInd> {
+        match (ref_type, &self.leader) {
+            (RefType::ProfilePoint, Some(leader)) if leader.refer.is_none() => vec![0],
+            _ => Vec::new(),
+        }
+    }
+
+    fn set_ref(
+        &mut self,
+        ref_type: RefType,
+        index: ResultInd,
+        result: RefResult,
+        other_ref: RefID,
+        _extra: &Option<RefResult>,
+    ) {
+        if let (RefType::ProfilePoint, 0, Some(leader)) = (ref_type, index, &mut self.leader) {
+            leader.set_reference(result, other_ref);
+        }
+    }
+
+    ///The leader stays where it was when what it points at goes away.
+    fn delete_ref(&mut self, ref_type: RefType, index: ResultInd) {
+        if let (RefType::ProfilePoint, 0, Some(leader)) = (ref_type, index, &mut self.leader) {
+            leader.refer = None;
+        }
+    }
+
+    fn set_associated_result_for_type(
+        &mut self,
+        ref_type: RefType,
+        index: ResultInd,
+        result: Option<RefResult>,
+    ) {
+        if let (RefType::ProfilePoint, 0, Some(leader)) = (ref_type, index, &mut self.leader) {
+            leader.update(result);
+        }
+    }
+
+    fn data_clone(&self) -> DataBox {
+        Box::new(self.clone())
+    }
+
+    fn as_position(&self) -> Option<&dyn Position> {
+        Some(self)
+    }
+
+    fn as_position_mut(&mut self) -> Option<&mut dyn Position> {
+        Some(self)
+    }
+
+    fn as_drawing_views(&self) -> Option<&dyn DrawingViews> {
+        Some(self)
+    }
+}
+
+impl Position for TextNote {
+    ///A leader pointing at something keeps pointing at it, only the text moves.
+    fn move_obj(&mut self, delta: &Vector3f) {
+        self.position += *delta;
+        if let Some(leader) = &mut self.leader {
+            if leader.refer.is_none() {
+                leader.info += *delta;
+            }
+        }
+    }
+
+    ///Around the position and the leader's tip, as far as the text's height in every direction.
+    fn get_axis_aligned_bounding_box(&self) -> Cube {
+        let tip = match &self.leader {
+            Some(leader) => leader.info,
+            None => self.position,
+        };
+        let pad = Vector3f::new(1.0, 1.0, 1.0) * self.text_style.height;
+        Cube::new(
+            Point3f::new(
+                self.position.x.min(tip.x),
+                self.position.y.min(tip.y),
+                self.position.z.min(tip.z),
+            ) - pad,
+            Point3f::new(
+                self.position.x.max(tip.x),
+                self.position.y.max(tip.y),
+                self.position.z.max(tip.z),
+            ) + pad,
+        )
+    }
+}
+
+impl DrawingViews for TextNote {
+    fn get_top(&self) -> DrawingData {
+        self.get_plan()
+    }
+
+    fn get_front(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_left(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_right(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_back(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_bottom(&self) -> DrawingData {
+        self.get_plan()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note() -> TextNote {
+        TextNote::new(
+            String::from("Note"),
+            Point3f::new(2.0, 1.0, 0.0),
+            TextStyle::new(String::from("Arial"), 0.25),
+            TextAlignment::Left,
+            radians(0.0),
+        )
+    }
+
+    #[test]
+    fn test_plan() {
+        let mut note = note();
+        let top = note.get_top();
+        assert_eq!(top.elements.len(), 1);
+        match &top.elements[0].element {
+            Element2D::Text(text) => {
+                assert_eq!(text.text, "Note");
+                assert_eq!(text.position, Point2f::new(2.0, 1.0));
+                assert_eq!(text.alignment, TextAlignment::Left);
+            }
+            _ => panic!("Expected text"),
+        }
+
+        //The leader runs from its tip to the text, with the arrow at the tip
+        note.set_leader(Point3f::new(2.0, 4.0, 0.0), None);
+        let top = note.get_top();
+        assert_eq!(top.elements.len(), 3);
+        match &top.elements[0].element {
+            Element2D::Line(line) => {
+                assert_eq!(line.first, Point2f::new(2.0, 4.0));
+                assert_eq!(line.second, Point2f::new(2.0, 1.0));
+            }
+            _ => panic!("Expected leader line"),
+        }
+        match &top.elements[1].element {
+            Element2D::Poly(poly) => {
+                assert_eq!(poly.pts[0], Point2f::new(2.0, 4.0));
+                assert!(poly.pts.iter().all(|pt| pt.y <= 4.0 && pt.y >= 3.75 - 1e-9));
+            }
+            _ => panic!("Expected arrow"),
+        }
+        assert!(matches!(top.elements[2].element, Element2D::Text(_)));
+        assert_eq!(note.get_bottom(), top);
+        assert!(note.get_front().elements.is_empty());
+    }
+
+    #[test]
+    fn test_move_obj() {
+        let mut note = note();
+        note.set_leader(Point3f::new(0.0, 0.0, 0.0), None);
+        note.move_obj(&Vector3f::new(1.0, 0.0, 0.0));
+        assert_eq!(note.position, Point3f::new(3.0, 1.0, 0.0));
+        assert_eq!(
+            note.leader.as_ref().unwrap().info,
+            Point3f::new(1.0, 0.0, 0.0)
+        );
+
+        //A leader pointing at something stays put
+        let other = RefID::new(ObjID::new_v4(), RefType::ProfilePoint, 0);
+        note.set_leader(Point3f::new(0.0, 0.0, 0.0), Some(other));
+        note.move_obj(&Vector3f::new(1.0, 0.0, 0.0));
+        assert_eq!(note.position, Point3f::new(4.0, 1.0, 0.0));
+        assert_eq!(
+            note.leader.as_ref().unwrap().info,
+            Point3f::new(0.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            note.get_refs(),
+            vec![Some(Reference::new(
+                RefID::new(*note.get_id(), RefType::ProfilePoint, 0),
+                other
+            ))]
+        );
+    }
+}
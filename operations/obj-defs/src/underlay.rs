@@ -0,0 +1,214 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+///A 2D plan traced from another CAD file, drawn in the top view at ground level.  It can't be
+/// moved or edited, only snapped to.  Every straight segment is a ProfileLine, with its ends as the
+/// ProfilePoints at twice its index and the one after that.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Underlay {
+    id: ObjID,
+    pub name: String,
+    elements: Vec<Element2D>,
+}
+
+impl Underlay {
+    pub fn new(name: String, elements: Vec<Element2D>) -> Underlay {
+        let id = ObjID::new_v4();
+        Underlay { id, name, elements }
+    }
+
+    pub fn get_elements(&self) -> &Vec<Element2D> {
+        &self.elements
+    }
+
+    fn get_lines(&self) -> Vec<Line> {
+        let mut lines = Vec::new();
+        for element in &self.elements {
+            let outline = match element {
+                Element2D::Line(line) => {
+                    lines.push(flat_line(&line.first, &line.second));
+                    continue;
+                }
+                Element2D::Rect(rect) => vec![
+                    rect.bottom_left,
+                    Point2f::new(rect.top_right.x, rect.bottom_left.y),
+                    rect.top_right,
+                    Point2f::new(rect.bottom_left.x, rect.top_right.y),
+                ],
+                Element2D::Poly(poly) => poly.pts.clone(),
+                Element2D::Arc(..) | Element2D::Text(..) => continue,
+            };
+            for (index, pt) in outline.iter().enumerate() {
+                lines.push(flat_line(pt, &outline[(index + 1) % outline.len()]));
+            }
+        }
+        lines
+    }
+
+    fn get_points(&self) -> Vec<Point3f> {
+        self.get_lines()
+            .iter()
+            .flat_map(|line| vec![line.pt_1, line.pt_2])
+            .collect()
+    }
+
+    fn get_bounding_box(&self) -> Cube {
+        let mut pts: Vec<Point2f> = Vec::new();
+        for element in &self.elements {
+            match element {
+                Element2D::Line(line) => pts.extend(&[line.first, line.second]),
+                Element2D::Arc(arc) => pts.extend(&[
+                    Point2f::new(arc.center.x - arc.radius, arc.center.y - arc.radius),
+                    Point2f::new(arc.center.x + arc.radius, arc.center.y + arc.radius),
+                ]),
+                Element2D::Rect(rect) => pts.extend(&[rect.bottom_left, rect.top_right]),
+                Element2D::Poly(poly) => pts.extend(&poly.pts),
+                Element2D::Text(text) => pts.push(text.position),
+            }
+        }
+        let mut bbox = Cube::default();
+        if let Some(first) = pts.first() {
+            bbox.bottom_left = Point3f::new(first.x, first.y, 0.0);
+            bbox.top_right = bbox.bottom_left;
+        }
+        for pt in &pts {
+            bbox.bottom_left.x = bbox.bottom_left.x.min(pt.x);
+            bbox.bottom_left.y = bbox.bottom_left.y.min(pt.y);
+            bbox.top_right.x = bbox.top_right.x.max(pt.x);
+            bbox.top_right.y = bbox.top_right.y.max(pt.y);
+        }
+        bbox
+    }
+
+    ///Thin grey lines with nothing filled in, so the underlay stays behind what's modelled on it.
+    fn get_plan(&self) -> DrawingData {
+        let elements = self
+            .elements
+            .iter()
+            .map(|element| DrawElement2D {
+                element: element.clone(),
+                line_thickness: 0.5,
+                line_color: RGBA::new(128, 128, 128, 1.0),
+                line_type: LineType::Solid,
+                fill_type: FillType::Solid {
+                    color: RGBA::new(255, 255, 255, 0.0),
+                },
+            })
+            .collect();
+        DrawingData { elements }
+    }
+}
+
+fn flat_line(first: &Point2f, second: &Point2f) -> Line {
+    Line::new(
+        Point3f::new(first.x, first.y, 0.0),
+        Point3f::new(second.x, second.y, 0.0),
+    )
+}
+
+#[async_trait::async_trait]
+#[typetag::serde]
+impl Data for Underlay {
+    fn get_id(&self) -> &ObjID {
+        &self.id
+    }
+
+    fn reset_id(&mut self) {
+        self.id = ObjID::new_v4();
+    }
+
+    async fn update(&self, _conn: &mut dyn GeomKernel) -> Result<UpdateOutput, ObjError> {
+        Ok(UpdateOutput::Other {
+            data: json! ({
+                "type": "Underlay",
+                "obj": {
+                    "Name": self.name,
+                    "Elements": self.elements.len()
+                }
+            }),
+        })
+    }
+
+    fn get_result(&self, ref_type: RefType, index: ResultInd) -> Option<RefResult> {
+        match ref_type {
+            RefType::Drawable => Some(RefResult::Empty),
+            RefType::Existence => Some(RefResult::Empty),
+            RefType::AxisAlignedBoundBox => match index {
+                0 => Some(self.get_bounding_box().as_result()),
+                _ => None,
+            },
+            RefType::ProfilePoint => self.get_points().get(index).map(|pt| pt.as_result()),
+            RefType::ProfileLine => self.get_lines().get(index).map(|line| line.as_result()),
+            _ => None,
+        }
+    }
+
+    fn get_results_for_type(&self, ref_type: RefType) -> Vec<RefResult> {
+        match ref_type {
+            RefType::Drawable => vec![RefResult::Empty],
+            RefType::Existence => vec![RefResult::Empty],
+            RefType::AxisAlignedBoundBox => vec![self.get_bounding_box().as_result()],
+            RefType::ProfilePoint => self.get_points().iter().map(|pt| pt.as_result()).collect(),
+            RefType::ProfileLine => self
+                .get_lines()
+                .iter()
+                .map(|line| line.as_result())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn get_num_results_for_type(&self, ref_type: RefType) -> usize {
+        match ref_type {
+            RefType::Drawable => 1,
+            RefType::Existence => 1,
+            RefType::AxisAlignedBoundBox => 1,
+            RefType::ProfilePoint => 2 * self.get_lines().len(),
+            RefType::ProfileLine => self.get_lines().len(),
+            _ => 0,
+        }
+    }
+
+    fn data_clone(&self) -> DataBox {
+        Box::new(self.clone())
+    }
+
+    fn as_drawing_views(&self) -> Option<&dyn DrawingViews> {
+        Some(self)
+    }
+}
+
+impl DrawingViews for Underlay {
+    fn get_top(&self) -> DrawingData {
+        self.get_plan()
+    }
+
+    fn get_front(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_left(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_right(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_back(&self) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+
+    fn get_bottom(&self) -> DrawingData {
+        self.get_plan()
+    }
+}
@@ -10,10 +10,56 @@ pub enum ViewType {
     Right,
     Back,
     Bottom,
+    ///Axonometric unless it's in perspective
     Custom {
         camera_pos: Point3f,
         target: Point3f,
+        #[serde(default)]
+        perspective: bool,
+        #[serde(default)]
+        clip: Option<Plane>,
     },
+    ///Looks through the SectionCut with this ID
+    Section {
+        section: ObjID,
+    },
+}
+
+impl ViewType {
+    ///The object's drawing for this view.  Sections need their SectionCut as well, so objects can't
+    /// be drawn in them on their own.
+    pub fn get_drawing(&self, views: &dyn DrawingViews) -> Option<DrawingData> {
+        match self {
+            ViewType::Top => Some(views.get_top()),
+            ViewType::Front => Some(views.get_front()),
+            ViewType::Left => Some(views.get_left()),
+            ViewType::Right => Some(views.get_right()),
+            ViewType::Back => Some(views.get_back()),
+            ViewType::Bottom => Some(views.get_bottom()),
+            ViewType::Custom {
+                camera_pos,
+                target,
+                perspective,
+                clip,
+            } => Some(views.get_projection(&Projection {
+                camera_pos: *camera_pos,
+                target: *target,
+                perspective: *perspective,
+                clip: *clip,
+                depth: None,
+            })),
+            ViewType::Section { .. } => None,
+        }
+    }
+}
+
+///What to do with edges hidden behind other objects.  Plans are drawn as they are either way.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum HiddenLines {
+    #[default]
+    Remove,
+    Dash,
+    Show,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -26,6 +72,16 @@ pub struct Viewport {
     pub origin: Point2f,
     /// The display scale of the viewport, in a unitless fraction
     pub scale: f64,
+    /// The width and height of the viewport on the sheet in meters.  If this is None, the viewport reaches to the
+    /// bottom right corner of the sheet.
+    #[serde(default)]
+    pub size: Option<Point2f>,
+    #[serde(default)]
+    pub hidden_lines: HiddenLines,
+    /// The point in the view's drawing that shows up in the middle of the viewport.  If this is None, the middle of
+    /// everything drawn in the view does.
+    #[serde(default)]
+    pub center: Option<Point2f>,
 }
 
 impl Viewport {
@@ -37,8 +93,15 @@ impl Viewport {
             view,
             origin,
             scale,
+            size: None,
+            hidden_lines: HiddenLines::default(),
+            center: None,
         }
     }
+
+    pub fn get_sheet(&self) -> Option<&ObjID> {
+        self.sheet.as_ref()
+    }
 }
 
 #[async_trait::async_trait]
@@ -61,7 +124,10 @@ impl Data for Viewport {
                         "view": self.view,
                         "sheet": sheet_id.to_string(),
                         "origin": self.origin,
-                        "scale": self.scale
+                        "scale": self.scale,
+                        "size": self.size,
+                        "hidden_lines": self.hidden_lines,
+                        "center": self.center
                     }
                 }),
             }),
@@ -126,6 +192,13 @@ impl Data for Viewport {
         }
     }
 
+    fn deletes_with_ref(&self, ref_type: RefType, _index: ResultInd) -> bool {
+        match ref_type {
+            RefType::Existence => true,
+            _ => false,
+        }
+    }
+
     fn data_clone(&self) -> DataBox {
         Box::new(self.clone())
     }
@@ -25,6 +25,14 @@ impl Wall {
         }
     }
 
+    fn get_openings(&self) -> Vec<Plane> {
+        self.openings
+            .iter()
+            .flatten()
+            .map(|open| open.info)
+            .collect()
+    }
+
     fn get_wall_points(
         &self,
     ) -> (
@@ -487,4 +495,25 @@ impl DrawingViews for Wall {
             elements: vec![DrawElement2D::new_default(Element2D::Rect(rect))],
         }
     }
+
+    ///Hosted doors' openings are cut through the wall, so the doors can be seen in them.
+    fn get_projection(&self, projection: &Projection) -> DrawingData {
+        projection.project_edges(&prism_edges_with_openings(
+            &self.first_pt.info,
+            &self.second_pt.info,
+            self.width,
+            self.height,
+            &self.get_openings(),
+        ))
+    }
+
+    fn get_faces(&self) -> Vec<Vec<Point3f>> {
+        prism_faces_with_openings(
+            &self.first_pt.info,
+            &self.second_pt.info,
+            self.width,
+            self.height,
+            &self.get_openings(),
+        )
+    }
 }
@@ -7,10 +7,14 @@ use std::default::Default;
 pub type Point2f = cgmath::Point2<f64>;
 pub type Point3f = cgmath::Point3<f64>;
 pub type WorldCoord = f64;
+pub type Vector2f = cgmath::Vector2<f64>;
 pub type Vector3f = cgmath::Vector3<f64>;
 pub type TransMat = cgmath::Matrix4<f64>;
 pub type Radians = cgmath::Rad<f64>;
 
+//Perspective views leave out anything closer to the camera than this.
+const NEAR_DISTANCE: WorldCoord = 0.01;
+
 pub fn radians(angle: f64) -> Radians {
     cgmath::Rad(angle)
 }
@@ -89,6 +93,11 @@ impl Plane {
     pub fn new(pt_1: Point3f, pt_2: Point3f, pt_3: Point3f) -> Plane {
         Plane { pt_1, pt_2, pt_3 }
     }
+
+    ///Follows the right hand rule through the three points, and isn't normalized
+    pub fn normal(&self) -> Vector3f {
+        (self.pt_2 - self.pt_1).cross(self.pt_3 - self.pt_1)
+    }
 }
 
 impl Default for Plane {
@@ -307,12 +316,64 @@ impl Poly2D {
     }
 }
 
+///Which part of a text its position is at, along its baseline
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+pub enum TextAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+///How text is written.  The height is the text's size, in the same units as the drawing it's in.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextStyle {
+    pub font: String,
+    pub height: WorldCoord,
+}
+
+impl TextStyle {
+    pub fn new(font: String, height: WorldCoord) -> TextStyle {
+        TextStyle { font, height }
+    }
+}
+
+///A single line of text, rotated counterclockwise around its position.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Text2D {
+    pub text: String,
+    pub position: Point2f,
+    pub font: String,
+    pub height: WorldCoord,
+    pub alignment: TextAlignment,
+    pub rotation: Radians,
+}
+
+impl Text2D {
+    pub fn new(
+        text: String,
+        position: Point2f,
+        style: &TextStyle,
+        alignment: TextAlignment,
+        rotation: Radians,
+    ) -> Text2D {
+        Text2D {
+            text,
+            position,
+            font: style.font.clone(),
+            height: style.height,
+            alignment,
+            rotation,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Element2D {
     Line(Line2D),
     Arc(Arc2D),
     Rect(Rect2D),
     Poly(Poly2D),
+    Text(Text2D),
 }
 
 ///A polygon projected into drawing space, but still in world coordinates.
@@ -336,6 +397,33 @@ impl DrawElement2D {
             fill_type: FillType::default(),
         }
     }
+
+    ///Thinner than what's drawn by default, for annotations like dimensions and leaders
+    pub fn new_annotation(element: Element2D) -> DrawElement2D {
+        DrawElement2D {
+            line_thickness: ANNOTATION_LINE_THICKNESS,
+            ..DrawElement2D::new_default(element)
+        }
+    }
+}
+
+//Annotations are drawn thinner than what they annotate.
+const ANNOTATION_LINE_THICKNESS: f32 = 0.5;
+
+///A solid black arrowhead pointing at tip, running back from it along dir for length.
+pub fn arrow(tip: Point2f, dir: Vector2f, length: WorldCoord) -> DrawElement2D {
+    let base = tip + dir * length;
+    let spread = Vector2f::new(-dir.y, dir.x) * (length / 6.0);
+    DrawElement2D {
+        fill_type: FillType::Solid {
+            color: RGBA::default(),
+        },
+        ..DrawElement2D::new_annotation(Element2D::Poly(Poly2D::new(vec![
+            tip,
+            base + spread,
+            base - spread,
+        ])))
+    }
 }
 
 ///3D triangle mesh data to draw in the model view
@@ -371,6 +459,121 @@ pub struct DrawingRepresentations {
     pub bottom: Option<DrawingData>,
 }
 
+///A view of the model from any direction, for drawings other than the six axis views.  Drawings are
+/// on the plane through the target facing the camera, with X to the right and Y up, and are at full
+/// size where they cross that plane.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Projection {
+    pub camera_pos: Point3f,
+    pub target: Point3f,
+    ///Otherwise the view is axonometric, and how far things are from the camera doesn't matter
+    pub perspective: bool,
+    ///Anything on the side of this plane its normal points to is cut away
+    pub clip: Option<Plane>,
+    ///How far past the target things are still drawn.  Without it, everything is.
+    pub depth: Option<WorldCoord>,
+}
+
+impl Projection {
+    pub fn new(camera_pos: Point3f, target: Point3f) -> Projection {
+        Projection {
+            camera_pos,
+            target,
+            perspective: false,
+            clip: None,
+            depth: None,
+        }
+    }
+
+    ///Unit vectors to the right, up, and into the view.  Looking straight up or down, right is +X.
+    pub fn get_axes(&self) -> (Vector3f, Vector3f, Vector3f) {
+        let forward = self.target - self.camera_pos;
+        let forward = if forward.magnitude2() > 0.0 {
+            forward.normalize()
+        } else {
+            -Vector3f::unit_z()
+        };
+        let right = forward.cross(Vector3f::unit_z());
+        let right = if right.magnitude2() > 1e-12 {
+            right.normalize()
+        } else {
+            Vector3f::unit_x()
+        };
+        (right, right.cross(forward), forward)
+    }
+
+    ///How far in front of the camera the point is
+    pub fn get_depth(&self, pt: &Point3f) -> WorldCoord {
+        let (_, _, forward) = self.get_axes();
+        (pt - self.camera_pos).dot(forward)
+    }
+
+    ///Where the point lands on the drawing, if it's in front of the camera
+    pub fn project_point(&self, pt: &Point3f) -> Option<Point2f> {
+        let (right, up, forward) = self.get_axes();
+        let offset = pt - self.target;
+        let flat = Point2f::new(offset.dot(right), offset.dot(up));
+        if !self.perspective {
+            return Some(flat);
+        }
+        let depth = (pt - self.camera_pos).dot(forward);
+        if depth <= 0.0 {
+            return None;
+        }
+        let scale = (self.target - self.camera_pos).magnitude() / depth;
+        Some(Point2f::new(flat.x * scale, flat.y * scale))
+    }
+
+    ///The part of a 3D line that isn't cut away by the clipping plane, past the depth, or behind the
+    /// camera in perspective views.
+    pub fn clip_line(&self, line: &Line) -> Option<Line> {
+        let (_, _, forward) = self.get_axes();
+        let mut planes = Vec::new();
+        if let Some(clip) = &self.clip {
+            planes.push((clip.pt_1, clip.normal()));
+        }
+        if let Some(depth) = self.depth {
+            planes.push((self.target + forward * depth, forward));
+        }
+        if self.perspective {
+            planes.push((self.camera_pos + forward * NEAR_DISTANCE, -forward));
+        }
+        let (mut first, mut second) = (line.pt_1, line.pt_2);
+        for (origin, normal) in planes {
+            let first_dist = (first - origin).dot(normal);
+            let second_dist = (second - origin).dot(normal);
+            if first_dist > 0.0 && second_dist > 0.0 {
+                return None;
+            } else if first_dist > 0.0 {
+                first += (second - first) * (first_dist / (first_dist - second_dist));
+            } else if second_dist > 0.0 {
+                second += (first - second) * (second_dist / (second_dist - first_dist));
+            }
+        }
+        Some(Line::new(first, second))
+    }
+
+    ///The part of a 3D line that's in view, flattened onto the drawing.
+    pub fn project_line(&self, line: &Line) -> Option<Line2D> {
+        let clipped = self.clip_line(line)?;
+        Some(Line2D::new(
+            self.project_point(&clipped.pt_1)?,
+            self.project_point(&clipped.pt_2)?,
+        ))
+    }
+
+    ///Draws whatever is in view of the edges of a 3D shape.  Edges seen end on are left out.
+    pub fn project_edges(&self, edges: &[Line]) -> DrawingData {
+        let elements = edges
+            .iter()
+            .filter_map(|edge| self.project_line(edge))
+            .filter(|line| (line.second - line.first).magnitude2() > 1e-12)
+            .map(|line| DrawElement2D::new_default(Element2D::Line(line)))
+            .collect();
+        DrawingData { elements }
+    }
+}
+
 ///Used for symbol instances
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct InstanceData {
@@ -503,6 +706,278 @@ pub fn offset_line(
     (first, second, third, fourth)
 }
 
+///The edges of a prism standing on the four corners of its base.
+pub fn prism_edges(base: &[Point3f; 4], height: WorldCoord) -> Vec<Line> {
+    let vert_offset = Vector3f::new(0.0, 0.0, height);
+    let mut edges = Vec::with_capacity(12);
+    for (index, pt) in base.iter().enumerate() {
+        let next = base[(index + 1) % base.len()];
+        edges.push(Line::new(*pt, next));
+        edges.push(Line::new(pt + vert_offset, next + vert_offset));
+        edges.push(Line::new(*pt, pt + vert_offset));
+    }
+    edges
+}
+
+///The faces of a prism standing on the four corners of its base, as its base, its top and its sides.
+pub fn prism_faces(base: &[Point3f; 4], height: WorldCoord) -> Vec<Vec<Point3f>> {
+    let vert_offset = Vector3f::new(0.0, 0.0, height);
+    let mut faces = vec![
+        base.to_vec(),
+        base.iter().map(|pt| pt + vert_offset).collect(),
+    ];
+    for (index, pt) in base.iter().enumerate() {
+        let next = base[(index + 1) % base.len()];
+        faces.push(vec![*pt, next, next + vert_offset, pt + vert_offset]);
+    }
+    faces
+}
+
+//How close an opening has to come to the bottom or top of a wall to be cut out of it.
+const OPENING_TOLERANCE: WorldCoord = 1e-9;
+
+///Where an opening is along a wall, as its start and end along the wall from its first point, and
+/// its bottom and top above its base.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct OpeningSpan {
+    start: WorldCoord,
+    end: WorldCoord,
+    bottom: WorldCoord,
+    top: WorldCoord,
+}
+
+///Openings are the planes of whatever fills them, from their bottom edge between pt_1 and pt_2 up
+/// to pt_3, like a door's ProfilePlane.  They're cut to the wall, and left out if they miss it.
+fn opening_spans(
+    first_pt: &Point3f,
+    dir: &Vector3f,
+    length: WorldCoord,
+    height: WorldCoord,
+    openings: &[Plane],
+) -> Vec<OpeningSpan> {
+    let mut spans: Vec<OpeningSpan> = openings
+        .iter()
+        .map(|opening| {
+            let along_1 = (opening.pt_1 - first_pt).dot(*dir);
+            let along_2 = (opening.pt_2 - first_pt).dot(*dir);
+            OpeningSpan {
+                start: along_1.min(along_2).max(0.0),
+                end: along_1.max(along_2).min(length),
+                bottom: (opening.pt_1.z.min(opening.pt_2.z) - first_pt.z).max(0.0),
+                top: (opening.pt_3.z - first_pt.z).min(height),
+            }
+        })
+        .filter(|span| {
+            span.end - span.start > OPENING_TOLERANCE && span.top - span.bottom > OPENING_TOLERANCE
+        })
+        .collect();
+    spans.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+    spans
+}
+
+///The parts of the wall's length that aren't taken up by the given spans.
+fn solid_intervals<'a>(
+    length: WorldCoord,
+    spans: impl Iterator<Item = &'a OpeningSpan>,
+) -> Vec<(WorldCoord, WorldCoord)> {
+    let mut intervals = Vec::new();
+    let mut start = 0.0;
+    for span in spans {
+        if span.start > start {
+            intervals.push((start, span.start));
+        }
+        start = start.max(span.end);
+    }
+    if length > start {
+        intervals.push((start, length));
+    }
+    intervals
+}
+
+///Points on a wall by how far along it they are, which side they're on, and how high up.
+struct WallFrame {
+    first_pt: Point3f,
+    dir: Vector3f,
+    offset: Vector3f,
+}
+
+impl WallFrame {
+    fn at(&self, along: WorldCoord, side: WorldCoord, up: WorldCoord) -> Point3f {
+        self.first_pt + self.dir * along + self.offset * side + Vector3f::unit_z() * up
+    }
+}
+
+///The edges of a prism width to either side of the line from first_pt to second_pt, with openings
+/// cut all the way through it.  Openings shouldn't overlap each other.
+pub fn prism_edges_with_openings(
+    first_pt: &Point3f,
+    second_pt: &Point3f,
+    width: WorldCoord,
+    height: WorldCoord,
+    openings: &[Plane],
+) -> Vec<Line> {
+    let (first, second, third, fourth) = offset_line(first_pt, second_pt, width);
+    let length = (second_pt - first_pt).magnitude();
+    if openings.is_empty() || length == 0.0 {
+        return prism_edges(&[first, second, third, fourth], height);
+    }
+    let dir = (second_pt - first_pt) / length;
+    let frame = WallFrame {
+        first_pt: *first_pt,
+        dir,
+        offset: first - first_pt,
+    };
+    let spans = opening_spans(first_pt, &dir, length, height, openings);
+    let mut edges = Vec::new();
+    for along in &[0.0, length] {
+        for side in &[1.0, -1.0] {
+            edges.push(Line::new(
+                frame.at(*along, *side, 0.0),
+                frame.at(*along, *side, height),
+            ));
+        }
+        for up in &[0.0, height] {
+            edges.push(Line::new(
+                frame.at(*along, 1.0, *up),
+                frame.at(*along, -1.0, *up),
+            ));
+        }
+    }
+    //The long edges are broken where openings reach the bottom or top of the wall
+    for up in &[0.0, height] {
+        let cut = spans.iter().filter(|span| {
+            (*up == 0.0 && span.bottom <= OPENING_TOLERANCE)
+                || (*up == height && span.top >= height - OPENING_TOLERANCE)
+        });
+        for (start, end) in solid_intervals(length, cut) {
+            for side in &[1.0, -1.0] {
+                edges.push(Line::new(
+                    frame.at(start, *side, *up),
+                    frame.at(end, *side, *up),
+                ));
+            }
+        }
+    }
+    for span in &spans {
+        for side in &[1.0, -1.0] {
+            for along in &[span.start, span.end] {
+                edges.push(Line::new(
+                    frame.at(*along, *side, span.bottom),
+                    frame.at(*along, *side, span.top),
+                ));
+            }
+            if span.bottom > OPENING_TOLERANCE {
+                edges.push(Line::new(
+                    frame.at(span.start, *side, span.bottom),
+                    frame.at(span.end, *side, span.bottom),
+                ));
+            }
+            if span.top < height - OPENING_TOLERANCE {
+                edges.push(Line::new(
+                    frame.at(span.start, *side, span.top),
+                    frame.at(span.end, *side, span.top),
+                ));
+            }
+        }
+        for along in &[span.start, span.end] {
+            for up in &[span.bottom, span.top] {
+                edges.push(Line::new(
+                    frame.at(*along, 1.0, *up),
+                    frame.at(*along, -1.0, *up),
+                ));
+            }
+        }
+    }
+    edges
+}
+
+///The faces of the same prism as prism_edges_with_openings.  Faces with openings in them are split
+/// into the pieces around them, and the sides of the openings are faces too.
+pub fn prism_faces_with_openings(
+    first_pt: &Point3f,
+    second_pt: &Point3f,
+    width: WorldCoord,
+    height: WorldCoord,
+    openings: &[Plane],
+) -> Vec<Vec<Point3f>> {
+    let (first, second, third, fourth) = offset_line(first_pt, second_pt, width);
+    let length = (second_pt - first_pt).magnitude();
+    if openings.is_empty() || length == 0.0 {
+        return prism_faces(&[first, second, third, fourth], height);
+    }
+    let dir = (second_pt - first_pt) / length;
+    let frame = WallFrame {
+        first_pt: *first_pt,
+        dir,
+        offset: first - first_pt,
+    };
+    let spans = opening_spans(first_pt, &dir, length, height, openings);
+    //A rectangle along the wall on one side, from start to end and bottom to top
+    let side_face = |side: WorldCoord, start: WorldCoord, end: WorldCoord, bottom, top| {
+        vec![
+            frame.at(start, side, bottom),
+            frame.at(end, side, bottom),
+            frame.at(end, side, top),
+            frame.at(start, side, top),
+        ]
+    };
+    //A rectangle through the wall at one height, from start to end
+    let level_face = |up: WorldCoord, start: WorldCoord, end: WorldCoord| {
+        vec![
+            frame.at(start, 1.0, up),
+            frame.at(end, 1.0, up),
+            frame.at(end, -1.0, up),
+            frame.at(start, -1.0, up),
+        ]
+    };
+    //A rectangle through the wall at one point along it, from bottom to top
+    let cross_face = |along: WorldCoord, bottom: WorldCoord, top: WorldCoord| {
+        vec![
+            frame.at(along, 1.0, bottom),
+            frame.at(along, -1.0, bottom),
+            frame.at(along, -1.0, top),
+            frame.at(along, 1.0, top),
+        ]
+    };
+    let mut faces = vec![
+        cross_face(0.0, 0.0, height),
+        cross_face(length, 0.0, height),
+    ];
+    for up in &[0.0, height] {
+        let cut = spans.iter().filter(|span| {
+            (*up == 0.0 && span.bottom <= OPENING_TOLERANCE)
+                || (*up == height && span.top >= height - OPENING_TOLERANCE)
+        });
+        for (start, end) in solid_intervals(length, cut) {
+            faces.push(level_face(*up, start, end));
+        }
+    }
+    for side in &[1.0, -1.0] {
+        for (start, end) in solid_intervals(length, spans.iter()) {
+            faces.push(side_face(*side, start, end, 0.0, height));
+        }
+        for span in &spans {
+            if span.bottom > OPENING_TOLERANCE {
+                faces.push(side_face(*side, span.start, span.end, 0.0, span.bottom));
+            }
+            if span.top < height - OPENING_TOLERANCE {
+                faces.push(side_face(*side, span.start, span.end, span.top, height));
+            }
+        }
+    }
+    for span in &spans {
+        faces.push(cross_face(span.start, span.bottom, span.top));
+        faces.push(cross_face(span.end, span.bottom, span.top));
+        if span.bottom > OPENING_TOLERANCE {
+            faces.push(level_face(span.bottom, span.start, span.end));
+        }
+        if span.top < height - OPENING_TOLERANCE {
+            faces.push(level_face(span.top, span.start, span.end));
+        }
+    }
+    faces
+}
+
 pub fn get_axis_aligned_bound_box(
     first_pt: &Point3f,
     second_pt: &Point3f,
@@ -601,6 +1076,279 @@ mod tests {
         );
     }
 
+    fn assert_close_2d(actual: Point2f, expected: Point2f) {
+        assert!(
+            (actual - expected).magnitude() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn assert_close_3d(actual: Point3f, expected: Point3f) {
+        assert!(
+            (actual - expected).magnitude() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    //Looking along +Y at the origin from 10 away, so X is right and Z is up
+    fn front_projection() -> Projection {
+        Projection::new(Point3f::new(0.0, -10.0, 0.0), Point3f::new(0.0, 0.0, 0.0))
+    }
+
+    fn has_edge(edges: &[Line], first: Point3f, second: Point3f) -> bool {
+        edges.iter().any(|edge| {
+            ((edge.pt_1 - first).magnitude() < 1e-9 && (edge.pt_2 - second).magnitude() < 1e-9)
+                || ((edge.pt_1 - second).magnitude() < 1e-9
+                    && (edge.pt_2 - first).magnitude() < 1e-9)
+        })
+    }
+
+    fn face_area(face: &[Point3f]) -> f64 {
+        let mut sum = Vector3f::new(0.0, 0.0, 0.0);
+        for index in 1..face.len() - 1 {
+            sum += (face[index] - face[0]).cross(face[index + 1] - face[0]);
+        }
+        sum.magnitude() / 2.0
+    }
+
+    #[test]
+    fn test_project_point() {
+        let mut projection = front_projection();
+        let (right, up, forward) = projection.get_axes();
+        assert_eq!(right, Vector3f::unit_x());
+        assert_eq!(up, Vector3f::unit_z());
+        assert_eq!(forward, Vector3f::unit_y());
+
+        //Axonometric views don't care how far away things are
+        let near = projection.project_point(&Point3f::new(1.0, -5.0, 2.0));
+        let far = projection.project_point(&Point3f::new(1.0, 10.0, 2.0));
+        assert_eq!(near, Some(Point2f::new(1.0, 2.0)));
+        assert_eq!(far, Some(Point2f::new(1.0, 2.0)));
+        let behind = projection.project_point(&Point3f::new(1.0, -20.0, 2.0));
+        assert_eq!(behind, Some(Point2f::new(1.0, 2.0)));
+
+        //Perspective views are at full size at the target, and shrink with distance
+        projection.perspective = true;
+        let at_target = projection
+            .project_point(&Point3f::new(1.0, 0.0, 2.0))
+            .unwrap();
+        assert_close_2d(at_target, Point2f::new(1.0, 2.0));
+        let far = projection
+            .project_point(&Point3f::new(1.0, 10.0, 2.0))
+            .unwrap();
+        assert_close_2d(far, Point2f::new(0.5, 1.0));
+        let near = projection
+            .project_point(&Point3f::new(1.0, -5.0, 2.0))
+            .unwrap();
+        assert_close_2d(near, Point2f::new(2.0, 4.0));
+        assert_eq!(
+            projection.project_point(&Point3f::new(1.0, -20.0, 2.0)),
+            None
+        );
+        assert_eq!(
+            projection.project_point(&Point3f::new(1.0, -10.0, 2.0)),
+            None
+        );
+
+        //Straight down, right stays +X
+        let plan = Projection::new(Point3f::new(0.0, 0.0, 10.0), Point3f::new(0.0, 0.0, 0.0));
+        let (right, up, _) = plan.get_axes();
+        assert_eq!(right, Vector3f::unit_x());
+        assert_eq!(up, Vector3f::unit_y());
+    }
+
+    #[test]
+    fn test_clip_line() {
+        let mut projection = front_projection();
+        let through = Line::new(Point3f::new(0.0, -20.0, 0.0), Point3f::new(0.0, 20.0, 0.0));
+        assert_eq!(projection.clip_line(&through), Some(through));
+
+        //Past the depth is cut off
+        projection.depth = Some(5.0);
+        let clipped = projection.clip_line(&through).unwrap();
+        assert_close_3d(clipped.pt_1, Point3f::new(0.0, -20.0, 0.0));
+        assert_close_3d(clipped.pt_2, Point3f::new(0.0, 5.0, 0.0));
+        let beyond = Line::new(Point3f::new(0.0, 6.0, 0.0), Point3f::new(1.0, 8.0, 0.0));
+        assert_eq!(projection.clip_line(&beyond), None);
+
+        //In perspective, so is anything behind the camera or too close to it
+        projection.perspective = true;
+        let clipped = projection.clip_line(&through).unwrap();
+        assert_close_3d(clipped.pt_1, Point3f::new(0.0, -10.0 + NEAR_DISTANCE, 0.0));
+        assert_close_3d(clipped.pt_2, Point3f::new(0.0, 5.0, 0.0));
+        let behind = Line::new(Point3f::new(0.0, -20.0, 0.0), Point3f::new(1.0, -11.0, 0.0));
+        assert_eq!(projection.clip_line(&behind), None);
+
+        //And the side of the clipping plane its normal points to
+        projection.perspective = false;
+        projection.depth = None;
+        projection.clip = Some(Plane::new(
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(0.0, 1.0, 0.0),
+            Point3f::new(0.0, 0.0, 1.0),
+        ));
+        let across = Line::new(Point3f::new(-1.0, 0.0, 0.0), Point3f::new(1.0, 0.0, 0.0));
+        let clipped = projection.clip_line(&across).unwrap();
+        assert_close_3d(clipped.pt_1, Point3f::new(-1.0, 0.0, 0.0));
+        assert_close_3d(clipped.pt_2, Point3f::new(0.0, 0.0, 0.0));
+        let cut_away = Line::new(Point3f::new(1.0, 0.0, 0.0), Point3f::new(2.0, 0.0, 0.0));
+        assert_eq!(projection.clip_line(&cut_away), None);
+    }
+
+    #[test]
+    fn test_project_edges() {
+        let base = [
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(2.0, 0.0, 0.0),
+            Point3f::new(2.0, 1.0, 0.0),
+            Point3f::new(0.0, 1.0, 0.0),
+        ];
+        let edges = prism_edges(&base, 3.0);
+        assert_eq!(edges.len(), 12);
+        assert!(has_edge(&edges, base[0], base[1]));
+        assert!(has_edge(
+            &edges,
+            Point3f::new(2.0, 1.0, 3.0),
+            Point3f::new(0.0, 1.0, 3.0)
+        ));
+        assert!(has_edge(&edges, base[3], Point3f::new(0.0, 1.0, 3.0)));
+
+        //From the front, the four edges running away from the camera are seen end on
+        let drawing = front_projection().project_edges(&edges);
+        assert_eq!(drawing.elements.len(), 8);
+        for element in &drawing.elements {
+            match &element.element {
+                Element2D::Line(line) => {
+                    for pt in &[line.first, line.second] {
+                        assert!(pt.x >= -1e-9 && pt.x <= 2.0 + 1e-9);
+                        assert!(pt.y >= -1e-9 && pt.y <= 3.0 + 1e-9);
+                    }
+                }
+                _ => panic!("Expected only lines, found {:?}", element),
+            }
+        }
+
+        //Cutting the box in half along its depth leaves only its front edges
+        let mut projection = front_projection();
+        projection.depth = Some(0.5);
+        let drawing = projection.project_edges(&edges);
+        assert_eq!(drawing.elements.len(), 4);
+
+        //And nothing's left once it's all past the depth
+        projection.target = Point3f::new(0.0, -5.0, 0.0);
+        projection.depth = Some(1.0);
+        assert!(projection.project_edges(&edges).elements.is_empty());
+    }
+
+    #[test]
+    fn test_prism_faces() {
+        let base = [
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(2.0, 0.0, 0.0),
+            Point3f::new(2.0, 1.0, 0.0),
+            Point3f::new(0.0, 1.0, 0.0),
+        ];
+        let faces = prism_faces(&base, 3.0);
+        assert_eq!(faces.len(), 6);
+        assert_eq!(faces[0], base.to_vec());
+        assert!(faces[1].iter().all(|pt| pt.z == 3.0));
+        let area: f64 = faces.iter().map(|face| face_area(face)).sum();
+        assert!((area - 2.0 * (2.0 + 6.0 + 3.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_prism_with_openings() {
+        let first = Point3f::new(0.0, 0.0, 0.0);
+        let second = Point3f::new(4.0, 0.0, 0.0);
+        let no_openings = prism_edges_with_openings(&first, &second, 0.1, 3.0, &[]);
+        let (a, b, c, d) = offset_line(&first, &second, 0.1);
+        assert_eq!(no_openings, prism_edges(&[a, b, c, d], 3.0));
+
+        //A door from 1 to 2 along the wall, 2 high, and a window from 2.5 to 3.5, 1 to 2 high
+        let door = Plane::new(
+            Point3f::new(1.0, 0.0, 0.0),
+            Point3f::new(2.0, 0.0, 0.0),
+            Point3f::new(2.0, 0.0, 2.0),
+        );
+        let window = Plane::new(
+            Point3f::new(2.5, 0.0, 1.0),
+            Point3f::new(3.5, 0.0, 1.0),
+            Point3f::new(3.5, 0.0, 2.0),
+        );
+        let openings = [door, window];
+        let edges = prism_edges_with_openings(&first, &second, 0.1, 3.0, &openings);
+        let side = a.y;
+        //The bottom edges stop at the door, the top edges run the whole way
+        assert!(!has_edge(&edges, a, b));
+        assert!(has_edge(&edges, a, Point3f::new(1.0, side, 0.0)));
+        assert!(has_edge(&edges, Point3f::new(2.0, side, 0.0), b));
+        assert!(has_edge(
+            &edges,
+            Point3f::new(0.0, side, 3.0),
+            Point3f::new(4.0, side, 3.0)
+        ));
+        //The door's outline on both faces, without a sill, and its head through the wall
+        for y in &[side, -side] {
+            assert!(has_edge(
+                &edges,
+                Point3f::new(1.0, *y, 0.0),
+                Point3f::new(1.0, *y, 2.0)
+            ));
+            assert!(has_edge(
+                &edges,
+                Point3f::new(1.0, *y, 2.0),
+                Point3f::new(2.0, *y, 2.0)
+            ));
+            assert!(has_edge(
+                &edges,
+                Point3f::new(2.5, *y, 1.0),
+                Point3f::new(3.5, *y, 1.0)
+            ));
+        }
+        assert!(!has_edge(
+            &edges,
+            Point3f::new(1.0, side, 0.0),
+            Point3f::new(2.0, side, 0.0)
+        ));
+        assert!(has_edge(
+            &edges,
+            Point3f::new(1.0, side, 2.0),
+            Point3f::new(1.0, -side, 2.0)
+        ));
+        //12 for the box, the bottom split in two and 4 reveals for each opening, 6 outline edges
+        // on each face for the door and 8 for the window
+        assert_eq!(edges.len(), 12 + 2 + 4 + 4 + 2 * (3 + 4));
+
+        //The faces lose the openings on both sides and the door's bit of floor, and gain their
+        // insides
+        let faces = prism_faces_with_openings(&first, &second, 0.1, 3.0, &openings);
+        let area: f64 = faces.iter().map(|face| face_area(face)).sum();
+        let solid = 2.0 * (4.0 * 0.2 + 4.0 * 3.0 + 0.2 * 3.0);
+        let cut = 2.0 * (2.0 + 1.0) + 1.0 * 0.2;
+        let reveals = 0.2 * (2.0 + 2.0 + 1.0) + 0.2 * (1.0 + 1.0 + 1.0 + 1.0);
+        assert!((area - (solid - cut + reveals)).abs() < 1e-9);
+
+        //Openings past the end of the wall are cut to it
+        let past = Plane::new(
+            Point3f::new(3.5, 0.0, 0.0),
+            Point3f::new(5.0, 0.0, 0.0),
+            Point3f::new(5.0, 0.0, 4.0),
+        );
+        let edges = prism_edges_with_openings(&first, &second, 0.1, 3.0, &[past]);
+        assert!(has_edge(
+            &edges,
+            Point3f::new(3.5, side, 0.0),
+            Point3f::new(3.5, side, 3.0)
+        ));
+        assert!(!edges
+            .iter()
+            .any(|edge| edge.pt_1.x > 4.0 + 1e-9 || edge.pt_2.x > 4.0 + 1e-9));
+    }
+
     #[test]
     fn test_get_axis_aligned_bound_box() {
         let pt_1 = Point3f::new(0.0, 0.0, 0.0);
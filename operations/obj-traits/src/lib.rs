@@ -145,6 +145,18 @@ pub trait DrawingViews {
     fn get_right(&self) -> DrawingData;
     fn get_back(&self) -> DrawingData;
     fn get_bottom(&self) -> DrawingData;
+    ///The drawing for a view from any direction.  Objects with no 3D shape have nothing to show.
+    #[allow(unused_variables)]
+    fn get_projection(&self, projection: &Projection) -> DrawingData {
+        DrawingData {
+            elements: Vec::new(),
+        }
+    }
+    ///The flat faces of the object's 3D shape, which hide what's behind them when drawings are
+    /// composed.  Objects without any are drawn as they are.
+    fn get_faces(&self) -> Vec<Vec<Point3f>> {
+        Vec::new()
+    }
     fn get_views(&self, flags: ViewFlags) -> DrawingRepresentations {
         let top = if flags.contains(ViewFlags::TOP) {
             Some(self.get_top())
@@ -258,6 +270,12 @@ pub trait Data: std::fmt::Debug + Send + Sync + downcast_rs::Downcast {
 
     fn delete_ref(&mut self, ref_type: RefType, index: ResultInd) {}
 
+    ///Whether this object should be deleted along with whatever the given reference points to,
+    /// like a door with the wall it's hosted on.
+    fn deletes_with_ref(&self, ref_type: RefType, index: ResultInd) -> bool {
+        false
+    }
+
     fn set_associated_result_for_type(
         &mut self,
         ref_type: RefType,
@@ -0,0 +1,571 @@
+use crate::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+//Dash lengths and hatch spacing in drawing units.  Views are drawn in meters of the model, and
+//sheets in meters of paper.
+const VIEW_PATTERN_LENGTH: f64 = 0.1;
+const SHEET_PATTERN_LENGTH: f64 = 0.003;
+
+//The lineweights DXF allows, in hundredths of a millimeter.
+const LINEWEIGHTS: &[i32] = &[
+    0, 5, 9, 13, 15, 18, 20, 25, 30, 35, 40, 50, 53, 60, 70, 80, 90, 100, 106, 120, 140, 158, 200,
+    211,
+];
+
+//How wide a line of thickness 1 prints, in hundredths of a millimeter.
+const LINEWEIGHT_PER_THICKNESS: f32 = 25.0;
+
+const SHEET_LAYER: &str = "SHEET";
+
+fn lineweight(thickness: f32) -> i32 {
+    let wanted = (thickness * LINEWEIGHT_PER_THICKNESS).round() as i32;
+    *LINEWEIGHTS
+        .iter()
+        .min_by_key(|weight| (*weight - wanted).abs())
+        .unwrap()
+}
+
+fn true_color(color: &RGBA) -> u32 {
+    (color.r as u32) << 16 | (color.g as u32) << 8 | color.b as u32
+}
+
+///Lines with the same color and thickness share a layer, which carries both.
+fn layer_name(color: &RGBA, weight: i32) -> String {
+    format!("LINES-{:06X}-{:03}", true_color(color), weight)
+}
+
+///Where a view's drawing coordinates end up in the DXF.
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    origin: Point2f,
+    scale: f64,
+}
+
+impl Placement {
+    fn apply(&self, pt: &Point2f) -> Point2f {
+        Point2f::new(
+            self.origin.x + pt.x * self.scale,
+            self.origin.y + pt.y * self.scale,
+        )
+    }
+}
+
+struct DxfWriter {
+    next_handle: u64,
+    model_space: u64,
+    paper_space: u64,
+    pattern_length: f64,
+    layers: BTreeMap<String, (RGBA, i32)>,
+    linetypes: BTreeSet<String>,
+    fonts: BTreeSet<String>,
+    entities: String,
+}
+
+fn pair(out: &mut String, code: i32, value: impl Display) {
+    out.push_str(&format!("{:>3}\n{}\n", code, value));
+}
+
+impl DxfWriter {
+    fn new(pattern_length: f64) -> DxfWriter {
+        let mut writer = DxfWriter {
+            next_handle: 1,
+            model_space: 0,
+            paper_space: 0,
+            pattern_length,
+            layers: BTreeMap::new(),
+            linetypes: BTreeSet::new(),
+            fonts: BTreeSet::new(),
+            entities: String::new(),
+        };
+        writer.model_space = writer.handle();
+        writer.paper_space = writer.handle();
+        writer
+    }
+
+    fn handle(&mut self) -> u64 {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        handle
+    }
+
+    fn entity_start(&mut self, kind: &str, layer: &str, line_type: &LineType) {
+        let handle = self.handle();
+        let mut out = String::new();
+        pair(&mut out, 0, kind);
+        pair(&mut out, 5, format!("{:X}", handle));
+        pair(&mut out, 330, format!("{:X}", self.model_space));
+        pair(&mut out, 100, "AcDbEntity");
+        pair(&mut out, 8, layer);
+        if let LineType::Dashed { name } = line_type {
+            self.linetypes.insert(name.clone());
+            pair(&mut out, 6, name);
+        }
+        self.entities.push_str(&out);
+    }
+
+    fn point(&mut self, code: i32, pt: &Point2f) {
+        pair(&mut self.entities, code, pt.x);
+        pair(&mut self.entities, code + 10, pt.y);
+    }
+
+    fn line(&mut self, layer: &str, line_type: &LineType, first: &Point2f, second: &Point2f) {
+        self.entity_start("LINE", layer, line_type);
+        pair(&mut self.entities, 100, "AcDbLine");
+        self.point(10, first);
+        pair(&mut self.entities, 30, 0.0);
+        self.point(11, second);
+        pair(&mut self.entities, 31, 0.0);
+    }
+
+    fn arc(&mut self, layer: &str, line_type: &LineType, arc: &Arc2D, placement: &Placement) {
+        let sweep = arc.end_angle.0 - arc.start_angle.0;
+        let full = sweep.abs() >= 2.0 * std::f64::consts::PI;
+        self.entity_start(if full { "CIRCLE" } else { "ARC" }, layer, line_type);
+        pair(&mut self.entities, 100, "AcDbCircle");
+        self.point(10, &placement.apply(&arc.center));
+        pair(&mut self.entities, 30, 0.0);
+        pair(&mut self.entities, 40, arc.radius * placement.scale);
+        if !full {
+            pair(&mut self.entities, 100, "AcDbArc");
+            pair(&mut self.entities, 50, arc.start_angle.0.to_degrees());
+            pair(&mut self.entities, 51, arc.end_angle.0.to_degrees());
+        }
+    }
+
+    ///Text gets a style named after its font.
+    fn text(&mut self, layer: &str, text: &Text2D, placement: &Placement) {
+        self.fonts.insert(text.font.clone());
+        self.entity_start("TEXT", layer, &LineType::Solid);
+        let position = placement.apply(&text.position);
+        let out = &mut self.entities;
+        pair(out, 100, "AcDbText");
+        pair(out, 10, position.x);
+        pair(out, 20, position.y);
+        pair(out, 30, 0.0);
+        pair(out, 40, text.height * placement.scale);
+        pair(out, 1, &text.text);
+        pair(out, 50, text.rotation.0.to_degrees());
+        pair(out, 7, &text.font);
+        let justification = match text.alignment {
+            TextAlignment::Left => 0,
+            TextAlignment::Center => 1,
+            TextAlignment::Right => 2,
+        };
+        pair(out, 72, justification);
+        //Anything but left aligned text is placed by its alignment point instead
+        pair(out, 11, position.x);
+        pair(out, 21, position.y);
+        pair(out, 31, 0.0);
+        pair(out, 100, "AcDbText");
+    }
+
+    fn polyline(&mut self, layer: &str, line_type: &LineType, pts: &[Point2f]) {
+        self.entity_start("LWPOLYLINE", layer, line_type);
+        pair(&mut self.entities, 100, "AcDbPolyline");
+        pair(&mut self.entities, 90, pts.len());
+        pair(&mut self.entities, 70, 1);
+        pair(&mut self.entities, 43, 0.0);
+        for pt in pts {
+            self.point(10, pt);
+        }
+    }
+
+    ///We don't have definitions for named hatch patterns, so every hatch keeps its name but is
+    /// drawn with 45 degree lines.
+    fn hatch(&mut self, layer: &str, name: &str, pts: &[Point2f]) {
+        self.entity_start("HATCH", layer, &LineType::Solid);
+        let out = &mut self.entities;
+        pair(out, 100, "AcDbHatch");
+        pair(out, 10, 0.0);
+        pair(out, 20, 0.0);
+        pair(out, 30, 0.0);
+        pair(out, 210, 0.0);
+        pair(out, 220, 0.0);
+        pair(out, 230, 1.0);
+        pair(out, 2, name);
+        pair(out, 70, 0);
+        pair(out, 71, 0);
+        pair(out, 91, 1);
+        //An external boundary made of a polyline
+        pair(out, 92, 3);
+        pair(out, 72, 0);
+        pair(out, 73, 1);
+        pair(out, 93, pts.len());
+        for pt in pts {
+            pair(out, 10, pt.x);
+            pair(out, 20, pt.y);
+        }
+        pair(out, 97, 0);
+        pair(out, 75, 0);
+        pair(out, 76, 1);
+        pair(out, 52, 0.0);
+        pair(out, 41, 1.0);
+        pair(out, 77, 0);
+        pair(out, 78, 1);
+        pair(out, 53, 45.0);
+        pair(out, 43, 0.0);
+        pair(out, 44, 0.0);
+        let offset = self.pattern_length * std::f64::consts::FRAC_1_SQRT_2;
+        pair(out, 45, -offset);
+        pair(out, 46, offset);
+        pair(out, 79, 0);
+        pair(out, 98, 0);
+    }
+
+    fn layer(&mut self, color: &RGBA, thickness: f32) -> String {
+        let weight = lineweight(thickness);
+        let name = layer_name(color, weight);
+        self.layers
+            .entry(name.clone())
+            .or_insert_with(|| (*color, weight));
+        name
+    }
+
+    fn element(&mut self, element: &DrawElement2D, placement: &Placement) {
+        let layer = self.layer(&element.line_color, element.line_thickness);
+        let line_type = &element.line_type;
+        let outline = match &element.element {
+            Element2D::Line(line) => {
+                let first = placement.apply(&line.first);
+                let second = placement.apply(&line.second);
+                self.line(&layer, line_type, &first, &second);
+                None
+            }
+            Element2D::Arc(arc) => {
+                self.arc(&layer, line_type, arc, placement);
+                None
+            }
+            Element2D::Text(text) => {
+                self.text(&layer, text, placement);
+                None
+            }
+            Element2D::Rect(rect) => Some(vec![
+                placement.apply(&rect.bottom_left),
+                placement.apply(&Point2f::new(rect.top_right.x, rect.bottom_left.y)),
+                placement.apply(&rect.top_right),
+                placement.apply(&Point2f::new(rect.bottom_left.x, rect.top_right.y)),
+            ]),
+            Element2D::Poly(poly) => Some(poly.pts.iter().map(|pt| placement.apply(pt)).collect()),
+        };
+        if let Some(pts) = outline {
+            //Solid fills are left out, since the default white one is on nearly everything.
+            if let FillType::Hatch { name } = &element.fill_type {
+                self.hatch(&layer, name, &pts);
+            }
+            self.polyline(&layer, line_type, &pts);
+        }
+    }
+
+    fn drawing(
+        &mut self,
+        objs: &[DataBox],
+        view: &ViewType,
+        hidden: HiddenLines,
+        placement: &Placement,
+    ) {
+        for element in &compose_view(objs, view, hidden) {
+            self.element(element, placement);
+        }
+    }
+
+    fn table(&mut self, out: &mut String, name: &str, records: &[String]) {
+        let handle = self.handle();
+        pair(out, 0, "TABLE");
+        pair(out, 2, name);
+        pair(out, 5, format!("{:X}", handle));
+        pair(out, 330, 0);
+        pair(out, 100, "AcDbSymbolTable");
+        pair(out, 70, records.len());
+        if name == "DIMSTYLE" {
+            pair(out, 100, "AcDbDimStyleTable");
+        }
+        for record in records {
+            out.push_str(record);
+        }
+        pair(out, 0, "ENDTAB");
+    }
+
+    fn record(&mut self, kind: &str, subclass: &str, name: &str) -> String {
+        let handle = self.handle();
+        let mut out = String::new();
+        pair(&mut out, 0, kind);
+        pair(&mut out, 5, format!("{:X}", handle));
+        pair(&mut out, 100, "AcDbSymbolTableRecord");
+        pair(&mut out, 100, subclass);
+        pair(&mut out, 2, name);
+        pair(&mut out, 70, 0);
+        out
+    }
+
+    fn linetype(&mut self, name: &str, description: &str, dashes: &[f64]) -> String {
+        let mut out = self.record("LTYPE", "AcDbLinetypeTableRecord", name);
+        pair(&mut out, 3, description);
+        pair(&mut out, 72, 65);
+        pair(&mut out, 73, dashes.len());
+        pair(
+            &mut out,
+            40,
+            dashes.iter().fold(0.0, |total, dash| total + dash.abs()),
+        );
+        for dash in dashes {
+            pair(&mut out, 49, dash);
+            pair(&mut out, 74, 0);
+        }
+        out
+    }
+
+    fn block(&mut self, out: &mut String, name: &str, record: u64) {
+        for (kind, subclass) in &[("BLOCK", "AcDbBlockBegin"), ("ENDBLK", "AcDbBlockEnd")] {
+            let handle = self.handle();
+            pair(out, 0, kind);
+            pair(out, 5, format!("{:X}", handle));
+            pair(out, 330, format!("{:X}", record));
+            pair(out, 100, "AcDbEntity");
+            pair(out, 8, 0);
+            pair(out, 100, subclass);
+            if *kind == "BLOCK" {
+                pair(out, 2, name);
+                pair(out, 70, 0);
+                pair(out, 10, 0.0);
+                pair(out, 20, 0.0);
+                pair(out, 30, 0.0);
+                pair(out, 3, name);
+                pair(out, 1, "");
+            }
+        }
+    }
+
+    fn finish(mut self) -> String {
+        let mut tables = String::new();
+        pair(&mut tables, 0, "SECTION");
+        pair(&mut tables, 2, "TABLES");
+        self.table(&mut tables, "VPORT", &[]);
+
+        let mut linetypes = vec![
+            self.linetype("ByBlock", "", &[]),
+            self.linetype("ByLayer", "", &[]),
+            self.linetype("Continuous", "Solid line", &[]),
+        ];
+        let dashes = [self.pattern_length, -self.pattern_length / 2.0];
+        for name in std::mem::take(&mut self.linetypes) {
+            linetypes.push(self.linetype(&name, "__ __ __", &dashes));
+        }
+        self.table(&mut tables, "LTYPE", &linetypes);
+
+        let mut layers = Vec::new();
+        let mut layer_styles = vec![(String::from("0"), (RGBA::default(), -3))];
+        layer_styles.extend(std::mem::take(&mut self.layers));
+        for (name, (color, weight)) in layer_styles {
+            let mut layer = self.record("LAYER", "AcDbLayerTableRecord", &name);
+            pair(&mut layer, 62, 7);
+            pair(&mut layer, 420, true_color(&color));
+            pair(&mut layer, 6, "Continuous");
+            pair(&mut layer, 370, weight);
+            layers.push(layer);
+        }
+        self.table(&mut tables, "LAYER", &layers);
+
+        let mut styles = Vec::new();
+        let fonts = std::mem::take(&mut self.fonts);
+        let mut fonts: Vec<(&str, &str)> = fonts
+            .iter()
+            .filter(|font| font.as_str() != "Standard")
+            .map(|font| (font.as_str(), font.as_str()))
+            .collect();
+        fonts.insert(0, ("Standard", "txt"));
+        for (name, font) in fonts {
+            let mut style = self.record("STYLE", "AcDbTextStyleTableRecord", name);
+            pair(&mut style, 40, 0.0);
+            pair(&mut style, 41, 1.0);
+            pair(&mut style, 50, 0.0);
+            pair(&mut style, 71, 0);
+            pair(&mut style, 42, 2.5);
+            pair(&mut style, 3, font);
+            pair(&mut style, 4, "");
+            styles.push(style);
+        }
+        self.table(&mut tables, "STYLE", &styles);
+        self.table(&mut tables, "VIEW", &[]);
+        self.table(&mut tables, "UCS", &[]);
+        let app = self.record("APPID", "AcDbRegAppTableRecord", "ACAD");
+        self.table(&mut tables, "APPID", &[app]);
+        self.table(&mut tables, "DIMSTYLE", &[]);
+
+        let mut block_records = Vec::new();
+        for (name, handle) in &[
+            ("*Model_Space", self.model_space),
+            ("*Paper_Space", self.paper_space),
+        ] {
+            let mut record = String::new();
+            pair(&mut record, 0, "BLOCK_RECORD");
+            pair(&mut record, 5, format!("{:X}", handle));
+            pair(&mut record, 100, "AcDbSymbolTableRecord");
+            pair(&mut record, 100, "AcDbBlockTableRecord");
+            pair(&mut record, 2, name);
+            block_records.push(record);
+        }
+        self.table(&mut tables, "BLOCK_RECORD", &block_records);
+        pair(&mut tables, 0, "ENDSEC");
+
+        let mut blocks = String::new();
+        pair(&mut blocks, 0, "SECTION");
+        pair(&mut blocks, 2, "BLOCKS");
+        self.block(&mut blocks, "*Model_Space", self.model_space);
+        self.block(&mut blocks, "*Paper_Space", self.paper_space);
+        pair(&mut blocks, 0, "ENDSEC");
+
+        let mut objects = String::new();
+        let root = self.handle();
+        let groups = self.handle();
+        pair(&mut objects, 0, "SECTION");
+        pair(&mut objects, 2, "OBJECTS");
+        pair(&mut objects, 0, "DICTIONARY");
+        pair(&mut objects, 5, format!("{:X}", root));
+        pair(&mut objects, 330, 0);
+        pair(&mut objects, 100, "AcDbDictionary");
+        pair(&mut objects, 281, 1);
+        pair(&mut objects, 3, "ACAD_GROUP");
+        pair(&mut objects, 350, format!("{:X}", groups));
+        pair(&mut objects, 0, "DICTIONARY");
+        pair(&mut objects, 5, format!("{:X}", groups));
+        pair(&mut objects, 330, format!("{:X}", root));
+        pair(&mut objects, 100, "AcDbDictionary");
+        pair(&mut objects, 281, 1);
+        pair(&mut objects, 0, "ENDSEC");
+
+        let mut out = String::new();
+        pair(&mut out, 0, "SECTION");
+        pair(&mut out, 2, "HEADER");
+        pair(&mut out, 9, "$ACADVER");
+        pair(&mut out, 1, "AC1015");
+        pair(&mut out, 9, "$HANDSEED");
+        pair(&mut out, 5, format!("{:X}", self.next_handle));
+        //Meters
+        pair(&mut out, 9, "$INSUNITS");
+        pair(&mut out, 70, 6);
+        pair(&mut out, 9, "$MEASUREMENT");
+        pair(&mut out, 70, 1);
+        pair(&mut out, 0, "ENDSEC");
+        out.push_str(&tables);
+        out.push_str(&blocks);
+        pair(&mut out, 0, "SECTION");
+        pair(&mut out, 2, "ENTITIES");
+        out.push_str(&self.entities);
+        pair(&mut out, 0, "ENDSEC");
+        out.push_str(&objects);
+        pair(&mut out, 0, "EOF");
+        out
+    }
+}
+
+///Writes the drawings of every object for the view as a DXF file, in model coordinates.  Lines
+/// go on a layer for each color and thickness, dashed lines use a linetype named after their
+/// dash, and hatched rects and polys get a HATCH under their outline.
+pub fn export_view_dxf(objs: &[DataBox], view: &ViewType, hidden: HiddenLines) -> String {
+    let mut writer = DxfWriter::new(VIEW_PATTERN_LENGTH);
+    let placement = Placement {
+        origin: Point2f::new(0.0, 0.0),
+        scale: 1.0,
+    };
+    writer.drawing(objs, view, hidden, &placement);
+    writer.finish()
+}
+
+///Writes the sheet as a DXF file in paper meters, with its outline and each of its viewports'
+/// views.  Views are placed the same way they are for printing, see compose_sheet, and clipped to
+/// their viewports.  Sheets and viewports measure down from the top left, while DXF measures up
+/// from the bottom left.
+pub fn export_sheet_dxf(sheet_id: &ObjID, objs: &[DataBox]) -> Result<String, ObjError> {
+    let sheet = compose_sheet(sheet_id, objs)?;
+    let mut writer = DxfWriter::new(SHEET_PATTERN_LENGTH);
+    let size = sheet.size;
+    let outline = [
+        Point2f::new(0.0, 0.0),
+        Point2f::new(size.x, 0.0),
+        Point2f::new(size.x, size.y),
+        Point2f::new(0.0, size.y),
+    ];
+    writer.polyline(SHEET_LAYER, &LineType::Solid, &outline);
+    writer.layers.insert(
+        String::from(SHEET_LAYER),
+        (RGBA::default(), lineweight(1.0)),
+    );
+
+    for viewport in &sheet.viewports {
+        let origin = viewport.drawing_origin();
+        let placement = Placement {
+            origin: Point2f::new(origin.x, size.y - origin.y),
+            scale: viewport.scale,
+        };
+        for element in &viewport.clipped_elements() {
+            writer.element(element, &placement);
+        }
+    }
+    Ok(writer.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///The points of every entity of the kind that isn't on the sheet's layer.
+    fn drawn_points(dxf: &str, kind: &str) -> Vec<Point2f> {
+        let pairs: Vec<&str> = dxf.lines().map(|line| line.trim()).collect();
+        let mut results = Vec::new();
+        let mut index = 0;
+        while index + 1 < pairs.len() {
+            if pairs[index] != "0" || pairs[index + 1] != kind {
+                index += 2;
+                continue;
+            }
+            let mut layer = "";
+            let mut pts = Vec::new();
+            let mut x = 0.0;
+            index += 2;
+            while index + 1 < pairs.len() && pairs[index] != "0" {
+                let value = pairs[index + 1];
+                match pairs[index] {
+                    "8" => layer = value,
+                    "10" | "11" => x = value.parse::<f64>().unwrap(),
+                    "20" | "21" => pts.push(Point2f::new(x, value.parse::<f64>().unwrap())),
+                    _ => (),
+                }
+                index += 2;
+            }
+            if layer != SHEET_LAYER {
+                results.extend(pts);
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn test_export_sheet_dxf() {
+        let sheet = Sheet::new(String::from("A4"), Point2f::new(0.297, 0.21));
+        let sheet_id = *sheet.get_id();
+        //Well above the drawing's origin, and far longer than the viewport is wide
+        let wall = Wall::new(
+            Point3f::new(-100.0, 10.0, 0.0),
+            Point3f::new(100.0, 10.0, 0.0),
+            0.2,
+            3.0,
+        );
+        let mut viewport = Viewport::new(sheet_id, ViewType::Top, Point2f::new(0.01, 0.01), 0.01);
+        viewport.center = Some(Point2f::new(0.0, 10.0));
+        let objs: Vec<DataBox> = vec![Box::new(sheet), Box::new(wall), Box::new(viewport)];
+        let dxf = export_sheet_dxf(&sheet_id, &objs).unwrap();
+        assert!(dxf.ends_with("EOF\n"));
+
+        //The viewport runs from 0.01 to 0.297 across and 0 to 0.2 up from the bottom.  The wall
+        // is drawn from 9.8 to 10.2 in the middle of it, and cut off at its sides.
+        let pts = drawn_points(&dxf, "LWPOLYLINE");
+        assert!(!pts.is_empty());
+        for pt in &pts {
+            assert!(pt.x >= 0.01 - 1e-9 && pt.x <= 0.297 + 1e-9, "{:?}", pt);
+            assert!(pt.y >= 0.098 - 1e-9 && pt.y <= 0.102 + 1e-9, "{:?}", pt);
+        }
+        assert!(pts.iter().any(|pt| (pt.x - 0.01).abs() < 1e-9));
+        assert!(pts.iter().any(|pt| (pt.x - 0.297).abs() < 1e-9));
+    }
+}
@@ -0,0 +1,470 @@
+use crate::*;
+use cgmath::{InnerSpace, Vector2};
+use std::collections::BTreeMap;
+
+#[derive(Debug)]
+pub struct DxfImport {
+    pub underlay: Underlay,
+    ///Entity types that can't be traced, with how many of each were left out.
+    pub skipped: BTreeMap<String, usize>,
+}
+
+///The group codes and values of an entity, after its type.
+type Group = [(i32, String)];
+
+///Reads the group code and value pairs that make up an ASCII DXF file.
+fn parse_pairs(text: &str) -> Result<Vec<(i32, String)>, ObjError> {
+    let mut lines = text.lines();
+    let mut pairs = Vec::new();
+    while let Some(code) = lines.next() {
+        let code = code.trim();
+        if code.is_empty() {
+            continue;
+        }
+        let code = code
+            .parse()
+            .map_err(|_| ObjError::Other(format!("Invalid DXF group code {:?}", code)))?;
+        let value = lines
+            .next()
+            .ok_or_else(|| ObjError::Other(format!("DXF group code {} has no value", code)))?;
+        pairs.push((code, String::from(value.trim())));
+    }
+    Ok(pairs)
+}
+
+fn value(group: &Group, code: i32) -> Option<&str> {
+    group
+        .iter()
+        .find(|(found, _)| *found == code)
+        .map(|(_, value)| value.as_str())
+}
+
+fn number(group: &Group, code: i32) -> Option<f64> {
+    value(group, code).and_then(|value| value.parse().ok())
+}
+
+///What one drawing unit is in meters, from $INSUNITS.  Unitless drawings are taken to be in meters.
+fn length_scale(pairs: &[(i32, String)]) -> f64 {
+    let units = pairs
+        .windows(2)
+        .find(|pair| pair[0].0 == 9 && pair[0].1 == "$INSUNITS")
+        .and_then(|pair| pair[1].1.parse::<i32>().ok());
+    match units {
+        Some(1) => 0.0254,
+        Some(2) => 0.3048,
+        Some(3) => 1609.344,
+        Some(4) => 0.001,
+        Some(5) => 0.01,
+        Some(7) => 1000.0,
+        Some(8) => 0.000_000_025_4,
+        Some(9) => 0.000_025_4,
+        Some(10) => 0.9144,
+        Some(14) => 0.1,
+        _ => 1.0,
+    }
+}
+
+///Arcs, circles and polylines are drawn in their own coordinate system, which for flat drawings is
+/// either the world's or the world's seen from below, so its X axis is flipped.
+fn is_mirrored(group: &Group) -> bool {
+    number(group, 230).map(|z| z < 0.0).unwrap_or(false)
+}
+
+struct Tracer {
+    scale: f64,
+    mirrored: bool,
+}
+
+impl Tracer {
+    fn point(&self, x: f64, y: f64) -> Point2f {
+        let x = if self.mirrored { -x } else { x };
+        Point2f::new(x * self.scale, y * self.scale)
+    }
+
+    fn arc(&self, center: Point2f, radius: f64, start: f64, end: f64) -> Element2D {
+        let (start, end) = if self.mirrored {
+            (std::f64::consts::PI - end, std::f64::consts::PI - start)
+        } else {
+            (start, end)
+        };
+        let end = if end <= start {
+            end + 2.0 * std::f64::consts::PI
+        } else {
+            end
+        };
+        Element2D::Arc(Arc2D {
+            center,
+            radius: radius * self.scale,
+            start_angle: radians(start),
+            end_angle: radians(end),
+        })
+    }
+
+    ///The arc between two polyline vertices, where the bulge is the tangent of a quarter of the
+    /// angle it sweeps, and it goes counterclockwise when that's positive.
+    fn bulge(&self, first: Point2f, second: Point2f, bulge: f64) -> Element2D {
+        let chord = second - first;
+        let offset = (1.0 - bulge * bulge) / (4.0 * bulge);
+        let center = first + chord / 2.0 + Vector2::new(-chord.y, chord.x) * offset;
+        let angle = |pt: Point2f| (pt.y - center.y).atan2(pt.x - center.x);
+        let (start, end) = if bulge > 0.0 {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        self.arc(
+            self.point(center.x, center.y),
+            (first - center).magnitude(),
+            angle(start),
+            angle(end),
+        )
+    }
+
+    fn polyline(&self, group: &Group) -> Vec<Element2D> {
+        let closed = number(group, 70).map(|flags| flags as i32 & 1 == 1) == Some(true);
+        let mut vertices: Vec<(Point2f, f64)> = Vec::new();
+        let mut x = None;
+        for (code, value) in group {
+            match code {
+                10 => x = value.parse::<f64>().ok(),
+                20 => {
+                    if let (Some(x), Ok(y)) = (x.take(), value.parse::<f64>()) {
+                        vertices.push((Point2f::new(x, y), 0.0));
+                    }
+                }
+                42 => {
+                    if let (Some(last), Ok(bulge)) = (vertices.last_mut(), value.parse()) {
+                        last.1 = bulge;
+                    }
+                }
+                _ => (),
+            }
+        }
+        if closed && vertices.len() > 2 && vertices.iter().all(|(_, bulge)| *bulge == 0.0) {
+            let pts = vertices
+                .iter()
+                .map(|(pt, _)| self.point(pt.x, pt.y))
+                .collect();
+            return vec![Element2D::Poly(Poly2D { pts })];
+        }
+        let count = if closed {
+            vertices.len()
+        } else {
+            vertices.len().saturating_sub(1)
+        };
+        (0..count)
+            .map(|index| {
+                let (first, bulge) = vertices[index];
+                let (second, _) = vertices[(index + 1) % vertices.len()];
+                if bulge == 0.0 {
+                    Element2D::Line(Line2D {
+                        first: self.point(first.x, first.y),
+                        second: self.point(second.x, second.y),
+                    })
+                } else {
+                    self.bulge(first, second, bulge)
+                }
+            })
+            .collect()
+    }
+
+    fn trace(&self, entity_type: &str, group: &Group) -> Option<Vec<Element2D>> {
+        let at = |x_code, y_code| Some(self.point(number(group, x_code)?, number(group, y_code)?));
+        match entity_type {
+            "LINE" => {
+                //Lines are always in world coordinates.
+                let tracer = Tracer {
+                    scale: self.scale,
+                    mirrored: false,
+                };
+                let first = tracer.point(number(group, 10)?, number(group, 20)?);
+                let second = tracer.point(number(group, 11)?, number(group, 21)?);
+                Some(vec![Element2D::Line(Line2D { first, second })])
+            }
+            "LWPOLYLINE" => Some(self.polyline(group)),
+            "ARC" => Some(vec![self.arc(
+                at(10, 20)?,
+                number(group, 40)?,
+                number(group, 50)?.to_radians(),
+                number(group, 51)?.to_radians(),
+            )]),
+            "CIRCLE" => Some(vec![self.arc(
+                at(10, 20)?,
+                number(group, 40)?,
+                0.0,
+                2.0 * std::f64::consts::PI,
+            )]),
+            _ => None,
+        }
+    }
+}
+
+///Traces the lines, polylines, arcs and circles in the model space of a DXF file into an underlay,
+/// converted to meters and flattened onto the ground.  Anything else, including blocks, is left out.
+pub fn import_dxf_underlay(name: String, text: &str) -> Result<DxfImport, ObjError> {
+    let pairs = parse_pairs(text)?;
+    let scale = length_scale(&pairs);
+    let start = pairs
+        .windows(2)
+        .position(|pair| {
+            pair[0] == (0, String::from("SECTION")) && pair[1] == (2, String::from("ENTITIES"))
+        })
+        .ok_or_else(|| ObjError::Other(String::from("DXF file has no ENTITIES section")))?;
+    let mut elements = Vec::new();
+    let mut skipped = BTreeMap::new();
+    let mut rest = &pairs[start + 2..];
+    while let Some(((_, entity_type), after)) = rest.split_first() {
+        let end = after
+            .iter()
+            .position(|(code, _)| *code == 0)
+            .unwrap_or(after.len());
+        let group = &after[..end];
+        rest = &after[end..];
+        if entity_type == "ENDSEC" {
+            break;
+        }
+        //Group 67 is set on entities in paper space.
+        if value(group, 67) == Some("1") {
+            continue;
+        }
+        let tracer = Tracer {
+            scale,
+            mirrored: is_mirrored(group),
+        };
+        match tracer.trace(entity_type, group) {
+            Some(traced) => elements.extend(traced),
+            None => *skipped.entry(entity_type.clone()).or_insert(0) += 1,
+        }
+    }
+    Ok(DxfImport {
+        underlay: Underlay::new(name, elements),
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    fn assert_close(actual: Point2f, expected: Point2f) {
+        assert!(
+            (actual - expected).magnitude() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    ///A DXF file in millimeters with the entities, each given as its type and then its group codes
+    /// and values.
+    fn dxf(entities: &[&[(i32, &str)]]) -> String {
+        let mut text = String::from("0\nSECTION\n2\nHEADER\n9\n$INSUNITS\n70\n4\n0\nENDSEC\n");
+        text.push_str("0\nSECTION\n2\nENTITIES\n");
+        for entity in entities {
+            for (code, value) in entity.iter() {
+                text.push_str(&format!("{}\n{}\n", code, value));
+            }
+        }
+        text.push_str("0\nENDSEC\n0\nEOF\n");
+        text
+    }
+
+    fn import(entities: &[&[(i32, &str)]]) -> Vec<Element2D> {
+        let import = import_dxf_underlay(String::from("plan"), &dxf(entities)).unwrap();
+        import.underlay.get_elements().clone()
+    }
+
+    ///The center, radius and the points at the start, middle and end of the arc.
+    fn arc_points(element: &Element2D) -> (Point2f, f64, [Point2f; 3]) {
+        let arc = match element {
+            Element2D::Arc(arc) => arc,
+            other => panic!("Expected an arc, got {:?}", other),
+        };
+        let at = |angle: f64| arc.center + Vector2::new(angle.cos(), angle.sin()) * arc.radius;
+        let (start, end) = (arc.start_angle.0, arc.end_angle.0);
+        (
+            arc.center,
+            arc.radius,
+            [at(start), at((start + end) / 2.0), at(end)],
+        )
+    }
+
+    #[test]
+    fn test_polyline_bulges() {
+        let quarter = format!("{}", (PI / 8.0).tan());
+        let elements = import(&[
+            //Counterclockwise half circle, which goes below the chord
+            &[
+                (0, "LWPOLYLINE"),
+                (90, "2"),
+                (70, "0"),
+                (10, "0"),
+                (20, "0"),
+                (42, "1"),
+                (10, "2000"),
+                (20, "0"),
+            ],
+            //Clockwise, which goes above
+            &[
+                (0, "LWPOLYLINE"),
+                (90, "2"),
+                (70, "0"),
+                (10, "0"),
+                (20, "0"),
+                (42, "-1"),
+                (10, "2000"),
+                (20, "0"),
+            ],
+            //A quarter turn counterclockwise around the origin, and back in a straight line
+            &[
+                (0, "LWPOLYLINE"),
+                (90, "2"),
+                (70, "1"),
+                (10, "1000"),
+                (20, "0"),
+                (42, &quarter),
+                (10, "0"),
+                (20, "1000"),
+            ],
+        ]);
+        assert_eq!(elements.len(), 4);
+
+        let (center, radius, [start, middle, end]) = arc_points(&elements[0]);
+        assert_close(center, Point2f::new(1.0, 0.0));
+        assert!((radius - 1.0).abs() < 1e-9);
+        assert_close(start, Point2f::new(0.0, 0.0));
+        assert_close(middle, Point2f::new(1.0, -1.0));
+        assert_close(end, Point2f::new(2.0, 0.0));
+
+        let (center, _, [start, middle, end]) = arc_points(&elements[1]);
+        assert_close(center, Point2f::new(1.0, 0.0));
+        assert_close(start, Point2f::new(2.0, 0.0));
+        assert_close(middle, Point2f::new(1.0, 1.0));
+        assert_close(end, Point2f::new(0.0, 0.0));
+
+        let (center, _, [start, _, end]) = arc_points(&elements[2]);
+        assert_close(center, Point2f::new(0.0, 0.0));
+        assert_close(start, Point2f::new(1.0, 0.0));
+        assert_close(end, Point2f::new(0.0, 1.0));
+        match &elements[3] {
+            Element2D::Line(line) => {
+                assert_close(line.first, Point2f::new(0.0, 1.0));
+                assert_close(line.second, Point2f::new(1.0, 0.0));
+            }
+            other => panic!("Expected a line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_mirrored() {
+        let elements = import(&[
+            //Seen from below, so X is flipped
+            &[
+                (0, "ARC"),
+                (10, "1000"),
+                (20, "0"),
+                (40, "1000"),
+                (50, "0"),
+                (51, "90"),
+                (210, "0"),
+                (220, "0"),
+                (230, "-1"),
+            ],
+            &[
+                (0, "LWPOLYLINE"),
+                (90, "2"),
+                (70, "0"),
+                (10, "0"),
+                (20, "0"),
+                (42, "1"),
+                (10, "2000"),
+                (20, "0"),
+                (210, "0"),
+                (220, "0"),
+                (230, "-1"),
+            ],
+            //Lines are in world coordinates whichever way they're extruded
+            &[
+                (0, "LINE"),
+                (10, "1000"),
+                (20, "0"),
+                (11, "2000"),
+                (21, "0"),
+                (230, "-1"),
+            ],
+        ]);
+        assert_eq!(elements.len(), 3);
+
+        //The quarter from (2, 0) to (1, 1) ends up going from (-1, 1) to (-2, 0)
+        let (center, _, [start, _, end]) = arc_points(&elements[0]);
+        assert_close(center, Point2f::new(-1.0, 0.0));
+        assert_close(start, Point2f::new(-1.0, 1.0));
+        assert_close(end, Point2f::new(-2.0, 0.0));
+        match &elements[0] {
+            Element2D::Arc(arc) => {
+                assert!((arc.end_angle.0 - arc.start_angle.0 - FRAC_PI_2).abs() < 1e-9)
+            }
+            _ => unreachable!(),
+        }
+
+        //Still below the chord, which now runs to (-2, 0)
+        let (center, _, [start, middle, end]) = arc_points(&elements[1]);
+        assert_close(center, Point2f::new(-1.0, 0.0));
+        assert_close(start, Point2f::new(-2.0, 0.0));
+        assert_close(middle, Point2f::new(-1.0, -1.0));
+        assert_close(end, Point2f::new(0.0, 0.0));
+
+        match &elements[2] {
+            Element2D::Line(line) => {
+                assert_close(line.first, Point2f::new(1.0, 0.0));
+                assert_close(line.second, Point2f::new(2.0, 0.0));
+            }
+            other => panic!("Expected a line, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_import_dxf_underlay() {
+        let elements = import(&[
+            &[
+                (0, "LWPOLYLINE"),
+                (90, "3"),
+                (70, "1"),
+                (10, "0"),
+                (20, "0"),
+                (10, "1000"),
+                (20, "0"),
+                (10, "1000"),
+                (20, "1000"),
+            ],
+            &[(0, "CIRCLE"), (10, "0"), (20, "0"), (40, "500")],
+            //Paper space
+            &[
+                (0, "LINE"),
+                (67, "1"),
+                (10, "0"),
+                (20, "0"),
+                (11, "1"),
+                (21, "1"),
+            ],
+        ]);
+        assert_eq!(elements.len(), 2);
+        match &elements[0] {
+            Element2D::Poly(poly) => assert_eq!(poly.pts.len(), 3),
+            other => panic!("Expected a poly, got {:?}", other),
+        }
+        match &elements[1] {
+            Element2D::Arc(arc) => {
+                assert!((arc.radius - 0.5).abs() < 1e-9);
+                assert!((arc.end_angle.0 - arc.start_angle.0 - 2.0 * PI).abs() < 1e-9);
+            }
+            other => panic!("Expected an arc, got {:?}", other),
+        }
+
+        let text = dxf(&[&[(0, "TEXT"), (10, "0"), (20, "0")]]);
+        let import = import_dxf_underlay(String::from("plan"), &text).unwrap();
+        assert_eq!(import.skipped.get("TEXT"), Some(&1));
+        assert!(import_dxf_underlay(String::from("plan"), "0\nEOF\n").is_err());
+    }
+}
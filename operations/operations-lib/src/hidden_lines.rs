@@ -0,0 +1,701 @@
+use crate::*;
+use cgmath::InnerSpace;
+
+//How close in meters points have to be to count as touching.  Edges that are exactly behind the
+//edge of a face, like the back corners of a wall in its elevation, are drawn rather than hidden.
+const TOUCHING: WorldCoord = 1e-6;
+
+//How hidden edges are drawn when they're dashed.
+const HIDDEN_LINE_TYPE: &str = "HIDDEN";
+const HIDDEN_LINE_THICKNESS: f32 = 0.5;
+
+///Where the view is seen from, and how points get onto its drawing.  Axis views are flattened the
+/// same way objects draw their own, so composed and uncomposed drawings line up.
+enum Viewer {
+    Axis {
+        forward: Vector3f,
+        flatten: fn(&Point3f) -> Point2f,
+    },
+    Custom(Projection),
+}
+
+impl Viewer {
+    ///Plans are left as they are, since they're cut at the floor and show symbols rather than
+    /// edges.  Sections are seen through their SectionCut's projection instead.
+    fn new(view: &ViewType) -> Option<Viewer> {
+        let axis = |forward, flatten| Some(Viewer::Axis { forward, flatten });
+        match view {
+            ViewType::Top | ViewType::Bottom | ViewType::Section { .. } => None,
+            ViewType::Front => axis(Vector3f::unit_y(), x_z),
+            ViewType::Back => axis(-Vector3f::unit_y(), x_z),
+            ViewType::Left => axis(Vector3f::unit_x(), y_z),
+            ViewType::Right => axis(-Vector3f::unit_x(), y_z),
+            ViewType::Custom {
+                camera_pos,
+                target,
+                perspective,
+                clip,
+            } => Some(Viewer::Custom(Projection {
+                camera_pos: *camera_pos,
+                target: *target,
+                perspective: *perspective,
+                clip: *clip,
+                depth: None,
+            })),
+        }
+    }
+
+    fn flatten(&self, pt: &Point3f) -> Option<Point2f> {
+        match self {
+            Viewer::Axis { flatten, .. } => Some(flatten(pt)),
+            Viewer::Custom(projection) => projection.project_point(pt),
+        }
+    }
+
+    ///How far the point is from the camera.  Only perspective views need it, so that points along
+    /// an edge can be found from where they land on the drawing.
+    fn depth(&self, pt: &Point3f) -> WorldCoord {
+        match self {
+            Viewer::Custom(projection) if projection.perspective => projection.get_depth(pt),
+            _ => 1.0,
+        }
+    }
+
+    ///The direction from the point to the camera, and how many of it away the camera is.
+    fn toward_camera(&self, pt: &Point3f) -> (Vector3f, f64) {
+        match self {
+            Viewer::Axis { forward, .. } => (-*forward, f64::INFINITY),
+            Viewer::Custom(projection) if projection.perspective => {
+                (projection.camera_pos - pt, 1.0)
+            }
+            Viewer::Custom(projection) => {
+                let (_, _, forward) = projection.get_axes();
+                (-forward, f64::INFINITY)
+            }
+        }
+    }
+
+    fn clip_line(&self, line: &Line) -> Option<Line> {
+        match self {
+            Viewer::Axis { .. } => Some(*line),
+            Viewer::Custom(projection) => projection.clip_line(line),
+        }
+    }
+
+    ///Whether the point is left after the clipping plane cuts the model.  Points on the plane are,
+    /// so section cuts can hide what's behind them.
+    fn keeps(&self, pt: &Point3f) -> bool {
+        match self {
+            Viewer::Custom(Projection {
+                clip: Some(clip), ..
+            }) => (pt - clip.pt_1).dot(clip.normal().normalize()) <= TOUCHING,
+            _ => true,
+        }
+    }
+}
+
+struct Face {
+    pts: Vec<Point3f>,
+    normal: Vector3f,
+}
+
+impl Face {
+    fn new(pts: Vec<Point3f>) -> Face {
+        //Newell's method, so faces with more than three points get an averaged normal
+        let mut normal = Vector3f::new(0.0, 0.0, 0.0);
+        for (index, pt) in pts.iter().enumerate() {
+            let next = pts[(index + 1) % pts.len()];
+            normal.x += (pt.y - next.y) * (pt.z + next.z);
+            normal.y += (pt.z - next.z) * (pt.x + next.x);
+            normal.z += (pt.x - next.x) * (pt.y + next.y);
+        }
+        Face { pts, normal }
+    }
+
+    ///Whether the point, which is on the face's plane, is inside it and not touching its edges.
+    fn surrounds(&self, pt: &Point3f) -> bool {
+        //Flatten everything onto the axis plane the face is closest to lying in
+        let normal = self.normal;
+        let flatten: fn(&Point3f) -> Point2f =
+            if normal.z.abs() >= normal.x.abs() && normal.z.abs() >= normal.y.abs() {
+                x_y
+            } else if normal.y.abs() >= normal.x.abs() {
+                x_z
+            } else {
+                y_z
+            };
+        let test = flatten(pt);
+        let mut inside = false;
+        for (index, pt) in self.pts.iter().enumerate() {
+            let first = flatten(pt);
+            let second = flatten(&self.pts[(index + 1) % self.pts.len()]);
+            if distance_to_segment(&test, &first, &second) < TOUCHING {
+                return false;
+            }
+            if (first.y > test.y) != (second.y > test.y) {
+                let cross_x =
+                    first.x + (test.y - first.y) / (second.y - first.y) * (second.x - first.x);
+                if test.x < cross_x {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+
+    ///Where along the line it passes through the face, if it does
+    fn pierced_at(&self, line: &Line) -> Option<f64> {
+        let dir = line.pt_2 - line.pt_1;
+        let denom = dir.dot(self.normal);
+        if denom.abs() < f64::EPSILON {
+            return None;
+        }
+        let interp = (self.pts[0] - line.pt_1).dot(self.normal) / denom;
+        if interp <= 0.0 || interp >= 1.0 {
+            return None;
+        }
+        if self.surrounds(&(line.pt_1 + dir * interp)) {
+            Some(interp)
+        } else {
+            None
+        }
+    }
+}
+
+fn distance_to_segment(pt: &Point2f, first: &Point2f, second: &Point2f) -> f64 {
+    let dir = second - first;
+    let length2 = dir.magnitude2();
+    if length2 == 0.0 {
+        return (pt - first).magnitude();
+    }
+    let interp = ((pt - first).dot(dir) / length2).clamp(0.0, 1.0);
+    (pt - (first + dir * interp)).magnitude()
+}
+
+///Where along the first segment it crosses the second, if it does
+fn crossing(first: &Line2D, second: &Line2D) -> Option<f64> {
+    let dir = first.second - first.first;
+    let other_dir = second.second - second.first;
+    let denom = dir.x * other_dir.y - dir.y * other_dir.x;
+    if denom.abs() < f64::EPSILON {
+        return None;
+    }
+    let offset = second.first - first.first;
+    let interp = (offset.x * other_dir.y - offset.y * other_dir.x) / denom;
+    let other_interp = (offset.x * dir.y - offset.y * dir.x) / denom;
+    if interp > 0.0 && interp < 1.0 && (0.0..=1.0).contains(&other_interp) {
+        Some(interp)
+    } else {
+        None
+    }
+}
+
+///The corners of the box around the points, or None if there aren't any.
+fn bounds(pts: &[Point2f]) -> Option<(Point2f, Point2f)> {
+    let first = pts.first()?;
+    Some(pts.iter().fold((*first, *first), |(min, max), pt| {
+        (
+            Point2f::new(min.x.min(pt.x), min.y.min(pt.y)),
+            Point2f::new(max.x.max(pt.x), max.y.max(pt.y)),
+        )
+    }))
+}
+
+fn line_bounds(line: &Line2D) -> (Point2f, Point2f) {
+    let pad = Vector2f::new(TOUCHING, TOUCHING);
+    let (min, max) = bounds(&[line.first, line.second]).unwrap();
+    (min - pad, max + pad)
+}
+
+///Buckets things by the cells of a grid over the drawing that their bounds cover, so only the
+/// things near a point or line are checked against it instead of all of them.
+struct Grid {
+    min: Point2f,
+    cell: f64,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<usize>>,
+    ///Things that can't be bounded on the drawing, which are near everything.
+    everywhere: Vec<usize>,
+}
+
+impl Grid {
+    ///Covers the bounds with about as many cells as there are things.
+    fn new(bounds: &[(Point2f, Point2f)], count: usize) -> Grid {
+        let (min, max) = match bounds.first() {
+            Some(first) => bounds.iter().fold(*first, |(min, max), (lower, upper)| {
+                (
+                    Point2f::new(min.x.min(lower.x), min.y.min(lower.y)),
+                    Point2f::new(max.x.max(upper.x), max.y.max(upper.y)),
+                )
+            }),
+            None => (Point2f::new(0.0, 0.0), Point2f::new(0.0, 0.0)),
+        };
+        let per_side = ((count as f64).sqrt().ceil() as usize).clamp(1, 1024);
+        let size = (max.x - min.x).max(max.y - min.y);
+        let cell = if size > 0.0 {
+            size / per_side as f64
+        } else {
+            1.0
+        };
+        let columns = (((max.x - min.x) / cell).ceil() as usize).clamp(1, per_side);
+        let rows = (((max.y - min.y) / cell).ceil() as usize).clamp(1, per_side);
+        Grid {
+            min,
+            cell,
+            columns,
+            rows,
+            cells: vec![Vec::new(); columns * rows],
+            everywhere: Vec::new(),
+        }
+    }
+
+    ///The columns and rows the bounds cover.  Anything past the edge of the grid is in the cells
+    /// along it.
+    fn covered(
+        &self,
+        min: &Point2f,
+        max: &Point2f,
+    ) -> (
+        std::ops::RangeInclusive<usize>,
+        std::ops::RangeInclusive<usize>,
+    ) {
+        let index = |value: f64, start: f64, count: usize| {
+            (((value - start) / self.cell).floor().max(0.0) as usize).min(count - 1)
+        };
+        (
+            index(min.x, self.min.x, self.columns)..=index(max.x, self.min.x, self.columns),
+            index(min.y, self.min.y, self.rows)..=index(max.y, self.min.y, self.rows),
+        )
+    }
+
+    fn insert(&mut self, thing: usize, bounds: Option<(Point2f, Point2f)>) {
+        let (min, max) = match bounds {
+            Some(bounds) => bounds,
+            None => {
+                self.everywhere.push(thing);
+                return;
+            }
+        };
+        let (columns, rows) = self.covered(&min, &max);
+        for row in rows {
+            for column in columns.clone() {
+                self.cells[row * self.columns + column].push(thing);
+            }
+        }
+    }
+
+    ///Everything whose bounds could overlap these, each only once.
+    fn near(&self, min: &Point2f, max: &Point2f) -> Vec<usize> {
+        let (columns, rows) = self.covered(min, max);
+        let mut near = self.everywhere.clone();
+        for row in rows {
+            for column in columns.clone() {
+                near.extend(&self.cells[row * self.columns + column]);
+            }
+        }
+        near.sort_unstable();
+        near.dedup();
+        near
+    }
+}
+
+fn same_line(first: &Line2D, second: &Line2D) -> bool {
+    let close = |first: &Point2f, second: &Point2f| (first - second).magnitude() < TOUCHING;
+    (close(&first.first, &second.first) && close(&first.second, &second.second))
+        || (close(&first.first, &second.second) && close(&first.second, &second.first))
+}
+
+///The edges around the faces, with the ones shared between faces only once.
+fn face_edges(faces: &[Vec<Point3f>]) -> Vec<Line> {
+    let mut edges: Vec<Line> = Vec::new();
+    for face in faces {
+        for (index, pt) in face.iter().enumerate() {
+            let next = face[(index + 1) % face.len()];
+            let same = |edge: &Line| {
+                let close = |first: &Point3f, second: &Point3f| {
+                    (first - second).magnitude2() < TOUCHING * TOUCHING
+                };
+                (close(&edge.pt_1, pt) && close(&edge.pt_2, &next))
+                    || (close(&edge.pt_1, &next) && close(&edge.pt_2, pt))
+            };
+            if !edges.iter().any(same) {
+                edges.push(Line::new(*pt, next));
+            }
+        }
+    }
+    edges
+}
+
+struct Composer<'a> {
+    viewer: &'a Viewer,
+    faces: Vec<Face>,
+    face_grid: Grid,
+    flat_edges: Vec<Line2D>,
+    edge_grid: Grid,
+}
+
+impl<'a> Composer<'a> {
+    fn new(viewer: &'a Viewer, faces: Vec<Face>, edges: &[Line]) -> Composer<'a> {
+        let flat_edges: Vec<Line2D> = edges
+            .iter()
+            .filter_map(|edge| {
+                let edge = viewer.clip_line(edge)?;
+                Some(Line2D::new(
+                    viewer.flatten(&edge.pt_1)?,
+                    viewer.flatten(&edge.pt_2)?,
+                ))
+            })
+            .collect();
+        //Faces with a corner that can't be flattened, like behind the camera in perspective, are
+        //checked against everything.
+        let face_bounds: Vec<Option<(Point2f, Point2f)>> = faces
+            .iter()
+            .map(|face| {
+                let flat: Option<Vec<Point2f>> =
+                    face.pts.iter().map(|pt| viewer.flatten(pt)).collect();
+                bounds(&flat?)
+            })
+            .collect();
+        let edge_bounds: Vec<(Point2f, Point2f)> = flat_edges.iter().map(line_bounds).collect();
+        let mut all_bounds: Vec<(Point2f, Point2f)> =
+            face_bounds.iter().flatten().cloned().collect();
+        all_bounds.extend(&edge_bounds);
+        let mut face_grid = Grid::new(&all_bounds, faces.len());
+        for (index, bounds) in face_bounds.into_iter().enumerate() {
+            face_grid.insert(index, bounds);
+        }
+        let mut edge_grid = Grid::new(&all_bounds, flat_edges.len());
+        for (index, bounds) in edge_bounds.into_iter().enumerate() {
+            edge_grid.insert(index, Some(bounds));
+        }
+        Composer {
+            viewer,
+            faces,
+            face_grid,
+            flat_edges,
+            edge_grid,
+        }
+    }
+
+    ///The faces that could be in front of or behind the point, or all of them if it can't be
+    /// flattened.
+    fn faces_at(&self, pt: &Point3f) -> Vec<&Face> {
+        match self.viewer.flatten(pt) {
+            Some(flat) => {
+                let pad = Vector2f::new(TOUCHING, TOUCHING);
+                self.face_grid
+                    .near(&(flat - pad), &(flat + pad))
+                    .into_iter()
+                    .map(|index| &self.faces[index])
+                    .collect()
+            }
+            None => self.faces.iter().collect(),
+        }
+    }
+
+    fn is_hidden(&self, pt: &Point3f) -> bool {
+        let (dir, camera) = self.viewer.toward_camera(pt);
+        let length = dir.magnitude();
+        self.faces_at(pt).into_iter().any(|face| {
+            let denom = dir.dot(face.normal);
+            if denom.abs() < f64::EPSILON {
+                return false;
+            }
+            let interp = (face.pts[0] - pt).dot(face.normal) / denom;
+            if interp * length <= TOUCHING || interp >= camera {
+                return false;
+            }
+            let hit = pt + dir * interp;
+            self.viewer.keeps(&hit) && face.surrounds(&hit)
+        })
+    }
+
+    ///Splits the edge where it could go behind something, and sorts out which pieces are hidden.
+    fn edge(&self, edge: &Line) -> Vec<(Line2D, bool)> {
+        let edge = match self.viewer.clip_line(edge) {
+            Some(edge) => edge,
+            None => return Vec::new(),
+        };
+        let (first, second) = match (
+            self.viewer.flatten(&edge.pt_1),
+            self.viewer.flatten(&edge.pt_2),
+        ) {
+            (Some(first), Some(second)) => (first, second),
+            _ => return Vec::new(),
+        };
+        if (second - first).magnitude() < TOUCHING {
+            return Vec::new();
+        }
+        let flat = Line2D::new(first, second);
+        //Crossings are found on the drawing, but in perspective they aren't the same distance along
+        //the edge in 3D, so they're moved back by how far away each end is.
+        let first_depth = self.viewer.depth(&edge.pt_1);
+        let second_depth = self.viewer.depth(&edge.pt_2);
+        let mut splits = vec![0.0, 1.0];
+        let (min, max) = line_bounds(&flat);
+        for other in self.edge_grid.near(&min, &max) {
+            if let Some(interp) = crossing(&flat, &self.flat_edges[other]) {
+                let scaled = interp * first_depth;
+                splits.push(scaled / (scaled + (1.0 - interp) * second_depth));
+            }
+        }
+        for face in self.face_grid.near(&min, &max) {
+            if let Some(interp) = self.faces[face].pierced_at(&edge) {
+                splits.push(interp);
+            }
+        }
+        splits.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        splits.dedup_by(|a, b| (*a - *b).abs() < f64::EPSILON);
+
+        let dir = edge.pt_2 - edge.pt_1;
+        let mut pieces: Vec<(f64, f64, bool)> = Vec::new();
+        for pair in splits.windows(2) {
+            let hidden = self.is_hidden(&(edge.pt_1 + dir * ((pair[0] + pair[1]) / 2.0)));
+            match pieces.last_mut() {
+                Some(last) if last.2 == hidden => last.1 = pair[1],
+                _ => pieces.push((pair[0], pair[1], hidden)),
+            }
+        }
+        pieces
+            .into_iter()
+            .filter_map(|(start, end, hidden)| {
+                let start = self.viewer.flatten(&(edge.pt_1 + dir * start))?;
+                let end = self.viewer.flatten(&(edge.pt_1 + dir * end))?;
+                Some((Line2D::new(start, end), hidden))
+            })
+            .collect()
+    }
+}
+
+///Composes the view of the viewport, with its hidden lines.
+pub fn compose_viewport(viewport_id: &ObjID, objs: &[DataBox]) -> Result<DrawingData, ObjError> {
+    let viewport = objs
+        .iter()
+        .find(|obj| obj.get_id() == viewport_id)
+        .ok_or(ObjError::ObjNotFound(*viewport_id))?;
+    let viewport = viewport
+        .downcast_ref::<Viewport>()
+        .ok_or_else(|| ObjError::ObjWrongType(*viewport_id, String::from("Viewport")))?;
+    Ok(DrawingData {
+        elements: compose_view(objs, &viewport.view, viewport.hidden_lines),
+    })
+}
+
+///Gathers every object's drawing for the view.  In elevations and custom views, objects with 3D
+/// faces are drawn from their edges instead, with whatever is behind another object's faces
+/// removed or dashed.
+pub fn compose_view(objs: &[DataBox], view: &ViewType, hidden: HiddenLines) -> Vec<DrawElement2D> {
+    if let ViewType::Section { section } = view {
+        return compose_section(objs, section, hidden);
+    }
+    match (hidden, Viewer::new(view)) {
+        (HiddenLines::Show, _) | (_, None) => objs
+            .iter()
+            .filter_map(|obj| view.get_drawing(obj.as_drawing_views()?))
+            .flat_map(|drawing| drawing.elements)
+            .collect(),
+        (_, Some(viewer)) => compose(objs, &viewer, hidden, Vec::new(), &|views| {
+            view.get_drawing(views)
+        }),
+    }
+}
+
+///Like compose_view, for a projection with the given faces hiding what's behind them as well as
+/// the objects' own.
+pub(crate) fn compose_projection(
+    objs: &[DataBox],
+    projection: &Projection,
+    hidden: HiddenLines,
+    faces: Vec<Vec<Point3f>>,
+) -> Vec<DrawElement2D> {
+    let own_drawing = |views: &dyn DrawingViews| Some(views.get_projection(projection));
+    if hidden == HiddenLines::Show {
+        return objs
+            .iter()
+            .filter_map(|obj| own_drawing(obj.as_drawing_views()?))
+            .flat_map(|drawing| drawing.elements)
+            .collect();
+    }
+    let viewer = Viewer::Custom(projection.clone());
+    compose(objs, &viewer, hidden, faces, &own_drawing)
+}
+
+fn compose(
+    objs: &[DataBox],
+    viewer: &Viewer,
+    hidden: HiddenLines,
+    faces: Vec<Vec<Point3f>>,
+    own_drawing: &dyn Fn(&dyn DrawingViews) -> Option<DrawingData>,
+) -> Vec<DrawElement2D> {
+    let mut elements = Vec::new();
+    let mut faces: Vec<Face> = faces.into_iter().map(Face::new).collect();
+    let mut edges = Vec::new();
+    for obj in objs {
+        let views = match obj.as_drawing_views() {
+            Some(views) => views,
+            None => continue,
+        };
+        let obj_faces = views.get_faces();
+        if obj_faces.is_empty() {
+            if let Some(drawing) = own_drawing(views) {
+                elements.extend(drawing.elements);
+            }
+            continue;
+        }
+        edges.extend(face_edges(&obj_faces));
+        faces.extend(
+            obj_faces
+                .into_iter()
+                .filter(|face| face.len() > 2)
+                .map(Face::new),
+        );
+    }
+    let composer = Composer::new(viewer, faces, &edges);
+    //Edges that line up on the drawing, like the front and back of a wall in its elevation, are
+    //only drawn once, and not dashed if any of them can be seen.
+    let mut pieces: Vec<(Line2D, bool)> =
+        edges.iter().flat_map(|edge| composer.edge(edge)).collect();
+    pieces.sort_by_key(|(_, is_hidden)| *is_hidden);
+    let piece_bounds: Vec<(Point2f, Point2f)> =
+        pieces.iter().map(|(line, _)| line_bounds(line)).collect();
+    let mut drawn_grid = Grid::new(&piece_bounds, pieces.len());
+    let mut drawn: Vec<Line2D> = Vec::new();
+    for ((line, is_hidden), (min, max)) in pieces.into_iter().zip(piece_bounds) {
+        if drawn_grid
+            .near(&min, &max)
+            .into_iter()
+            .any(|other| same_line(&drawn[other], &line))
+        {
+            continue;
+        }
+        drawn_grid.insert(drawn.len(), Some((min, max)));
+        drawn.push(line);
+        if !is_hidden {
+            elements.push(DrawElement2D::new_default(Element2D::Line(line)));
+        } else if hidden == HiddenLines::Dash {
+            elements.push(DrawElement2D {
+                element: Element2D::Line(line),
+                line_thickness: HIDDEN_LINE_THICKNESS,
+                line_color: RGBA::default(),
+                line_type: LineType::Dashed {
+                    name: String::from(HIDDEN_LINE_TYPE),
+                },
+                fill_type: FillType::default(),
+            });
+        }
+    }
+    elements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wall(first: (f64, f64), second: (f64, f64), height: WorldCoord) -> DataBox {
+        Box::new(Wall::new(
+            Point3f::new(first.0, first.1, 0.0),
+            Point3f::new(second.0, second.1, 0.0),
+            0.1,
+            height,
+        ))
+    }
+
+    fn lines(elements: &[DrawElement2D], dashed: bool) -> Vec<Line2D> {
+        elements
+            .iter()
+            .filter(|element| matches!(element.line_type, LineType::Dashed { .. }) == dashed)
+            .filter_map(|element| match &element.element {
+                Element2D::Line(line) => Some(*line),
+                _ => None,
+            })
+            .collect()
+    }
+
+    ///Whether some line covers the segment, going either way.
+    fn has_line(lines: &[Line2D], first: (f64, f64), second: (f64, f64)) -> bool {
+        let line = Line2D::new(
+            Point2f::new(first.0, first.1),
+            Point2f::new(second.0, second.1),
+        );
+        lines.iter().any(|other| same_line(other, &line))
+    }
+
+    #[test]
+    fn test_grid() {
+        let far = (Point2f::new(9.0, 9.0), Point2f::new(10.0, 10.0));
+        let near = (Point2f::new(0.0, 0.0), Point2f::new(1.0, 1.0));
+        let across = (Point2f::new(0.0, 5.0), Point2f::new(10.0, 5.0));
+        let mut grid = Grid::new(&[far, near, across], 4);
+        grid.insert(0, Some(far));
+        grid.insert(1, Some(near));
+        grid.insert(2, Some(across));
+        grid.insert(3, None);
+        let pt = Point2f::new(0.5, 0.5);
+        assert_eq!(grid.near(&pt, &pt), vec![1, 3]);
+        let pt = Point2f::new(0.5, 5.0);
+        assert_eq!(grid.near(&pt, &pt), vec![2, 3]);
+        //Past the edge of the grid is the same as along it
+        let pt = Point2f::new(20.0, 20.0);
+        assert_eq!(grid.near(&pt, &pt), vec![0, 2, 3]);
+        assert_eq!(
+            grid.near(&Point2f::new(0.0, 0.0), &Point2f::new(10.0, 10.0)),
+            vec![0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_front_view() {
+        //A short wall all the way behind a long one, and a tall one that's only partly behind it
+        let objs = vec![
+            wall((0.0, 0.0), (10.0, 0.0), 3.0),
+            wall((1.0, 5.0), (3.0, 5.0), 2.0),
+            wall((6.0, 5.0), (8.0, 5.0), 5.0),
+        ];
+        let removed = compose_view(&objs, &ViewType::Front, HiddenLines::Remove);
+        let seen = lines(&removed, false);
+        assert!(lines(&removed, true).is_empty());
+        assert!(has_line(&seen, (0.0, 3.0), (10.0, 3.0)));
+        assert!(has_line(&seen, (6.0, 5.0), (8.0, 5.0)));
+        assert!(has_line(&seen, (6.0, 3.0), (6.0, 5.0)));
+        //The front and back of the long wall line up, so its top is only drawn once
+        let tops = seen
+            .iter()
+            .filter(|line| (line.first.y - 3.0).abs() < 1e-9 && (line.second.y - 3.0).abs() < 1e-9)
+            .count();
+        assert_eq!(tops, 1);
+        assert!(!has_line(&seen, (1.0, 2.0), (3.0, 2.0)));
+        assert!(!has_line(&seen, (6.0, 0.0), (6.0, 3.0)));
+
+        let dashed = compose_view(&objs, &ViewType::Front, HiddenLines::Dash);
+        let seen_dashed = lines(&dashed, false);
+        let hidden = lines(&dashed, true);
+        assert_eq!(seen_dashed.len(), seen.len());
+        assert!(has_line(&hidden, (1.0, 2.0), (3.0, 2.0)));
+        assert!(has_line(&hidden, (6.0, 0.0), (6.0, 3.0)));
+        assert!(!has_line(&hidden, (6.0, 3.0), (6.0, 5.0)));
+        assert!(dashed
+            .iter()
+            .filter(|element| matches!(element.line_type, LineType::Dashed { .. }))
+            .all(|element| element.line_thickness == HIDDEN_LINE_THICKNESS));
+
+        //Objects draw themselves when nothing is hidden
+        let shown = compose_view(&objs, &ViewType::Front, HiddenLines::Show);
+        let own: usize = objs
+            .iter()
+            .map(|obj| obj.as_drawing_views().unwrap().get_front().elements.len())
+            .sum();
+        assert_eq!(shown.len(), own);
+    }
+
+    #[test]
+    fn test_plan_isnt_composed() {
+        let objs = vec![
+            wall((0.0, 0.0), (10.0, 0.0), 3.0),
+            wall((1.0, 5.0), (3.0, 5.0), 2.0),
+        ];
+        let composed = compose_view(&objs, &ViewType::Top, HiddenLines::Remove);
+        let own = compose_view(&objs, &ViewType::Top, HiddenLines::Show);
+        assert_eq!(composed.len(), own.len());
+    }
+}
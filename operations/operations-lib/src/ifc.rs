@@ -0,0 +1,723 @@
+use crate::*;
+use cgmath::{InnerSpace, Matrix4, SquareMatrix};
+use std::collections::HashMap;
+
+//How far openings stick out of each face of their host wall, as a fraction of its thickness, so
+//the faces don't coincide.
+const OPENING_OVERSHOOT: f64 = 0.1;
+
+const GUID_CHARS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz_$";
+
+///IFC GlobalIds are the 128 bits of a GUID written in 22 characters of IFC's own base 64.
+fn ifc_guid(id: &ObjID) -> String {
+    let bits = id.as_u128();
+    let mut guid = String::with_capacity(22);
+    guid.push(GUID_CHARS[(bits >> 126) as usize] as char);
+    for i in 0..21 {
+        let shift = 120 - 6 * i;
+        guid.push(GUID_CHARS[((bits >> shift) & 63) as usize] as char);
+    }
+    guid
+}
+
+fn real(val: f64) -> String {
+    let text = format!("{}", val);
+    if text.contains('.') {
+        text
+    } else {
+        text + "."
+    }
+}
+
+fn string(text: &str) -> String {
+    let mut escaped = String::from("'");
+    for c in text.chars() {
+        match c {
+            '\'' => escaped.push_str("''"),
+            '\\' => escaped.push_str("\\\\"),
+            ' '..='~' => escaped.push(c),
+            _ => {
+                let mut buf = [0u16; 2];
+                for unit in c.encode_utf16(&mut buf) {
+                    escaped.push_str(&format!("\\X2\\{:04X}\\X0\\", unit));
+                }
+            }
+        }
+    }
+    escaped.push('\'');
+    escaped
+}
+
+fn refs(ids: &[usize]) -> String {
+    let refs: Vec<String> = ids.iter().map(|id| format!("#{}", id)).collect();
+    format!("({})", refs.join(","))
+}
+
+fn unit_or(vec: Vector3f, default: Vector3f) -> Vector3f {
+    if vec.magnitude2() > 0.0 {
+        vec.normalize()
+    } else {
+        default
+    }
+}
+
+struct StepWriter {
+    entities: Vec<String>,
+    z_dir: usize,
+    origin_placement: usize,
+    context: usize,
+    body: usize,
+    axis: usize,
+}
+
+impl StepWriter {
+    fn new() -> StepWriter {
+        let mut writer = StepWriter {
+            entities: Vec::new(),
+            z_dir: 0,
+            origin_placement: 0,
+            context: 0,
+            body: 0,
+            axis: 0,
+        };
+        writer.z_dir = writer.direction(&Vector3f::unit_z());
+        writer.origin_placement =
+            writer.placement(&Point3f::new(0.0, 0.0, 0.0), &Vector3f::unit_x());
+        writer.context = writer.add(format!(
+            "IFCGEOMETRICREPRESENTATIONCONTEXT($,'Model',3,1.E-05,#{},$)",
+            writer.origin_placement
+        ));
+        writer.body = writer.add(format!(
+            "IFCGEOMETRICREPRESENTATIONSUBCONTEXT('Body','Model',*,*,*,*,#{},$,.MODEL_VIEW.,$)",
+            writer.context
+        ));
+        writer.axis = writer.add(format!(
+            "IFCGEOMETRICREPRESENTATIONSUBCONTEXT('Axis','Model',*,*,*,*,#{},$,.GRAPH_VIEW.,$)",
+            writer.context
+        ));
+        writer
+    }
+
+    fn add(&mut self, entity: String) -> usize {
+        self.entities.push(entity);
+        self.entities.len()
+    }
+
+    fn point(&mut self, pt: &Point3f) -> usize {
+        self.add(format!(
+            "IFCCARTESIANPOINT(({},{},{}))",
+            real(pt.x),
+            real(pt.y),
+            real(pt.z)
+        ))
+    }
+
+    fn point_2d(&mut self, x: f64, y: f64) -> usize {
+        self.add(format!("IFCCARTESIANPOINT(({},{}))", real(x), real(y)))
+    }
+
+    fn direction(&mut self, dir: &Vector3f) -> usize {
+        self.add(format!(
+            "IFCDIRECTION(({},{},{}))",
+            real(dir.x),
+            real(dir.y),
+            real(dir.z)
+        ))
+    }
+
+    ///Z stays up, and X runs along x_dir.
+    fn placement(&mut self, origin: &Point3f, x_dir: &Vector3f) -> usize {
+        let location = self.point(origin);
+        let x_dir = self.direction(x_dir);
+        self.add(format!(
+            "IFCAXIS2PLACEMENT3D(#{},#{},#{})",
+            location, self.z_dir, x_dir
+        ))
+    }
+
+    fn local_placement(
+        &mut self,
+        relative_to: Option<usize>,
+        origin: &Point3f,
+        x_dir: &Vector3f,
+    ) -> usize {
+        let placement = self.placement(origin, x_dir);
+        let relative_to = match relative_to {
+            Some(id) => format!("#{}", id),
+            None => String::from("$"),
+        };
+        self.add(format!("IFCLOCALPLACEMENT({},#{})", relative_to, placement))
+    }
+
+    ///A box running length along X from the placement's origin, centered on its X axis.
+    fn extruded_box(&mut self, length: f64, thickness: f64, height: f64) -> usize {
+        let center = self.point_2d(length / 2.0, 0.0);
+        let position = self.add(format!("IFCAXIS2PLACEMENT2D(#{},$)", center));
+        let profile = self.add(format!(
+            "IFCRECTANGLEPROFILEDEF(.AREA.,$,#{},{},{})",
+            position,
+            real(length),
+            real(thickness)
+        ));
+        let solid = self.add(format!(
+            "IFCEXTRUDEDAREASOLID(#{},#{},#{},{})",
+            profile,
+            self.origin_placement,
+            self.z_dir,
+            real(height)
+        ));
+        self.add(format!(
+            "IFCSHAPEREPRESENTATION(#{},'Body','SweptSolid',(#{}))",
+            self.body, solid
+        ))
+    }
+
+    fn axis_line(&mut self, length: f64) -> usize {
+        let start = self.point_2d(0.0, 0.0);
+        let end = self.point_2d(length, 0.0);
+        let line = self.add(format!("IFCPOLYLINE((#{},#{}))", start, end));
+        self.add(format!(
+            "IFCSHAPEREPRESENTATION(#{},'Axis','Curve2D',(#{}))",
+            self.axis, line
+        ))
+    }
+
+    fn product_shape(&mut self, representations: &[usize]) -> usize {
+        self.add(format!(
+            "IFCPRODUCTDEFINITIONSHAPE($,$,{})",
+            refs(representations)
+        ))
+    }
+
+    fn wall(&mut self, storey: usize, wall: &Wall) -> usize {
+        let first = wall.first_pt.info;
+        let dir = wall.second_pt.info - first;
+        let length = dir.magnitude();
+        let placement = self.local_placement(
+            Some(storey),
+            &first,
+            &unit_or(Vector3f::new(dir.x, dir.y, 0.0), Vector3f::unit_x()),
+        );
+        let axis = self.axis_line(length);
+        let body = self.extruded_box(length, 2.0 * wall.width, wall.height);
+        let shape = self.product_shape(&[axis, body]);
+        self.add(format!(
+            "IFCWALLSTANDARDCASE({},$,'Wall',$,$,#{},#{},$,.STANDARD.)",
+            string(&ifc_guid(wall.get_id())),
+            placement,
+            shape
+        ))
+    }
+
+    fn door(&mut self, storey: usize, door: &Door) -> usize {
+        let first = door.dir.line.pt_1;
+        let dir = door.dir.line.pt_2 - first;
+        let length = dir.magnitude();
+        let placement = self.local_placement(
+            Some(storey),
+            &first,
+            &unit_or(Vector3f::new(dir.x, dir.y, 0.0), Vector3f::unit_x()),
+        );
+        let body = self.extruded_box(length, 2.0 * door.width, door.height);
+        let shape = self.product_shape(&[body]);
+        self.add(format!(
+            "IFCDOOR({},$,'Door',$,$,#{},#{},$,{},{},.DOOR.,.SINGLE_SWING_LEFT.,$)",
+            string(&ifc_guid(door.get_id())),
+            placement,
+            shape,
+            real(door.height),
+            real(length)
+        ))
+    }
+
+    ///The opening is cut through the whole thickness of the wall, over the plane the door hosted in
+    /// it shares with it.
+    fn opening(&mut self, storey: usize, wall: &Wall, plane: &Plane) -> usize {
+        let dir = plane.pt_2 - plane.pt_1;
+        let length = dir.magnitude();
+        let height = plane.pt_3.z - plane.pt_2.z;
+        let placement = self.local_placement(
+            Some(storey),
+            &plane.pt_1,
+            &unit_or(Vector3f::new(dir.x, dir.y, 0.0), Vector3f::unit_x()),
+        );
+        let thickness = 2.0 * wall.width * (1.0 + 2.0 * OPENING_OVERSHOOT);
+        let body = self.extruded_box(length, thickness, height);
+        let shape = self.product_shape(&[body]);
+        self.add(format!(
+            "IFCOPENINGELEMENT({},$,'Opening',$,$,#{},#{},$,.OPENING.)",
+            string(&ifc_guid(&ObjID::new_v4())),
+            placement,
+            shape
+        ))
+    }
+
+    ///The map shares the symbol file's triangles between all of the symbol's instances.  Without
+    /// them, only the symbol definition's bounding box is written.
+    fn representation_map(&mut self, bbox: &Cube, triangles: &[[Point3f; 3]]) -> usize {
+        let shape = if triangles.is_empty() {
+            let corner = self.point(&bbox.bottom_left);
+            let size = bbox.top_right - bbox.bottom_left;
+            let bounding_box = self.add(format!(
+                "IFCBOUNDINGBOX(#{},{},{},{})",
+                corner,
+                real(size.x),
+                real(size.y),
+                real(size.z)
+            ));
+            self.add(format!(
+                "IFCSHAPEREPRESENTATION(#{},'Body','BoundingBox',(#{}))",
+                self.body, bounding_box
+            ))
+        } else {
+            let mut coords = Vec::new();
+            let mut faces = Vec::new();
+            for triangle in triangles {
+                for pt in triangle {
+                    coords.push(format!("({},{},{})", real(pt.x), real(pt.y), real(pt.z)));
+                }
+                let last = coords.len();
+                faces.push(format!("({},{},{})", last - 2, last - 1, last));
+            }
+            let pts = self.add(format!("IFCCARTESIANPOINTLIST3D(({}))", coords.join(",")));
+            let face_set = self.add(format!(
+                "IFCTRIANGULATEDFACESET(#{},$,$,({}),$)",
+                pts,
+                faces.join(",")
+            ));
+            self.add(format!(
+                "IFCSHAPEREPRESENTATION(#{},'Body','Tessellation',(#{}))",
+                self.body, face_set
+            ))
+        };
+        self.add(format!(
+            "IFCREPRESENTATIONMAP(#{},#{})",
+            self.origin_placement, shape
+        ))
+    }
+
+    fn symbol_instance(
+        &mut self,
+        storey: usize,
+        instance: &SymbolInstance,
+        map: Option<usize>,
+    ) -> usize {
+        let placement = self.local_placement(
+            Some(storey),
+            &Point3f::new(0.0, 0.0, 0.0),
+            &Vector3f::unit_x(),
+        );
+        let shape = match map {
+            Some(map) => {
+                let mat = instance.transform;
+                let axes = [mat.x.truncate(), mat.y.truncate(), mat.z.truncate()];
+                let defaults = [Vector3f::unit_x(), Vector3f::unit_y(), Vector3f::unit_z()];
+                let mut dirs = Vec::new();
+                for (axis, default) in axes.iter().zip(defaults.iter()) {
+                    dirs.push(self.direction(&unit_or(*axis, *default)));
+                }
+                let origin = self.point(&Point3f::new(mat.w.x, mat.w.y, mat.w.z));
+                let operator = self.add(format!(
+                    "IFCCARTESIANTRANSFORMATIONOPERATOR3DNONUNIFORM(#{},#{},#{},{},#{},{},{})",
+                    dirs[0],
+                    dirs[1],
+                    origin,
+                    real(axes[0].magnitude()),
+                    dirs[2],
+                    real(axes[1].magnitude()),
+                    real(axes[2].magnitude())
+                ));
+                let item = self.add(format!("IFCMAPPEDITEM(#{},#{})", map, operator));
+                let body = self.add(format!(
+                    "IFCSHAPEREPRESENTATION(#{},'Body','MappedRepresentation',(#{}))",
+                    self.body, item
+                ));
+                format!("#{}", self.product_shape(&[body]))
+            }
+            None => String::from("$"),
+        };
+        self.add(format!(
+            "IFCBUILDINGELEMENTPROXY({},$,'Symbol',$,$,#{},{},$,$)",
+            string(&ifc_guid(instance.get_id())),
+            placement,
+            shape
+        ))
+    }
+
+    fn relation(&mut self, entity: &str, relating: usize, related: &str) -> usize {
+        self.add(format!(
+            "{}({},$,$,$,#{},{})",
+            entity,
+            string(&ifc_guid(&ObjID::new_v4())),
+            relating,
+            related
+        ))
+    }
+}
+
+///The triangles of a symbol file's meshes.  Anything else in it with a volume is written as its
+/// bounding box.
+fn symbol_triangles(objs: &[DataBox]) -> Vec<[Point3f; 3]> {
+    let mut triangles = Vec::new();
+    for obj in objs {
+        if let Some(mesh) = obj.downcast_ref::<Mesh>() {
+            triangles.extend(mesh.get_triangles().iter().cloned());
+        } else if let Some(position) = obj.as_position() {
+            let bbox = position.get_axis_aligned_bounding_box();
+            let size = bbox.top_right - bbox.bottom_left;
+            if size.x > 0.0 && size.y > 0.0 && size.z > 0.0 {
+                box_triangles(&Matrix4::identity(), bbox.bottom_left, size, &mut triangles);
+            }
+        }
+    }
+    triangles
+}
+
+///Writes the objects out as an IFC4 STEP file, with everything on a single storey.  Walls become
+/// IfcWallStandardCase, doors become IfcDoor filling an IfcOpeningElement in their host wall, and
+/// symbol instances become proxies holding an IfcMappedItem of their symbol definition, with the
+/// geometry from its file in symbol_files.  Other objects are skipped.
+pub fn export_ifc(name: &str, objs: &Vec<DataBox>, symbol_files: &[SymbolFile]) -> String {
+    let mut writer = StepWriter::new();
+    let length_unit = writer.add(String::from("IFCSIUNIT(*,.LENGTHUNIT.,$,.METRE.)"));
+    let angle_unit = writer.add(String::from("IFCSIUNIT(*,.PLANEANGLEUNIT.,$,.RADIAN.)"));
+    let units = writer.add(format!(
+        "IFCUNITASSIGNMENT((#{},#{}))",
+        length_unit, angle_unit
+    ));
+    let project = writer.add(format!(
+        "IFCPROJECT({},$,{},$,$,$,$,(#{}),#{})",
+        string(&ifc_guid(&ObjID::new_v4())),
+        string(name),
+        writer.context,
+        units
+    ));
+    let origin = Point3f::new(0.0, 0.0, 0.0);
+    let site_placement = writer.local_placement(None, &origin, &Vector3f::unit_x());
+    let site = writer.add(format!(
+        "IFCSITE({},$,'Site',$,$,#{},$,$,.ELEMENT.,$,$,$,$,$)",
+        string(&ifc_guid(&ObjID::new_v4())),
+        site_placement
+    ));
+    let building_placement =
+        writer.local_placement(Some(site_placement), &origin, &Vector3f::unit_x());
+    let building = writer.add(format!(
+        "IFCBUILDING({},$,'Building',$,$,#{},$,$,.ELEMENT.,$,$,$)",
+        string(&ifc_guid(&ObjID::new_v4())),
+        building_placement
+    ));
+    let storey_placement =
+        writer.local_placement(Some(building_placement), &origin, &Vector3f::unit_x());
+    let storey = writer.add(format!(
+        "IFCBUILDINGSTOREY({},$,'Level 1',$,$,#{},$,$,.ELEMENT.,0.)",
+        string(&ifc_guid(&ObjID::new_v4())),
+        storey_placement
+    ));
+    writer.relation("IFCRELAGGREGATES", project, &refs(&[site]));
+    writer.relation("IFCRELAGGREGATES", site, &refs(&[building]));
+    writer.relation("IFCRELAGGREGATES", building, &refs(&[storey]));
+
+    let mut by_id = HashMap::new();
+    for obj in objs {
+        by_id.insert(*obj.get_id(), obj);
+    }
+    let mut elements = Vec::new();
+    let mut walls = HashMap::new();
+    let mut doors = HashMap::new();
+    let mut maps = HashMap::new();
+    for obj in objs {
+        if let Some(wall) = obj.downcast_ref::<Wall>() {
+            let element = writer.wall(storey_placement, wall);
+            walls.insert(*obj.get_id(), element);
+            elements.push(element);
+        } else if let Some(door) = obj.downcast_ref::<Door>() {
+            let element = writer.door(storey_placement, door);
+            doors.insert(*obj.get_id(), element);
+            elements.push(element);
+        } else if let Some(instance) = obj.downcast_ref::<SymbolInstance>() {
+            let sym_def = obj
+                .get_refs()
+                .into_iter()
+                .flatten()
+                .find(|refer| refer.owner.ref_type == RefType::AxisAlignedBoundBox)
+                .map(|refer| refer.other.id);
+            let map = match sym_def {
+                Some(sym_def) => match maps.get(&sym_def) {
+                    Some(map) => Some(*map),
+                    None => match by_id
+                        .get(&sym_def)
+                        .and_then(|def| def.downcast_ref::<SymbolDef>())
+                    {
+                        Some(def) => match def.get_result(RefType::AxisAlignedBoundBox, 0) {
+                            Some(RefResult::Cube(bbox)) => {
+                                let triangles = symbol_files
+                                    .iter()
+                                    .find(|sym_file| sym_file.file == *def.get_sym_file())
+                                    .map(|sym_file| symbol_triangles(&sym_file.objects))
+                                    .unwrap_or_default();
+                                let map = writer.representation_map(&bbox, &triangles);
+                                maps.insert(sym_def, map);
+                                Some(map)
+                            }
+                            _ => None,
+                        },
+                        None => None,
+                    },
+                },
+                None => None,
+            };
+            elements.push(writer.symbol_instance(storey_placement, instance, map));
+        }
+    }
+
+    //Doors are hosted in walls through the planes the walls reference on them.
+    for obj in objs {
+        let (wall, wall_element) = match (obj.downcast_ref::<Wall>(), walls.get(obj.get_id())) {
+            (Some(wall), Some(element)) => (wall, *element),
+            _ => continue,
+        };
+        for refer in obj.get_refs().into_iter().flatten() {
+            if refer.owner.ref_type != RefType::ProfilePlane {
+                continue;
+            }
+            let door = match doors.get(&refer.other.id) {
+                Some(door) => *door,
+                None => continue,
+            };
+            if let Some(RefResult::Plane(plane)) =
+                obj.get_result(RefType::ProfilePlane, refer.owner.index)
+            {
+                let opening = writer.opening(storey_placement, wall, &plane);
+                writer.relation("IFCRELVOIDSELEMENT", wall_element, &format!("#{}", opening));
+                writer.relation("IFCRELFILLSELEMENT", opening, &format!("#{}", door));
+            }
+        }
+    }
+    if !elements.is_empty() {
+        writer.add(format!(
+            "IFCRELCONTAINEDINSPATIALSTRUCTURE({},$,$,$,{},#{})",
+            string(&ifc_guid(&ObjID::new_v4())),
+            refs(&elements),
+            storey
+        ));
+    }
+
+    let mut ifc = String::from("ISO-10303-21;\nHEADER;\n");
+    ifc.push_str("FILE_DESCRIPTION(('ViewDefinition [ReferenceView]'),'2;1');\n");
+    ifc.push_str(&format!(
+        "FILE_NAME({},'',(''),(''),'kafka-cad','kafka-cad','');\n",
+        string(name)
+    ));
+    ifc.push_str("FILE_SCHEMA(('IFC4'));\nENDSEC;\nDATA;\n");
+    for (index, entity) in writer.entities.iter().enumerate() {
+        ifc.push_str(&format!("#{}={};\n", index + 1, entity));
+    }
+    ifc.push_str("ENDSEC;\nEND-ISO-10303-21;\n");
+    ifc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::step::*;
+
+    fn of_type<'a>(
+        entities: &'a HashMap<u64, StepEntity>,
+        name: &str,
+    ) -> Vec<(u64, &'a StepEntity)> {
+        let mut found: Vec<(u64, &StepEntity)> = entities
+            .iter()
+            .filter(|(_, entity)| entity.name == name)
+            .map(|(id, entity)| (*id, entity))
+            .collect();
+        found.sort_by_key(|(id, _)| *id);
+        found
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    fn contained(entities: &HashMap<u64, StepEntity>) -> Vec<u64> {
+        let rels = of_type(entities, "IFCRELCONTAINEDINSPATIALSTRUCTURE");
+        assert_eq!(rels.len(), 1);
+        rels[0]
+            .1
+            .arg(4)
+            .as_list()
+            .iter()
+            .filter_map(|element| element.as_ref_id())
+            .collect()
+    }
+
+    #[test]
+    fn test_wall() {
+        let wall = Wall::new(
+            Point3f::new(1.0, 0.0, 0.0),
+            Point3f::new(5.0, 0.0, 0.0),
+            0.1,
+            3.0,
+        );
+        let ifc = export_ifc("Plan", &vec![Box::new(wall.clone()) as DataBox], &[]);
+        assert!(ifc.starts_with("ISO-10303-21;"));
+        assert!(ifc.contains("FILE_SCHEMA(('IFC4'));"));
+        let entities = parse_step(&ifc).unwrap();
+
+        let walls = of_type(&entities, "IFCWALLSTANDARDCASE");
+        assert_eq!(walls.len(), 1);
+        let (wall_id, wall_entity) = walls[0];
+        assert_eq!(
+            wall_entity.arg(0).as_str(),
+            Some(ifc_guid(wall.get_id()).as_str())
+        );
+        assert_eq!(contained(&entities), vec![wall_id]);
+        let profiles = of_type(&entities, "IFCRECTANGLEPROFILEDEF");
+        assert_eq!(profiles.len(), 1);
+        assert_close(profiles[0].1.arg(3).as_f64().unwrap(), 4.0);
+        assert_close(profiles[0].1.arg(4).as_f64().unwrap(), 0.2);
+        let solids = of_type(&entities, "IFCEXTRUDEDAREASOLID");
+        assert_close(solids[0].1.arg(3).as_f64().unwrap(), 3.0);
+
+        //Reading it back gives the same wall
+        let import = import_ifc(&ifc).unwrap();
+        let imported = import
+            .objects
+            .iter()
+            .find_map(|obj| obj.downcast_ref::<Wall>())
+            .unwrap();
+        assert!((imported.first_pt.info - wall.first_pt.info).magnitude() < 1e-9);
+        assert!((imported.second_pt.info - wall.second_pt.info).magnitude() < 1e-9);
+        assert_close(imported.width, 0.1);
+        assert_close(imported.height, 3.0);
+    }
+
+    #[test]
+    fn test_door_in_wall() {
+        let door = Door::new(
+            Point3f::new(1.0, 0.0, 0.0),
+            Point3f::new(2.0, 0.0, 0.0),
+            0.1,
+            2.0,
+        );
+        let opening = door.get_result(RefType::ProfilePlane, 0).unwrap();
+        let door_ref = RefID::new(*door.get_id(), RefType::ProfilePlane, 0);
+        let mut wall = Box::new(Wall::new(
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(4.0, 0.0, 0.0),
+            0.1,
+            3.0,
+        )) as DataBox;
+        assert!(wall.add_ref(RefType::ProfilePlane, opening, door_ref, &None));
+        let ifc = export_ifc("Plan", &vec![wall, Box::new(door) as DataBox], &[]);
+        let entities = parse_step(&ifc).unwrap();
+
+        let (wall_id, _) = of_type(&entities, "IFCWALLSTANDARDCASE")[0];
+        let doors = of_type(&entities, "IFCDOOR");
+        assert_eq!(doors.len(), 1);
+        let (door_id, door_entity) = doors[0];
+        assert_close(door_entity.arg(8).as_f64().unwrap(), 2.0);
+        assert_close(door_entity.arg(9).as_f64().unwrap(), 1.0);
+        assert_eq!(contained(&entities), vec![wall_id, door_id]);
+
+        //The opening goes through the wall, and sticks out of both of its faces
+        let openings = of_type(&entities, "IFCOPENINGELEMENT");
+        assert_eq!(openings.len(), 1);
+        let (opening_id, _) = openings[0];
+        let profiles = of_type(&entities, "IFCRECTANGLEPROFILEDEF");
+        let (_, profile) = profiles[profiles.len() - 1];
+        assert_close(profile.arg(3).as_f64().unwrap(), 1.0);
+        assert_close(
+            profile.arg(4).as_f64().unwrap(),
+            0.2 * (1.0 + 2.0 * OPENING_OVERSHOOT),
+        );
+
+        let voids = of_type(&entities, "IFCRELVOIDSELEMENT");
+        assert_eq!(voids.len(), 1);
+        assert_eq!(voids[0].1.arg(4).as_ref_id(), Some(wall_id));
+        assert_eq!(voids[0].1.arg(5).as_ref_id(), Some(opening_id));
+        let fills = of_type(&entities, "IFCRELFILLSELEMENT");
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].1.arg(4).as_ref_id(), Some(opening_id));
+        assert_eq!(fills[0].1.arg(5).as_ref_id(), Some(door_id));
+    }
+
+    #[test]
+    fn test_symbol_instances() {
+        //Two stools sharing a tetrahedron, the second one 5 along X and turned a quarter turn
+        let import = import_ifc(
+            "DATA;
+#1=IFCCARTESIANPOINTLIST3D(((0.,0.,0.),(1.,0.,0.),(0.,1.,0.),(0.,0.,1.)));
+#2=IFCTRIANGULATEDFACESET(#1,$,.T.,((1,3,2),(1,2,4),(1,4,3),(2,3,4)),$);
+#3=IFCSHAPEREPRESENTATION($,'Body','Tessellation',(#2));
+#4=IFCCARTESIANPOINT((0.,0.,0.));
+#5=IFCAXIS2PLACEMENT3D(#4,$,$);
+#6=IFCREPRESENTATIONMAP(#5,#3);
+#7=IFCCARTESIANTRANSFORMATIONOPERATOR3D($,$,#4,$,$);
+#8=IFCMAPPEDITEM(#6,#7);
+#9=IFCSHAPEREPRESENTATION($,'Body','MappedRepresentation',(#8));
+#10=IFCPRODUCTDEFINITIONSHAPE($,$,(#9));
+#11=IFCLOCALPLACEMENT($,#5);
+#12=IFCCARTESIANPOINT((5.,0.,0.));
+#13=IFCDIRECTION((0.,0.,1.));
+#14=IFCDIRECTION((0.,1.,0.));
+#15=IFCAXIS2PLACEMENT3D(#12,#13,#14);
+#16=IFCLOCALPLACEMENT($,#15);
+#17=IFCFURNISHINGELEMENT('a',$,'Stool',$,$,#11,#10,$);
+#18=IFCFURNISHINGELEMENT('b',$,'Stool',$,$,#16,#10,$);
+ENDSEC;",
+        )
+        .unwrap();
+        let ifc = export_ifc("Plan", &import.objects, &import.symbol_files);
+        let entities = parse_step(&ifc).unwrap();
+
+        //The symbol's mesh is written once, and both proxies map it
+        assert_eq!(of_type(&entities, "IFCREPRESENTATIONMAP").len(), 1);
+        let face_sets = of_type(&entities, "IFCTRIANGULATEDFACESET");
+        assert_eq!(face_sets.len(), 1);
+        assert_eq!(face_sets[0].1.arg(3).as_list().len(), 4);
+        assert_eq!(of_type(&entities, "IFCMAPPEDITEM").len(), 2);
+        let proxies = of_type(&entities, "IFCBUILDINGELEMENTPROXY");
+        assert_eq!(proxies.len(), 2);
+        assert_eq!(
+            contained(&entities),
+            proxies.iter().map(|(id, _)| *id).collect::<Vec<u64>>()
+        );
+
+        //And reading it back puts the stools where they were
+        let reimport = import_ifc(&ifc).unwrap();
+        assert_eq!(reimport.symbol_files.len(), 1);
+        let mesh = reimport.symbol_files[0].objects[0]
+            .downcast_ref::<Mesh>()
+            .unwrap();
+        assert_eq!(mesh.get_triangles().len(), 4);
+        let originals: Vec<&SymbolInstance> = import
+            .objects
+            .iter()
+            .filter_map(|obj| obj.downcast_ref::<SymbolInstance>())
+            .collect();
+        let instances: Vec<&SymbolInstance> = reimport
+            .objects
+            .iter()
+            .filter_map(|obj| obj.downcast_ref::<SymbolInstance>())
+            .collect();
+        assert_eq!(instances.len(), 2);
+        for (instance, original) in instances.iter().zip(originals.iter()) {
+            let corner = Point3f::new(1.0, 0.0, 0.0);
+            let moved = instance.transform.transform_point(corner);
+            let expected = original.transform.transform_point(corner);
+            assert!((moved - expected).magnitude() < 1e-9);
+        }
+
+        //Without the symbol file there's only the definition's bounding box to go on
+        let ifc = export_ifc("Plan", &import.objects, &[]);
+        let entities = parse_step(&ifc).unwrap();
+        assert_eq!(of_type(&entities, "IFCBOUNDINGBOX").len(), 1);
+        assert!(of_type(&entities, "IFCTRIANGULATEDFACESET").is_empty());
+        assert_eq!(of_type(&entities, "IFCMAPPEDITEM").len(), 2);
+    }
+}
@@ -0,0 +1,1180 @@
+use crate::step::*;
+use crate::*;
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, SquareMatrix};
+use std::collections::{BTreeMap, HashMap};
+
+type Entities = HashMap<u64, StepEntity>;
+
+//IFC doors don't say how thick their leaf is unless their body is a plain box, so this is half the
+//thickness of doors that aren't, the same as Door::width.
+const DOOR_LEAF_WIDTH: WorldCoord = 0.025;
+
+//Placements, representation maps and the like are nested far less deeply than this in any real
+//file, so going further means the file refers back to itself.
+const MAX_DEPTH: usize = 64;
+
+//How many straight sides a circle gets when it's turned into triangles.
+const CIRCLE_SEGMENTS: usize = 24;
+
+//Products that are part of the spatial structure rather than the building.  Openings are made
+//again by the walls hosting doors.
+const SPATIAL_TYPES: &[&str] = &[
+    "IFCSITE",
+    "IFCBUILDING",
+    "IFCBUILDINGSTOREY",
+    "IFCSPACE",
+    "IFCOPENINGELEMENT",
+];
+
+const WALL_TYPES: &[&str] = &["IFCWALL", "IFCWALLSTANDARDCASE", "IFCWALLELEMENTEDCASE"];
+
+///A symbol's geometry, which goes in a file of its own that the SymbolDef in the imported file
+/// points at.
+#[derive(Debug)]
+pub struct SymbolFile {
+    pub file: FileID,
+    pub name: String,
+    pub objects: Vec<DataBox>,
+}
+
+#[derive(Debug, Default)]
+pub struct IfcImport {
+    pub objects: Vec<DataBox>,
+    ///Has to be created before the objects are added, since their symbol definitions point at it.
+    pub symbol_files: Vec<SymbolFile>,
+    ///Entity types that had no native object and came in as symbols, with how many of each.
+    pub as_symbols: BTreeMap<String, usize>,
+    ///Entity types that had no geometry that could be read, with how many of each were left out.
+    pub skipped: BTreeMap<String, usize>,
+}
+
+fn get<'a>(entities: &'a Entities, val: &StepValue) -> Option<&'a StepEntity> {
+    entities.get(&val.as_ref_id()?)
+}
+
+fn coords(val: &StepValue) -> Option<Vector3f> {
+    let coords: Vec<f64> = val
+        .as_list()
+        .iter()
+        .filter_map(|val| val.as_f64())
+        .collect();
+    match coords.len() {
+        2 => Some(Vector3f::new(coords[0], coords[1], 0.0)),
+        3 => Some(Vector3f::new(coords[0], coords[1], coords[2])),
+        _ => None,
+    }
+}
+
+fn point(entities: &Entities, val: &StepValue) -> Option<Point3f> {
+    let entity = get(entities, val)?;
+    if entity.name != "IFCCARTESIANPOINT" {
+        return None;
+    }
+    coords(entity.arg(0)).map(Point3f::from_vec)
+}
+
+fn direction(entities: &Entities, val: &StepValue) -> Option<Vector3f> {
+    let entity = get(entities, val)?;
+    if entity.name != "IFCDIRECTION" {
+        return None;
+    }
+    coords(entity.arg(0)).filter(|dir| dir.magnitude2() > 0.0)
+}
+
+fn frame(origin: Point3f, x: Vector3f, y: Vector3f, z: Vector3f) -> Matrix4<f64> {
+    Matrix4::from_cols(
+        x.extend(0.0),
+        y.extend(0.0),
+        z.extend(0.0),
+        origin.to_vec().extend(1.0),
+    )
+}
+
+///IfcAxis2Placement3D and IfcAxis2Placement2D, as the transform from their coordinates to their
+/// parent's.
+fn axis_placement(entities: &Entities, val: &StepValue) -> Matrix4<f64> {
+    let entity = match get(entities, val) {
+        Some(entity) => entity,
+        None => return Matrix4::identity(),
+    };
+    let origin = point(entities, entity.arg(0)).unwrap_or_else(Point3f::origin);
+    let (z, x_guess) = match entity.name.as_str() {
+        "IFCAXIS2PLACEMENT3D" => (
+            direction(entities, entity.arg(1)).unwrap_or_else(Vector3f::unit_z),
+            direction(entities, entity.arg(2)).unwrap_or_else(Vector3f::unit_x),
+        ),
+        "IFCAXIS2PLACEMENT2D" => (
+            Vector3f::unit_z(),
+            direction(entities, entity.arg(1)).unwrap_or_else(Vector3f::unit_x),
+        ),
+        _ => return Matrix4::identity(),
+    };
+    let z = z.normalize();
+    let mut x = x_guess - z * x_guess.dot(z);
+    if x.magnitude2() == 0.0 {
+        x = if z.x.abs() < 0.9 {
+            Vector3f::unit_x()
+        } else {
+            Vector3f::unit_y()
+        };
+        x = x - z * x.dot(z);
+    }
+    let x = x.normalize();
+    frame(origin, x, z.cross(x), z)
+}
+
+///The transform from an IfcLocalPlacement's coordinates to the world's.
+fn object_placement(entities: &Entities, val: &StepValue, depth: usize) -> Matrix4<f64> {
+    match get(entities, val) {
+        Some(entity) if entity.name == "IFCLOCALPLACEMENT" && depth < MAX_DEPTH => {
+            object_placement(entities, entity.arg(0), depth + 1)
+                * axis_placement(entities, entity.arg(1))
+        }
+        _ => Matrix4::identity(),
+    }
+}
+
+fn transform_operator(entities: &Entities, val: &StepValue) -> Matrix4<f64> {
+    let entity = match get(entities, val) {
+        Some(entity) => entity,
+        None => return Matrix4::identity(),
+    };
+    let scale = entity.arg(3).as_f64().unwrap_or(1.0);
+    let (scale_2, scale_3) = if entity.name == "IFCCARTESIANTRANSFORMATIONOPERATOR3DNONUNIFORM" {
+        (
+            entity.arg(5).as_f64().unwrap_or(scale),
+            entity.arg(6).as_f64().unwrap_or(scale),
+        )
+    } else {
+        (scale, scale)
+    };
+    let x = direction(entities, entity.arg(0)).unwrap_or_else(Vector3f::unit_x);
+    let y = direction(entities, entity.arg(1)).unwrap_or_else(Vector3f::unit_y);
+    let z = direction(entities, entity.arg(4)).unwrap_or_else(Vector3f::unit_z);
+    let origin = point(entities, entity.arg(2)).unwrap_or_else(Point3f::origin);
+    frame(
+        origin,
+        x.normalize() * scale,
+        y.normalize() * scale_2,
+        z.normalize() * scale_3,
+    )
+}
+
+///How many meters are in the file's length unit.
+fn length_scale(entities: &Entities) -> f64 {
+    for entity in entities.values() {
+        if entity.name != "IFCUNITASSIGNMENT" {
+            continue;
+        }
+        for unit in entity.arg(0).as_list() {
+            let unit = match get(entities, unit) {
+                Some(unit) if unit.arg(1).as_str() == Some("LENGTHUNIT") => unit,
+                _ => continue,
+            };
+            match unit.name.as_str() {
+                "IFCSIUNIT" => {
+                    return match unit.arg(2).as_str() {
+                        Some("KILO") => 1000.0,
+                        Some("DECI") => 0.1,
+                        Some("CENTI") => 0.01,
+                        Some("MILLI") => 0.001,
+                        Some("MICRO") => 0.000_001,
+                        _ => 1.0,
+                    }
+                }
+                //Feet and inches, which are defined as a multiple of meters.
+                "IFCCONVERSIONBASEDUNIT" => {
+                    if let Some(factor) =
+                        get(entities, unit.arg(3)).and_then(|measure| measure.arg(0).as_f64())
+                    {
+                        return factor;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+    1.0
+}
+
+///The items of the product's body, or of all of its representations if none is called Body.
+fn body_items<'a>(entities: &'a Entities, product: &StepEntity) -> Vec<&'a StepValue> {
+    let reps: Vec<&StepEntity> = match get(entities, product.arg(6)) {
+        Some(shape) => shape
+            .arg(2)
+            .as_list()
+            .iter()
+            .filter_map(|rep| get(entities, rep))
+            .collect(),
+        None => return Vec::new(),
+    };
+    let bodies: Vec<&StepEntity> = reps
+        .iter()
+        .filter(|rep| rep.arg(1).as_str() == Some("Body"))
+        .cloned()
+        .collect();
+    let reps = if bodies.is_empty() { reps } else { bodies };
+    reps.iter()
+        .flat_map(|rep| rep.arg(3).as_list().iter())
+        .collect()
+}
+
+///A box extruded straight up from a rectangle.  The frame is centered on the rectangle, with X
+/// along its x_dim side.
+struct Extrusion {
+    frame: Matrix4<f64>,
+    x_dim: f64,
+    y_dim: f64,
+    depth: f64,
+}
+
+fn rectangle(entities: &Entities, profile: &StepEntity) -> Option<(Matrix4<f64>, f64, f64)> {
+    match profile.name.as_str() {
+        "IFCRECTANGLEPROFILEDEF" => Some((
+            axis_placement(entities, profile.arg(2)),
+            profile.arg(3).as_f64()?,
+            profile.arg(4).as_f64()?,
+        )),
+        //A closed polyline with four corners at right angles, which is how a lot of exporters
+        // write walls.
+        "IFCARBITRARYCLOSEDPROFILEDEF" => {
+            let curve = get(entities, profile.arg(2))?;
+            if curve.name != "IFCPOLYLINE" {
+                return None;
+            }
+            let mut pts: Vec<Point3f> = curve
+                .arg(0)
+                .as_list()
+                .iter()
+                .filter_map(|pt| point(entities, pt))
+                .collect();
+            if pts.len() == 5 && (pts[4] - pts[0]).magnitude2() < 1e-12 {
+                pts.pop();
+            }
+            if pts.len() != 4 {
+                return None;
+            }
+            let side_1 = pts[1] - pts[0];
+            let side_2 = pts[2] - pts[1];
+            let x_dim = side_1.magnitude();
+            let y_dim = side_2.magnitude();
+            let closes = (pts[3] - (pts[0] + side_2)).magnitude2() < 1e-12;
+            if x_dim == 0.0
+                || y_dim == 0.0
+                || side_1.dot(side_2).abs() > 1e-6 * x_dim * y_dim
+                || !closes
+            {
+                return None;
+            }
+            let center = pts[0] + (side_1 + side_2) / 2.0;
+            let x = side_1 / x_dim;
+            let y = Vector3f::new(-x.y, x.x, 0.0);
+            Some((frame(center, x, y, Vector3f::unit_z()), x_dim, y_dim))
+        }
+        _ => None,
+    }
+}
+
+fn extrusion(entities: &Entities, items: &[&StepValue]) -> Option<Extrusion> {
+    if items.len() != 1 {
+        return None;
+    }
+    let solid = get(entities, items[0])?;
+    if solid.name != "IFCEXTRUDEDAREASOLID" {
+        return None;
+    }
+    let extruded = direction(entities, solid.arg(2))?.normalize();
+    if extruded.z < 1.0 - 1e-6 {
+        return None;
+    }
+    let (profile_frame, x_dim, y_dim) = rectangle(entities, get(entities, solid.arg(0))?)?;
+    Some(Extrusion {
+        frame: axis_placement(entities, solid.arg(1)) * profile_frame,
+        x_dim,
+        y_dim,
+        depth: solid.arg(3).as_f64()?,
+    })
+}
+
+///The line down the middle of the extrusion's longer side, with its thickness and height, once it's
+/// placed in the world.  Anything that doesn't end up standing straight up has no line.
+fn extrusion_line(
+    extrusion: &Extrusion,
+    placement: &Matrix4<f64>,
+    scale: f64,
+) -> Option<(Point3f, Point3f, WorldCoord, WorldCoord)> {
+    let world = placement * extrusion.frame;
+    let up = world.z.truncate();
+    if up.magnitude2() == 0.0 || up.normalize().z < 1.0 - 1e-6 {
+        return None;
+    }
+    let (half, thickness) = if extrusion.x_dim >= extrusion.y_dim {
+        (
+            Vector3f::new(extrusion.x_dim / 2.0, 0.0, 0.0),
+            extrusion.y_dim,
+        )
+    } else {
+        (
+            Vector3f::new(0.0, extrusion.y_dim / 2.0, 0.0),
+            extrusion.x_dim,
+        )
+    };
+    let first = world.transform_point(Point3f::from_vec(-half));
+    let second = world.transform_point(Point3f::from_vec(half));
+    Some((first, second, thickness * scale, extrusion.depth * scale))
+}
+
+///The outline of a profile, in the coordinates mat maps to.  Voids in the profile are left out.
+fn profile_outline(
+    entities: &Entities,
+    profile: &StepEntity,
+    mat: &Matrix4<f64>,
+) -> Option<Vec<Point3f>> {
+    let placed = |pts: Vec<Point3f>, placement: &StepValue| {
+        let placement = mat * axis_placement(entities, placement);
+        pts.into_iter()
+            .map(|pt| placement.transform_point(pt))
+            .collect()
+    };
+    match profile.name.as_str() {
+        "IFCRECTANGLEPROFILEDEF" => {
+            let half_x = profile.arg(3).as_f64()? / 2.0;
+            let half_y = profile.arg(4).as_f64()? / 2.0;
+            let corners = vec![
+                Point3f::new(-half_x, -half_y, 0.0),
+                Point3f::new(half_x, -half_y, 0.0),
+                Point3f::new(half_x, half_y, 0.0),
+                Point3f::new(-half_x, half_y, 0.0),
+            ];
+            Some(placed(corners, profile.arg(2)))
+        }
+        "IFCCIRCLEPROFILEDEF" => {
+            let radius = profile.arg(3).as_f64()?;
+            let sides = (0..CIRCLE_SEGMENTS)
+                .map(|side| {
+                    let angle = side as f64 / CIRCLE_SEGMENTS as f64 * std::f64::consts::PI * 2.0;
+                    Point3f::new(radius * angle.cos(), radius * angle.sin(), 0.0)
+                })
+                .collect();
+            Some(placed(sides, profile.arg(2)))
+        }
+        "IFCARBITRARYCLOSEDPROFILEDEF" | "IFCARBITRARYPROFILEDEFWITHVOIDS" => {
+            let curve = get(entities, profile.arg(2))?;
+            //Arcs in an indexed polycurve are cut straight across.
+            let mut pts: Vec<Point3f> = match curve.name.as_str() {
+                "IFCPOLYLINE" => curve
+                    .arg(0)
+                    .as_list()
+                    .iter()
+                    .filter_map(|pt| point(entities, pt))
+                    .collect(),
+                "IFCINDEXEDPOLYCURVE" => get(entities, curve.arg(0))?
+                    .arg(0)
+                    .as_list()
+                    .iter()
+                    .filter_map(coords)
+                    .map(Point3f::from_vec)
+                    .collect(),
+                _ => return None,
+            };
+            if pts.len() > 1 && (pts[pts.len() - 1] - pts[0]).magnitude2() < 1e-12 {
+                pts.pop();
+            }
+            Some(pts.into_iter().map(|pt| mat.transform_point(pt)).collect())
+        }
+        _ => None,
+    }
+}
+
+///Splits a flat outline, which can be concave but can't cross itself, into triangles by clipping
+/// off its corners one at a time.  The triangles go the same way around as the outline.
+fn triangulate(outline: &[Point3f], triangles: &mut Vec<[Point3f; 3]>) {
+    if outline.len() < 3 {
+        return;
+    }
+    let mut normal = Vector3f::new(0.0, 0.0, 0.0);
+    for (index, pt) in outline.iter().enumerate() {
+        let next = outline[(index + 1) % outline.len()];
+        normal += pt.to_vec().cross(next.to_vec());
+    }
+    //Flattened onto whichever axis plane the outline is least edge on to, with the sign keeping
+    // its corners going counter-clockwise.
+    let (x, y, sign) = if normal.z.abs() >= normal.x.abs() && normal.z.abs() >= normal.y.abs() {
+        (0, 1, normal.z.signum())
+    } else if normal.x.abs() >= normal.y.abs() {
+        (1, 2, normal.x.signum())
+    } else {
+        (2, 0, normal.y.signum())
+    };
+    let flat: Vec<(f64, f64)> = outline.iter().map(|pt| (pt[x], pt[y])).collect();
+    let turn = |a: usize, b: usize, c: usize| {
+        let (first, second, third) = (flat[a], flat[b], flat[c]);
+        ((second.0 - first.0) * (third.1 - first.1) - (second.1 - first.1) * (third.0 - first.0))
+            * sign
+    };
+    let mut remaining: Vec<usize> = (0..outline.len()).collect();
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let ear = (0..len).find(|index| {
+            let (prev, cur, next) = (
+                remaining[(index + len - 1) % len],
+                remaining[*index],
+                remaining[(index + 1) % len],
+            );
+            turn(prev, cur, next) > 0.0
+                && remaining.iter().all(|other| {
+                    [prev, cur, next].contains(other)
+                        || turn(prev, cur, *other) < 0.0
+                        || turn(cur, next, *other) < 0.0
+                        || turn(next, prev, *other) < 0.0
+                })
+        });
+        //Only an outline that crosses itself or folds back has no ears, and a fan is as good as
+        // anything for those.
+        let index = ear.unwrap_or(1);
+        let (prev, cur, next) = (
+            remaining[(index + len - 1) % len],
+            remaining[index],
+            remaining[(index + 1) % len],
+        );
+        triangles.push([outline[prev], outline[cur], outline[next]]);
+        remaining.remove(index);
+    }
+    triangles.push([
+        outline[remaining[0]],
+        outline[remaining[1]],
+        outline[remaining[2]],
+    ]);
+}
+
+pub(crate) fn box_triangles(
+    mat: &Matrix4<f64>,
+    corner: Point3f,
+    size: Vector3f,
+    triangles: &mut Vec<[Point3f; 3]>,
+) {
+    let pt = |x: f64, y: f64, z: f64| {
+        mat.transform_point(corner + Vector3f::new(x * size.x, y * size.y, z * size.z))
+    };
+    let bottom = vec![
+        pt(0., 0., 0.),
+        pt(0., 1., 0.),
+        pt(1., 1., 0.),
+        pt(1., 0., 0.),
+    ];
+    let top: Vec<Point3f> = bottom
+        .iter()
+        .map(|corner| corner + mat.transform_vector(Vector3f::new(0.0, 0.0, size.z)))
+        .collect();
+    prism_triangles(&bottom, &top, triangles);
+}
+
+///The sides and ends of a solid between two copies of an outline.
+fn prism_triangles(bottom: &[Point3f], top: &[Point3f], triangles: &mut Vec<[Point3f; 3]>) {
+    let reversed: Vec<Point3f> = bottom.iter().rev().cloned().collect();
+    triangulate(&reversed, triangles);
+    triangulate(top, triangles);
+    for index in 0..bottom.len() {
+        let next = (index + 1) % bottom.len();
+        triangles.push([bottom[index], bottom[next], top[next]]);
+        triangles.push([bottom[index], top[next], top[index]]);
+    }
+}
+
+///The points of an IfcCartesianPointList3D picked out by an IFC4 face set's 1-based indices.
+fn indexed_points(
+    entities: &Entities,
+    face_set: &StepEntity,
+    indices: &[StepValue],
+    mat: &Matrix4<f64>,
+) -> Vec<Point3f> {
+    let pts = match get(entities, face_set.arg(0)) {
+        Some(list) => list.arg(0).as_list(),
+        None => return Vec::new(),
+    };
+    indices
+        .iter()
+        .filter_map(|index| {
+            let index = index.as_f64()? as usize;
+            coords(pts.get(index.checked_sub(1)?)?)
+        })
+        .map(|pt| mat.transform_point(Point3f::from_vec(pt)))
+        .collect()
+}
+
+///Turns everything in a representation item into triangles, in the coordinates mat maps to.
+fn collect_triangles(
+    entities: &Entities,
+    val: &StepValue,
+    mat: &Matrix4<f64>,
+    triangles: &mut Vec<[Point3f; 3]>,
+    depth: usize,
+) {
+    if depth > MAX_DEPTH {
+        return;
+    }
+    let entity = match get(entities, val) {
+        Some(entity) => entity,
+        None => {
+            for val in val.as_list() {
+                collect_triangles(entities, val, mat, triangles, depth + 1);
+            }
+            return;
+        }
+    };
+    match entity.name.as_str() {
+        "IFCEXTRUDEDAREASOLID" => {
+            let base = mat * axis_placement(entities, entity.arg(1));
+            let bottom = match get(entities, entity.arg(0))
+                .and_then(|profile| profile_outline(entities, profile, &base))
+            {
+                Some(bottom) => bottom,
+                None => return,
+            };
+            let extruded = direction(entities, entity.arg(2)).unwrap_or_else(Vector3f::unit_z);
+            let offset =
+                base.transform_vector(extruded.normalize() * entity.arg(3).as_f64().unwrap_or(0.0));
+            let top: Vec<Point3f> = bottom.iter().map(|pt| pt + offset).collect();
+            prism_triangles(&bottom, &top, triangles);
+        }
+        "IFCTRIANGULATEDFACESET" => {
+            for face in entity.arg(3).as_list() {
+                let corners = indexed_points(entities, entity, face.as_list(), mat);
+                if corners.len() == 3 {
+                    triangles.push([corners[0], corners[1], corners[2]]);
+                }
+            }
+        }
+        "IFCPOLYGONALFACESET" => {
+            for face in entity.arg(2).as_list() {
+                if let Some(face) = get(entities, face) {
+                    let outline = indexed_points(entities, entity, face.arg(0).as_list(), mat);
+                    triangulate(&outline, triangles);
+                }
+            }
+        }
+        //Holes in faces are filled in.
+        "IFCFACE" => {
+            let bounds: Vec<&StepEntity> = entity
+                .arg(0)
+                .as_list()
+                .iter()
+                .filter_map(|bound| get(entities, bound))
+                .collect();
+            let outer = bounds
+                .iter()
+                .find(|bound| bound.name == "IFCFACEOUTERBOUND")
+                .or_else(|| bounds.first());
+            if let Some(polyloop) = outer.and_then(|outer| get(entities, outer.arg(0))) {
+                let outline: Vec<Point3f> = polyloop
+                    .arg(0)
+                    .as_list()
+                    .iter()
+                    .filter_map(|pt| point(entities, pt))
+                    .map(|pt| mat.transform_point(pt))
+                    .collect();
+                triangulate(&outline, triangles);
+            }
+        }
+        "IFCBOUNDINGBOX" => {
+            if let Some(corner) = point(entities, entity.arg(0)) {
+                let size = Vector3f::new(
+                    entity.arg(1).as_f64().unwrap_or(0.0),
+                    entity.arg(2).as_f64().unwrap_or(0.0),
+                    entity.arg(3).as_f64().unwrap_or(0.0),
+                );
+                box_triangles(mat, corner, size, triangles);
+            }
+        }
+        "IFCBLOCK" => {
+            let size = Vector3f::new(
+                entity.arg(1).as_f64().unwrap_or(0.0),
+                entity.arg(2).as_f64().unwrap_or(0.0),
+                entity.arg(3).as_f64().unwrap_or(0.0),
+            );
+            let base = mat * axis_placement(entities, entity.arg(0));
+            box_triangles(&base, Point3f::origin(), size, triangles);
+        }
+        "IFCMAPPEDITEM" => {
+            if let Some(map) = get(entities, entity.arg(0)) {
+                let map_mat = mat
+                    * transform_operator(entities, entity.arg(1))
+                    * axis_placement(entities, map.arg(0));
+                if let Some(rep) = get(entities, map.arg(1)) {
+                    collect_triangles(entities, rep.arg(3), &map_mat, triangles, depth + 1);
+                }
+            }
+        }
+        //Only the first operand can add to the result of a boolean.
+        "IFCBOOLEANRESULT" | "IFCBOOLEANCLIPPINGRESULT" => {
+            collect_triangles(entities, entity.arg(1), mat, triangles, depth + 1);
+        }
+        name if name.starts_with("IFCAXIS2PLACEMENT") || name == "IFCDIRECTION" => (),
+        //Breps, shells and surface models, which all come down to faces.
+        _ => {
+            for arg in &entity.args {
+                collect_triangles(entities, arg, mat, triangles, depth + 1);
+            }
+        }
+    }
+}
+
+///Each symbol gets a file holding its geometry as a Mesh, moved so the bottom left corner of its
+/// bounding box is at the origin.  Elements sharing an IfcRepresentationMap share a symbol.
+struct Symbols {
+    defs: HashMap<u64, (ObjID, Cube)>,
+    files: Vec<SymbolFile>,
+    objects: Vec<DataBox>,
+}
+
+impl Symbols {
+    ///The triangles are in meters, in the coordinates that transform maps to the world's.  They're
+    /// only used for the first instance with a key.
+    fn instance(
+        &mut self,
+        key: u64,
+        name: &str,
+        triangles: Vec<[Point3f; 3]>,
+        transform: TransMat,
+    ) -> Option<DataBox> {
+        let (def_id, def_bbox) = match self.defs.get(&key) {
+            Some(def) => *def,
+            None => {
+                if triangles.is_empty() {
+                    return None;
+                }
+                let mut mesh = Mesh::new(triangles);
+                let bbox = mesh.get_axis_aligned_bounding_box();
+                mesh.move_obj(&-bbox.bottom_left.to_vec());
+                let file = FileID::new_v4();
+                let def = SymbolDef::new(
+                    file,
+                    0,
+                    Cube {
+                        bottom_left: Point3f::origin(),
+                        top_right: Point3f::from_vec(bbox.top_right - bbox.bottom_left),
+                    },
+                );
+                let def_id = *def.get_id();
+                self.files.push(SymbolFile {
+                    file,
+                    name: String::from(name),
+                    objects: vec![Box::new(mesh) as DataBox],
+                });
+                self.objects.push(Box::new(def));
+                self.defs.insert(key, (def_id, bbox));
+                (def_id, bbox)
+            }
+        };
+        let mut instance = SymbolInstance::new();
+        instance.transform = transform * Matrix4::from_translation(def_bbox.bottom_left.to_vec());
+        let mut instance = Box::new(instance) as DataBox;
+        let result = Cube {
+            bottom_left: Point3f::origin(),
+            top_right: Point3f::from_vec(def_bbox.top_right - def_bbox.bottom_left),
+        };
+        instance.set_ref(
+            RefType::AxisAlignedBoundBox,
+            0,
+            result.as_result(),
+            RefID::new(def_id, RefType::AxisAlignedBoundBox, 0),
+            &None,
+        );
+        Some(instance)
+    }
+}
+
+fn symbol_instance(
+    entities: &Entities,
+    symbols: &mut Symbols,
+    product: (u64, &StepEntity),
+    items: &[&StepValue],
+    placement: &Matrix4<f64>,
+    scale: f64,
+) -> Option<DataBox> {
+    let (id, entity) = product;
+    let name = entity.arg(2).as_str().unwrap_or(&entity.name);
+    //The symbol file is in meters, so the file's own units are taken back out of the placement.
+    let to_meters = Matrix4::from_scale(scale);
+    let from_meters = Matrix4::from_scale(1.0 / scale);
+    //A single mapped item shares its map's symbol with everything else that uses it.
+    if items.len() == 1 {
+        if let Some(item) = get(entities, items[0]).filter(|item| item.name == "IFCMAPPEDITEM") {
+            let map_id = item.arg(0).as_ref_id()?;
+            let map = entities.get(&map_id)?;
+            let mut triangles = Vec::new();
+            if !symbols.defs.contains_key(&map_id) {
+                let map_mat = to_meters * axis_placement(entities, map.arg(0));
+                if let Some(rep) = get(entities, map.arg(1)) {
+                    collect_triangles(entities, rep.arg(3), &map_mat, &mut triangles, 0);
+                }
+            }
+            let transform = placement * transform_operator(entities, item.arg(1)) * from_meters;
+            return symbols.instance(map_id, name, triangles, transform);
+        }
+    }
+    let mut triangles = Vec::new();
+    for item in items {
+        collect_triangles(entities, item, &to_meters, &mut triangles, 0);
+    }
+    symbols.instance(id, name, triangles, placement * from_meters)
+}
+
+fn count(counts: &mut BTreeMap<String, usize>, name: &str) {
+    *counts.entry(String::from(name)).or_insert(0) += 1;
+}
+
+///Reads the walls and doors in an IFC STEP file as Wall and Door objects, with each door hosted in
+/// the wall it fills an opening in.  Walls and doors are only read when their body is a box
+/// standing straight up, although doors also fall back to their overall width and height.
+/// Everything else with geometry comes in as a symbol instance, with the geometry turned into
+/// triangles in a symbol file of its own.  The types of those are reported along with the types
+/// of elements that couldn't be read at all.
+pub fn import_ifc(text: &str) -> Result<IfcImport, ObjError> {
+    let entities = parse_step(text)?;
+    let scale = length_scale(&entities);
+    let root = Matrix4::from_scale(scale);
+    let mut import = IfcImport::default();
+    let mut symbols = Symbols {
+        defs: HashMap::new(),
+        files: Vec::new(),
+        objects: Vec::new(),
+    };
+    let mut natives = BTreeMap::new();
+    let mut ids: Vec<&u64> = entities.keys().collect();
+    ids.sort();
+    for id in ids {
+        let entity = &entities[id];
+        let is_product = entity.args.len() >= 7
+            && matches!(entity.arg(0), StepValue::Str(..))
+            && get(&entities, entity.arg(5))
+                .map(|placement| placement.name.ends_with("PLACEMENT"))
+                .unwrap_or(false);
+        if !is_product || SPATIAL_TYPES.contains(&entity.name.as_str()) {
+            continue;
+        }
+        let placement = root * object_placement(&entities, entity.arg(5), 0);
+        let items = body_items(&entities, entity);
+        let extruded = extrusion(&entities, &items)
+            .and_then(|extrusion| extrusion_line(&extrusion, &placement, scale));
+        let native: Option<DataBox> = if WALL_TYPES.contains(&entity.name.as_str()) {
+            extruded.map(|(first, second, thickness, height)| {
+                Box::new(Wall::new(first, second, thickness / 2.0, height)) as DataBox
+            })
+        } else if entity.name == "IFCDOOR" {
+            match extruded {
+                Some((first, second, thickness, height)) => {
+                    Some(Box::new(Door::new(first, second, thickness / 2.0, height)) as DataBox)
+                }
+                None => match (entity.arg(8).as_f64(), entity.arg(9).as_f64()) {
+                    (Some(height), Some(width)) if width > 0.0 => {
+                        let first = placement.transform_point(Point3f::origin());
+                        let along = placement.x.truncate().normalize() * width * scale;
+                        let second = first + Vector3f::new(along.x, along.y, 0.0);
+                        Some(
+                            Box::new(Door::new(first, second, DOOR_LEAF_WIDTH, height * scale))
+                                as DataBox,
+                        )
+                    }
+                    _ => None,
+                },
+            }
+        } else {
+            None
+        };
+        match native {
+            Some(native) => {
+                natives.insert(*id, native);
+            }
+            None => {
+                let product = (*id, entity);
+                match symbol_instance(&entities, &mut symbols, product, &items, &placement, scale) {
+                    Some(instance) => {
+                        count(&mut import.as_symbols, &entity.name);
+                        symbols.objects.push(instance);
+                    }
+                    None => count(&mut import.skipped, &entity.name),
+                }
+            }
+        }
+    }
+
+    //Doors fill openings, which void walls.
+    let mut opening_hosts = HashMap::new();
+    for entity in entities.values() {
+        if entity.name == "IFCRELVOIDSELEMENT" {
+            if let (Some(wall), Some(opening)) =
+                (entity.arg(4).as_ref_id(), entity.arg(5).as_ref_id())
+            {
+                opening_hosts.insert(opening, wall);
+            }
+        }
+    }
+    for entity in entities.values() {
+        if entity.name != "IFCRELFILLSELEMENT" {
+            continue;
+        }
+        let host = entity
+            .arg(4)
+            .as_ref_id()
+            .and_then(|opening| opening_hosts.get(&opening));
+        let (wall_id, door_id) = match (host, entity.arg(5).as_ref_id()) {
+            (Some(wall_id), Some(door_id)) => (*wall_id, door_id),
+            _ => continue,
+        };
+        //An element can't fill an opening in itself, however the file says it does.
+        if wall_id == door_id || !natives.contains_key(&wall_id) || !natives.contains_key(&door_id)
+        {
+            continue;
+        }
+        let mut wall = natives.remove(&wall_id).unwrap();
+        let mut door = natives.remove(&door_id).unwrap();
+        //The door is snapped onto the wall by the end closest to the wall's start, so it keeps
+        // its place along the wall.
+        let guess = match (wall.downcast_ref::<Wall>(), door.downcast_ref::<Door>()) {
+            (Some(wall), Some(door)) => {
+                let dir = wall.second_pt.info - wall.first_pt.info;
+                let line = &door.dir.line;
+                if (line.pt_1 - line.pt_2).dot(dir) <= 0.0 {
+                    line.pt_1
+                } else {
+                    line.pt_2
+                }
+            }
+            _ => Point3f::origin(),
+        };
+        if let Err(e) = join_refs(
+            &mut wall,
+            &mut door,
+            RefType::ProfilePlane,
+            RefType::ProfileLine,
+            &guess,
+        ) {
+            warn!("Couldn't host door {} in wall {}: {}", door_id, wall_id, e);
+        }
+        natives.insert(wall_id, wall);
+        natives.insert(door_id, door);
+    }
+
+    import.objects = natives.into_values().collect();
+    import.objects.extend(symbols.objects);
+    import.symbol_files = symbols.files;
+    Ok(import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Point3f, expected: Point3f) {
+        assert!(
+            (actual - expected).magnitude() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn data(lines: &str) -> Entities {
+        parse_step(&format!("DATA;\n{}\nENDSEC;\n", lines)).unwrap()
+    }
+
+    #[test]
+    fn test_object_placement() {
+        //Turned a quarter turn to the left and moved along X, inside a parent moved up Z
+        let entities = data(
+            "#1=IFCCARTESIANPOINT((0.,0.,3.));
+#2=IFCAXIS2PLACEMENT3D(#1,$,$);
+#3=IFCLOCALPLACEMENT($,#2);
+#4=IFCCARTESIANPOINT((2.,0.,0.));
+#5=IFCDIRECTION((0.,0.,1.));
+#6=IFCDIRECTION((0.,1.,0.));
+#7=IFCAXIS2PLACEMENT3D(#4,#5,#6);
+#8=IFCLOCALPLACEMENT(#3,#7);
+#9=IFCLOCALPLACEMENT(#9,#7);",
+        );
+        let placement = object_placement(&entities, &StepValue::Ref(8), 0);
+        assert_close(
+            placement.transform_point(Point3f::new(1.0, 0.0, 0.0)),
+            Point3f::new(2.0, 1.0, 3.0),
+        );
+        assert_close(
+            placement.transform_point(Point3f::new(0.0, 1.0, 0.0)),
+            Point3f::new(1.0, 0.0, 3.0),
+        );
+        //A placement relative to itself stops instead of recursing forever
+        object_placement(&entities, &StepValue::Ref(9), 0);
+    }
+
+    #[test]
+    fn test_length_scale() {
+        assert_eq!(length_scale(&data("#1=IFCWALL('a');")), 1.0);
+        let millimeters = data(
+            "#1=IFCSIUNIT(*,.LENGTHUNIT.,.MILLI.,.METRE.);
+#2=IFCSIUNIT(*,.PLANEANGLEUNIT.,$,.RADIAN.);
+#3=IFCUNITASSIGNMENT((#2,#1));",
+        );
+        assert_eq!(length_scale(&millimeters), 0.001);
+        let feet = data(
+            "#1=IFCSIUNIT(*,.LENGTHUNIT.,$,.METRE.);
+#2=IFCMEASUREWITHUNIT(IFCLENGTHMEASURE(0.3048),#1);
+#3=IFCDIMENSIONALEXPONENTS(1,0,0,0,0,0,0);
+#4=IFCCONVERSIONBASEDUNIT(#3,.LENGTHUNIT.,'FOOT',#2);
+#5=IFCUNITASSIGNMENT((#4));",
+        );
+        assert_eq!(length_scale(&feet), 0.3048);
+    }
+
+    #[test]
+    fn test_rectangle() {
+        let entities = data(
+            "#1=IFCCARTESIANPOINT((1.,1.));
+#2=IFCAXIS2PLACEMENT2D(#1,$);
+#3=IFCRECTANGLEPROFILEDEF(.AREA.,$,#2,4.,0.2);
+#4=IFCCARTESIANPOINT((0.,0.));
+#5=IFCCARTESIANPOINT((0.,3.));
+#6=IFCCARTESIANPOINT((-0.2,3.));
+#7=IFCCARTESIANPOINT((-0.2,0.));
+#8=IFCPOLYLINE((#4,#5,#6,#7,#4));
+#9=IFCARBITRARYCLOSEDPROFILEDEF(.AREA.,$,#8);
+#10=IFCCARTESIANPOINT((1.,3.));
+#11=IFCPOLYLINE((#4,#5,#10,#7));
+#12=IFCARBITRARYCLOSEDPROFILEDEF(.AREA.,$,#11);",
+        );
+        let (frame, x_dim, y_dim) = rectangle(&entities, &entities[&3]).unwrap();
+        assert_eq!((x_dim, y_dim), (4.0, 0.2));
+        assert_close(
+            frame.transform_point(Point3f::origin()),
+            Point3f::new(1.0, 1.0, 0.0),
+        );
+
+        //The polyline's first side is along Y, so the frame's X is too
+        let (frame, x_dim, y_dim) = rectangle(&entities, &entities[&9]).unwrap();
+        assert!((x_dim - 3.0).abs() < 1e-9 && (y_dim - 0.2).abs() < 1e-9);
+        assert_close(
+            frame.transform_point(Point3f::origin()),
+            Point3f::new(-0.1, 1.5, 0.0),
+        );
+        assert_close(
+            frame.transform_point(Point3f::new(1.0, 0.0, 0.0)),
+            Point3f::new(-0.1, 2.5, 0.0),
+        );
+
+        //Not at right angles
+        assert!(rectangle(&entities, &entities[&12]).is_none());
+    }
+
+    //In millimeters, with the storey 1m along X.  The wall runs 4m from there, and the door fills
+    // an opening 2m along it.
+    const MODEL: &str = "ISO-10303-21;
+HEADER;
+FILE_DESCRIPTION(('ViewDefinition [CoordinationView]'),'2;1');
+ENDSEC;
+DATA;
+#1=IFCSIUNIT(*,.LENGTHUNIT.,.MILLI.,.METRE.);
+#2=IFCUNITASSIGNMENT((#1));
+#3=IFCCARTESIANPOINT((0.,0.,0.));
+#4=IFCDIRECTION((0.,0.,1.));
+#5=IFCDIRECTION((1.,0.,0.));
+#6=IFCAXIS2PLACEMENT3D(#3,$,$);
+#7=IFCLOCALPLACEMENT($,#6);
+#8=IFCCARTESIANPOINT((1000.,0.,0.));
+#9=IFCAXIS2PLACEMENT3D(#8,#4,#5);
+#10=IFCLOCALPLACEMENT(#7,#9);
+#11=IFCBUILDINGSTOREY('storey',$,'Level 1',$,$,#10,$,$,.ELEMENT.,0.);
+#12=IFCCARTESIANPOINT((2000.,0.));
+#13=IFCAXIS2PLACEMENT2D(#12,$);
+#14=IFCRECTANGLEPROFILEDEF(.AREA.,$,#13,4000.,200.);
+#15=IFCEXTRUDEDAREASOLID(#14,#6,#4,3000.);
+#16=IFCSHAPEREPRESENTATION($,'Body','SweptSolid',(#15));
+#17=IFCPRODUCTDEFINITIONSHAPE($,$,(#16));
+#18=IFCWALLSTANDARDCASE('wall',$,'Wall',$,$,#10,#17,$);
+#19=IFCCARTESIANPOINT((2000.,0.,0.));
+#20=IFCAXIS2PLACEMENT3D(#19,$,$);
+#21=IFCLOCALPLACEMENT(#10,#20);
+#22=IFCOPENINGELEMENT('opening',$,$,$,$,#21,$,$);
+#23=IFCRELVOIDSELEMENT('voids',$,$,$,#18,#22);
+#24=IFCCARTESIANPOINT((0.,-100.));
+#25=IFCCARTESIANPOINT((1000.,-100.));
+#26=IFCCARTESIANPOINT((1000.,100.));
+#27=IFCCARTESIANPOINT((0.,100.));
+#28=IFCPOLYLINE((#24,#25,#26,#27,#24));
+#29=IFCARBITRARYCLOSEDPROFILEDEF(.AREA.,$,#28);
+#30=IFCEXTRUDEDAREASOLID(#29,#6,#4,2100.);
+#31=IFCSHAPEREPRESENTATION($,'Body','SweptSolid',(#30));
+#32=IFCPRODUCTDEFINITIONSHAPE($,$,(#31));
+#33=IFCDOOR('door',$,'Door',$,$,#21,#32,$,2100.,1000.);
+#34=IFCRELFILLSELEMENT('fills',$,$,$,#22,#33);
+#35=IFCRELFILLSELEMENT('fills itself',$,$,$,#22,#18);
+#36=IFCFURNISHINGELEMENT('chair',$,'Chair',$,$,#21,#32,$);
+#37=IFCDOOR('no body',$,'Door',$,$,#21,$,$,$,$);
+ENDSEC;
+END-ISO-10303-21;
+";
+
+    #[test]
+    fn test_import_ifc() {
+        let import = import_ifc(MODEL).unwrap();
+        let mut skipped = BTreeMap::new();
+        skipped.insert(String::from("IFCDOOR"), 1);
+        assert_eq!(import.skipped, skipped);
+        let mut as_symbols = BTreeMap::new();
+        as_symbols.insert(String::from("IFCFURNISHINGELEMENT"), 1);
+        assert_eq!(import.as_symbols, as_symbols);
+        assert_eq!(import.objects.len(), 4);
+
+        let wall = import
+            .objects
+            .iter()
+            .find_map(|obj| obj.downcast_ref::<Wall>())
+            .unwrap();
+        assert_close(wall.first_pt.info, Point3f::new(1.0, 0.0, 0.0));
+        assert_close(wall.second_pt.info, Point3f::new(5.0, 0.0, 0.0));
+        assert!((wall.width - 0.1).abs() < 1e-9);
+        assert!((wall.height - 3.0).abs() < 1e-9);
+
+        let door = import
+            .objects
+            .iter()
+            .find_map(|obj| obj.downcast_ref::<Door>())
+            .unwrap();
+        assert_close(door.dir.line.pt_1, Point3f::new(3.0, 0.0, 0.0));
+        assert_close(door.dir.line.pt_2, Point3f::new(4.0, 0.0, 0.0));
+        assert!((door.height - 2.1).abs() < 1e-9);
+
+        //Hosted in the wall, which has an opening for it
+        assert!(door
+            .get_refs()
+            .into_iter()
+            .flatten()
+            .any(|refer| refer.other.id == *wall.get_id()));
+        assert!(wall
+            .get_refs()
+            .into_iter()
+            .flatten()
+            .any(|refer| refer.other.id == *door.get_id()));
+    }
+
+    #[test]
+    fn test_symbol_instance() {
+        //The chair has the door's body, which is a box 1m along X, 0.2m through and 2.1m high
+        let import = import_ifc(MODEL).unwrap();
+        let def = import
+            .objects
+            .iter()
+            .find_map(|obj| obj.downcast_ref::<SymbolDef>())
+            .unwrap();
+        let instance = import
+            .objects
+            .iter()
+            .find_map(|obj| obj.downcast_ref::<SymbolInstance>())
+            .unwrap();
+        assert!(instance
+            .get_refs()
+            .into_iter()
+            .flatten()
+            .any(|refer| refer.other.id == *def.get_id()));
+        assert_close(
+            instance.transform.transform_point(Point3f::origin()),
+            Point3f::new(3.0, -0.1, 0.0),
+        );
+        assert_close(
+            instance
+                .transform
+                .transform_point(Point3f::new(1.0, 0.2, 2.1)),
+            Point3f::new(4.0, 0.1, 2.1),
+        );
+
+        //The symbol file holds the box, with its corner at the origin
+        assert_eq!(import.symbol_files.len(), 1);
+        let symbol_file = &import.symbol_files[0];
+        assert_eq!(symbol_file.name, "Chair");
+        assert_eq!(*def.get_sym_file(), symbol_file.file);
+        let mesh = symbol_file.objects[0].downcast_ref::<Mesh>().unwrap();
+        assert_eq!(mesh.get_triangles().len(), 12);
+        let bbox = mesh.get_axis_aligned_bounding_box();
+        assert_close(bbox.bottom_left, Point3f::origin());
+        assert_close(bbox.top_right, Point3f::new(1.0, 0.2, 2.1));
+    }
+
+    #[test]
+    fn test_mapped_symbols() {
+        //Two stools sharing a tetrahedron, the second one 5 along X and turned a quarter turn
+        let import = import_ifc(
+            "DATA;
+#1=IFCCARTESIANPOINTLIST3D(((0.,0.,0.),(1.,0.,0.),(0.,1.,0.),(0.,0.,1.)));
+#2=IFCTRIANGULATEDFACESET(#1,$,.T.,((1,3,2),(1,2,4),(1,4,3),(2,3,4)),$);
+#3=IFCSHAPEREPRESENTATION($,'Body','Tessellation',(#2));
+#4=IFCCARTESIANPOINT((0.,0.,0.));
+#5=IFCAXIS2PLACEMENT3D(#4,$,$);
+#6=IFCREPRESENTATIONMAP(#5,#3);
+#7=IFCCARTESIANTRANSFORMATIONOPERATOR3D($,$,#4,$,$);
+#8=IFCMAPPEDITEM(#6,#7);
+#9=IFCSHAPEREPRESENTATION($,'Body','MappedRepresentation',(#8));
+#10=IFCPRODUCTDEFINITIONSHAPE($,$,(#9));
+#11=IFCLOCALPLACEMENT($,#5);
+#12=IFCCARTESIANPOINT((5.,0.,0.));
+#13=IFCDIRECTION((0.,0.,1.));
+#14=IFCDIRECTION((0.,1.,0.));
+#15=IFCAXIS2PLACEMENT3D(#12,#13,#14);
+#16=IFCLOCALPLACEMENT($,#15);
+#17=IFCFURNISHINGELEMENT('a',$,'Stool',$,$,#11,#10,$);
+#18=IFCFURNISHINGELEMENT('b',$,'Stool',$,$,#16,#10,$);
+ENDSEC;",
+        )
+        .unwrap();
+        assert_eq!(import.symbol_files.len(), 1);
+        let mesh = import.symbol_files[0].objects[0]
+            .downcast_ref::<Mesh>()
+            .unwrap();
+        assert_eq!(mesh.get_triangles().len(), 4);
+        let defs = import
+            .objects
+            .iter()
+            .filter(|obj| obj.downcast_ref::<SymbolDef>().is_some())
+            .count();
+        assert_eq!(defs, 1);
+        let instances: Vec<&SymbolInstance> = import
+            .objects
+            .iter()
+            .filter_map(|obj| obj.downcast_ref::<SymbolInstance>())
+            .collect();
+        assert_eq!(instances.len(), 2);
+        assert_close(
+            instances[0]
+                .transform
+                .transform_point(Point3f::new(1.0, 0.0, 0.0)),
+            Point3f::new(1.0, 0.0, 0.0),
+        );
+        assert_close(
+            instances[1]
+                .transform
+                .transform_point(Point3f::new(1.0, 0.0, 0.0)),
+            Point3f::new(5.0, 1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn test_triangulate() {
+        //An L, which a fan from the first corner would cover past the inside corner
+        let outline = vec![
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(2.0, 0.0, 0.0),
+            Point3f::new(2.0, 1.0, 0.0),
+            Point3f::new(1.0, 1.0, 0.0),
+            Point3f::new(1.0, 2.0, 0.0),
+            Point3f::new(0.0, 2.0, 0.0),
+        ];
+        let mut triangles = Vec::new();
+        triangulate(&outline, &mut triangles);
+        assert_eq!(triangles.len(), 4);
+        let area: f64 = triangles
+            .iter()
+            .map(|[first, second, third]| (second - first).cross(third - first).z / 2.0)
+            .sum();
+        assert!((area - 3.0).abs() < 1e-9);
+        for [first, second, third] in &triangles {
+            assert!((second - first).cross(third - first).z > 0.0);
+        }
+    }
+}
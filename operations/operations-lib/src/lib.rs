@@ -2,10 +2,26 @@ use tracing::*;
 pub use obj_defs::*;
 pub use indexmap;
 
+mod dxf;
+mod dxf_import;
+mod hidden_lines;
+mod ifc;
+mod ifc_import;
 mod joins;
 mod ops;
+mod section;
+mod sheet_render;
+mod step;
 mod updates;
 
+pub use dxf::*;
+pub use dxf_import::*;
+pub use hidden_lines::*;
+pub use ifc::*;
+pub use ifc_import::*;
 pub use joins::*;
 pub use ops::*;
+pub use section::*;
+pub use sheet_render::*;
+pub use step::*;
 pub use updates::*;
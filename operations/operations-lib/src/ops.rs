@@ -1,4 +1,5 @@
 use crate::*;
+use std::collections::HashSet;
 
 pub fn move_objects(objs: &mut Vec<DataBox>, delta: &Vector3f) {
     for obj in objs {
@@ -61,6 +62,88 @@ pub fn remove_objs_from_visibility_group(
     Ok(())
 }
 
+fn get_profile_point(obj: &DataBox, index: ResultInd) -> Result<(Point3f, RefID), ObjError> {
+    let refer = RefID::new(*obj.get_id(), RefType::ProfilePoint, index);
+    match obj.get_result(RefType::ProfilePoint, index) {
+        Some(RefResult::Point(pt)) => Ok((pt, refer)),
+        _ => Err(ObjError::GeomNotFound(refer)),
+    }
+}
+
+///Creates a dimension between a ProfilePoint on each object, referencing both so it follows them.
+pub fn create_dimension(
+    first_obj: &DataBox,
+    first_index: ResultInd,
+    second_obj: &DataBox,
+    second_index: ResultInd,
+    offset: WorldCoord,
+    text_style: TextStyle,
+) -> Result<Dimension, ObjError> {
+    let (first, first_ref) = get_profile_point(first_obj, first_index)?;
+    let (second, second_ref) = get_profile_point(second_obj, second_index)?;
+    let mut dim = Dimension::new(first, second, offset, text_style);
+    dim.set_ref(
+        RefType::ProfilePoint,
+        0,
+        RefResult::Point(first),
+        first_ref,
+        &None,
+    );
+    dim.set_ref(
+        RefType::ProfilePoint,
+        1,
+        RefResult::Point(second),
+        second_ref,
+        &None,
+    );
+    Ok(dim)
+}
+
+///Points the note's leader at a ProfilePoint on the target, which it follows from then on.
+pub fn add_leader_to_note(
+    note: &mut TextNote,
+    target: &DataBox,
+    index: ResultInd,
+) -> Result<(), ObjError> {
+    let (tip, tip_ref) = get_profile_point(target, index)?;
+    note.set_leader(tip, Some(tip_ref));
+    Ok(())
+}
+
+///Removes the references that dependents hold to objects being deleted, one at a time so each dependent lets go of
+/// whatever it got through them, like a wall's opening for a door.  If cascade is set, dependents that go away with
+/// one of the deleted objects are taken out of dependents and their IDs returned, so they can be deleted as well.
+pub fn delete_refs_to_objects(
+    dependents: &mut Vec<DataBox>,
+    deleted: &HashSet<ObjID>,
+    cascade: bool,
+) -> Vec<ObjID> {
+    let mut cascaded = Vec::new();
+    for obj in dependents.iter_mut() {
+        let id = *obj.get_id();
+        let to_delete: Vec<RefID> = obj
+            .get_refs()
+            .into_iter()
+            .flatten()
+            .filter(|refer| deleted.contains(&refer.other.id))
+            .map(|refer| refer.owner)
+            .collect();
+        if cascade
+            && to_delete
+                .iter()
+                .any(|owner| obj.deletes_with_ref(owner.ref_type, owner.index))
+        {
+            cascaded.push(id);
+        } else {
+            for owner in to_delete {
+                obj.delete_ref(owner.ref_type, owner.index);
+            }
+        }
+    }
+    dependents.retain(|obj| !cascaded.contains(obj.get_id()));
+    cascaded
+}
+
 pub async fn get_obj_update_info(
     geom_conn: &mut GeomConn,
     obj: &DataBox,
@@ -72,3 +155,287 @@ pub async fn get_obj_update_info(
     };
     Ok((output, views_opt))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indexmap::IndexMap;
+
+    #[test]
+    fn test_delete_refs_to_objects() {
+        let door = Door::new(
+            Point3f::new(1.0, 0.0, 0.0),
+            Point3f::new(2.0, 0.0, 0.0),
+            0.2,
+            2.0,
+        );
+        let opening = door.get_result(RefType::ProfilePlane, 0).unwrap();
+        let door_ref = RefID::new(*door.get_id(), RefType::ProfilePlane, 0);
+        let mut wall = Box::new(Wall::new(
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(4.0, 0.0, 0.0),
+            0.2,
+            3.0,
+        )) as DataBox;
+        assert!(wall.add_ref(RefType::ProfilePlane, opening, door_ref, &None));
+        let mut dependents = vec![wall];
+        let mut deleted = HashSet::new();
+        deleted.insert(*door.get_id());
+
+        //The door was the wall's only reference, and its opening has to go with it
+        let cascaded = delete_refs_to_objects(&mut dependents, &deleted, false);
+        assert!(cascaded.is_empty());
+        assert!(dependents[0]
+            .get_refs()
+            .into_iter()
+            .flatten()
+            .all(|refer| refer.other.id != door_ref.id));
+        assert_eq!(dependents[0].get_result(RefType::ProfilePlane, 0), None);
+    }
+
+    #[test]
+    fn test_delete_refs_to_objects_cascade() {
+        let mut wall = Box::new(Wall::new(
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(4.0, 0.0, 0.0),
+            0.2,
+            3.0,
+        )) as DataBox;
+        let wall_id = *wall.get_id();
+        let mut door = Box::new(Door::new(
+            Point3f::new(1.0, 0.0, 0.0),
+            Point3f::new(2.0, 0.0, 0.0),
+            0.2,
+            2.0,
+        )) as DataBox;
+        let door_id = *door.get_id();
+        join_refs(
+            &mut wall,
+            &mut door,
+            RefType::ProfilePlane,
+            RefType::ProfileLine,
+            &Point3f::new(1.0, 0.0, 0.0),
+        )
+        .unwrap();
+        assert!(door
+            .get_refs()
+            .into_iter()
+            .flatten()
+            .any(|refer| refer.other.id == wall_id));
+        let dim = create_dimension(
+            &wall,
+            0,
+            &wall,
+            1,
+            1.0,
+            TextStyle::new(String::from("Arial"), 0.25),
+        )
+        .unwrap();
+        let dim_id = *dim.get_id();
+        let sheet = Sheet::new(String::from("Sheet"), Point2f::new(0.4, 0.5));
+        let viewport = Viewport::new(*sheet.get_id(), ViewType::Top, Point2f::new(0.1, 0.1), 0.25);
+        let viewport_id = *viewport.get_id();
+
+        //Without cascading, the door stays and only lets go of the wall
+        let mut dependents = vec![door.data_clone(), Box::new(dim.clone()) as DataBox];
+        let mut deleted = HashSet::new();
+        deleted.insert(wall_id);
+        let cascaded = delete_refs_to_objects(&mut dependents, &deleted, false);
+        assert!(cascaded.is_empty());
+        assert_eq!(dependents.len(), 2);
+        assert!(dependents[0]
+            .get_refs()
+            .into_iter()
+            .flatten()
+            .all(|refer| refer.other.id != wall_id));
+
+        //The door is hosted on the wall, so it goes with it, but the dimension only loses its references
+        let mut dependents = vec![door, Box::new(dim) as DataBox];
+        let cascaded = delete_refs_to_objects(&mut dependents, &deleted, true);
+        assert_eq!(cascaded, vec![door_id]);
+        assert_eq!(dependents.len(), 1);
+        assert_eq!(*dependents[0].get_id(), dim_id);
+        assert!(dependents[0]
+            .get_refs()
+            .into_iter()
+            .flatten()
+            .next()
+            .is_none());
+
+        //A viewport goes with its sheet through its Existence reference
+        let mut deleted = HashSet::new();
+        deleted.insert(*sheet.get_id());
+        let mut dependents = vec![viewport.data_clone()];
+        assert!(delete_refs_to_objects(&mut dependents, &deleted, false).is_empty());
+        let mut dependents = vec![Box::new(viewport) as DataBox];
+        let cascaded = delete_refs_to_objects(&mut dependents, &deleted, true);
+        assert_eq!(cascaded, vec![viewport_id]);
+        assert!(dependents.is_empty());
+    }
+
+    #[test]
+    fn test_dimension_follows_wall() {
+        let mut wall = Wall::new(
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(4.0, 0.0, 0.0),
+            0.2,
+            3.0,
+        );
+        let wall_id = *wall.get_id();
+        let dim = create_dimension(
+            &(Box::new(wall.clone()) as DataBox),
+            0,
+            &(Box::new(wall.clone()) as DataBox),
+            1,
+            1.0,
+            TextStyle::new(String::from("Arial"), 0.25),
+        )
+        .unwrap();
+        let dim_id = *dim.get_id();
+        assert_eq!(dim.get_text(), "4000");
+
+        //Stretch the wall and push the change through the dimension's references
+        wall.second_pt.info = Point3f::new(6.5, 0.0, 0.0);
+        let refs = dim.get_refs().into_iter().flatten().collect::<Vec<_>>();
+        assert_eq!(refs.len(), 2);
+        assert!(refs.iter().all(|refer| refer.other.id == wall_id));
+        let mut objs = IndexMap::new();
+        objs.insert(wall_id, Some(Box::new(wall) as DataBox));
+        objs.insert(dim_id, Some(Box::new(dim) as DataBox));
+        update_all(&mut objs, refs);
+
+        let dim = objs[&dim_id]
+            .as_ref()
+            .unwrap()
+            .downcast_ref::<Dimension>()
+            .unwrap();
+        assert_eq!(dim.second_pt.info, Point3f::new(6.5, 0.0, 0.0));
+        assert_eq!(dim.get_length(), 6.5);
+        assert_eq!(dim.get_text(), "6500");
+        assert!(dim
+            .get_top()
+            .elements
+            .iter()
+            .any(|element| match &element.element {
+                Element2D::Text(text) => text.text == "6500",
+                _ => false,
+            }));
+    }
+
+    fn leader_tip(objs: &IndexMap<ObjID, Option<DataBox>>, note_id: &ObjID) -> Point3f {
+        objs[note_id]
+            .as_ref()
+            .unwrap()
+            .downcast_ref::<TextNote>()
+            .unwrap()
+            .leader
+            .as_ref()
+            .unwrap()
+            .info
+    }
+
+    #[test]
+    fn test_leader_follows_wall() {
+        let mut wall = Wall::new(
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(4.0, 0.0, 0.0),
+            0.2,
+            3.0,
+        );
+        let wall_id = *wall.get_id();
+        let mut note = TextNote::new(
+            String::from("Note"),
+            Point3f::new(2.0, 2.0, 0.0),
+            TextStyle::new(String::from("Arial"), 0.25),
+            TextAlignment::Left,
+            radians(0.0),
+        );
+        let note_id = *note.get_id();
+        add_leader_to_note(&mut note, &(Box::new(wall.clone()) as DataBox), 1).unwrap();
+        assert_eq!(
+            note.leader.as_ref().unwrap().info,
+            Point3f::new(4.0, 0.0, 0.0)
+        );
+
+        wall.second_pt.info = Point3f::new(5.0, 1.0, 0.0);
+        let refs = note.get_refs().into_iter().flatten().collect::<Vec<_>>();
+        let mut objs = IndexMap::new();
+        objs.insert(wall_id, Some(Box::new(wall) as DataBox));
+        objs.insert(note_id, Some(Box::new(note) as DataBox));
+        update_all(&mut objs, refs);
+        assert_eq!(leader_tip(&objs, &note_id), Point3f::new(5.0, 1.0, 0.0));
+        let note = objs[&note_id].as_ref().unwrap();
+        assert!(note
+            .as_drawing_views()
+            .unwrap()
+            .get_top()
+            .elements
+            .iter()
+            .any(|element| match &element.element {
+                Element2D::Line(line) => line.first == Point2f::new(5.0, 1.0),
+                _ => false,
+            }));
+    }
+
+    #[test]
+    fn test_add_leader_to_note_with_leader() {
+        let first = Wall::new(
+            Point3f::new(0.0, 0.0, 0.0),
+            Point3f::new(4.0, 0.0, 0.0),
+            0.2,
+            3.0,
+        );
+        let mut second = Wall::new(
+            Point3f::new(0.0, 5.0, 0.0),
+            Point3f::new(4.0, 5.0, 0.0),
+            0.2,
+            3.0,
+        );
+        let second_id = *second.get_id();
+        let mut note = TextNote::new(
+            String::from("Note"),
+            Point3f::new(2.0, 2.0, 0.0),
+            TextStyle::new(String::from("Arial"), 0.25),
+            TextAlignment::Left,
+            radians(0.0),
+        );
+        let note_id = *note.get_id();
+        add_leader_to_note(&mut note, &(Box::new(first.clone()) as DataBox), 0).unwrap();
+
+        //The note's one leader moves over to the new target and lets go of the old one
+        add_leader_to_note(&mut note, &(Box::new(second.clone()) as DataBox), 1).unwrap();
+        assert_eq!(
+            note.leader.as_ref().unwrap().info,
+            Point3f::new(4.0, 5.0, 0.0)
+        );
+        let refs = note.get_refs().into_iter().flatten().collect::<Vec<_>>();
+        assert_eq!(
+            refs,
+            vec![Reference::new(
+                RefID::new(note_id, RefType::ProfilePoint, 0),
+                RefID::new(second_id, RefType::ProfilePoint, 1)
+            )]
+        );
+
+        second.second_pt.info = Point3f::new(3.0, 6.0, 0.0);
+        let mut objs = IndexMap::new();
+        objs.insert(*first.get_id(), Some(Box::new(first) as DataBox));
+        objs.insert(second_id, Some(Box::new(second) as DataBox));
+        objs.insert(note_id, Some(Box::new(note) as DataBox));
+        update_all(&mut objs, refs);
+        assert_eq!(leader_tip(&objs, &note_id), Point3f::new(3.0, 6.0, 0.0));
+
+        //Only ProfilePoints can be pointed at
+        let mut note = objs[&note_id]
+            .as_ref()
+            .unwrap()
+            .downcast_ref::<TextNote>()
+            .unwrap()
+            .clone();
+        assert!(add_leader_to_note(&mut note, objs[&second_id].as_ref().unwrap(), 2).is_err());
+        assert_eq!(
+            note.leader.as_ref().unwrap().info,
+            Point3f::new(3.0, 6.0, 0.0)
+        );
+    }
+}
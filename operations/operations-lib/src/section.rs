@@ -0,0 +1,283 @@
+use crate::*;
+use cgmath::InnerSpace;
+use indexmap::IndexMap;
+
+//How close in meters the ends of cut lines have to be to join them into an outline.
+const JOINED: WorldCoord = 1e-6;
+
+//How what a section cuts through is drawn, heavier than the edges seen beyond the cut.
+const CUT_LINE_THICKNESS: f32 = 2.0;
+const CUT_HATCH: &str = "ANSI31";
+
+///Subscribes the section to the bounding boxes of the objects in objs its cut line passes through,
+/// and drops the ones it no longer does, whether they moved or the section did.  Returns whether
+/// what the section cuts through changed.
+pub fn add_objs_cut_by_section<'a>(
+    section: &mut DataBox,
+    objs: impl IntoIterator<Item = &'a DataBox>,
+) -> Result<bool, ObjError> {
+    let section_id = *section.get_id();
+    let cut = match section.downcast_mut::<SectionCut>() {
+        Some(cut) => cut,
+        None => {
+            return Err(ObjError::ObjWrongType(
+                section_id,
+                String::from("SectionCut"),
+            ))
+        }
+    };
+    let mut new_cuts = Vec::new();
+    for obj in objs.into_iter().filter(|obj| *obj.get_id() != section_id) {
+        if let Some(RefResult::Cube(bbox)) = obj.get_result(RefType::AxisAlignedBoundBox, 0) {
+            match cut.get_cut_index(obj.get_id()) {
+                Some(index) => cut.set_associated_result_for_type(
+                    RefType::AxisAlignedBoundBox,
+                    index,
+                    Some(RefResult::Cube(bbox)),
+                ),
+                None if cut.crosses(&bbox) => new_cuts.push((*obj.get_id(), bbox)),
+                None => (),
+            }
+        }
+    }
+    let changed = cut.drop_uncut() || !new_cuts.is_empty();
+    for (id, bbox) in new_cuts {
+        cut.add_ref(
+            RefType::AxisAlignedBoundBox,
+            RefResult::Cube(bbox),
+            RefID::new(id, RefType::AxisAlignedBoundBox, 0),
+            &None,
+        );
+    }
+    Ok(changed)
+}
+
+///Objects can move into or out of a section's cut without referencing it, so after an update every
+/// SectionCut in objs is subscribed again against objs and candidates, which are the other objects
+/// in the file that could be cut.  SectionCuts among the candidates are only checked against objs,
+/// and are returned if what they cut through changed.
+pub fn update_section_cuts(
+    objs: &mut IndexMap<ObjID, Option<DataBox>>,
+    candidates: Vec<DataBox>,
+) -> Vec<DataBox> {
+    let section_ids: Vec<ObjID> = objs
+        .iter()
+        .filter(|(_, obj_opt)| match obj_opt {
+            Some(obj) => obj.downcast_ref::<SectionCut>().is_some(),
+            None => false,
+        })
+        .map(|(id, _)| *id)
+        .collect();
+    for id in section_ids {
+        //Taken out while it looks at the others so objs can still be read
+        if let Some(mut section) = objs.get_mut(&id).and_then(|obj_opt| obj_opt.take()) {
+            let others = objs.values().flatten().chain(candidates.iter());
+            if let Err(e) = add_objs_cut_by_section(&mut section, others) {
+                error!("{:?}", e);
+            }
+            objs.insert(id, Some(section));
+        }
+    }
+    let mut changed = Vec::new();
+    for mut candidate in candidates {
+        if candidate.downcast_ref::<SectionCut>().is_none() {
+            continue;
+        }
+        match add_objs_cut_by_section(&mut candidate, objs.values().flatten()) {
+            Ok(true) => changed.push(candidate),
+            Ok(false) => (),
+            Err(e) => error!("{:?}", e),
+        }
+    }
+    changed
+}
+
+///The outlines left where the plane cuts through a closed shape's faces.
+fn cut_outlines(plane: &Plane, faces: &[Vec<Point3f>]) -> Vec<Vec<Point3f>> {
+    let normal = plane.normal().normalize();
+    let mut segments = Vec::new();
+    for face in faces {
+        let dists: Vec<f64> = face
+            .iter()
+            .map(|pt| (pt - plane.pt_1).dot(normal))
+            .collect();
+        let mut crossings = Vec::new();
+        for (index, pt) in face.iter().enumerate() {
+            let next = (index + 1) % face.len();
+            if (dists[index] > 0.0) != (dists[next] > 0.0) {
+                let interp = dists[index] / (dists[index] - dists[next]);
+                crossings.push(pt + (face[next] - pt) * interp);
+            }
+        }
+        //The face is inside every other stretch between where its edges cross the plane
+        if crossings.len() < 2 {
+            continue;
+        }
+        let dir = crossings[1] - crossings[0];
+        let start = crossings[0];
+        crossings.sort_by(|a, b| {
+            (a - start)
+                .dot(dir)
+                .partial_cmp(&(b - start).dot(dir))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        for pair in crossings.chunks(2) {
+            if let [first, second] = pair {
+                segments.push((*first, *second));
+            }
+        }
+    }
+
+    let close = |first: &Point3f, second: &Point3f| (first - second).magnitude() < JOINED;
+    let mut outlines = Vec::new();
+    while let Some((first, second)) = segments.pop() {
+        let mut outline = vec![first, second];
+        while let Some(index) = segments.iter().position(|(first, second)| {
+            close(first, &outline[outline.len() - 1]) || close(second, &outline[outline.len() - 1])
+        }) {
+            let (first, second) = segments.swap_remove(index);
+            if close(&first, &outline[outline.len() - 1]) {
+                outline.push(second);
+            } else {
+                outline.push(first);
+            }
+        }
+        if close(&outline[0], &outline[outline.len() - 1]) {
+            outline.pop();
+        }
+        if outline.len() > 2 {
+            outlines.push(outline);
+        }
+    }
+    outlines
+}
+
+///Draws the section, with what its cut line passes through filled with hatches over the edges seen
+/// beyond the cut.  Nothing is drawn if the SectionCut isn't there.
+pub fn compose_section(
+    objs: &[DataBox],
+    section_id: &ObjID,
+    hidden: HiddenLines,
+) -> Vec<DrawElement2D> {
+    let section = match objs
+        .iter()
+        .filter(|obj| obj.get_id() == section_id)
+        .find_map(|obj| obj.downcast_ref::<SectionCut>())
+    {
+        Some(section) => section,
+        None => return Vec::new(),
+    };
+    let projection = section.get_projection();
+    let plane = section.get_cut_plane();
+    let mut outlines = Vec::new();
+    for obj in objs {
+        match obj.get_result(RefType::AxisAlignedBoundBox, 0) {
+            Some(RefResult::Cube(bbox)) if section.crosses(&bbox) => (),
+            _ => continue,
+        }
+        if let Some(views) = obj.as_drawing_views() {
+            outlines.extend(cut_outlines(&plane, &views.get_faces()));
+        }
+    }
+    let fills: Vec<DrawElement2D> = outlines
+        .iter()
+        .filter_map(|outline| {
+            let pts = outline
+                .iter()
+                .map(|pt| projection.project_point(pt))
+                .collect::<Option<Vec<Point2f>>>()?;
+            Some(DrawElement2D {
+                element: Element2D::Poly(Poly2D::new(pts)),
+                line_thickness: CUT_LINE_THICKNESS,
+                line_color: RGBA::default(),
+                line_type: LineType::Solid,
+                fill_type: FillType::Hatch {
+                    name: String::from(CUT_HATCH),
+                },
+            })
+        })
+        .collect();
+    let mut elements = compose_projection(objs, &projection, hidden, outlines);
+    elements.extend(fills);
+    elements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wall(first: (f64, f64), second: (f64, f64)) -> DataBox {
+        Box::new(Wall::new(
+            Point3f::new(first.0, first.1, 0.0),
+            Point3f::new(second.0, second.1, 0.0),
+            0.1,
+            3.0,
+        ))
+    }
+
+    fn section() -> DataBox {
+        Box::new(SectionCut::new(
+            Point3f::new(0.0, -5.0, 0.0),
+            Point3f::new(0.0, 5.0, 0.0),
+            10.0,
+            SectionDirection::Left,
+        ))
+    }
+
+    fn cut_objs(section: &DataBox) -> Vec<ObjID> {
+        section.downcast_ref::<SectionCut>().unwrap().get_cut_objs()
+    }
+
+    #[test]
+    fn test_add_objs_cut_by_section() {
+        let mut objs = vec![wall((-1.0, 0.0), (1.0, 0.0)), wall((5.0, 0.0), (7.0, 0.0))];
+        let crossing = *objs[0].get_id();
+        let beside = *objs[1].get_id();
+        let mut section = section();
+        assert!(add_objs_cut_by_section(&mut section, &objs).unwrap());
+        assert_eq!(cut_objs(&section), vec![crossing]);
+        assert!(!add_objs_cut_by_section(&mut section, &objs).unwrap());
+
+        //One wall moves out of the cut and the other moves in
+        move_objects(&mut objs, &Vector3f::new(-6.0, 0.0, 0.0));
+        assert!(add_objs_cut_by_section(&mut section, &objs).unwrap());
+        assert_eq!(cut_objs(&section), vec![beside]);
+
+        let mut not_section = wall((0.0, 0.0), (1.0, 0.0));
+        assert!(add_objs_cut_by_section(&mut not_section, &objs).is_err());
+    }
+
+    #[test]
+    fn test_update_section_cuts() {
+        let moved = wall((-1.0, 0.0), (1.0, 0.0));
+        let moved_id = *moved.get_id();
+        let standing = wall((-1.0, 2.0), (1.0, 2.0));
+        let standing_id = *standing.get_id();
+        let mut objs = IndexMap::new();
+        objs.insert(moved_id, Some(moved));
+
+        //A section that only sees the file's other objects picks up the one moved into it
+        let candidate = section();
+        let candidate_id = *candidate.get_id();
+        let changed = update_section_cuts(&mut objs, vec![candidate, standing.data_clone()]);
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].get_id(), &candidate_id);
+        assert_eq!(cut_objs(&changed[0]), vec![moved_id]);
+
+        //Once it's subscribed, nothing changes until something does
+        let changed = update_section_cuts(&mut objs, changed);
+        assert!(changed.is_empty());
+
+        //A section being updated looks at everything
+        let updating = section();
+        let updating_id = *updating.get_id();
+        objs.insert(updating_id, Some(updating));
+        let changed = update_section_cuts(&mut objs, vec![standing]);
+        assert!(changed.is_empty());
+        let mut cut = cut_objs(objs[&updating_id].as_ref().unwrap());
+        cut.sort();
+        let mut expected = vec![moved_id, standing_id];
+        expected.sort();
+        assert_eq!(cut, expected);
+    }
+}
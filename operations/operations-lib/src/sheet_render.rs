@@ -0,0 +1,893 @@
+use crate::*;
+use std::collections::BTreeSet;
+use std::f64::consts::{FRAC_1_SQRT_2, FRAC_PI_2, PI};
+use std::fmt::Write;
+
+//How wide a line of thickness 1 prints, in meters.  This matches the lineweight DXF export uses.
+const LINE_WIDTH_PER_THICKNESS: f64 = 0.000_25;
+
+//Dashes and hatch lines on paper, in meters.
+const DASH_LENGTH: f64 = 0.003;
+const HATCH_SPACING: f64 = 0.003;
+
+const POINTS_PER_METER: f64 = 72.0 / 0.0254;
+
+//Without font metrics, text in PDFs is aligned by guessing it's this many of its heights wide for
+//each character.
+const PDF_CHARACTER_WIDTH: f64 = 0.6;
+
+///A viewport's drawing, still in drawing coordinates, and where it goes on the sheet.
+#[derive(Debug, Clone)]
+pub struct ViewportDrawing {
+    ///Meters right and down from the sheet's top left corner.
+    pub origin: Point2f,
+    pub size: Point2f,
+    ///The point in the drawing that lands in the middle of the viewport.
+    pub center: Point2f,
+    pub scale: f64,
+    pub elements: Vec<DrawElement2D>,
+}
+
+impl ViewportDrawing {
+    ///Where the drawing's origin lands on the sheet.
+    pub fn drawing_origin(&self) -> Point2f {
+        Point2f::new(
+            self.origin.x + self.size.x / 2.0 - self.center.x * self.scale,
+            self.origin.y + self.size.y / 2.0 + self.center.y * self.scale,
+        )
+    }
+
+    ///Drawings are Y up, while sheets measure down from the top.
+    fn to_sheet(&self, pt: &Point2f) -> Point2f {
+        let origin = self.drawing_origin();
+        Point2f::new(origin.x + pt.x * self.scale, origin.y - pt.y * self.scale)
+    }
+
+    ///The bottom left and top right corners of the part of the drawing the viewport shows.
+    pub fn visible(&self) -> (Point2f, Point2f) {
+        let half = Vector2f::new(self.size.x, self.size.y) / (2.0 * self.scale);
+        (self.center - half, self.center + half)
+    }
+
+    fn outline(&self, element: &Element2D) -> Option<Vec<Point2f>> {
+        match element {
+            Element2D::Rect(..) | Element2D::Poly(..) => Some(
+                element_extents(element)
+                    .iter()
+                    .map(|pt| self.to_sheet(pt))
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    ///The elements cut down to the part of the drawing the viewport shows, for formats that can't
+    /// clip them.  Rects and polys that get cut become polys, arcs are split where they leave the
+    /// viewport, and text is kept whole as long as its position is inside.
+    pub fn clipped_elements(&self) -> Vec<DrawElement2D> {
+        let (min, max) = self.visible();
+        let mut results = Vec::new();
+        for element in &self.elements {
+            for clipped in clip_element(&element.element, &min, &max) {
+                let mut result = element.clone();
+                result.element = clipped;
+                results.push(result);
+            }
+        }
+        results
+    }
+
+    ///Whether any of the element could land inside the viewport.
+    fn overlaps(&self, element: &Element2D) -> bool {
+        let (min, max) = match bounds(&element_extents(element)) {
+            Some(bounds) => bounds,
+            None => return false,
+        };
+        let (visible_min, visible_max) = self.visible();
+        max.x >= visible_min.x
+            && min.x <= visible_max.x
+            && max.y >= visible_min.y
+            && min.y <= visible_max.y
+    }
+}
+
+///Points whose bounds cover the element, in drawing coordinates.  Rects and polys give their
+/// outline, arcs count as their whole circle, and text as its position.
+fn element_extents(element: &Element2D) -> Vec<Point2f> {
+    match element {
+        Element2D::Line(line) => vec![line.first, line.second],
+        Element2D::Text(text) => vec![text.position],
+        Element2D::Arc(arc) => vec![
+            Point2f::new(arc.center.x - arc.radius, arc.center.y - arc.radius),
+            Point2f::new(arc.center.x + arc.radius, arc.center.y + arc.radius),
+        ],
+        Element2D::Rect(rect) => vec![
+            rect.bottom_left,
+            Point2f::new(rect.top_right.x, rect.bottom_left.y),
+            rect.top_right,
+            Point2f::new(rect.bottom_left.x, rect.top_right.y),
+        ],
+        Element2D::Poly(poly) => poly.pts.clone(),
+    }
+}
+
+fn bounds(pts: &[Point2f]) -> Option<(Point2f, Point2f)> {
+    let first = pts.first()?;
+    let mut min = *first;
+    let mut max = *first;
+    for pt in pts {
+        min = Point2f::new(min.x.min(pt.x), min.y.min(pt.y));
+        max = Point2f::new(max.x.max(pt.x), max.y.max(pt.y));
+    }
+    Some((min, max))
+}
+
+///The middle of everything in the drawing, or its origin if there's nothing in it.
+fn drawing_center(elements: &[DrawElement2D]) -> Point2f {
+    let pts: Vec<Point2f> = elements
+        .iter()
+        .flat_map(|element| element_extents(&element.element))
+        .collect();
+    match bounds(&pts) {
+        Some((min, max)) => Point2f::new((min.x + max.x) / 2.0, (min.y + max.y) / 2.0),
+        None => Point2f::new(0.0, 0.0),
+    }
+}
+
+fn inside(pt: &Point2f, min: &Point2f, max: &Point2f) -> bool {
+    pt.x >= min.x && pt.x <= max.x && pt.y >= min.y && pt.y <= max.y
+}
+
+fn clip_element(element: &Element2D, min: &Point2f, max: &Point2f) -> Vec<Element2D> {
+    match element {
+        Element2D::Line(line) => clip_line(line, min, max)
+            .map(Element2D::Line)
+            .into_iter()
+            .collect(),
+        Element2D::Arc(arc) => clip_arc(arc, min, max)
+            .into_iter()
+            .map(Element2D::Arc)
+            .collect(),
+        Element2D::Text(text) => {
+            if inside(&text.position, min, max) {
+                vec![element.clone()]
+            } else {
+                Vec::new()
+            }
+        }
+        Element2D::Rect(..) | Element2D::Poly(..) => {
+            let pts = element_extents(element);
+            if pts.iter().all(|pt| inside(pt, min, max)) {
+                return vec![element.clone()];
+            }
+            let clipped = clip_poly(&pts, min, max);
+            if clipped.len() < 3 {
+                Vec::new()
+            } else {
+                vec![Element2D::Poly(Poly2D::new(clipped))]
+            }
+        }
+    }
+}
+
+///Liang-Barsky, narrowing down the part of the line between each pair of sides.
+fn clip_line(line: &Line2D, min: &Point2f, max: &Point2f) -> Option<Line2D> {
+    let delta = line.second - line.first;
+    let mut enter: f64 = 0.0;
+    let mut leave: f64 = 1.0;
+    for (towards, room) in &[
+        (-delta.x, line.first.x - min.x),
+        (delta.x, max.x - line.first.x),
+        (-delta.y, line.first.y - min.y),
+        (delta.y, max.y - line.first.y),
+    ] {
+        if towards.abs() < f64::EPSILON {
+            if *room < 0.0 {
+                return None;
+            }
+        } else if *towards < 0.0 {
+            enter = enter.max(room / towards);
+        } else {
+            leave = leave.min(room / towards);
+        }
+    }
+    if enter > leave {
+        return None;
+    }
+    Some(Line2D::new(
+        line.first + delta * enter,
+        line.first + delta * leave,
+    ))
+}
+
+///Sutherland-Hodgman, cutting the outline down by each side of the viewport in turn.
+fn clip_poly(pts: &[Point2f], min: &Point2f, max: &Point2f) -> Vec<Point2f> {
+    let mut result = pts.to_vec();
+    for (axis, value, keep_above) in &[
+        (0, min.x, true),
+        (0, max.x, false),
+        (1, min.y, true),
+        (1, max.y, false),
+    ] {
+        let keep = |pt: &Point2f| {
+            if *keep_above {
+                pt[*axis] >= *value
+            } else {
+                pt[*axis] <= *value
+            }
+        };
+        let crossing = |from: &Point2f, to: &Point2f| {
+            let along = (value - from[*axis]) / (to[*axis] - from[*axis]);
+            from + (to - from) * along
+        };
+        let outline = std::mem::take(&mut result);
+        for (index, current) in outline.iter().enumerate() {
+            let previous = &outline[(index + outline.len() - 1) % outline.len()];
+            if keep(current) {
+                if !keep(previous) {
+                    result.push(crossing(previous, current));
+                }
+                result.push(*current);
+            } else if keep(previous) {
+                result.push(crossing(previous, current));
+            }
+        }
+    }
+    result
+}
+
+///Splits the arc where it crosses the sides of the viewport, and keeps the pieces inside.
+fn clip_arc(arc: &Arc2D, min: &Point2f, max: &Point2f) -> Vec<Arc2D> {
+    let sweep = arc_sweep(arc);
+    if sweep.abs() < f64::EPSILON {
+        return Vec::new();
+    }
+    //How far along the sweep each crossing is, from 0 at the start to 1 at the end
+    let mut alongs = vec![0.0, 1.0];
+    for (axis, value) in &[(0, min.x), (0, max.x), (1, min.y), (1, max.y)] {
+        let offset = (value - arc.center[*axis]) / arc.radius;
+        if offset.abs() > 1.0 {
+            continue;
+        }
+        let angles = if *axis == 0 {
+            [offset.acos(), -offset.acos()]
+        } else {
+            [offset.asin(), PI - offset.asin()]
+        };
+        for angle in &angles {
+            let mut turn = (angle - arc.start_angle.0) % (2.0 * PI);
+            if sweep > 0.0 && turn < 0.0 {
+                turn += 2.0 * PI;
+            } else if sweep < 0.0 && turn > 0.0 {
+                turn -= 2.0 * PI;
+            }
+            let along = turn / sweep;
+            if along > 0.0 && along < 1.0 {
+                alongs.push(along);
+            }
+        }
+    }
+    alongs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let mut results = Vec::new();
+    for piece in alongs.windows(2) {
+        if (piece[1] - piece[0]).abs() < f64::EPSILON {
+            continue;
+        }
+        let middle = arc_point(arc, arc.start_angle.0 + sweep * (piece[0] + piece[1]) / 2.0);
+        if inside(&middle, min, max) {
+            results.push(Arc2D::new(
+                arc.center,
+                arc.radius,
+                radians(arc.start_angle.0 + sweep * piece[0]),
+                radians(arc.start_angle.0 + sweep * piece[1]),
+            ));
+        }
+    }
+    results
+}
+
+#[derive(Debug, Clone)]
+pub struct SheetDrawing {
+    ///The paper size in meters.
+    pub size: Point2f,
+    pub viewports: Vec<ViewportDrawing>,
+}
+
+///Gathers the drawing of every object for the view of each viewport on the sheet, centered on the
+/// viewport's center, or the middle of the drawing if it doesn't have one.  Elements that fall
+/// completely outside of their viewport are left out, and the rest are clipped when written.
+pub fn compose_sheet(sheet_id: &ObjID, objs: &[DataBox]) -> Result<SheetDrawing, ObjError> {
+    let sheet = objs
+        .iter()
+        .find(|obj| obj.get_id() == sheet_id)
+        .ok_or(ObjError::ObjNotFound(*sheet_id))?;
+    let sheet = sheet
+        .downcast_ref::<Sheet>()
+        .ok_or_else(|| ObjError::ObjWrongType(*sheet_id, String::from("Sheet")))?;
+    let viewports = objs
+        .iter()
+        .filter_map(|obj| obj.downcast_ref::<Viewport>())
+        .filter(|viewport| viewport.get_sheet() == Some(sheet_id));
+    let mut results = Vec::new();
+    for viewport in viewports {
+        let size = viewport.size.unwrap_or_else(|| {
+            Point2f::new(
+                sheet.print_size.x - viewport.origin.x,
+                sheet.print_size.y - viewport.origin.y,
+            )
+        });
+        let elements = compose_view(objs, &viewport.view, viewport.hidden_lines);
+        let mut drawing = ViewportDrawing {
+            origin: viewport.origin,
+            size,
+            center: viewport.center.unwrap_or_else(|| drawing_center(&elements)),
+            scale: viewport.scale,
+            elements: Vec::new(),
+        };
+        for element in elements {
+            if drawing.overlaps(&element.element) {
+                drawing.elements.push(element);
+            }
+        }
+        results.push(drawing);
+    }
+    Ok(SheetDrawing {
+        size: sheet.print_size,
+        viewports: results,
+    })
+}
+
+fn arc_sweep(arc: &Arc2D) -> f64 {
+    (arc.end_angle.0 - arc.start_angle.0).clamp(-2.0 * PI, 2.0 * PI)
+}
+
+fn arc_point(arc: &Arc2D, angle: f64) -> Point2f {
+    Point2f::new(
+        arc.center.x + arc.radius * angle.cos(),
+        arc.center.y + arc.radius * angle.sin(),
+    )
+}
+
+fn svg_color(color: &RGBA) -> String {
+    format!("rgb({},{},{})", color.r, color.g, color.b)
+}
+
+fn hatch_id(name: &str) -> String {
+    let safe: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("hatch-{}", safe)
+}
+
+fn svg_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn svg_points(pts: &[Point2f]) -> String {
+    let pts: Vec<String> = pts.iter().map(|pt| format!("{},{}", pt.x, pt.y)).collect();
+    pts.join(" ")
+}
+
+fn svg_element(out: &mut String, viewport: &ViewportDrawing, element: &DrawElement2D) {
+    let mut style = format!(
+        "stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"{}\"",
+        svg_color(&element.line_color),
+        element.line_color.a,
+        element.line_thickness as f64 * LINE_WIDTH_PER_THICKNESS
+    );
+    if let LineType::Dashed { .. } = element.line_type {
+        write!(
+            style,
+            " stroke-dasharray=\"{} {}\"",
+            DASH_LENGTH,
+            DASH_LENGTH / 2.0
+        )
+        .unwrap();
+    }
+    match &element.element {
+        Element2D::Line(line) => {
+            let first = viewport.to_sheet(&line.first);
+            let second = viewport.to_sheet(&line.second);
+            writeln!(
+                out,
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" {}/>",
+                first.x, first.y, second.x, second.y, style
+            )
+            .unwrap();
+        }
+        Element2D::Arc(arc) => {
+            let sweep = arc_sweep(arc);
+            let radius = arc.radius * viewport.scale;
+            if sweep.abs() >= 2.0 * PI {
+                let center = viewport.to_sheet(&arc.center);
+                writeln!(
+                    out,
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" {}/>",
+                    center.x, center.y, radius, style
+                )
+                .unwrap();
+            } else {
+                let start = viewport.to_sheet(&arc_point(arc, arc.start_angle.0));
+                let end = viewport.to_sheet(&arc_point(arc, arc.end_angle.0));
+                let large = if sweep.abs() > PI { 1 } else { 0 };
+                //Counterclockwise in the drawing is clockwise once Y points down.
+                let sweep_flag = if sweep > 0.0 { 0 } else { 1 };
+                writeln!(
+                    out,
+                    "<path d=\"M {} {} A {} {} 0 {} {} {} {}\" fill=\"none\" {}/>",
+                    start.x, start.y, radius, radius, large, sweep_flag, end.x, end.y, style
+                )
+                .unwrap();
+            }
+        }
+        Element2D::Text(text) => {
+            let position = viewport.to_sheet(&text.position);
+            let anchor = match text.alignment {
+                TextAlignment::Left => "start",
+                TextAlignment::Center => "middle",
+                TextAlignment::Right => "end",
+            };
+            //Counterclockwise in the drawing is clockwise once Y points down.
+            writeln!(
+                out,
+                "<text x=\"{}\" y=\"{}\" font-family=\"{}\" font-size=\"{}\" text-anchor=\"{}\" transform=\"rotate({} {} {})\" fill=\"{}\" fill-opacity=\"{}\">{}</text>",
+                position.x,
+                position.y,
+                svg_text(&text.font),
+                text.height * viewport.scale,
+                anchor,
+                -text.rotation.0.to_degrees(),
+                position.x,
+                position.y,
+                svg_color(&element.line_color),
+                element.line_color.a,
+                svg_text(&text.text)
+            )
+            .unwrap();
+        }
+        other => {
+            let pts = viewport.outline(other).unwrap_or_default();
+            let fill = match &element.fill_type {
+                FillType::Solid { color } => {
+                    format!("fill=\"{}\" fill-opacity=\"{}\"", svg_color(color), color.a)
+                }
+                FillType::Hatch { name } => format!("fill=\"url(#{})\"", hatch_id(name)),
+            };
+            writeln!(
+                out,
+                "<polygon points=\"{}\" {} {}/>",
+                svg_points(&pts),
+                fill,
+                style
+            )
+            .unwrap();
+        }
+    }
+}
+
+///Writes the sheet as an SVG at its paper size, in meters.  Hatches are drawn as 45 degree lines
+/// whatever their name, since there are no pattern definitions for them.
+pub fn sheet_svg(sheet: &SheetDrawing) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}mm\" height=\"{}mm\" viewBox=\"0 0 {} {}\">",
+        sheet.size.x * 1000.0,
+        sheet.size.y * 1000.0,
+        sheet.size.x,
+        sheet.size.y
+    )
+    .unwrap();
+    out.push_str("<defs>\n");
+    let hatches: BTreeSet<&String> = sheet
+        .viewports
+        .iter()
+        .flat_map(|viewport| viewport.elements.iter())
+        .filter_map(|element| match &element.fill_type {
+            FillType::Hatch { name } => Some(name),
+            _ => None,
+        })
+        .collect();
+    for name in hatches {
+        writeln!(
+            out,
+            "<pattern id=\"{}\" patternUnits=\"userSpaceOnUse\" width=\"{}\" height=\"{}\" patternTransform=\"rotate(45)\">\
+             <line x1=\"0\" y1=\"0\" x2=\"0\" y2=\"{}\" stroke=\"black\" stroke-width=\"{}\"/></pattern>",
+            hatch_id(name),
+            HATCH_SPACING,
+            HATCH_SPACING,
+            HATCH_SPACING,
+            LINE_WIDTH_PER_THICKNESS
+        )
+        .unwrap();
+    }
+    for (index, viewport) in sheet.viewports.iter().enumerate() {
+        writeln!(
+            out,
+            "<clipPath id=\"viewport-{}\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/></clipPath>",
+            index, viewport.origin.x, viewport.origin.y, viewport.size.x, viewport.size.y
+        )
+        .unwrap();
+    }
+    out.push_str("</defs>\n");
+    writeln!(
+        out,
+        "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>",
+        sheet.size.x, sheet.size.y
+    )
+    .unwrap();
+    for (index, viewport) in sheet.viewports.iter().enumerate() {
+        writeln!(out, "<g clip-path=\"url(#viewport-{})\">", index).unwrap();
+        for element in &viewport.elements {
+            svg_element(&mut out, viewport, element);
+        }
+        out.push_str("</g>\n");
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+fn pdf_path(out: &mut String, pts: &[Point2f]) {
+    for (index, pt) in pts.iter().enumerate() {
+        let op = if index == 0 { "m" } else { "l" };
+        writeln!(out, "{} {} {}", pt.x, pt.y, op).unwrap();
+    }
+    out.push_str("h\n");
+}
+
+///Arcs are approximated with a cubic Bezier for every quarter turn or less.
+fn pdf_arc(out: &mut String, viewport: &ViewportDrawing, arc: &Arc2D) {
+    let sweep = arc_sweep(arc);
+    let segments = (sweep.abs() / FRAC_PI_2).ceil().max(1.0) as usize;
+    let step = sweep / segments as f64;
+    let handle = 4.0 / 3.0 * (step / 4.0).tan() * arc.radius;
+    let start = viewport.to_sheet(&arc_point(arc, arc.start_angle.0));
+    writeln!(out, "{} {} m", start.x, start.y).unwrap();
+    for segment in 0..segments {
+        let from = arc.start_angle.0 + step * segment as f64;
+        let to = from + step;
+        let first = arc_point(arc, from);
+        let last = arc_point(arc, to);
+        let control_1 = Point2f::new(first.x - handle * from.sin(), first.y + handle * from.cos());
+        let control_2 = Point2f::new(last.x + handle * to.sin(), last.y - handle * to.cos());
+        let control_1 = viewport.to_sheet(&control_1);
+        let control_2 = viewport.to_sheet(&control_2);
+        let last = viewport.to_sheet(&last);
+        writeln!(
+            out,
+            "{} {} {} {} {} {} c",
+            control_1.x, control_1.y, control_2.x, control_2.y, last.x, last.y
+        )
+        .unwrap();
+    }
+    out.push_str("S\n");
+}
+
+fn pdf_text(out: &mut String, viewport: &ViewportDrawing, element: &DrawElement2D, text: &Text2D) {
+    let color = &element.line_color;
+    let size = text.height * viewport.scale;
+    let width = size * PDF_CHARACTER_WIDTH * text.text.chars().count() as f64;
+    let shift = match text.alignment {
+        TextAlignment::Left => 0.0,
+        TextAlignment::Center => width / 2.0,
+        TextAlignment::Right => width,
+    };
+    //The page is drawn Y down, so the text is flipped back upright.
+    let (sin, cos) = text.rotation.0.sin_cos();
+    let position = viewport.to_sheet(&text.position);
+    let escaped = text
+        .text
+        .replace('\\', "\\\\")
+        .replace('(', "\\(")
+        .replace(')', "\\)");
+    writeln!(
+        out,
+        "{} {} {} rg BT /F1 {} Tf {} {} {} {} {} {} Tm {} 0 Td ({}) Tj ET",
+        color.r as f64 / 255.0,
+        color.g as f64 / 255.0,
+        color.b as f64 / 255.0,
+        size,
+        cos,
+        -sin,
+        -sin,
+        -cos,
+        position.x,
+        position.y,
+        -shift,
+        escaped
+    )
+    .unwrap();
+}
+
+///Fills the outline with 45 degree lines by clipping a set of them to it.
+fn pdf_hatch(out: &mut String, pts: &[Point2f]) {
+    let min_x = pts.iter().fold(f64::MAX, |min, pt| min.min(pt.x));
+    let max_x = pts.iter().fold(f64::MIN, |max, pt| max.max(pt.x));
+    let min_y = pts.iter().fold(f64::MAX, |min, pt| min.min(pt.y));
+    let max_y = pts.iter().fold(f64::MIN, |max, pt| max.max(pt.y));
+    out.push_str("q\n");
+    pdf_path(out, pts);
+    out.push_str("W n\n0 0 0 RG\n");
+    writeln!(out, "{} w [] 0 d", LINE_WIDTH_PER_THICKNESS).unwrap();
+    //Lines of constant x + y, spaced so they're HATCH_SPACING apart.
+    let step = HATCH_SPACING / FRAC_1_SQRT_2;
+    let mut offset = min_x + min_y;
+    while offset <= max_x + max_y {
+        writeln!(
+            out,
+            "{} {} m {} {} l",
+            offset - max_y,
+            max_y,
+            offset - min_y,
+            min_y
+        )
+        .unwrap();
+        offset += step;
+    }
+    out.push_str("S\nQ\n");
+}
+
+fn pdf_element(out: &mut String, viewport: &ViewportDrawing, element: &DrawElement2D) {
+    let color = &element.line_color;
+    writeln!(
+        out,
+        "{} {} {} RG {} w",
+        color.r as f64 / 255.0,
+        color.g as f64 / 255.0,
+        color.b as f64 / 255.0,
+        element.line_thickness as f64 * LINE_WIDTH_PER_THICKNESS
+    )
+    .unwrap();
+    match element.line_type {
+        LineType::Dashed { .. } => {
+            writeln!(out, "[{} {}] 0 d", DASH_LENGTH, DASH_LENGTH / 2.0).unwrap()
+        }
+        LineType::Solid => out.push_str("[] 0 d\n"),
+    }
+    match &element.element {
+        Element2D::Line(line) => {
+            let first = viewport.to_sheet(&line.first);
+            let second = viewport.to_sheet(&line.second);
+            writeln!(
+                out,
+                "{} {} m {} {} l S",
+                first.x, first.y, second.x, second.y
+            )
+            .unwrap();
+        }
+        Element2D::Arc(arc) => pdf_arc(out, viewport, arc),
+        Element2D::Text(text) => pdf_text(out, viewport, element, text),
+        other => {
+            let pts = viewport.outline(other).unwrap_or_default();
+            match &element.fill_type {
+                FillType::Solid { color } => {
+                    writeln!(
+                        out,
+                        "{} {} {} rg",
+                        color.r as f64 / 255.0,
+                        color.g as f64 / 255.0,
+                        color.b as f64 / 255.0
+                    )
+                    .unwrap();
+                    pdf_path(out, &pts);
+                    out.push_str("B\n");
+                }
+                FillType::Hatch { .. } => {
+                    pdf_hatch(out, &pts);
+                    pdf_path(out, &pts);
+                    out.push_str("S\n");
+                }
+            }
+        }
+    }
+}
+
+///Writes the sheet as a single page vector PDF at its paper size.  Hatches are drawn like they are
+/// in the SVG, transparency is ignored, and all text is in Helvetica.
+pub fn sheet_pdf(sheet: &SheetDrawing) -> Vec<u8> {
+    let width = sheet.size.x * POINTS_PER_METER;
+    let height = sheet.size.y * POINTS_PER_METER;
+    let mut content = String::new();
+    //Draw in meters from the top left, like the sheet.
+    writeln!(
+        content,
+        "{} 0 0 {} 0 {} cm",
+        POINTS_PER_METER, -POINTS_PER_METER, height
+    )
+    .unwrap();
+    content.push_str("1 J 1 j\n");
+    for viewport in &sheet.viewports {
+        writeln!(
+            content,
+            "q\n{} {} {} {} re W n",
+            viewport.origin.x, viewport.origin.y, viewport.size.x, viewport.size.y
+        )
+        .unwrap();
+        for element in &viewport.elements {
+            pdf_element(&mut content, viewport, element);
+        }
+        content.push_str("Q\n");
+    }
+
+    let objects = [
+        String::from("<< /Type /Catalog /Pages 2 0 R >>"),
+        String::from("<< /Type /Pages /Kids [3 0 R] /Count 1 >>"),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents 4 0 R /Resources << /Font << /F1 5 0 R >> >> >>",
+            width, height
+        ),
+        format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ),
+        String::from("<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>"),
+    ];
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        write!(pdf, "{} 0 obj\n{}\nendobj\n", index + 1, object).unwrap();
+    }
+    let xref = pdf.len();
+    write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).unwrap();
+    for offset in offsets {
+        writeln!(pdf, "{:010} 00000 n ", offset).unwrap();
+    }
+    write!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    )
+    .unwrap();
+    pdf.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Point2f, expected: Point2f) {
+        assert!(
+            (actual.x - expected.x).abs() < 1e-9 && (actual.y - expected.y).abs() < 1e-9,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_viewport_placement() {
+        let viewport = ViewportDrawing {
+            origin: Point2f::new(0.1, 0.1),
+            size: Point2f::new(0.2, 0.1),
+            center: Point2f::new(5.0, 5.0),
+            scale: 0.01,
+            elements: Vec::new(),
+        };
+        assert_close(
+            viewport.to_sheet(&Point2f::new(5.0, 5.0)),
+            Point2f::new(0.2, 0.15),
+        );
+        //Right and up in the drawing is right and up the sheet
+        assert_close(
+            viewport.to_sheet(&Point2f::new(6.0, 6.0)),
+            Point2f::new(0.21, 0.14),
+        );
+        let (min, max) = viewport.visible();
+        assert_close(min, Point2f::new(-5.0, 0.0));
+        assert_close(max, Point2f::new(15.0, 10.0));
+        assert!(viewport.overlaps(&Element2D::Line(Line2D::new(
+            Point2f::new(14.0, 9.0),
+            Point2f::new(20.0, 20.0)
+        ))));
+        assert!(!viewport.overlaps(&Element2D::Line(Line2D::new(
+            Point2f::new(16.0, 0.0),
+            Point2f::new(20.0, 10.0)
+        ))));
+    }
+
+    #[test]
+    fn test_clipped_elements() {
+        let elements = vec![
+            //Crosses the right side
+            Element2D::Line(Line2D::new(Point2f::new(0.0, 5.0), Point2f::new(20.0, 5.0))),
+            //Entirely outside
+            Element2D::Line(Line2D::new(
+                Point2f::new(16.0, 0.0),
+                Point2f::new(20.0, 10.0),
+            )),
+            //Inside, so it stays a rect
+            Element2D::Rect(Rect2D::new(Point2f::new(0.0, 0.0), Point2f::new(1.0, 1.0))),
+            //Hangs off the bottom
+            Element2D::Rect(Rect2D::new(Point2f::new(0.0, -1.0), Point2f::new(1.0, 1.0))),
+            //A circle around the top right corner only shows the quarter inside
+            Element2D::Arc(Arc2D::new(
+                Point2f::new(15.0, 10.0),
+                1.0,
+                radians(0.0),
+                radians(2.0 * PI),
+            )),
+        ];
+        let viewport = ViewportDrawing {
+            origin: Point2f::new(0.1, 0.1),
+            size: Point2f::new(0.2, 0.1),
+            center: Point2f::new(5.0, 5.0),
+            scale: 0.01,
+            elements: elements
+                .into_iter()
+                .map(DrawElement2D::new_annotation)
+                .collect(),
+        };
+        let clipped: Vec<Element2D> = viewport
+            .clipped_elements()
+            .into_iter()
+            .map(|element| element.element)
+            .collect();
+        assert_eq!(clipped.len(), 4);
+        match &clipped[0] {
+            Element2D::Line(line) => {
+                assert_close(line.first, Point2f::new(0.0, 5.0));
+                assert_close(line.second, Point2f::new(15.0, 5.0));
+            }
+            other => panic!("Expected a line, got {:?}", other),
+        }
+        assert!(matches!(clipped[1], Element2D::Rect(..)));
+        match &clipped[2] {
+            Element2D::Poly(poly) => {
+                assert_eq!(poly.pts.len(), 4);
+                assert!(poly.pts.iter().all(|pt| pt.y >= -1e-9));
+            }
+            other => panic!("Expected a poly, got {:?}", other),
+        }
+        match &clipped[3] {
+            Element2D::Arc(arc) => {
+                assert!((arc.start_angle.0 - PI).abs() < 1e-9);
+                assert!((arc.end_angle.0 - 1.5 * PI).abs() < 1e-9);
+            }
+            other => panic!("Expected an arc, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_compose_sheet() {
+        let sheet = Sheet::new(String::from("A4"), Point2f::new(0.297, 0.21));
+        let sheet_id = *sheet.get_id();
+        //Well above the drawing's origin, which used to land in the viewport's top left corner
+        let wall = Wall::new(
+            Point3f::new(0.0, 10.0, 0.0),
+            Point3f::new(4.0, 10.0, 0.0),
+            0.2,
+            3.0,
+        );
+        let centered = Viewport::new(sheet_id, ViewType::Top, Point2f::new(0.01, 0.01), 0.01);
+        let mut off_center = Viewport::new(sheet_id, ViewType::Top, Point2f::new(0.01, 0.01), 0.01);
+        off_center.center = Some(Point2f::new(100.0, 100.0));
+        let objs: Vec<DataBox> = vec![
+            Box::new(sheet),
+            Box::new(wall),
+            Box::new(centered),
+            Box::new(off_center),
+        ];
+        let drawing = compose_sheet(&sheet_id, &objs).unwrap();
+        assert_close(drawing.size, Point2f::new(0.297, 0.21));
+        assert_eq!(drawing.viewports.len(), 2);
+        let centered = &drawing.viewports[0];
+        assert_close(centered.size, Point2f::new(0.287, 0.2));
+        assert_close(centered.center, Point2f::new(2.0, 10.0));
+        assert!(!centered.elements.is_empty());
+        assert!(drawing.viewports[1].elements.is_empty());
+
+        let svg = sheet_svg(&drawing);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("<clipPath id=\"viewport-1\">"));
+        let pdf = String::from_utf8(sheet_pdf(&drawing)).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4"));
+        assert!(pdf.ends_with("%%EOF\n"));
+    }
+}
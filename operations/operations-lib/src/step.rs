@@ -0,0 +1,371 @@
+use crate::*;
+use std::collections::HashMap;
+
+///An attribute of an entity instance in a STEP file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepValue {
+    Null,
+    Derived,
+    Ref(u64),
+    Int(i64),
+    Real(f64),
+    Str(String),
+    Enum(String),
+    List(Vec<StepValue>),
+    Typed(String, Box<StepValue>),
+}
+
+impl StepValue {
+    pub fn as_ref_id(&self) -> Option<u64> {
+        match self {
+            StepValue::Ref(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            StepValue::Real(val) => Some(*val),
+            StepValue::Int(val) => Some(*val as f64),
+            StepValue::Typed(_, val) => val.as_f64(),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            StepValue::Str(val) | StepValue::Enum(val) => Some(val),
+            StepValue::Typed(_, val) => val.as_str(),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> &[StepValue] {
+        match self {
+            StepValue::List(vals) => vals,
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepEntity {
+    pub name: String,
+    pub args: Vec<StepValue>,
+}
+
+impl StepEntity {
+    pub fn arg(&self, index: usize) -> &StepValue {
+        self.args.get(index).unwrap_or(&StepValue::Null)
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.chars.peek() {
+            if c.is_whitespace() {
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, pred: F) -> String {
+        let mut taken = String::new();
+        while let Some(c) = self.chars.peek() {
+            if pred(*c) {
+                taken.push(*c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        taken
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ObjError> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            other => Err(ObjError::Other(format!(
+                "Expected {:?} in STEP data, found {:?}",
+                expected, other
+            ))),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ObjError> {
+        let mut raw = String::new();
+        loop {
+            match self.chars.next() {
+                Some('\'') => {
+                    if let Some('\'') = self.chars.peek() {
+                        self.chars.next();
+                        raw.push('\'');
+                    } else {
+                        return Ok(decode_string(&raw));
+                    }
+                }
+                Some(c) => raw.push(c),
+                None => return Err(ObjError::Other(String::from("Unterminated STEP string"))),
+            }
+        }
+    }
+
+    fn list(&mut self) -> Result<Vec<StepValue>, ObjError> {
+        let mut vals = Vec::new();
+        self.skip_whitespace();
+        if let Some(')') = self.chars.peek() {
+            self.chars.next();
+            return Ok(vals);
+        }
+        loop {
+            vals.push(self.value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => (),
+                Some(')') => return Ok(vals),
+                other => {
+                    return Err(ObjError::Other(format!(
+                        "Expected , or ) in STEP list, found {:?}",
+                        other
+                    )))
+                }
+            }
+        }
+    }
+
+    fn entity(&mut self) -> Result<(String, Vec<StepValue>), ObjError> {
+        self.skip_whitespace();
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        self.expect('(')?;
+        Ok((name.to_uppercase(), self.list()?))
+    }
+
+    fn value(&mut self) -> Result<StepValue, ObjError> {
+        self.skip_whitespace();
+        let c = match self.chars.peek() {
+            Some(c) => *c,
+            None => return Err(ObjError::Other(String::from("Unexpected end of STEP data"))),
+        };
+        match c {
+            '$' => {
+                self.chars.next();
+                Ok(StepValue::Null)
+            }
+            '*' => {
+                self.chars.next();
+                Ok(StepValue::Derived)
+            }
+            '#' => {
+                self.chars.next();
+                let id = self.take_while(|c| c.is_ascii_digit());
+                id.parse()
+                    .map(StepValue::Ref)
+                    .map_err(|_| ObjError::Other(format!("Invalid STEP reference #{}", id)))
+            }
+            '\'' => {
+                self.chars.next();
+                Ok(StepValue::Str(self.string()?))
+            }
+            '.' => {
+                self.chars.next();
+                let val = self.take_while(|c| c != '.');
+                self.expect('.')?;
+                Ok(StepValue::Enum(val))
+            }
+            '(' => {
+                self.chars.next();
+                Ok(StepValue::List(self.list()?))
+            }
+            '"' => {
+                //Binary, which IFC doesn't use for anything we read.
+                self.chars.next();
+                self.take_while(|c| c != '"');
+                self.expect('"')?;
+                Ok(StepValue::Null)
+            }
+            c if c.is_ascii_alphabetic() => {
+                let (name, mut vals) = self.entity()?;
+                let val = if vals.len() == 1 {
+                    vals.pop().unwrap()
+                } else {
+                    StepValue::List(vals)
+                };
+                Ok(StepValue::Typed(name, Box::new(val)))
+            }
+            _ => {
+                let num = self.take_while(|c| {
+                    c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'E' || c == 'e'
+                });
+                if num.contains('.') || num.contains('E') || num.contains('e') {
+                    num.parse()
+                        .map(StepValue::Real)
+                        .map_err(|_| ObjError::Other(format!("Invalid STEP number {:?}", num)))
+                } else {
+                    num.parse()
+                        .map(StepValue::Int)
+                        .map_err(|_| ObjError::Other(format!("Invalid STEP value at {:?}", c)))
+                }
+            }
+        }
+    }
+}
+
+///Undoes the escapes in a STEP string.  Only the \X2\ form is decoded, which is what IFC
+/// exporters use for anything outside of ASCII.
+fn decode_string(raw: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = raw;
+    while let Some(start) = rest.find('\\') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("\\\\") {
+            decoded.push('\\');
+            rest = &rest[2..];
+        } else if rest.starts_with("\\X2\\") {
+            let end = rest.find("\\X0\\").unwrap_or(rest.len());
+            let hex = &rest[4..end];
+            //Anything but hex digits is written as it is, rather than sliced up mid character.
+            if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                decoded.push_str(&rest[..4]);
+                rest = &rest[4..];
+                continue;
+            }
+            let units: Vec<u16> = (0..hex.len() / 4)
+                .filter_map(|i| u16::from_str_radix(&hex[i * 4..i * 4 + 4], 16).ok())
+                .collect();
+            decoded.push_str(&String::from_utf16_lossy(&units));
+            rest = &rest[(end + 4).min(rest.len())..];
+        } else {
+            decoded.push('\\');
+            rest = &rest[1..];
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+///Splits the DATA section into statements on the semicolons that aren't inside strings.
+fn statements(data: &str) -> Vec<&str> {
+    let mut results = Vec::new();
+    let mut in_string = false;
+    let mut start = 0;
+    for (index, c) in data.char_indices() {
+        match c {
+            '\'' => in_string = !in_string,
+            ';' if !in_string => {
+                results.push(data[start..index].trim());
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    results
+}
+
+///Reads every entity instance in the DATA section of a STEP file, by its ID.  Complex instances,
+/// which combine several entities in one, are skipped.
+pub fn parse_step(text: &str) -> Result<HashMap<u64, StepEntity>, ObjError> {
+    let start = text
+        .find("DATA;")
+        .ok_or_else(|| ObjError::Other(String::from("STEP file has no DATA section")))?;
+    let mut entities = HashMap::new();
+    for statement in statements(&text[start + 5..]) {
+        if statement == "ENDSEC" {
+            break;
+        }
+        let mut parser = Parser {
+            chars: statement.chars().peekable(),
+        };
+        let id = match parser.value()? {
+            StepValue::Ref(id) => id,
+            other => {
+                return Err(ObjError::Other(format!(
+                    "Expected an entity ID in STEP data, found {:?}",
+                    other
+                )))
+            }
+        };
+        parser.expect('=')?;
+        parser.skip_whitespace();
+        if let Some('(') = parser.chars.peek() {
+            continue;
+        }
+        let (name, args) = parser.entity()?;
+        entities.insert(id, StepEntity { name, args });
+    }
+    Ok(entities)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_step() {
+        let text = "ISO-10303-21;
+HEADER;
+FILE_NAME('header;not data','2020-01-01T00:00:00',(''),(''),'','','');
+ENDSEC;
+DATA;
+#1=IFCCARTESIANPOINT((0.,-1.5,2.E3));
+#2 = IfcWall('2O2Fr$t4X7Zf8NOew3FLOH',$,'It''s; a wall',*,.NOTDEFINED.,#1,(#1,#2),IFCLABEL('Label'),42);
+#3=(IFCNAMEDUNIT(*,.LENGTHUNIT.)IFCSIUNIT());
+ENDSEC;
+END-ISO-10303-21;
+";
+        let entities = parse_step(text).unwrap();
+        assert_eq!(entities.len(), 2);
+        assert_eq!(
+            entities[&1],
+            StepEntity {
+                name: String::from("IFCCARTESIANPOINT"),
+                args: vec![StepValue::List(vec![
+                    StepValue::Real(0.0),
+                    StepValue::Real(-1.5),
+                    StepValue::Real(2000.0),
+                ])],
+            }
+        );
+        let wall = &entities[&2];
+        assert_eq!(wall.name, "IFCWALL");
+        assert_eq!(wall.arg(0).as_str(), Some("2O2Fr$t4X7Zf8NOew3FLOH"));
+        assert_eq!(wall.arg(1), &StepValue::Null);
+        assert_eq!(wall.arg(2).as_str(), Some("It's; a wall"));
+        assert_eq!(wall.arg(3), &StepValue::Derived);
+        assert_eq!(wall.arg(4), &StepValue::Enum(String::from("NOTDEFINED")));
+        assert_eq!(wall.arg(5).as_ref_id(), Some(1));
+        assert_eq!(
+            wall.arg(6).as_list(),
+            &[StepValue::Ref(1), StepValue::Ref(2)]
+        );
+        assert_eq!(wall.arg(7).as_str(), Some("Label"));
+        assert_eq!(wall.arg(8), &StepValue::Int(42));
+        assert_eq!(wall.arg(9), &StepValue::Null);
+    }
+
+    #[test]
+    fn test_parse_step_errors() {
+        assert!(parse_step("ISO-10303-21;\nHEADER;\nENDSEC;\n").is_err());
+        assert!(parse_step("DATA;\n#1=IFCWALL(1,;\n").is_err());
+        assert!(parse_step("DATA;\n#1=IFCWALL(1 2);\n").is_err());
+        assert!(parse_step("DATA;\nIFCWALL(1);\n").is_err());
+    }
+
+    #[test]
+    fn test_decode_string() {
+        assert_eq!(decode_string("plain"), "plain");
+        assert_eq!(decode_string("back\\\\slash"), "back\\slash");
+        assert_eq!(decode_string("caf\\X2\\00E9\\X0\\!"), "café!");
+        assert_eq!(decode_string("\\X2\\00480069\\X0\\"), "Hi");
+        //Not hex, and would be cut in the middle of the é if it were read four bytes at a time
+        assert_eq!(decode_string("\\X2\\000é\\X0\\"), "\\X2\\000é\\X0\\");
+        assert_eq!(decode_string("\\X2\\00E9"), "é");
+    }
+}
@@ -125,7 +125,13 @@ impl operations_server::Operations for OperationsService {
         let refers = from_ref_msgs(&update_msg.obj_refs)?;
         let mut objs = get_map_from_change_msgs(&update_msg.objects)?;
         operations::update_all(&mut objs, refers);
-        let changes = to_change_msgs(&update_msg.objects, &objs).map_err(to_status)?;
+        let candidates = get_map_from_change_msgs(&update_msg.candidates)?;
+        let candidates = candidates.into_iter().filter_map(|(_, obj)| obj).collect();
+        let resubscribed = operations::update_section_cuts(&mut objs, candidates);
+        let mut changes = to_change_msgs(&update_msg.objects, &objs).map_err(to_status)?;
+        let resubscribed =
+            to_resubscribed_msgs(&update_msg.candidates, &resubscribed).map_err(to_status)?;
+        changes.extend(resubscribed);
         Ok(Response::new(UpdateObjectsOutput { objects: changes }))
     }
 
@@ -184,6 +190,223 @@ impl operations_server::Operations for OperationsService {
             viewport: Some(viewport_msg),
         }))
     }
+
+    #[instrument]
+    async fn create_section_cut(
+        &self,
+        request: Request<CreateSectionCutInput>,
+    ) -> Result<Response<CreateSectionCutOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let objects = from_obj_msgs(&msg.objects)?;
+        let mut section = Box::new(to_section_cut(msg)?) as DataBox;
+        operations::add_objs_cut_by_section(&mut section, &objects).map_err(to_status)?;
+        let section_msg = to_object_msg(&section).map_err(to_status)?;
+        Ok(Response::new(CreateSectionCutOutput {
+            section: Some(section_msg),
+        }))
+    }
+
+    #[instrument]
+    async fn create_dimension(
+        &self,
+        request: Request<CreateDimensionInput>,
+    ) -> Result<Response<CreateDimensionOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let first_obj = from_obj_msg_opt(&msg.first_obj)?;
+        let second_obj = from_obj_msg_opt(&msg.second_obj)?;
+        let dim = operations::create_dimension(
+            &first_obj,
+            msg.first_index as ResultInd,
+            &second_obj,
+            msg.second_index as ResultInd,
+            msg.offset,
+            to_text_style(&msg.text_style)?,
+        )
+        .map_err(to_status)?;
+        let dim_msg = to_object_msg(&(Box::new(dim) as DataBox)).map_err(to_status)?;
+        Ok(Response::new(CreateDimensionOutput {
+            dimension: Some(dim_msg),
+        }))
+    }
+
+    #[instrument]
+    async fn create_text_note(
+        &self,
+        request: Request<CreateTextNoteInput>,
+    ) -> Result<Response<CreateTextNoteOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut note = to_text_note(&msg.text)?;
+        if msg.leader_target.is_some() {
+            let target = from_obj_msg_opt(&msg.leader_target)?;
+            operations::add_leader_to_note(&mut note, &target, msg.leader_index as ResultInd)
+                .map_err(to_status)?;
+        }
+        let note_msg = to_object_msg(&(Box::new(note) as DataBox)).map_err(to_status)?;
+        Ok(Response::new(CreateTextNoteOutput {
+            note: Some(note_msg),
+        }))
+    }
+
+    #[instrument]
+    async fn delete_references(
+        &self,
+        request: Request<DeleteReferencesInput>,
+    ) -> Result<Response<DeleteReferencesOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut deleted = std::collections::HashSet::new();
+        for obj_id in &msg.obj_ids {
+            deleted.insert(to_obj_id(obj_id)?);
+        }
+        let mut dependents = from_obj_msgs(&msg.dependents)?;
+        let cascaded = operations::delete_refs_to_objects(&mut dependents, &deleted, msg.cascade);
+        let modified = to_object_msgs(&dependents)?;
+        Ok(Response::new(DeleteReferencesOutput {
+            modified,
+            cascaded: cascaded.into_iter().map(|id| id.to_string()).collect(),
+        }))
+    }
+
+    #[instrument]
+    async fn export_ifc(
+        &self,
+        request: Request<ExportIfcInput>,
+    ) -> Result<Response<ExportIfcOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let objects = from_obj_msgs(&msg.objects)?;
+        let mut symbol_files = Vec::new();
+        for symbol_file in &msg.symbol_files {
+            symbol_files.push(operations::SymbolFile {
+                file: to_obj_id(&symbol_file.file)?,
+                name: symbol_file.name.clone(),
+                objects: from_obj_msgs(&symbol_file.objects)?,
+            });
+        }
+        let ifc = operations::export_ifc(&msg.name, &objects, &symbol_files);
+        Ok(Response::new(ExportIfcOutput { ifc }))
+    }
+
+    #[instrument]
+    async fn import_ifc(
+        &self,
+        request: Request<ImportIfcInput>,
+    ) -> Result<Response<ImportIfcOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let import = operations::import_ifc(&msg.ifc).map_err(to_status)?;
+        let to_counts = |counts: std::collections::BTreeMap<String, usize>| {
+            counts
+                .into_iter()
+                .map(|(entity_type, count)| EntityCountMsg {
+                    entity_type,
+                    count: count as u64,
+                })
+                .collect()
+        };
+        let mut symbol_files = Vec::new();
+        for symbol_file in import.symbol_files {
+            symbol_files.push(SymbolFileMsg {
+                file: symbol_file.file.to_string(),
+                name: symbol_file.name,
+                objects: to_object_msgs(&symbol_file.objects)?,
+            });
+        }
+        Ok(Response::new(ImportIfcOutput {
+            objects: to_object_msgs(&import.objects)?,
+            as_symbols: to_counts(import.as_symbols),
+            skipped: to_counts(import.skipped),
+            symbol_files,
+        }))
+    }
+
+    #[instrument]
+    async fn import_dxf_underlay(
+        &self,
+        request: Request<ImportDxfUnderlayInput>,
+    ) -> Result<Response<ImportDxfUnderlayOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let import =
+            operations::import_dxf_underlay(msg.name.clone(), &msg.dxf).map_err(to_status)?;
+        let underlay = Box::new(import.underlay) as DataBox;
+        let underlay_msg = to_object_msg(&underlay).map_err(to_status)?;
+        Ok(Response::new(ImportDxfUnderlayOutput {
+            underlay: Some(underlay_msg),
+            skipped: import
+                .skipped
+                .into_iter()
+                .map(|(entity_type, count)| EntityCountMsg {
+                    entity_type,
+                    count: count as u64,
+                })
+                .collect(),
+        }))
+    }
+
+    #[instrument]
+    async fn export_dxf(
+        &self,
+        request: Request<ExportDxfInput>,
+    ) -> Result<Response<ExportDxfOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let objects = from_obj_msgs(&msg.objects)?;
+        let view = match &msg.target {
+            Some(export_dxf_input::Target::SheetId(sheet_id)) => {
+                let dxf = operations::export_sheet_dxf(&to_obj_id(sheet_id)?, &objects)
+                    .map_err(to_status)?;
+                return Ok(Response::new(ExportDxfOutput { dxf }));
+            }
+            Some(export_dxf_input::Target::Top(..)) => ViewType::Top,
+            Some(export_dxf_input::Target::Front(..)) => ViewType::Front,
+            Some(export_dxf_input::Target::Left(..)) => ViewType::Left,
+            Some(export_dxf_input::Target::Right(..)) => ViewType::Right,
+            Some(export_dxf_input::Target::Back(..)) => ViewType::Back,
+            Some(export_dxf_input::Target::Bottom(..)) => ViewType::Bottom,
+            Some(export_dxf_input::Target::Custom(msg)) => to_custom_view(msg)?,
+            Some(export_dxf_input::Target::SectionId(id)) => ViewType::Section {
+                section: to_obj_id(id)?,
+            },
+            None => return Err(Status::invalid_argument("No view or sheet to export")),
+        };
+        let dxf = operations::export_view_dxf(&objects, &view, to_hidden_lines(msg.hidden_lines));
+        Ok(Response::new(ExportDxfOutput { dxf }))
+    }
+
+    #[instrument]
+    async fn render_sheet(
+        &self,
+        request: Request<RenderSheetInput>,
+    ) -> Result<Response<RenderSheetOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let objects = from_obj_msgs(&msg.objects)?;
+        let sheet =
+            operations::compose_sheet(&to_obj_id(&msg.sheet_id)?, &objects).map_err(to_status)?;
+        Ok(Response::new(RenderSheetOutput {
+            svg: operations::sheet_svg(&sheet),
+            pdf: operations::sheet_pdf(&sheet),
+        }))
+    }
+
+    #[instrument]
+    async fn compose_viewport(
+        &self,
+        request: Request<ComposeViewportInput>,
+    ) -> Result<Response<ComposeViewportOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let objects = from_obj_msgs(&msg.objects)?;
+        let drawing = operations::compose_viewport(&to_obj_id(&msg.viewport_id)?, &objects)
+            .map_err(to_status)?;
+        Ok(Response::new(ComposeViewportOutput {
+            drawing: encode_drawing_data(Some(drawing)),
+        }))
+    }
 }
 
 #[tokio::main]
@@ -32,6 +32,80 @@ pub fn to_vector_3f(msg: &Option<Vector3Msg>) -> Result<Vector3f, tonic::Status>
     }
 }
 
+pub fn to_plane(msg: &Option<PlaneMsg>) -> Result<Plane, tonic::Status> {
+    if let Some(plane_msg) = msg {
+        Ok(Plane::new(
+            to_point_3f(&plane_msg.first)?,
+            to_point_3f(&plane_msg.second)?,
+            to_point_3f(&plane_msg.third)?,
+        ))
+    } else {
+        Err(tonic::Status::invalid_argument("No plane passed in"))
+    }
+}
+
+pub fn to_custom_view(msg: &CustomViewMsg) -> Result<ViewType, tonic::Status> {
+    let clip = match msg.clip {
+        Some(..) => Some(to_plane(&msg.clip)?),
+        None => None,
+    };
+    Ok(ViewType::Custom {
+        camera_pos: to_point_3f(&msg.camera_pos)?,
+        target: to_point_3f(&msg.target)?,
+        perspective: msg.perspective,
+        clip,
+    })
+}
+
+pub fn to_hidden_lines(msg: i32) -> HiddenLines {
+    match HiddenLinesMsg::from_i32(msg) {
+        Some(HiddenLinesMsg::Dash) => HiddenLines::Dash,
+        Some(HiddenLinesMsg::Show) => HiddenLines::Show,
+        _ => HiddenLines::Remove,
+    }
+}
+
+pub fn to_section_cut(msg: &CreateSectionCutInput) -> Result<SectionCut, tonic::Status> {
+    let direction = match SectionDirectionMsg::from_i32(msg.direction) {
+        Some(SectionDirectionMsg::Right) => SectionDirection::Right,
+        _ => SectionDirection::Left,
+    };
+    Ok(SectionCut::new(
+        to_point_3f(&msg.first_pt)?,
+        to_point_3f(&msg.second_pt)?,
+        msg.depth,
+        direction,
+    ))
+}
+
+pub fn to_text_style(msg: &Option<TextStyleMsg>) -> Result<TextStyle, tonic::Status> {
+    if let Some(style_msg) = msg {
+        Ok(TextStyle::new(style_msg.font.clone(), style_msg.height))
+    } else {
+        Err(tonic::Status::invalid_argument("No text style passed in"))
+    }
+}
+
+pub fn to_text_note(msg: &Option<Text2DMsg>) -> Result<TextNote, tonic::Status> {
+    if let Some(text_msg) = msg {
+        let position = to_point_2f(&text_msg.position)?;
+        let alignment = match TextAlignmentMsg::from_i32(text_msg.alignment) {
+            Some(TextAlignmentMsg::Center) => TextAlignment::Center,
+            Some(TextAlignmentMsg::Right) => TextAlignment::Right,
+            _ => TextAlignment::Left,
+        };
+        Ok(TextNote::new(
+            text_msg.text.clone(),
+            Point3f::new(position.x, position.y, 0.0),
+            to_text_style(&text_msg.style)?,
+            alignment,
+            radians(text_msg.rotation),
+        ))
+    } else {
+        Err(tonic::Status::invalid_argument("No text passed in"))
+    }
+}
+
 pub fn to_door(
     first_pt: &Option<Point3Msg>,
     second_pt: &Option<Point3Msg>,
@@ -72,18 +146,26 @@ pub fn to_viewport(viewport: CreateViewportInput) -> Result<Viewport, tonic::Sta
         Some(create_viewport_input::ViewType::Right(..)) => ViewType::Right,
         Some(create_viewport_input::ViewType::Back(..)) => ViewType::Back,
         Some(create_viewport_input::ViewType::Bottom(..)) => ViewType::Bottom,
-        Some(create_viewport_input::ViewType::Custom(msg)) => ViewType::Custom {
-            camera_pos: to_point_3f(&msg.camera_pos)?,
-            target: to_point_3f(&msg.target)?,
+        Some(create_viewport_input::ViewType::Custom(msg)) => to_custom_view(&msg)?,
+        Some(create_viewport_input::ViewType::SectionId(id)) => ViewType::Section {
+            section: to_obj_id(&id)?,
         },
         None => return Err(tonic::Status::invalid_argument("No view type passed in")),
     };
-    Ok(Viewport::new(
+    let mut result = Viewport::new(
         to_obj_id(&viewport.sheet_id)?,
         view,
         to_point_2f(&viewport.origin)?,
         viewport.scale,
-    ))
+    );
+    if viewport.size.is_some() {
+        result.size = Some(to_point_2f(&viewport.size)?);
+    }
+    result.hidden_lines = to_hidden_lines(viewport.hidden_lines);
+    if viewport.center.is_some() {
+        result.center = Some(to_point_2f(&viewport.center)?);
+    }
+    Ok(result)
 }
 
 pub fn from_ref_type_msg(ref_type: i32) -> Result<RefType, tonic::Status> {
@@ -296,6 +378,31 @@ pub fn to_change_msgs(
     Ok(results)
 }
 
+///Modifications for the candidate SectionCuts whose cut changed, made by whoever the candidates
+/// were fetched for.
+pub fn to_resubscribed_msgs(
+    candidates: &[ChangeMsg],
+    resubscribed: &[DataBox],
+) -> Result<Vec<ChangeMsg>, ObjError> {
+    let mut results = Vec::new();
+    for obj in resubscribed {
+        let id = obj.get_id().to_string();
+        let old_change = candidates.iter().find(|change| match &change.change_type {
+            Some(change_msg::ChangeType::Add(object))
+            | Some(change_msg::ChangeType::Modify(object)) => object.id == id,
+            _ => false,
+        });
+        if let Some(old_change) = old_change {
+            results.push(ChangeMsg {
+                user: old_change.user.clone(),
+                change_type: Some(change_msg::ChangeType::Modify(to_object_msg(obj)?)),
+                change_source: old_change.change_source.clone(),
+            });
+        }
+    }
+    Ok(results)
+}
+
 fn from_json(json_opt: Option<serde_json::Value>) -> String {
     match json_opt {
         Some(json) => json.to_string(),
@@ -394,6 +501,23 @@ fn encode_draw_element_2d(element: DrawElement2D) -> DrawElement2DMsg {
             }
             draw_element2_d_msg::Element::Poly(Poly2DMsg { pts })
         }
+        Element2D::Text(text) => {
+            let alignment = match text.alignment {
+                TextAlignment::Left => TextAlignmentMsg::Left,
+                TextAlignment::Center => TextAlignmentMsg::Center,
+                TextAlignment::Right => TextAlignmentMsg::Right,
+            };
+            draw_element2_d_msg::Element::Text(Text2DMsg {
+                text: text.text,
+                position: encode_point2(text.position),
+                style: Some(TextStyleMsg {
+                    font: text.font,
+                    height: text.height,
+                }),
+                alignment: alignment as i32,
+                rotation: text.rotation.0,
+            })
+        }
     };
     let fill_type = match element.fill_type {
         FillType::Solid { color } => {
@@ -414,7 +538,7 @@ fn encode_draw_element_2d(element: DrawElement2D) -> DrawElement2DMsg {
     }
 }
 
-fn encode_drawing_data(data_opt: Option<DrawingData>) -> Option<DrawingDataMsg> {
+pub fn encode_drawing_data(data_opt: Option<DrawingData>) -> Option<DrawingDataMsg> {
     match data_opt {
         Some(data) => {
             let mut elements = Vec::new();
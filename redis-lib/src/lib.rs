@@ -0,0 +1,52 @@
+//! Helpers shared by the services that keep their state in Redis.  Every service keys what it
+//! stores for a file with the file's ID followed by a colon, so a file can be purged by pattern.
+
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+
+/// Returns every key matching the pattern.  SCAN is used rather than KEYS so Redis isn't blocked
+/// while it walks the keyspace.
+pub async fn scan_keys(
+    conn: &mut MultiplexedConnection,
+    pattern: &str,
+) -> Result<Vec<String>, redis::RedisError> {
+    let mut keys = Vec::new();
+    let mut cursor: u64 = 0;
+    loop {
+        let (next, mut batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(1000)
+            .query_async(conn)
+            .await?;
+        keys.append(&mut batch);
+        if next == 0 {
+            return Ok(keys);
+        }
+        cursor = next;
+    }
+}
+
+/// Every key stored for the file.  File IDs can't contain glob characters for this to find all of
+/// them.
+pub async fn file_keys(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+) -> Result<Vec<String>, redis::RedisError> {
+    scan_keys(conn, &format!("{}:*", file)).await
+}
+
+/// Removes everything stored for the file.  Deleting a file that's already gone does nothing, so
+/// a purge that failed part way through can be run again.
+pub async fn delete_file(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+) -> Result<(), redis::RedisError> {
+    let keys = file_keys(conn, file).await?;
+    if !keys.is_empty() {
+        conn.del::<_, ()>(keys).await?;
+    }
+    Ok(())
+}
@@ -41,6 +41,10 @@ fn obj_rep_cache(file: &str, key: &str) -> String {
     format!("{}:{}", file, key)
 }
 
+fn file_reps(file: &str) -> String {
+    format!("{}:reps", file)
+}
+
 async fn store_object_rep(
     conn: &mut MultiplexedConnection,
     file: &str,
@@ -78,5 +82,33 @@ pub async fn update_object_rep_cache(
     let object = representation::UpdateChangeMsg::decode(input)?;
     info!("Updating object rep cache: {:?}", object);
     store_object_rep(conn, file, &object.obj_id, input).await?;
+    let file_reps = file_reps(file);
+    match object.update.and_then(|update| update.output) {
+        Some(representation::update_output_msg::Output::Delete(..)) => {
+            conn.srem(&file_reps, &object.obj_id).await?;
+        }
+        _ => {
+            conn.sadd(&file_reps, &object.obj_id).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn get_file_reps(
+    conn: &mut MultiplexedConnection,
+    file: &str,
+) -> Result<Vec<representation::UpdateChangeMsg>, RepCacheError> {
+    trace!("getting all objects in file {}", file);
+    let obj_ids: Vec<String> = conn.smembers(file_reps(file)).await?;
+    let mut reps = Vec::new();
+    for obj_id in obj_ids {
+        reps.push(get_object_rep(conn, file, &obj_id).await?);
+    }
+    Ok(reps)
+}
+
+/// Removes everything stored for the file.
+pub async fn delete_file(conn: &mut MultiplexedConnection, file: &str) -> Result<(), RepCacheError> {
+    redis_lib::delete_file(conn, file).await?;
     Ok(())
 }
@@ -60,6 +60,36 @@ impl rep_cache_server::RepCache for RepCacheService {
         }
         Ok(Response::new(GetObjectRepresentationsOutput { reps }))
     }
+
+    #[instrument]
+    async fn get_file_representations(
+        &self,
+        request: Request<GetFileRepresentationsInput>,
+    ) -> Result<Response<GetFileRepresentationsOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let reps = cache::get_file_reps(&mut redis_conn, &msg.file)
+            .instrument(info_span!("get_file_reps"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(GetFileRepresentationsOutput { reps }))
+    }
+
+    #[instrument]
+    async fn delete_file(
+        &self,
+        request: Request<DeleteFileInput>,
+    ) -> Result<Response<DeleteFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        cache::delete_file(&mut redis_conn, &msg.file)
+            .instrument(info_span!("delete_file"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(DeleteFileOutput {}))
+    }
 }
 
 #[tokio::main]
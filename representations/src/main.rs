@@ -87,6 +87,8 @@ pub async fn calc_representation(
             user: user,
             obj_id,
             update: Some(repr),
+            offset: 0,
+            interest: InterestChange::None as i32,
         };
         produce::submit_representations(broker, topic, file, update_change).await?;
     }
@@ -14,6 +14,8 @@ pub async fn submit_representations(
     let producer: FutureProducer = ClientConfig::new()
         .set("bootstrap.servers", brokers)
         .set("message.timeout.ms", "5000")
+        // updates works out which partition a file is on to replay it, so this has to stay put
+        .set("partitioner", "consistent_random")
         .create()
         .expect("Producer creation error");
 
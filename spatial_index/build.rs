@@ -0,0 +1,19 @@
+fn main() {
+    tonic_build::configure()
+        .build_client(false)
+        .build_server(true)
+        .compile(
+            &[
+                "../proto/spatial_index.proto",
+                "../proto/geom.proto",
+                "../proto/object_state.proto",
+            ],
+            &["../proto"],
+        )
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+    tonic_build::configure()
+        .build_client(true)
+        .build_server(false)
+        .compile(&["../proto/files.proto"], &["../proto"])
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+}
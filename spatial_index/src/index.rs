@@ -0,0 +1,269 @@
+//! Each file's index is an R-tree over every version of every object's bounding box still in history.
+//! A version is live from the offset it was written at until the offset of the next change to its object,
+//! so queries at older offsets see the boxes as they were.
+
+use super::*;
+use dashmap::DashMap;
+use obj_defs::*;
+use object_state::change_msg::ChangeType;
+use prost::Message;
+use rstar::{PointDistance, RTree, RTreeObject, SelectionFunction, AABB};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use thiserror::Error;
+
+pub const DEFAULT_HISTORY: usize = 5;
+
+#[derive(Debug, Error)]
+pub enum IndexError {
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error("Bincode error: {0}")]
+    BincodeError(#[from] bincode::Error),
+    #[error("Prost decode error: {0}")]
+    ProstDecodeError(#[from] prost::DecodeError),
+}
+
+impl Into<tonic::Status> for IndexError {
+    fn into(self) -> tonic::Status {
+        let msg = format!("{}", self);
+        let code = match self {
+            IndexError::InvalidInput(..) => tonic::Code::InvalidArgument,
+            IndexError::BincodeError(..) | IndexError::ProstDecodeError(..) => {
+                tonic::Code::Internal
+            }
+        };
+        tonic::Status::new(code, msg)
+    }
+}
+
+pub fn to_status<T: Into<IndexError>>(err: T) -> tonic::Status {
+    let index_error: IndexError = err.into();
+    index_error.into()
+}
+
+pub type Indexes = Arc<DashMap<String, FileIndex>>;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Version {
+    obj_id: String,
+    from: i64,
+    to: Option<i64>,
+    bbox: Cube,
+}
+
+impl Version {
+    fn live_at(&self, offset: i64) -> bool {
+        self.from <= offset && self.to.map_or(true, |to| offset < to)
+    }
+}
+
+fn to_array(pt: &Point3f) -> [f64; 3] {
+    [pt.x, pt.y, pt.z]
+}
+
+impl RTreeObject for Version {
+    type Envelope = AABB<[f64; 3]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        AABB::from_corners(
+            to_array(&self.bbox.bottom_left),
+            to_array(&self.bbox.top_right),
+        )
+    }
+}
+
+impl PointDistance for Version {
+    fn distance_2(&self, point: &[f64; 3]) -> f64 {
+        self.envelope().distance_2(point)
+    }
+}
+
+/// Returns the distance along the ray to where it enters the box, or None if it misses.
+/// The direction must be normalized.
+fn ray_distance(origin: &[f64; 3], dir: &[f64; 3], envelope: &AABB<[f64; 3]>) -> Option<f64> {
+    let lower = envelope.lower();
+    let upper = envelope.upper();
+    let mut t_min = 0.0f64;
+    let mut t_max = f64::INFINITY;
+    for axis in 0..3 {
+        if dir[axis].abs() < f64::EPSILON {
+            if origin[axis] < lower[axis] || origin[axis] > upper[axis] {
+                return None;
+            }
+        } else {
+            let t_1 = (lower[axis] - origin[axis]) / dir[axis];
+            let t_2 = (upper[axis] - origin[axis]) / dir[axis];
+            t_min = t_min.max(t_1.min(t_2));
+            t_max = t_max.min(t_1.max(t_2));
+            if t_min > t_max {
+                return None;
+            }
+        }
+    }
+    Some(t_min)
+}
+
+struct SelectRay {
+    origin: [f64; 3],
+    dir: [f64; 3],
+    offset: i64,
+}
+
+impl SelectionFunction<Version> for SelectRay {
+    fn should_unpack_parent(&self, envelope: &AABB<[f64; 3]>) -> bool {
+        ray_distance(&self.origin, &self.dir, envelope).is_some()
+    }
+
+    fn should_unpack_leaf(&self, leaf: &Version) -> bool {
+        leaf.live_at(self.offset)
+            && ray_distance(&self.origin, &self.dir, &leaf.envelope()).is_some()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub obj_id: String,
+    pub distance: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct FileIndex {
+    tree: RTree<Version>,
+    history: HashMap<String, VecDeque<Version>>,
+    ///Deleted files keep an empty index, so changes still on the topic for them are ignored.
+    deleted: bool,
+}
+
+impl FileIndex {
+    pub fn deleted() -> FileIndex {
+        FileIndex {
+            deleted: true,
+            ..Default::default()
+        }
+    }
+
+    /// Records the object's bounding box as of the offset.  A box of None means the object was deleted,
+    /// or doesn't have a bounding box any more.
+    pub fn update(&mut self, obj_id: &str, offset: i64, bbox: Option<Cube>, history: usize) {
+        if self.deleted {
+            return;
+        }
+        let tree = &mut self.tree;
+        let versions = self.history.entry(String::from(obj_id)).or_default();
+        if let Some(latest) = versions.back_mut() {
+            if latest.to.is_none() {
+                tree.remove(latest);
+                latest.to = Some(offset);
+                tree.insert(latest.clone());
+            }
+        }
+        if let Some(bbox) = bbox {
+            let version = Version {
+                obj_id: String::from(obj_id),
+                from: offset,
+                to: None,
+                bbox,
+            };
+            tree.insert(version.clone());
+            versions.push_back(version);
+        }
+        while versions.len() > history {
+            if let Some(oldest) = versions.pop_front() {
+                tree.remove(&oldest);
+            }
+        }
+        if versions.is_empty() {
+            self.history.remove(obj_id);
+        }
+    }
+
+    pub fn query_box(&self, offset: i64, bbox: &Cube) -> Vec<String> {
+        let envelope = AABB::from_corners(to_array(&bbox.bottom_left), to_array(&bbox.top_right));
+        self.tree
+            .locate_in_envelope_intersecting(&envelope)
+            .filter(|version| version.live_at(offset))
+            .map(|version| version.obj_id.clone())
+            .collect()
+    }
+
+    pub fn query_ray(&self, offset: i64, origin: &Point3f, dir: &Vector3f) -> Vec<Hit> {
+        let length = (dir.x * dir.x + dir.y * dir.y + dir.z * dir.z).sqrt();
+        if length < f64::EPSILON {
+            return Vec::new();
+        }
+        let origin = to_array(origin);
+        let dir = [dir.x / length, dir.y / length, dir.z / length];
+        let select = SelectRay {
+            origin,
+            dir,
+            offset,
+        };
+        let mut hits: Vec<Hit> = self
+            .tree
+            .locate_with_selection_function(select)
+            .filter_map(|version| {
+                let distance = ray_distance(&origin, &dir, &version.envelope())?;
+                Some(Hit {
+                    obj_id: version.obj_id.clone(),
+                    distance,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            a.distance
+                .partial_cmp(&b.distance)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        hits
+    }
+
+    pub fn nearest(&self, offset: i64, point: &Point3f, count: usize) -> Vec<Hit> {
+        let point = to_array(point);
+        self.tree
+            .nearest_neighbor_iter(&point)
+            .filter(|version| version.live_at(offset))
+            .take(count)
+            .map(|version| Hit {
+                obj_id: version.obj_id.clone(),
+                distance: version.distance_2(&point).sqrt(),
+            })
+            .collect()
+    }
+}
+
+fn get_bbox(obj_data: &[u8]) -> Result<Option<Cube>, IndexError> {
+    let obj: DataBox = bincode::deserialize(obj_data)?;
+    Ok(obj
+        .get_result(RefType::AxisAlignedBoundBox, 0)
+        .and_then(Cube::from_result))
+}
+
+pub fn update_index(
+    indexes: &Indexes,
+    file: &str,
+    offset: i64,
+    msg: &[u8],
+    history: usize,
+) -> Result<(), IndexError> {
+    let change = object_state::ChangeMsg::decode(msg)?;
+    let (obj_id, bbox) = match change.change_type {
+        Some(ChangeType::Add(object)) | Some(ChangeType::Modify(object)) => {
+            let bbox = get_bbox(&object.obj_data)?;
+            (object.id, bbox)
+        }
+        Some(ChangeType::Delete(msg)) => (msg.id, None),
+        None => return Ok(()),
+    };
+    trace!("Object {} in file {} has bounds {:?}", obj_id, file, bbox);
+    indexes
+        .entry(String::from(file))
+        .or_default()
+        .update(&obj_id, offset, bbox, history);
+    Ok(())
+}
+
+/// Drops everything indexed for the file, and keeps it from being indexed again.
+pub fn delete_file(indexes: &Indexes, file: &str) {
+    indexes.insert(String::from(file), FileIndex::deleted());
+}
@@ -0,0 +1,137 @@
+use crate::files::files_client::FilesClient;
+use crate::files::GetFileInput;
+use crate::index::{delete_file, Indexes};
+use futures::StreamExt;
+use rdkafka::config::{ClientConfig, RDKafkaLogLevel};
+use rdkafka::consumer::stream_consumer::StreamConsumer;
+use rdkafka::consumer::Consumer;
+use rdkafka::message::Message;
+use thiserror::Error;
+use trace_lib::TracedRequest;
+use tracing::*;
+
+#[derive(Debug, Error)]
+pub enum UpdateError {
+    #[error("Kafka error: {0}")]
+    KafkaError(#[from] rdkafka::error::KafkaError),
+    #[error("Index error: {0}")]
+    IndexError(#[from] crate::index::IndexError),
+    #[error("Transport error: {0}")]
+    TransportError(#[from] tonic::transport::Error),
+    #[error("Files error: {0}")]
+    FilesError(#[from] tonic::Status),
+    #[error("String error: {0}")]
+    StringError(#[from] std::str::Utf8Error),
+    #[error("Message from partition {partition} and offset {offset} has no payload")]
+    PayloadError { partition: i32, offset: i64 },
+    #[error("Message from partition {partition} and offset {offset} has no file key set")]
+    FileError { partition: i32, offset: i64 },
+}
+
+async fn file_exists(files_url: &str, file: &str) -> Result<bool, UpdateError> {
+    let mut client = FilesClient::connect(String::from(files_url)).await?;
+    let resp = client
+        .get_file(TracedRequest::new(GetFileInput {
+            id: String::from(file),
+        }))
+        .await;
+    match resp {
+        Ok(..) => Ok(true),
+        Err(status) if status.code() == tonic::Code::NotFound => Ok(false),
+        Err(status) => Err(status.into()),
+    }
+}
+
+/// The whole topic is read again every time the service starts, including the changes to files
+/// deleted since, so the first time a file comes up it's checked for.  If the check fails the file
+/// is indexed anyway.
+async fn check_deleted(indexes: &Indexes, files_url: &str, file: &str) {
+    if indexes.contains_key(file) {
+        return;
+    }
+    match file_exists(files_url, file).await {
+        Ok(true) => (),
+        Ok(false) => {
+            info!("File {} has been deleted, ignoring it", file);
+            delete_file(indexes, file);
+        }
+        Err(e) => error!("Couldn't check whether file {} exists: {}", file, e),
+    }
+}
+
+async fn handle_message<M: Message>(
+    indexes: &Indexes,
+    files_url: &str,
+    m: &M,
+    history: usize,
+) -> Result<(), UpdateError> {
+    let partition = m.partition();
+    let offset = m.offset();
+    let bytes = m
+        .payload()
+        .ok_or(UpdateError::PayloadError { partition, offset })?;
+    let file_bytes = m
+        .key()
+        .ok_or(UpdateError::FileError { partition, offset })?;
+    let file = std::str::from_utf8(file_bytes)?;
+    check_deleted(indexes, files_url, file).await;
+    crate::index::update_index(indexes, file, offset, bytes, history)?;
+    Ok(())
+}
+
+async fn handle_stream(
+    indexes: Indexes,
+    files_url: &str,
+    brokers: &str,
+    group_id: &str,
+    topic: &str,
+    history: usize,
+) -> Result<(), UpdateError> {
+    // The index is only kept in memory, so it's rebuilt from the start of the topic every time
+    // the service starts rather than picking up from committed offsets.
+    let consumer: StreamConsumer<rdkafka::consumer::DefaultConsumerContext> = ClientConfig::new()
+        .set("group.id", group_id)
+        .set("bootstrap.servers", brokers)
+        .set("enable.partition.eof", "false")
+        .set("session.timeout.ms", "6000")
+        .set("enable.auto.commit", "false")
+        .set("auto.offset.reset", "earliest")
+        .set_log_level(RDKafkaLogLevel::Debug)
+        .create()?;
+
+    consumer.subscribe(&[topic])?;
+
+    let mut message_stream = consumer.start();
+
+    while let Some(message) = message_stream.next().await {
+        match message {
+            Ok(m) => {
+                let span = info_span!("handle_message");
+                let _enter = span.enter();
+                if let Err(e) = handle_message(&indexes, files_url, &m, history).await {
+                    error!("{}", e);
+                }
+            }
+            Err(e) => {
+                let span = info_span!("kafka message error");
+                let _enter = span.enter();
+                error!("{}", e);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn update_index_from_log(
+    indexes: Indexes,
+    files_url: String,
+    brokers: String,
+    group_id: String,
+    topic: String,
+    history: usize,
+) {
+    std::thread::sleep(std::time::Duration::from_secs(30));
+    if let Err(e) = handle_stream(indexes, &files_url, &brokers, &group_id, &topic, history).await {
+        error!("{}", e);
+    }
+}
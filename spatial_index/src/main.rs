@@ -0,0 +1,164 @@
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use trace_lib::*;
+use tracing::*;
+
+mod index;
+mod kafka;
+#[cfg(test)]
+mod tests;
+use index::*;
+use kafka::*;
+
+mod geom {
+    tonic::include_proto!("geom");
+}
+
+mod files {
+    tonic::include_proto!("files");
+}
+
+mod object_state {
+    tonic::include_proto!("object_state");
+}
+
+mod spatial_index {
+    tonic::include_proto!("spatial_index");
+}
+use spatial_index::*;
+
+fn to_point(pt_opt: &Option<geom::Point3Msg>, name: &str) -> Result<obj_defs::Point3f, Status> {
+    match pt_opt {
+        Some(pt) => Ok(obj_defs::Point3f::new(pt.x, pt.y, pt.z)),
+        None => Err(to_status(IndexError::InvalidInput(format!(
+            "{} is required",
+            name
+        )))),
+    }
+}
+
+fn to_hit_msgs(hits: Vec<Hit>) -> Vec<HitMsg> {
+    hits.into_iter()
+        .map(|hit| HitMsg {
+            obj_id: hit.obj_id,
+            distance: hit.distance,
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct SpatialService {
+    indexes: Indexes,
+}
+
+#[tonic::async_trait]
+impl spatial_index_server::SpatialIndex for SpatialService {
+    #[instrument]
+    async fn query_box(
+        &self,
+        request: Request<QueryBoxInput>,
+    ) -> Result<Response<QueryBoxOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        info!("Query box: {:?}", msg);
+        let bbox = obj_defs::Cube::new(
+            to_point(&msg.bottom_left, "Bottom left")?,
+            to_point(&msg.top_right, "Top right")?,
+        );
+        let obj_ids = match self.indexes.get(&msg.file) {
+            Some(index) => index.query_box(msg.offset, &bbox),
+            None => Vec::new(),
+        };
+        Ok(Response::new(QueryBoxOutput { obj_ids }))
+    }
+
+    #[instrument]
+    async fn query_ray(
+        &self,
+        request: Request<QueryRayInput>,
+    ) -> Result<Response<QueryRayOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        info!("Query ray: {:?}", msg);
+        let origin = to_point(&msg.origin, "Origin")?;
+        let dir = match &msg.direction {
+            Some(dir) => obj_defs::Vector3f::new(dir.x, dir.y, dir.z),
+            None => {
+                return Err(to_status(IndexError::InvalidInput(String::from(
+                    "Direction is required",
+                ))))
+            }
+        };
+        let hits = match self.indexes.get(&msg.file) {
+            Some(index) => index.query_ray(msg.offset, &origin, &dir),
+            None => Vec::new(),
+        };
+        Ok(Response::new(QueryRayOutput {
+            hits: to_hit_msgs(hits),
+        }))
+    }
+
+    #[instrument]
+    async fn nearest(
+        &self,
+        request: Request<NearestInput>,
+    ) -> Result<Response<NearestOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        info!("Nearest: {:?}", msg);
+        let point = to_point(&msg.point, "Point")?;
+        let hits = match self.indexes.get(&msg.file) {
+            Some(index) => index.nearest(msg.offset, &point, msg.count as usize),
+            None => Vec::new(),
+        };
+        Ok(Response::new(NearestOutput {
+            hits: to_hit_msgs(hits),
+        }))
+    }
+
+    #[instrument]
+    async fn delete_file(
+        &self,
+        request: Request<DeleteFileInput>,
+    ) -> Result<Response<DeleteFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        info!("Delete file: {:?}", msg);
+        delete_file(&self.indexes, &msg.file);
+        Ok(Response::new(DeleteFileOutput {}))
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let addr = std::env::var("RUN_URL").unwrap().parse().unwrap();
+    let jaeger_url = std::env::var("JAEGER_URL").unwrap();
+    let broker = std::env::var("BROKER").unwrap();
+    let group = std::env::var("GROUP").unwrap();
+    let topic = std::env::var("TOPIC").unwrap();
+    let files_url = std::env::var("FILES_URL").unwrap();
+    let history = match std::env::var("HISTORY") {
+        Ok(history) => history.parse()?,
+        Err(..) => DEFAULT_HISTORY,
+    };
+    init_tracer(&jaeger_url, "spatial_index")?;
+    let indexes = Indexes::default();
+    tokio::spawn(update_index_from_log(
+        indexes.clone(),
+        files_url,
+        broker,
+        group,
+        topic,
+        history,
+    ));
+
+    let svc = spatial_index_server::SpatialIndexServer::new(SpatialService { indexes });
+
+    println!("Running on {:?}", addr);
+    Server::builder()
+        .add_service(svc)
+        .serve(addr)
+        .await
+        .unwrap();
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use super::*;
+use obj_defs::*;
+use object_state::change_msg::ChangeType;
+use prost::Message;
+
+fn cube(bl: (f64, f64, f64), tr: (f64, f64, f64)) -> Cube {
+    Cube::new(
+        Point3f::new(bl.0, bl.1, bl.2),
+        Point3f::new(tr.0, tr.1, tr.2),
+    )
+}
+
+fn ids(hits: Vec<Hit>) -> Vec<String> {
+    hits.into_iter().map(|hit| hit.obj_id).collect()
+}
+
+fn change_msg(change_type: ChangeType) -> Vec<u8> {
+    let msg = object_state::ChangeMsg {
+        user: String::from("user"),
+        change_type: Some(change_type),
+        change_source: Some(object_state::change_msg::ChangeSource::UserAction(
+            object_state::EmptyMsg {},
+        )),
+    };
+    let mut bytes = Vec::new();
+    msg.encode(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn test_query_box() {
+    let mut index = FileIndex::default();
+    index.update("a", 0, Some(cube((0.0, 0.0, 0.0), (1.0, 1.0, 1.0))), 5);
+    index.update("b", 1, Some(cube((5.0, 5.0, 0.0), (6.0, 6.0, 1.0))), 5);
+
+    let mut found = index.query_box(1, &cube((-1.0, -1.0, -1.0), (10.0, 10.0, 10.0)));
+    found.sort();
+    assert_eq!(found, vec![String::from("a"), String::from("b")]);
+    let found = index.query_box(1, &cube((0.5, 0.5, 0.5), (2.0, 2.0, 2.0)));
+    assert_eq!(found, vec![String::from("a")]);
+    let found = index.query_box(0, &cube((4.0, 4.0, 0.0), (7.0, 7.0, 1.0)));
+    assert!(found.is_empty());
+}
+
+#[test]
+fn test_versions_by_offset() {
+    let mut index = FileIndex::default();
+    index.update("a", 0, Some(cube((0.0, 0.0, 0.0), (1.0, 1.0, 1.0))), 5);
+    index.update("a", 2, Some(cube((10.0, 0.0, 0.0), (11.0, 1.0, 1.0))), 5);
+    index.update("a", 4, None, 5);
+
+    let old = cube((0.0, 0.0, 0.0), (1.0, 1.0, 1.0));
+    let new = cube((10.0, 0.0, 0.0), (11.0, 1.0, 1.0));
+    assert_eq!(index.query_box(1, &old), vec![String::from("a")]);
+    assert!(index.query_box(1, &new).is_empty());
+    assert!(index.query_box(3, &old).is_empty());
+    assert_eq!(index.query_box(3, &new), vec![String::from("a")]);
+    assert!(index.query_box(4, &new).is_empty());
+
+    index.update("a", 5, Some(cube((20.0, 0.0, 0.0), (21.0, 1.0, 1.0))), 2);
+    assert!(index.query_box(1, &old).is_empty());
+    assert_eq!(index.query_box(3, &new), vec![String::from("a")]);
+}
+
+#[test]
+fn test_query_ray() {
+    let mut index = FileIndex::default();
+    index.update("near", 0, Some(cube((2.0, -1.0, -1.0), (3.0, 1.0, 1.0))), 5);
+    index.update("far", 0, Some(cube((8.0, -1.0, -1.0), (9.0, 1.0, 1.0))), 5);
+    index.update("off", 0, Some(cube((5.0, 5.0, -1.0), (6.0, 6.0, 1.0))), 5);
+
+    let origin = Point3f::new(0.0, 0.0, 0.0);
+    let hits = index.query_ray(0, &origin, &Vector3f::new(2.0, 0.0, 0.0));
+    assert_eq!(hits.len(), 2);
+    assert_eq!(hits[0].obj_id, "near");
+    assert!((hits[0].distance - 2.0).abs() < 1e-9);
+    assert_eq!(hits[1].obj_id, "far");
+    assert!((hits[1].distance - 8.0).abs() < 1e-9);
+
+    let hits = index.query_ray(0, &origin, &Vector3f::new(-1.0, 0.0, 0.0));
+    assert!(hits.is_empty());
+    let hits = index.query_ray(0, &origin, &Vector3f::new(0.0, 0.0, 0.0));
+    assert!(hits.is_empty());
+}
+
+#[test]
+fn test_nearest() {
+    let mut index = FileIndex::default();
+    index.update("a", 0, Some(cube((1.0, 0.0, 0.0), (2.0, 1.0, 1.0))), 5);
+    index.update("b", 0, Some(cube((4.0, 0.0, 0.0), (5.0, 1.0, 1.0))), 5);
+    index.update("c", 0, Some(cube((10.0, 0.0, 0.0), (11.0, 1.0, 1.0))), 5);
+    index.update("a", 1, None, 5);
+
+    let point = Point3f::new(0.0, 0.0, 0.0);
+    assert_eq!(
+        ids(index.nearest(0, &point, 2)),
+        vec![String::from("a"), String::from("b")]
+    );
+    let hits = index.nearest(1, &point, 2);
+    assert_eq!(
+        ids(hits.clone()),
+        vec![String::from("b"), String::from("c")]
+    );
+    assert!((hits[0].distance - 4.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_update_index() {
+    let indexes = Indexes::default();
+    let wall = Wall::new(
+        Point3f::new(0.0, 0.0, 0.0),
+        Point3f::new(10.0, 0.0, 0.0),
+        1.0,
+        3.0,
+    );
+    let id = wall.get_id().to_string();
+    let data: DataBox = Box::new(wall);
+    let add = change_msg(ChangeType::Add(object_state::ObjectMsg {
+        id: id.clone(),
+        dependencies: None,
+        obj_data: bincode::serialize(&data).unwrap(),
+    }));
+    update_index(&indexes, "file", 0, &add, DEFAULT_HISTORY).unwrap();
+    let delete = change_msg(ChangeType::Delete(object_state::DeleteMsg {
+        id: id.clone(),
+    }));
+    update_index(&indexes, "file", 1, &delete, DEFAULT_HISTORY).unwrap();
+
+    let index = indexes.get("file").unwrap();
+    let region = cube((4.0, -1.0, 0.0), (6.0, 1.0, 1.0));
+    assert_eq!(index.query_box(0, &region), vec![id]);
+    assert!(index.query_box(1, &region).is_empty());
+}
+
+#[test]
+fn test_delete_file() {
+    let indexes = Indexes::default();
+    let region = cube((-1.0, -1.0, -1.0), (2.0, 2.0, 2.0));
+    indexes.entry(String::from("file")).or_default().update(
+        "a",
+        0,
+        Some(cube((0.0, 0.0, 0.0), (1.0, 1.0, 1.0))),
+        5,
+    );
+    delete_file(&indexes, "file");
+    assert!(indexes
+        .get("file")
+        .unwrap()
+        .query_box(0, &region)
+        .is_empty());
+
+    //Changes still on the topic for it don't bring it back
+    indexes.get_mut("file").unwrap().update(
+        "b",
+        1,
+        Some(cube((0.0, 0.0, 0.0), (1.0, 1.0, 1.0))),
+        5,
+    );
+    assert!(indexes
+        .get("file")
+        .unwrap()
+        .query_box(1, &region)
+        .is_empty());
+}
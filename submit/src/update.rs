@@ -108,12 +108,91 @@ async fn get_objects_to_update(
     }
 }
 
+async fn get_objects_of_type(
+    dep_client: &mut DepClient,
+    file: &str,
+    type_name: &str,
+) -> Result<Vec<String>, tonic::Status> {
+    let input = GetObjectsOfTypeInput {
+        file: String::from(file),
+        type_name: String::from(type_name),
+    };
+    let resp = dep_client
+        .get_objects_of_type(TracedRequest::new(input))
+        .instrument(info_span!("get_objects_of_type"))
+        .await;
+    Ok(trace_response(resp)?.obj_ids)
+}
+
+async fn get_latest_obj_ids(
+    obj_client: &mut ObjClient,
+    file: &str,
+) -> Result<Vec<String>, tonic::Status> {
+    let input = GetLatestObjectListInput {
+        file: String::from(file),
+    };
+    let resp = obj_client
+        .get_latest_object_list(TracedRequest::new(input))
+        .instrument(info_span!("get_latest_object_list"))
+        .await;
+    let mut stream = trace_response(resp)?;
+    let mut obj_ids = Vec::new();
+    while let Some(msg) = stream.message().await? {
+        obj_ids.push(msg.obj_id);
+    }
+    Ok(obj_ids)
+}
+
+fn changed_obj_id(change: &ChangeMsg) -> Option<&String> {
+    match &change.change_type {
+        Some(change_msg::ChangeType::Add(object))
+        | Some(change_msg::ChangeType::Modify(object)) => Some(&object.id),
+        Some(change_msg::ChangeType::Delete(msg)) => Some(&msg.id),
+        None => None,
+    }
+}
+
+///SectionCuts cut through objects that don't reference them, so the file's other SectionCuts are
+/// fetched to check the changes against.  If a SectionCut is one of the submitted changes, it could
+/// now cut through anything, so everything else in the file is fetched instead.
+async fn get_section_candidates(
+    obj_client: &mut ObjClient,
+    dep_client: &mut DepClient,
+    file: &String,
+    offset: i64,
+    user: String,
+    submitted: &IndexMap<String, ChangeMsg>,
+    fetched: &[ChangeMsg],
+) -> Result<Vec<ChangeMsg>, tonic::Status> {
+    let sections = get_objects_of_type(dep_client, file, "SectionCut").await?;
+    if sections.is_empty() {
+        return Ok(Vec::new());
+    }
+    let section_changed = sections.iter().any(|id| submitted.contains_key(id));
+    let obj_ids = if section_changed {
+        get_latest_obj_ids(obj_client, file).await?
+    } else {
+        sections
+    };
+    let fetched: HashSet<&String> = fetched.iter().filter_map(changed_obj_id).collect();
+    let obj_ids = obj_ids
+        .into_iter()
+        .filter(|id| !submitted.contains_key(id) && !fetched.contains(id))
+        .collect();
+    get_objects_to_update(obj_client, file, offset, user, obj_ids).await
+}
+
 async fn update(
     ops_client: &mut OpsClient,
     obj_refs: Vec<ReferenceMsg>,
     objects: Vec<ChangeMsg>,
+    candidates: Vec<ChangeMsg>,
 ) -> Result<Vec<ChangeMsg>, tonic::Status> {
-    let input = UpdateObjectsInput { obj_refs, objects };
+    let input = UpdateObjectsInput {
+        obj_refs,
+        objects,
+        candidates,
+    };
     let resp = ops_client
         .update_objects(TracedRequest::new(input))
         .instrument(info_span!("update_objects"))
@@ -137,10 +216,21 @@ pub async fn update_changes(
     trace!("Got references: {:?}", refers);
     let obj_ids = get_obj_ids_to_fetch(&refers, &objects);
     trace!("Fetching objects: {:?}", obj_ids);
-    let mut fetched_objs = get_objects_to_update(obj_client, &file, offset, user, obj_ids).await?;
+    let mut fetched_objs =
+        get_objects_to_update(obj_client, &file, offset, user.clone(), obj_ids).await?;
+    let candidates = get_section_candidates(
+        obj_client,
+        dep_client,
+        &file,
+        offset,
+        user,
+        &objects,
+        &fetched_objs,
+    )
+    .await?;
     let mut obj_vec: Vec<ChangeMsg> = objects.drain(..).map(|(_, val)| val).collect();
     obj_vec.append(&mut fetched_objs);
     debug!("Updating objects: {:?}", obj_vec);
-    let results = update(ops_client, refers, obj_vec).await?;
+    let results = update(ops_client, refers, obj_vec, candidates).await?;
     Ok(results)
 }
@@ -6,7 +6,9 @@ fn main() {
             &[
                 "../../proto/api.proto",
                 "../../proto/geom.proto",
+                "../../proto/object_state.proto",
                 "../../proto/representation.proto",
+                "../../proto/acl.proto",
             ],
             &["../../proto"],
         )
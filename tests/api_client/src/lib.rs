@@ -7,11 +7,19 @@ mod geom {
 }
 pub use geom::*;
 
+pub mod object_state {
+    tonic::include_proto!("object_state");
+}
+
 mod representation {
     tonic::include_proto!("representation");
 }
 pub use representation::*;
 
+pub mod acl {
+    tonic::include_proto!("acl");
+}
+
 pub mod api {
     tonic::include_proto!("api");
 }
@@ -19,13 +27,22 @@ pub use api::*;
 
 pub type ApiClient = api_client::ApiClient<Channel>;
 
-pub async fn begin_undo_event(client: &mut ApiClient, file: &String, user: &String) -> Result<()> {
+pub async fn begin_undo_event(
+    client: &mut ApiClient,
+    file: &String,
+    user: &String,
+    label: &str,
+) -> Result<String> {
     let undo_input = BeginUndoEventInput {
         file: file.clone(),
         user: user.clone(),
+        label: String::from(label),
     };
-    client.begin_undo_event(Request::new(undo_input)).await?;
-    Ok(())
+    let output = client
+        .begin_undo_event(Request::new(undo_input))
+        .await?
+        .into_inner();
+    Ok(output.event)
 }
 
 pub async fn undo_latest(
@@ -40,6 +57,7 @@ pub async fn undo_latest(
             user: user.clone(),
             offset,
         }),
+        policy: ConflictPolicy::Skip as i32,
     };
     let output = client.undo_latest(Request::new(input)).await?.into_inner();
     Ok(output.offset)
@@ -57,6 +75,7 @@ pub async fn redo_latest(
             user: user.clone(),
             offset,
         }),
+        policy: ConflictPolicy::Skip as i32,
     };
     let output = client.redo_latest(Request::new(input)).await?.into_inner();
     Ok(output.offset)
@@ -82,10 +101,12 @@ pub async fn delete_objects(
     client: &mut ApiClient,
     prefix: &OpPrefixMsg,
     ids: Vec<String>,
+    cascade: bool,
 ) -> Result<i64> {
     let input = DeleteObjectsInput {
         prefix: Some(prefix.clone()),
         obj_ids: ids,
+        cascade,
     };
 
     let output = client
@@ -160,6 +181,9 @@ pub async fn create_viewport(
         view_type: Some(view_type),
         origin: Some(origin.clone()),
         scale,
+        size: None,
+        hidden_lines: 0,
+        center: None,
     };
 
     let output = client
@@ -168,3 +192,247 @@ pub async fn create_viewport(
         .into_inner();
     Ok((output.viewport_id, output.offset))
 }
+
+pub async fn create_section_cut(
+    client: &mut ApiClient,
+    prefix: &OpPrefixMsg,
+    first_pt: &Point3Msg,
+    second_pt: &Point3Msg,
+    depth: f64,
+    direction: SectionDirectionMsg,
+) -> Result<(String, i64)> {
+    let input = CreateSectionCutInput {
+        prefix: Some(prefix.clone()),
+        first_pt: Some(first_pt.clone()),
+        second_pt: Some(second_pt.clone()),
+        depth,
+        direction: direction as i32,
+    };
+
+    let output = client
+        .create_section_cut(Request::new(input))
+        .await?
+        .into_inner();
+    Ok((output.section_id, output.offset))
+}
+
+/// Dimensions between the ProfilePoints at first and second's indices, whatever their ref types say.
+pub async fn create_dimension(
+    client: &mut ApiClient,
+    prefix: &OpPrefixMsg,
+    first: &object_state::RefIdMsg,
+    second: &object_state::RefIdMsg,
+    offset: f64,
+    text_style: &TextStyleMsg,
+) -> Result<(String, i64)> {
+    let input = CreateDimensionInput {
+        prefix: Some(prefix.clone()),
+        first_id: first.id.clone(),
+        first_index: first.index,
+        second_id: second.id.clone(),
+        second_index: second.index,
+        offset,
+        text_style: Some(text_style.clone()),
+    };
+
+    let output = client
+        .create_dimension(Request::new(input))
+        .await?
+        .into_inner();
+    Ok((output.dimension_id, output.offset))
+}
+
+/// Writes a note in plan.  The leader, if there is one, points at the ProfilePoint at leader's index.
+pub async fn create_text_note(
+    client: &mut ApiClient,
+    prefix: &OpPrefixMsg,
+    text: &Text2DMsg,
+    leader: Option<&object_state::RefIdMsg>,
+) -> Result<(String, i64)> {
+    let input = CreateTextNoteInput {
+        prefix: Some(prefix.clone()),
+        text: Some(text.clone()),
+        leader_id: leader.map(|refer| refer.id.clone()).unwrap_or_default(),
+        leader_index: leader.map(|refer| refer.index).unwrap_or_default(),
+    };
+
+    let output = client
+        .create_text_note(Request::new(input))
+        .await?
+        .into_inner();
+    Ok((output.note_id, output.offset))
+}
+
+pub async fn get_subscribers(
+    client: &mut ApiClient,
+    prefix: &OpPrefixMsg,
+    ids: Vec<object_state::RefIdMsg>,
+    transitive: bool,
+) -> Result<Vec<object_state::ReferenceMsg>> {
+    let input = GetSubscribersInput {
+        prefix: Some(prefix.clone()),
+        ids,
+        transitive,
+    };
+
+    let output = client
+        .get_subscribers(Request::new(input))
+        .await?
+        .into_inner();
+    Ok(output.references)
+}
+
+pub async fn get_object_subscribers(
+    client: &mut ApiClient,
+    prefix: &OpPrefixMsg,
+    obj_ids: Vec<String>,
+    transitive: bool,
+) -> Result<Vec<object_state::ReferenceMsg>> {
+    let input = GetObjectSubscribersInput {
+        prefix: Some(prefix.clone()),
+        obj_ids,
+        transitive,
+    };
+
+    let output = client
+        .get_object_subscribers(Request::new(input))
+        .await?
+        .into_inner();
+    Ok(output.references)
+}
+
+pub async fn get_publishers(
+    client: &mut ApiClient,
+    prefix: &OpPrefixMsg,
+    obj_ids: Vec<String>,
+) -> Result<Vec<object_state::ReferenceMsg>> {
+    let input = GetPublishersInput {
+        prefix: Some(prefix.clone()),
+        obj_ids,
+    };
+
+    let output = client
+        .get_publishers(Request::new(input))
+        .await?
+        .into_inner();
+    Ok(output.references)
+}
+
+/// Subscribes to the file's representations, replaying the changes after since_offset first if
+/// it's set.
+pub async fn subscribe_file(
+    client: &mut ApiClient,
+    file: &String,
+    user: &String,
+    since_offset: Option<i64>,
+) -> Result<tonic::Streaming<UpdateChangeMsg>> {
+    let input = SubscribeFileInput {
+        file: file.clone(),
+        user: user.clone(),
+        replay: since_offset.is_some(),
+        since_offset: since_offset.unwrap_or(0),
+    };
+    let stream = client
+        .subscribe_file(Request::new(input))
+        .await?
+        .into_inner();
+    Ok(stream)
+}
+
+pub async fn create_file(client: &mut ApiClient, name: &str, owner: &String) -> Result<String> {
+    let input = CreateFileInput {
+        name: String::from(name),
+        owner: owner.clone(),
+    };
+    let output = client.create_file(Request::new(input)).await?.into_inner();
+    match output.file {
+        Some(file) => Ok(file.id),
+        None => Err(anyhow::anyhow!("No file returned")),
+    }
+}
+
+pub async fn delete_file(client: &mut ApiClient, file: &String) -> Result<()> {
+    let input = DeleteFileInput { file: file.clone() };
+    client.delete_file(Request::new(input)).await?;
+    Ok(())
+}
+
+pub async fn export_gltf(client: &mut ApiClient, file: &String) -> Result<Vec<u8>> {
+    let input = ExportGltfInput { file: file.clone() };
+    let output = client.export_gltf(Request::new(input)).await?.into_inner();
+    Ok(output.glb)
+}
+
+pub async fn import_ifc(
+    client: &mut ApiClient,
+    prefix: &OpPrefixMsg,
+    ifc: String,
+) -> Result<(i64, Vec<String>, String)> {
+    let input = ImportIfcInput {
+        prefix: Some(prefix.clone()),
+        ifc,
+    };
+    let output = client.import_ifc(Request::new(input)).await?.into_inner();
+    Ok((output.offset, output.obj_ids, output.event))
+}
+
+pub async fn import_dxf_underlay(
+    client: &mut ApiClient,
+    prefix: &OpPrefixMsg,
+    name: String,
+    dxf: String,
+) -> Result<(i64, String)> {
+    let input = ImportDxfUnderlayInput {
+        prefix: Some(prefix.clone()),
+        name,
+        dxf,
+    };
+    let output = client
+        .import_dxf_underlay(Request::new(input))
+        .await?
+        .into_inner();
+    Ok((output.offset, output.obj_id))
+}
+
+pub async fn export_sheet_dxf(
+    client: &mut ApiClient,
+    file: &String,
+    sheet_id: &String,
+) -> Result<String> {
+    let input = ExportDxfInput {
+        file: file.clone(),
+        target: Some(export_dxf_input::Target::SheetId(sheet_id.clone())),
+        hidden_lines: 0,
+    };
+    let output = client.export_dxf(Request::new(input)).await?.into_inner();
+    Ok(output.dxf)
+}
+
+pub async fn get_viewport_drawing(
+    client: &mut ApiClient,
+    file: &str,
+    viewport_id: &str,
+) -> Result<Option<DrawingDataMsg>> {
+    let input = GetViewportDrawingInput {
+        file: file.to_owned(),
+        viewport_id: viewport_id.to_owned(),
+    };
+    let output = client
+        .get_viewport_drawing(Request::new(input))
+        .await?
+        .into_inner();
+    Ok(output.drawing)
+}
+
+pub async fn render_sheet(
+    client: &mut ApiClient,
+    file: &String,
+    sheet_id: &String,
+) -> Result<(String, Vec<u8>)> {
+    let input = RenderSheetInput {
+        file: file.clone(),
+        sheet_id: sheet_id.clone(),
+    };
+    let output = client.render_sheet(Request::new(input)).await?.into_inner();
+    Ok((output.svg, output.pdf))
+}
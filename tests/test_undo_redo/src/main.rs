@@ -1,5 +1,5 @@
 use ::api_client::*;
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use log::*;
 
 async fn create_floor(
@@ -8,7 +8,7 @@ async fn create_floor(
     user: String,
     level: u64,
 ) -> Result<(i64, Vec<String>)> {
-    begin_undo_event(client, &file, &user).await?;
+    begin_undo_event(client, &file, &user, "Create sheet").await?;
     let mut prefix = OpPrefixMsg {
         file: file.clone(),
         user: user.clone(),
@@ -32,7 +32,7 @@ async fn create_floor(
     )
     .await?;
     prefix.offset = offset;
-    begin_undo_event(client, &file, &user).await?;
+    begin_undo_event(client, &file, &user, &format!("Create floor {}", level)).await?;
 
     let width: f64 = 1.0;
     let height: f64 = 10.0;
@@ -88,14 +88,14 @@ async fn create_floor(
     prefix.offset = join_objs_at_pt(client, &prefix, &ids[2], &ids[3], &pt_4).await?;
     prefix.offset = join_objs_at_pt(client, &prefix, &ids[3], &ids[0], &pt_1).await?;
 
-    begin_undo_event(client, &file, &user).await?;
+    begin_undo_event(client, &file, &user, "Move and delete wall").await?;
     let delta = Vector3Msg {
         x: length / 2.0,
         y: 0.0,
         z: 0.0,
     };
     prefix.offset = move_objects(client, &prefix, vec![ids[1].clone()], &delta).await?;
-    prefix.offset = delete_objects(client, &prefix, vec![ids[1].clone()]).await?;
+    prefix.offset = delete_objects(client, &prefix, vec![ids[1].clone()], false).await?;
     prefix.offset = undo_latest(client, &prefix.file, &prefix.user, prefix.offset).await?;
     info!("Undone");
     prefix.offset = redo_latest(client, &prefix.file, &prefix.user, prefix.offset).await?;
@@ -103,6 +103,112 @@ async fn create_floor(
     Ok((prefix.offset, ids))
 }
 
+//A wall 4m long with a door filling an opening 2m along it, in millimeters.
+const WALL_WITH_DOOR: &str = "ISO-10303-21;
+HEADER;
+FILE_DESCRIPTION(('ViewDefinition [CoordinationView]'),'2;1');
+ENDSEC;
+DATA;
+#1=IFCSIUNIT(*,.LENGTHUNIT.,.MILLI.,.METRE.);
+#2=IFCUNITASSIGNMENT((#1));
+#3=IFCCARTESIANPOINT((0.,0.,0.));
+#4=IFCDIRECTION((0.,0.,1.));
+#5=IFCDIRECTION((1.,0.,0.));
+#6=IFCAXIS2PLACEMENT3D(#3,$,$);
+#7=IFCLOCALPLACEMENT($,#6);
+#8=IFCCARTESIANPOINT((2000.,0.));
+#9=IFCAXIS2PLACEMENT2D(#8,$);
+#10=IFCRECTANGLEPROFILEDEF(.AREA.,$,#9,4000.,200.);
+#11=IFCEXTRUDEDAREASOLID(#10,#6,#4,3000.);
+#12=IFCSHAPEREPRESENTATION($,'Body','SweptSolid',(#11));
+#13=IFCPRODUCTDEFINITIONSHAPE($,$,(#12));
+#14=IFCWALLSTANDARDCASE('wall',$,'Wall',$,$,#7,#13,$);
+#15=IFCCARTESIANPOINT((2000.,0.,0.));
+#16=IFCAXIS2PLACEMENT3D(#15,$,$);
+#17=IFCLOCALPLACEMENT(#7,#16);
+#18=IFCOPENINGELEMENT('opening',$,$,$,$,#17,$,$);
+#19=IFCRELVOIDSELEMENT('voids',$,$,$,#14,#18);
+#20=IFCCARTESIANPOINT((0.,-100.));
+#21=IFCCARTESIANPOINT((1000.,-100.));
+#22=IFCCARTESIANPOINT((1000.,100.));
+#23=IFCCARTESIANPOINT((0.,100.));
+#24=IFCPOLYLINE((#20,#21,#22,#23,#20));
+#25=IFCARBITRARYCLOSEDPROFILEDEF(.AREA.,$,#24);
+#26=IFCEXTRUDEDAREASOLID(#25,#6,#4,2100.);
+#27=IFCSHAPEREPRESENTATION($,'Body','SweptSolid',(#26));
+#28=IFCPRODUCTDEFINITIONSHAPE($,$,(#27));
+#29=IFCDOOR('door',$,'Door',$,$,#17,#28,$,2100.,1000.);
+#30=IFCRELFILLSELEMENT('fills',$,$,$,#18,#29);
+ENDSEC;
+END-ISO-10303-21;
+";
+
+//Deleting with cascade set takes hosted objects along, viewports with their sheet and doors with their wall.
+async fn delete_hosts(
+    client: &mut ApiClient,
+    file: String,
+    user: String,
+    offset: i64,
+) -> Result<i64> {
+    begin_undo_event(client, &file, &user, "Delete hosts").await?;
+    let mut prefix = OpPrefixMsg { file, user, offset };
+    let (sheet_id, offset) = create_sheet(
+        client,
+        &prefix,
+        String::from("Sheet 2"),
+        &Point2Msg { x: 0.4, y: 0.5 },
+    )
+    .await?;
+    prefix.offset = offset;
+    let (viewport_id, offset) = create_viewport(
+        client,
+        &prefix,
+        sheet_id.clone(),
+        create_viewport_input::ViewType::Top(EmptyMsg {}),
+        &Point2Msg { x: 0.1, y: 0.1 },
+        0.25,
+    )
+    .await?;
+    prefix.offset = offset;
+    get_viewport_drawing(client, &prefix.file, &viewport_id).await?;
+    prefix.offset = delete_objects(client, &prefix, vec![sheet_id], true).await?;
+    ensure!(
+        get_viewport_drawing(client, &prefix.file, &viewport_id)
+            .await
+            .is_err(),
+        "Viewport {} wasn't deleted with its sheet",
+        viewport_id
+    );
+
+    let (offset, obj_ids, _) = import_ifc(client, &prefix, String::from(WALL_WITH_DOOR)).await?;
+    prefix.offset = offset;
+    //The door's line is snapped to the wall, while the wall's opening comes from the door
+    let hosted = get_publishers(client, &prefix, obj_ids)
+        .await?
+        .into_iter()
+        .find_map(|refer| match (refer.owner, refer.other) {
+            (Some(owner), Some(other))
+                if owner.ref_type == object_state::ref_id_msg::RefType::ProfileLine as i32 =>
+            {
+                Some((owner.id, other.id))
+            }
+            _ => None,
+        });
+    let (door_id, wall_id) = match hosted {
+        Some(ids) => ids,
+        None => anyhow::bail!("Imported door isn't hosted on its wall"),
+    };
+    prefix.offset = delete_objects(client, &prefix, vec![wall_id], true).await?;
+    ensure!(
+        get_publishers(client, &prefix, vec![door_id.clone()])
+            .await?
+            .is_empty(),
+        "Door {} wasn't deleted with its wall",
+        door_id
+    );
+    Ok(prefix.offset)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     env_logger::Builder::new()
@@ -115,7 +221,8 @@ async fn main() -> Result<()> {
     let mut client = ApiClient::connect("http://127.0.0.1:8080").await?;
     let now = std::time::SystemTime::now();
     let user = uuid::Uuid::new_v4().to_string();
-    let _ = create_floor(&mut client, file.clone(), user.clone(), level).await?;
+    let (offset, _) = create_floor(&mut client, file.clone(), user.clone(), level).await?;
+    let _ = delete_hosts(&mut client, file.clone(), user.clone(), offset).await?;
     let elapsed = now.elapsed().unwrap();
     info!("Test took {:?} seconds", elapsed.as_secs_f32());
 
@@ -13,6 +13,12 @@ var user: string = null;
 var connection: any = null;
 var client: any = null;
 var file_to_sym_def: Map<string, string> = new Map();
+var last_offset: Map<string, number> = new Map();
+// Signed JWT sent with every request when the servers have authentication turned on
+var auth_token: string = null;
+// Selection and cursor of the other users with each file open, keyed by file then user
+var collaborators: Map<string, Map<string, any>> = new Map();
+var presence_listener: (file: string) => void = null;
 
 export function initialize() {
     client = new ApiClient("http://localhost:8080", undefined, undefined);
@@ -20,12 +26,34 @@ export function initialize() {
     return user;
 }
 
+export function setToken(token: string) {
+    auth_token = token;
+}
+
+function metadata() {
+    return auth_token ? { "authorization": "Bearer " + auth_token } : {};
+}
+
 export async function setConnection(connection_url: string) {
+    if (auth_token) {
+        connection_url += "?token=" + encodeURIComponent(auth_token);
+    }
     connection = new WebsocketAsPromised(connection_url, {
         unpackMessage: (data: any) => {
-            if (data) {
+            if (typeof data === "string") {
+                try {
+                    handlePresence(JSON.parse(data));
+                }
+                catch (e) {
+                    console.log("msg " + data);
+                }
+            }
+            else if (data) {
                 data.arrayBuffer().then(buffer => {
                     var deser = updates.UpdateChangeMsg.deserializeBinary(buffer);
+                    if (deser.getOffset() > (last_offset.get(deser.getFile()) || 0)) {
+                        last_offset.set(deser.getFile(), deser.getOffset());
+                    }
                     handleUpdate(deser);
                     return deser
                 })
@@ -44,6 +72,47 @@ export async function setConnection(connection_url: string) {
     }
 }
 
+function handlePresence(event: any) {
+    var file: string = null;
+    if (event.Join) {
+        file = event.Join.filename;
+        if (!collaborators.has(file)) {
+            collaborators.set(file, new Map());
+        }
+        collaborators.get(file).set(event.Join.user, { selection: [], cursor: null });
+    }
+    else if (event.Leave) {
+        file = event.Leave.filename;
+        if (collaborators.has(file)) {
+            collaborators.get(file).delete(event.Leave.user);
+        }
+    }
+    else if (event.Update) {
+        file = event.Update.filename;
+        if (!collaborators.has(file)) {
+            collaborators.set(file, new Map());
+        }
+        collaborators.get(file).set(event.Update.user, {
+            selection: event.Update.selection,
+            cursor: event.Update.cursor
+        });
+    }
+    else {
+        return;
+    }
+    if (presence_listener) {
+        presence_listener(file);
+    }
+}
+
+export function getCollaborators(name: string) {
+    return collaborators.get(name) || new Map();
+}
+
+export function onPresenceChange(listener: (file: string) => void) {
+    presence_listener = listener;
+}
+
 import { Renderer } from '../rendering/renderer'
 import { UpdateChangeMsg } from "../proto/representation_pb";
 
@@ -75,7 +144,8 @@ export function subToFile(name: string, user: string) {
     var sub = {
         "Subscribe": {
             "filename": name,
-            "user": user
+            "user": user,
+            "since_offset": last_offset.has(name) ? last_offset.get(name) : null
         }
     };
     var msg = JSON.stringify(sub);
@@ -85,11 +155,41 @@ export function subToFile(name: string, user: string) {
     }
 }
 
+export function setInterest(name: string, bottom_left: Point3d, top_right: Point3d, level: number) {
+    var interest = {
+        "SetInterest": {
+            "filename": name,
+            "bbox": {
+                "bottom_left": { "x": bottom_left.x, "y": bottom_left.y, "z": bottom_left.z },
+                "top_right": { "x": top_right.x, "y": top_right.y, "z": top_right.z }
+            },
+            "level": level
+        }
+    };
+    if (connection) {
+        connection.send(JSON.stringify(interest));
+    }
+}
+
+export function sendPresence(name: string, user: string, selection: Array<string>, cursor: Point3d) {
+    var presence = {
+        "Presence": {
+            "filename": name,
+            "user": user,
+            "selection": selection,
+            "cursor": cursor ? { "x": cursor.x, "y": cursor.y, "z": cursor.z } : null
+        }
+    };
+    if (connection) {
+        connection.send(JSON.stringify(presence));
+    }
+}
+
 export function openFile(file_id: string) {
     console.log("open file " + file_id);
     var fileInput = new api.OpenFileInput();
     fileInput.setFile(file_id);
-    var stream = client.openFile(fileInput);
+    var stream = client.openFile(fileInput, metadata());
     stream.on('status', function (status) {
         console.log(status.code);
         console.log(status.details);
@@ -109,8 +209,9 @@ export function beginUndoEvent(desc: string) {
     var eventInput = new api.BeginUndoEventInput();
     eventInput.setFile(filename);
     eventInput.setUser(user);
+    eventInput.setLabel(desc);
     return new Promise((resolve: (value: string) => void, reject: (value: any) => void) => {
-        client.beginUndoEvent(eventInput, {}, function (err, response) {
+        client.beginUndoEvent(eventInput, metadata(), function (err, response) {
             if (err) {
                 reject(err)
             } else {
@@ -135,6 +236,10 @@ export function deleteObject(event: string, id: string) {
 function handleUpdate(msg: UpdateChangeMsg) {
     var file = msg.getFile();
     var id = msg.getObjId();
+    if (msg.getInterest() == updates.InterestChange.LEAVE) {
+        renderer.deleteMesh(id);
+        return;
+    }
     var update = msg.getUpdate();
     var outputCase = update.getOutputCase();
     switch (outputCase) {
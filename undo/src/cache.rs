@@ -17,6 +17,11 @@ fn redo_stack(file: &str, user: &str) -> String {
     format!("{}:{}:redo", file, user)
 }
 
+///Hash holding the label and creation time of an event, for displaying the history.
+fn event_info(event: &str) -> String {
+    format!("{}:info", event)
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub enum UndoChangeType {
     Add,
@@ -79,6 +84,7 @@ async fn delete_event_if_empty(
     let event_len: u64 = redis_conn.llen(event).await?;
     if event_len == 0 {
         redis_conn.lrem(stack, 1, event).await?;
+        redis_conn.del(event_info(event)).await?;
     }
     Ok(())
 }
@@ -239,30 +245,132 @@ pub async fn update_undo_cache(
     Ok(())
 }
 
-async fn begin_event(redis_conn: &mut MultiplexedConnection, stack: &str) -> Result<(), UndoError> {
+fn source_stacks(file: &str, user: &str, source: &change_msg::ChangeSource) -> Option<(String, String)> {
+    match source {
+        change_msg::ChangeSource::Undo(..) => Some((undo_stack(file, user), redo_stack(file, user))),
+        change_msg::ChangeSource::Redo(..) => Some((redo_stack(file, user), undo_stack(file, user))),
+        change_msg::ChangeSource::UserAction(..) => None,
+    }
+}
+
+///Removes the entries for obj_ids from an event being undone or redone, when they were skipped rather than inverted.
+/// Otherwise they'd never leave the event, and it would stay on top of the stack forever.
+pub async fn drop_entries(
+    redis_conn: &mut MultiplexedConnection,
+    file: &str,
+    user: &str,
+    source: &change_msg::ChangeSource,
+    obj_ids: Vec<String>,
+) -> Result<(), UndoError> {
+    let event = match source {
+        change_msg::ChangeSource::Undo(event) | change_msg::ChangeSource::Redo(event) => event,
+        change_msg::ChangeSource::UserAction(..) => return Ok(()),
+    };
+    if let Some((from_stack, _)) = source_stacks(file, user, source) {
+        for obj_id in obj_ids {
+            pop_entry_from_event(redis_conn, file, event, &obj_id).await?;
+        }
+        delete_event_if_empty(redis_conn, &from_stack, event).await?;
+    }
+    Ok(())
+}
+
+///Takes back the event begun on the other stack by an undo or redo that didn't go through.
+pub async fn cancel(
+    redis_conn: &mut MultiplexedConnection,
+    file: &str,
+    user: &str,
+    source: &change_msg::ChangeSource,
+) -> Result<(), UndoError> {
+    if let Some((_, to_stack)) = source_stacks(file, user, source) {
+        let event = get_current_event_in_stack(redis_conn, file, user, &to_stack).await?;
+        delete_event_if_empty(redis_conn, &to_stack, &event).await?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+pub struct EventInfo {
+    pub event: String,
+    pub label: String,
+    ///Milliseconds since the Unix epoch
+    pub timestamp: i64,
+    pub entries: Vec<UndoEntry>,
+}
+
+async fn begin_event(
+    redis_conn: &mut MultiplexedConnection,
+    stack: &str,
+    label: &str,
+) -> Result<String, UndoError> {
     let event = Uuid::new_v4().to_string();
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_millis() as i64)
+        .unwrap_or(0);
+    redis_conn
+        .hset_multiple(
+            event_info(&event),
+            &[("label", label.to_string()), ("timestamp", timestamp.to_string())],
+        )
+        .await?;
     push_event_to_stack(redis_conn, stack, &event).await?;
-    Ok(())
+    Ok(event)
+}
+
+async fn get_event_label(
+    redis_conn: &mut MultiplexedConnection,
+    event: &str,
+) -> Result<String, UndoError> {
+    let label: Option<String> = redis_conn.hget(event_info(event), "label").await?;
+    Ok(label.unwrap_or_default())
+}
+
+async fn get_event_info(
+    redis_conn: &mut MultiplexedConnection,
+    event: String,
+) -> Result<EventInfo, UndoError> {
+    let (label, timestamp): (Option<String>, Option<i64>) = redis_conn
+        .hget(event_info(&event), &["label", "timestamp"])
+        .await?;
+    let entries = get_event_entries(redis_conn, &event).await?;
+    Ok(EventInfo {
+        event,
+        label: label.unwrap_or_default(),
+        timestamp: timestamp.unwrap_or_default(),
+        entries,
+    })
 }
 
 pub async fn begin_undo_event(
     redis_conn: &mut MultiplexedConnection,
     file: &str,
     user: &str,
+    label: &str,
+) -> Result<String, UndoError> {
+    let undo_stack = undo_stack(file, user);
+    begin_event(redis_conn, &undo_stack, label).await
+}
+
+///Only takes the event off the stack if nothing was recorded in it.
+pub async fn cancel_undo_event(
+    redis_conn: &mut MultiplexedConnection,
+    file: &str,
+    user: &str,
+    event: &str,
 ) -> Result<(), UndoError> {
     let undo_stack = undo_stack(file, user);
-    begin_event(redis_conn, &undo_stack).await?;
-    Ok(())
+    delete_event_if_empty(redis_conn, &undo_stack, event).await
 }
 
 async fn begin_redo_event(
     redis_conn: &mut MultiplexedConnection,
     file: &str,
     user: &str,
-) -> Result<(), UndoError> {
+    label: &str,
+) -> Result<String, UndoError> {
     let redo_stack = redo_stack(file, user);
-    begin_event(redis_conn, &redo_stack).await?;
-    Ok(())
+    begin_event(redis_conn, &redo_stack, label).await
 }
 
 async fn get_current_event_and_list(
@@ -284,7 +392,39 @@ pub async fn undo(
 ) -> Result<(String, Vec<UndoEntry>), UndoError> {
     let undo_stack = undo_stack(file, user);
     let results = get_current_event_and_list(redis_conn, file, user, &undo_stack).await?;
-    begin_redo_event(redis_conn, file, user).await?;
+    let label = get_event_label(redis_conn, &results.0).await?;
+    begin_redo_event(redis_conn, file, user, &label).await?;
+    Ok(results)
+}
+
+///Gets every event from the top of the undo stack down to and including the given one, latest first.
+/// The entries of all of them end up in a single redo event.
+pub async fn undo_to(
+    redis_conn: &mut MultiplexedConnection,
+    file: &str,
+    user: &str,
+    event: &str,
+) -> Result<Vec<(String, Vec<UndoEntry>)>, UndoError> {
+    let undo_stack = undo_stack(file, user);
+    let events: Vec<String> = redis_conn.lrange(&undo_stack, 0, -1).await?;
+    let position = events.iter().position(|stack_event| stack_event == event);
+    let position = match position {
+        Some(position) => position,
+        None => {
+            return Err(UndoError::NoEventInStack(
+                String::from(event),
+                String::from(user),
+                String::from(file),
+            ))
+        }
+    };
+    let mut results = Vec::new();
+    for stack_event in events.into_iter().skip(position).rev() {
+        let list = get_event_entries(redis_conn, &stack_event).await?;
+        results.push((stack_event, list));
+    }
+    let label = get_event_label(redis_conn, event).await?;
+    begin_redo_event(redis_conn, file, user, &label).await?;
     Ok(results)
 }
 
@@ -295,10 +435,55 @@ pub async fn redo(
 ) -> Result<(String, Vec<UndoEntry>), UndoError> {
     let redo_stack = redo_stack(file, user);
     let results = get_current_event_and_list(redis_conn, file, user, &redo_stack).await?;
-    begin_undo_event(redis_conn, file, user).await?;
+    let label = get_event_label(redis_conn, &results.0).await?;
+    begin_undo_event(redis_conn, file, user, &label).await?;
     Ok(results)
 }
 
+///Returns the undo and redo stacks, latest event first.  Events without any changes in them are left out,
+/// as there's nothing to undo in them.
+pub async fn get_history(
+    redis_conn: &mut MultiplexedConnection,
+    file: &str,
+    user: &str,
+) -> Result<(Vec<EventInfo>, Vec<EventInfo>), UndoError> {
+    let mut stacks = Vec::new();
+    for stack in &[undo_stack(file, user), redo_stack(file, user)] {
+        let events: Vec<String> = redis_conn.lrange(stack, 0, -1).await?;
+        let mut infos = Vec::new();
+        for event in events.into_iter().rev() {
+            let info = get_event_info(redis_conn, event).await?;
+            if info.entries.len() > 0 {
+                infos.push(info);
+            }
+        }
+        stacks.push(infos);
+    }
+    let redo_events = stacks.pop().unwrap_or_default();
+    let undo_events = stacks.pop().unwrap_or_default();
+    Ok((undo_events, redo_events))
+}
+
+/// Removes everything stored for the file, along with the events on its stacks.
+pub async fn delete_file(conn: &mut MultiplexedConnection, file: &str) -> Result<(), UndoError> {
+    let keys = redis_lib::file_keys(conn, file).await?;
+    // Events are keyed by their own ID, so they're found through the stacks that hold them.
+    for stack in keys
+        .iter()
+        .filter(|key| key.ends_with(":undo") || key.ends_with(":redo"))
+    {
+        let events: Vec<String> = conn.lrange(stack, 0, -1).await?;
+        for event in events {
+            let info = event_info(&event);
+            conn.del::<_, ()>(&[event, info]).await?;
+        }
+    }
+    if !keys.is_empty() {
+        conn.del::<_, ()>(keys).await?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -324,7 +509,7 @@ mod tests {
         let user = Uuid::new_v4().to_string();
         let obj_1 = Uuid::new_v4().to_string();
 
-        begin_undo_event(&mut conn, &file, &user).await.unwrap();
+        begin_undo_event(&mut conn, &file, &user, "Add object").await.unwrap();
 
         let offset = 1;
         let msg = ChangeMsg {
@@ -420,4 +605,89 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[tokio_macros::test]
+    async fn test_history() {
+        let mut conn = test_get_conn().await;
+        let file = Uuid::new_v4().to_string();
+        let user = Uuid::new_v4().to_string();
+        let obj_1 = Uuid::new_v4().to_string();
+        let obj_2 = Uuid::new_v4().to_string();
+
+        let add_msg = |id: &String| ChangeMsg {
+            user: user.clone(),
+            change_type: Some(change_msg::ChangeType::Add(ObjectMsg {
+                id: id.clone(),
+                dependencies: None,
+                obj_data: Vec::new(),
+            })),
+            change_source: Some(change_msg::ChangeSource::UserAction(EmptyMsg {})),
+        };
+        let first = begin_undo_event(&mut conn, &file, &user, "First")
+            .await
+            .unwrap();
+        update_undo_cache_inner(&mut conn, &file, 1, add_msg(&obj_1))
+            .await
+            .unwrap();
+        let second = begin_undo_event(&mut conn, &file, &user, "Second")
+            .await
+            .unwrap();
+        update_undo_cache_inner(&mut conn, &file, 2, add_msg(&obj_2))
+            .await
+            .unwrap();
+        //Left open and empty, so it shouldn't show up
+        let empty = begin_undo_event(&mut conn, &file, &user, "Empty")
+            .await
+            .unwrap();
+        //Events with changes in them can't be cancelled
+        cancel_undo_event(&mut conn, &file, &user, &second)
+            .await
+            .unwrap();
+        let stack: Vec<String> = conn.lrange(undo_stack(&file, &user), 0, -1).await.unwrap();
+        assert_eq!(stack, vec![first.clone(), second.clone(), empty.clone()]);
+
+        let (undo_events, redo_events) = get_history(&mut conn, &file, &user).await.unwrap();
+        assert_eq!(undo_events.len(), 2);
+        assert_eq!(undo_events[0].event, second);
+        assert_eq!(undo_events[0].label, "Second");
+        assert_eq!(undo_events[0].entries[0].obj_id, obj_2);
+        assert_eq!(undo_events[1].event, first);
+        assert_eq!(undo_events[1].label, "First");
+        assert!(undo_events[0].timestamp >= undo_events[1].timestamp);
+        assert!(redo_events.is_empty());
+
+        cancel_undo_event(&mut conn, &file, &user, &empty)
+            .await
+            .unwrap();
+        let stack: Vec<String> = conn.lrange(undo_stack(&file, &user), 0, -1).await.unwrap();
+        assert_eq!(stack, vec![first.clone(), second.clone()]);
+
+        assert!(undo_to(&mut conn, &file, &user, "Not an event").await.is_err());
+        let events = undo_to(&mut conn, &file, &user, &first).await.unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].0, second);
+        assert_eq!(events[0].1[0].obj_id, obj_2);
+        assert_eq!(events[1].0, first);
+        assert_eq!(events[1].1[0].obj_id, obj_1);
+        for (event, entries) in events {
+            for entry in entries {
+                let undo_msg = ChangeMsg {
+                    user: user.clone(),
+                    change_type: Some(change_msg::ChangeType::Delete(DeleteMsg {
+                        id: entry.obj_id,
+                    })),
+                    change_source: Some(change_msg::ChangeSource::Undo(event.clone())),
+                };
+                update_undo_cache_inner(&mut conn, &file, 3, undo_msg)
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let (undo_events, redo_events) = get_history(&mut conn, &file, &user).await.unwrap();
+        assert!(undo_events.is_empty());
+        assert_eq!(redo_events.len(), 1);
+        assert_eq!(redo_events[0].label, "First");
+        assert_eq!(redo_events[0].entries.len(), 2);
+    }
 }
@@ -1,16 +1,17 @@
 use crate::*;
 use trace_lib::TracedRequest;
 
-async fn get_all_previous_objects(
+///Gets each object in entries at the offset returned by get_offset.
+async fn get_all_objects_at(
     obj_client: &mut ObjClient,
     file: &str,
     entries: &Vec<UndoEntry>,
+    get_offset: impl Fn(&UndoEntry) -> i64,
 ) -> Result<Vec<OptionChangeMsg>, Status> {
     let mut obj_ids = Vec::new();
     for entry in entries {
-        //Get the offset - 1 so we get the previous state of the object
         obj_ids.push(ObjectAtOffset {
-            offset: entry.offset - 1,
+            offset: get_offset(entry),
             obj_id: entry.obj_id.clone(),
         });
     }
@@ -26,6 +27,57 @@ async fn get_all_previous_objects(
     Ok(objs_msg.objects)
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub obj_id: String,
+    ///The latest other user to change the object
+    pub user: String,
+}
+
+///Gets who changed each object in entries after the entry was recorded.
+async fn get_all_changed_by(
+    obj_client: &mut ObjClient,
+    file: &str,
+    entries: &Vec<UndoEntry>,
+) -> Result<Vec<ChangedByMsg>, Status> {
+    let mut obj_ids = Vec::new();
+    for entry in entries {
+        obj_ids.push(ObjectAtOffset {
+            offset: entry.offset,
+            obj_id: entry.obj_id.clone(),
+        });
+    }
+    let input = GetChangedByInput {
+        file: String::from(file),
+        obj_ids,
+    };
+    let changed_by_msg = obj_client
+        .get_changed_by(TracedRequest::new(input))
+        .await?
+        .into_inner();
+    Ok(changed_by_msg.objects)
+}
+
+///An entry conflicts if anyone else changed its object after the entry was recorded, even if this user changed it
+/// again since.  Undoing it would wipe out their work.  If only this user changed it, those changes are part of later
+/// events of theirs, which get undone first.
+fn find_conflicts(
+    user: &str,
+    entries: &Vec<UndoEntry>,
+    changed_by: &Vec<ChangedByMsg>,
+) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    for (entry, changed_by) in entries.iter().zip(changed_by.iter()) {
+        if let Some(other) = changed_by.users.iter().rev().find(|other| *other != user) {
+            conflicts.push(Conflict {
+                obj_id: entry.obj_id.clone(),
+                user: other.clone(),
+            });
+        }
+    }
+    conflicts
+}
+
 fn invert_changes_inner(
     user: &str,
     source: change_msg::ChangeSource,
@@ -127,16 +179,41 @@ fn invert_changes_inner(
     inverted
 }
 
+///Inverts the entries of an event, checking each object for changes made by other users since the entry was recorded.
+/// Conflicting entries are handled according to the policy, and returned so the caller can show them.
 pub async fn invert_changes(
     obj_client: &mut ObjClient,
     file: &str,
     user: &str,
     source: change_msg::ChangeSource,
     entries: Vec<UndoEntry>,
-) -> Result<Vec<ChangeMsg>, Status> {
-    let previous = get_all_previous_objects(obj_client, file, &entries).await?;
+    policy: ConflictPolicy,
+) -> Result<(Vec<ChangeMsg>, Vec<Conflict>), Status> {
+    let changed_by = get_all_changed_by(obj_client, file, &entries).await?;
+    let conflicts = find_conflicts(user, &entries, &changed_by);
+    if conflicts.len() > 0 {
+        info!("Got conflicts: {:?}", conflicts);
+    }
+    let entries = match policy {
+        ConflictPolicy::Overwrite => entries,
+        ConflictPolicy::Skip => entries
+            .into_iter()
+            .filter(|entry| !conflicts.iter().any(|conflict| conflict.obj_id == entry.obj_id))
+            .collect(),
+        ConflictPolicy::Abort => {
+            if conflicts.len() > 0 {
+                return Ok((Vec::new(), conflicts));
+            }
+            entries
+        }
+    };
+    //Get the offset - 1 so we get the previous state of the object
+    let previous = get_all_objects_at(obj_client, file, &entries, |entry| entry.offset - 1).await?;
     info!("Got previous: {:?}", previous);
-    Ok(invert_changes_inner(user, source, entries, previous))
+    Ok((
+        invert_changes_inner(user, source, entries, previous),
+        conflicts,
+    ))
 }
 
 #[cfg(test)]
@@ -224,4 +301,49 @@ mod tests {
         assert_eq!(inverted[1], answers[1]);
         assert_eq!(inverted[2], answers[2]);
     }
+
+    fn changed_by_msg(users: &[&str]) -> ChangedByMsg {
+        ChangedByMsg {
+            users: users.iter().map(|user| String::from(*user)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let user_1 = "test user";
+        let user_2 = "other user";
+        let entries = vec![
+            UndoEntry {
+                obj_id: String::from("obj_1"),
+                offset: 1,
+                change_type: UndoChangeType::Modify,
+            },
+            UndoEntry {
+                obj_id: String::from("obj_2"),
+                offset: 2,
+                change_type: UndoChangeType::Modify,
+            },
+            UndoEntry {
+                obj_id: String::from("obj_3"),
+                offset: 3,
+                change_type: UndoChangeType::Modify,
+            },
+        ];
+        let changed_by = vec![
+            //Untouched since
+            changed_by_msg(&[]),
+            //Changed by the same user in a later event
+            changed_by_msg(&[user_1]),
+            //Changed by someone else, then by the same user again
+            changed_by_msg(&[user_2, user_1]),
+        ];
+        let conflicts = find_conflicts(user_1, &entries, &changed_by);
+        assert_eq!(
+            conflicts,
+            vec![Conflict {
+                obj_id: String::from("obj_3"),
+                user: String::from(user_2),
+            }]
+        );
+    }
 }
@@ -37,6 +37,8 @@ pub enum UndoError {
     NoUndoEvent(String, String),
     #[error("Obj {0} not found in undo event {1} in file {2}")]
     NoObjInUndoEvent(String, String, String),
+    #[error("Event {0} not found in the undo stack of user {1} in file {2}")]
+    NoEventInStack(String, String, String),
     #[error("Prost encode error: {0}")]
     ProstEncodeError(#[from] prost::EncodeError),
     #[error("Prost decode error: {0}")]
@@ -55,7 +57,9 @@ impl Into<tonic::Status> for UndoError {
             | UndoError::ProstEncodeError(..)
             | UndoError::BincodeError(..)
             | UndoError::ProstDecodeError(..) => tonic::Code::Internal,
-            UndoError::NoUndoEvent(..) | UndoError::NoObjInUndoEvent(..) => tonic::Code::NotFound,
+            UndoError::NoUndoEvent(..)
+            | UndoError::NoObjInUndoEvent(..)
+            | UndoError::NoEventInStack(..) => tonic::Code::NotFound,
         };
         tonic::Status::new(code, msg)
     }
@@ -99,11 +103,26 @@ impl undo_server::Undo for UndoService {
         propagate_trace(request.metadata());
         let msg = request.get_ref();
         let mut redis_conn = get_redis_conn(&self.redis_url).await?;
-        cache::begin_undo_event(&mut redis_conn, &msg.file, &msg.user)
+        let event = cache::begin_undo_event(&mut redis_conn, &msg.file, &msg.user, &msg.label)
             .instrument(info_span!("cache::begin_undo_event"))
             .await
             .map_err(to_status)?;
-        Ok(Response::new(BeginUndoEventOutput {}))
+        Ok(Response::new(BeginUndoEventOutput { event }))
+    }
+
+    #[instrument]
+    async fn cancel_undo_event(
+        &self,
+        request: Request<CancelUndoEventInput>,
+    ) -> Result<Response<CancelUndoEventOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        cache::cancel_undo_event(&mut redis_conn, &msg.file, &msg.user, &msg.event)
+            .instrument(info_span!("cache::cancel_undo_event"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(CancelUndoEventOutput {}))
     }
 
     #[instrument]
@@ -118,20 +137,36 @@ impl undo_server::Undo for UndoService {
             .instrument(info_span!("objects_client::connect"))
             .await
             .map_err(unavailable)?;
+        let policy = to_policy(msg.policy)?;
         let (event, latest) = cache::undo(&mut redis_conn, &msg.file, &msg.user)
             .instrument(info_span!("cache::undo"))
             .await
             .map_err(to_status)?;
-        let changes = invert::invert_changes(
+        let source = change_msg::ChangeSource::Undo(event);
+        let (mut changes, conflicts) = invert_event(
+            &mut redis_conn,
             &mut obj_client,
             &msg.file,
             &msg.user,
-            change_msg::ChangeSource::Undo(event),
+            source.clone(),
             latest,
+            policy,
         )
-        .instrument(info_span!("invert_changes"))
         .await?;
-        Ok(Response::new(UndoLatestOutput { changes }))
+        finish(
+            &mut redis_conn,
+            &msg.file,
+            &msg.user,
+            &source,
+            policy,
+            &conflicts,
+            &mut changes,
+        )
+        .await?;
+        Ok(Response::new(UndoLatestOutput {
+            changes,
+            conflicts: to_conflict_msgs(conflicts),
+        }))
     }
 
     #[instrument]
@@ -146,20 +181,200 @@ impl undo_server::Undo for UndoService {
             .instrument(info_span!("objects_client::connect"))
             .await
             .map_err(unavailable)?;
+        let policy = to_policy(msg.policy)?;
         let (event, latest) = cache::redo(&mut redis_conn, &msg.file, &msg.user)
             .instrument(info_span!("redo"))
             .await
             .map_err(to_status)?;
-        let changes = invert::invert_changes(
+        let source = change_msg::ChangeSource::Redo(event);
+        let (mut changes, conflicts) = invert_event(
+            &mut redis_conn,
             &mut obj_client,
             &msg.file,
             &msg.user,
-            change_msg::ChangeSource::Redo(event),
+            source.clone(),
             latest,
+            policy,
         )
-        .instrument(info_span!("invert_changes"))
         .await?;
-        Ok(Response::new(RedoLatestOutput { changes }))
+        finish(
+            &mut redis_conn,
+            &msg.file,
+            &msg.user,
+            &source,
+            policy,
+            &conflicts,
+            &mut changes,
+        )
+        .await?;
+        Ok(Response::new(RedoLatestOutput {
+            changes,
+            conflicts: to_conflict_msgs(conflicts),
+        }))
+    }
+
+    #[instrument]
+    async fn undo_to(&self, request: Request<UndoToInput>) -> Result<Response<UndoToOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let mut obj_client = objects_client::ObjectsClient::connect(self.obj_url.clone())
+            .instrument(info_span!("objects_client::connect"))
+            .await
+            .map_err(unavailable)?;
+        let policy = to_policy(msg.policy)?;
+        let events = cache::undo_to(&mut redis_conn, &msg.file, &msg.user, &msg.event)
+            .instrument(info_span!("cache::undo_to"))
+            .await
+            .map_err(to_status)?;
+        let mut changes = Vec::new();
+        let mut conflicts = Vec::new();
+        let mut source = change_msg::ChangeSource::Undo(msg.event.clone());
+        //Latest first, so each object ends up in the state from before the earliest event
+        for (event, entries) in events {
+            source = change_msg::ChangeSource::Undo(event);
+            let (event_changes, event_conflicts) = invert_event(
+                &mut redis_conn,
+                &mut obj_client,
+                &msg.file,
+                &msg.user,
+                source.clone(),
+                entries,
+                policy,
+            )
+            .await?;
+            changes.extend(event_changes);
+            conflicts.extend(event_conflicts);
+        }
+        //Either every event goes through or none of them do
+        finish(
+            &mut redis_conn,
+            &msg.file,
+            &msg.user,
+            &source,
+            policy,
+            &conflicts,
+            &mut changes,
+        )
+        .await?;
+        Ok(Response::new(UndoToOutput {
+            changes,
+            conflicts: to_conflict_msgs(conflicts),
+        }))
+    }
+
+    #[instrument]
+    async fn list_undo_history(
+        &self,
+        request: Request<ListUndoHistoryInput>,
+    ) -> Result<Response<ListUndoHistoryOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        let (undo_events, redo_events) = cache::get_history(&mut redis_conn, &msg.file, &msg.user)
+            .instrument(info_span!("cache::get_history"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(ListUndoHistoryOutput {
+            undo_events: undo_events.into_iter().map(to_undo_event_msg).collect(),
+            redo_events: redo_events.into_iter().map(to_undo_event_msg).collect(),
+        }))
+    }
+
+    #[instrument]
+    async fn delete_file(
+        &self,
+        request: Request<undo::DeleteFileInput>,
+    ) -> Result<Response<undo::DeleteFileOutput>, Status> {
+        propagate_trace(request.metadata());
+        let msg = request.get_ref();
+        let mut redis_conn = get_redis_conn(&self.redis_url).await?;
+        cache::delete_file(&mut redis_conn, &msg.file)
+            .instrument(info_span!("cache::delete_file"))
+            .await
+            .map_err(to_status)?;
+        Ok(Response::new(undo::DeleteFileOutput {}))
+    }
+}
+
+fn to_policy(policy: i32) -> Result<ConflictPolicy, Status> {
+    ConflictPolicy::from_i32(policy)
+        .ok_or_else(|| Status::invalid_argument(format!("Unknown conflict policy {}", policy)))
+}
+
+fn to_conflict_msgs(conflicts: Vec<invert::Conflict>) -> Vec<ConflictMsg> {
+    conflicts
+        .into_iter()
+        .map(|conflict| ConflictMsg {
+            obj_id: conflict.obj_id,
+            user: conflict.user,
+        })
+        .collect()
+}
+
+///Inverts the entries of an event.  Skipped entries are dropped from the event, since they'll never be inverted.
+async fn invert_event(
+    redis_conn: &mut redis::aio::MultiplexedConnection,
+    obj_client: &mut ObjClient,
+    file: &str,
+    user: &str,
+    source: change_msg::ChangeSource,
+    entries: Vec<UndoEntry>,
+    policy: ConflictPolicy,
+) -> Result<(Vec<ChangeMsg>, Vec<invert::Conflict>), Status> {
+    let (changes, conflicts) =
+        invert::invert_changes(obj_client, file, user, source.clone(), entries, policy)
+            .instrument(info_span!("invert_changes"))
+            .await?;
+    if let ConflictPolicy::Skip = policy {
+        if conflicts.len() > 0 {
+            let obj_ids = conflicts.iter().map(|conflict| conflict.obj_id.clone()).collect();
+            cache::drop_entries(redis_conn, file, user, &source, obj_ids)
+                .instrument(info_span!("cache::drop_entries"))
+                .await
+                .map_err(to_status)?;
+        }
+    }
+    Ok((changes, conflicts))
+}
+
+///Clears the changes if the undo or redo shouldn't go through.  If that leaves nothing to submit, including when every
+/// entry was skipped, the event begun on the other stack is taken back since nothing will ever be recorded in it.
+async fn finish(
+    redis_conn: &mut redis::aio::MultiplexedConnection,
+    file: &str,
+    user: &str,
+    source: &change_msg::ChangeSource,
+    policy: ConflictPolicy,
+    conflicts: &Vec<invert::Conflict>,
+    changes: &mut Vec<ChangeMsg>,
+) -> Result<(), Status> {
+    if let ConflictPolicy::Abort = policy {
+        if conflicts.len() > 0 {
+            changes.clear();
+        }
+    }
+    if changes.is_empty() {
+        cache::cancel(redis_conn, file, user, source)
+            .instrument(info_span!("cache::cancel"))
+            .await
+            .map_err(to_status)?;
+    }
+    Ok(())
+}
+
+fn to_undo_event_msg(info: EventInfo) -> UndoEventMsg {
+    let mut obj_ids = Vec::new();
+    for entry in info.entries {
+        if !obj_ids.contains(&entry.obj_id) {
+            obj_ids.push(entry.obj_id);
+        }
+    }
+    UndoEventMsg {
+        event: info.event,
+        label: info.label,
+        timestamp: info.timestamp,
+        obj_ids,
     }
 }
 
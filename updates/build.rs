@@ -0,0 +1,16 @@
+fn main() {
+    tonic_build::configure()
+        .build_client(true)
+        .build_server(true)
+        .compile(
+            &[
+                "../proto/rep_cache.proto",
+                "../proto/geom.proto",
+                "../proto/representation.proto",
+                "../proto/updates.proto",
+                "../proto/acl.proto",
+            ],
+            &["../proto"],
+        )
+        .unwrap_or_else(|e| panic!("Failed to compile protos {:?}", e));
+}
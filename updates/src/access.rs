@@ -0,0 +1,91 @@
+//! Authenticates websocket connections during the handshake, and checks a user can read a file
+//! before subscribing them to it.
+
+use super::*;
+use acl::Role;
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+
+/// Browsers can't set headers on a websocket, so the token can also be given as a `token` query
+/// parameter.
+fn token(request: &Request) -> Option<String> {
+    if let Some(header) = request.headers().get("authorization") {
+        return header.to_str().ok().map(String::from);
+    }
+    request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .filter_map(|pair| {
+                let mut parts = pair.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some("token"), Some(token)) => Some(String::from(token)),
+                    _ => None,
+                }
+            })
+            .next()
+    })
+}
+
+/// Accepts the websocket if its token verifies, and returns the user it was issued to.  Returns
+/// None if authentication is off.
+pub async fn accept(
+    stream: tokio::net::TcpStream,
+    verifier: &Option<auth_lib::Verifier>,
+) -> Result<(
+    tokio_tungstenite::WebSocketStream<tokio::net::TcpStream>,
+    Option<String>,
+)> {
+    let mut user = None;
+    // The error type is tungstenite's.
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request, response: Response| {
+        let verifier = match verifier {
+            Some(verifier) => verifier,
+            None => return Ok(response),
+        };
+        let verified = token(request)
+            .ok_or(auth_lib::AuthError::MissingToken)
+            .and_then(|token| verifier.verify(&token));
+        match verified {
+            Ok(verified) => {
+                user = Some(verified);
+                Ok(response)
+            }
+            Err(e) => {
+                warn!("Rejecting websocket: {}", e);
+                let mut error = ErrorResponse::new(Some(format!("{}", e)));
+                *error.status_mut() = tungstenite::http::StatusCode::UNAUTHORIZED;
+                Err(error)
+            }
+        }
+    };
+    let ws_stream = tokio_tungstenite::accept_hdr_async(stream, callback).await?;
+    Ok((ws_stream, user))
+}
+
+/// Fails unless the user can view the file.  Nobody can view a file nobody has claimed, unless
+/// open_unclaimed is set while existing files are being claimed.
+pub async fn check_viewer(
+    acl_url: &str,
+    open_unclaimed: bool,
+    file: &str,
+    user: &str,
+) -> Result<()> {
+    let mut client = acl::acl_client::AclClient::connect(String::from(acl_url)).await?;
+    let output = client
+        .get_role(tonic::Request::new(acl::GetRoleInput {
+            file: String::from(file),
+            user: String::from(user),
+        }))
+        .await?
+        .into_inner();
+    if !output.claimed {
+        if open_unclaimed {
+            return Ok(());
+        }
+        return Err(anyhow::anyhow!("{} hasn't been claimed by anybody", file));
+    }
+    if output.role < Role::Viewer as i32 {
+        return Err(anyhow::anyhow!("{} can't view {}", user, file));
+    }
+    Ok(())
+}
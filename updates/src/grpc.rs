@@ -0,0 +1,179 @@
+//! Serves the same subscriptions as the websocket over a server-streaming RPC on GRPC_URL, so
+//! clients get typed messages instead of speaking the JSON protocol.  The api forwards it as
+//! SubscribeFile.  Presence isn't sent on this side.
+
+use super::*;
+use representation::UpdateChangeMsg;
+use tonic::{Request, Response, Status};
+use updates::updates_server::Updates;
+use updates::SubscribeFileInput;
+
+pub struct UpdatesService {
+    pub config: Config,
+}
+
+fn to_rep(msg: UpdateMessage) -> Result<UpdateChangeMsg, Status> {
+    msg.to_rep().map_err(|e| {
+        Status::internal(format!(
+            "Invalid representation at offset {}: {:?}",
+            msg.offset, e
+        ))
+    })
+}
+
+#[tonic::async_trait]
+impl Updates for UpdatesService {
+    type SubscribeFileStream = mpsc::Receiver<Result<UpdateChangeMsg, Status>>;
+
+    async fn subscribe_file(
+        &self,
+        request: Request<SubscribeFileInput>,
+    ) -> Result<Response<Self::SubscribeFileStream>, Status> {
+        let msg = request.into_inner();
+        info!("New subscribe RPC for {:?}", msg.file);
+        let connection = NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed);
+        let mut subscriptions = Subscriptions::new(connection);
+        let queue = Arc::new(Mutex::new(SendQueue::new(
+            self.config.queue_policy,
+            self.config.queue_limit,
+        )));
+        let (doorbell, mut doorbell_rcv) = mpsc::channel(1);
+        // Subscribe before replaying so nothing published in between is missed.
+        subscriptions.subscribe(
+            msg.file.clone(),
+            ChannelSend {
+                queue: queue.clone(),
+                doorbell,
+                user: msg.user,
+                connection,
+                presence: Presence::default(),
+            },
+        );
+        let replayed = if msg.replay {
+            let (replayed, resync) = kafka::replay(
+                &self.config.brokers,
+                &self.config.topic,
+                &msg.file,
+                msg.since_offset,
+            )
+            .await
+            .map_err(|e| Status::unavailable(format!("{}", e)))?;
+            if let Some(Resync::Truncated { offset, .. }) = resync {
+                return Err(Status::data_loss(format!(
+                    "Updates before offset {} are no longer kept, load the file again",
+                    offset
+                )));
+            }
+            replayed
+        } else {
+            Vec::new()
+        };
+        let (mut tx, rx) = mpsc::channel(100);
+        tokio::spawn(async move {
+            // Dropping the subscriptions once the client goes away takes its channel back out of
+            // FILE_TO_CHANNEL_MAP.
+            let _subscriptions = subscriptions;
+            let mut last_sent = None;
+            for msg in replayed {
+                last_sent = Some(msg.offset);
+                if tx.send(to_rep(msg)).await.is_err() {
+                    return;
+                }
+                metrics::MESSAGES_SENT.fetch_add(1, Ordering::Relaxed);
+            }
+            while doorbell_rcv.recv().await.is_some() {
+                queue.lock().unwrap().drain_presence();
+                let (pending, gaps) = queue.lock().unwrap().drain();
+                for msg in pending {
+                    match last_sent {
+                        Some(offset) if msg.offset <= offset => {
+                            debug!("Message at offset {} already replayed", msg.offset);
+                        }
+                        _ => {
+                            last_sent = Some(msg.offset);
+                            if tx.send(to_rep(msg)).await.is_err() {
+                                debug!("Subscriber went away, closing");
+                                return;
+                            }
+                            metrics::MESSAGES_SENT.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+                // There's no message for a gap in the stream, so it's ended with the last dropped
+                // offset for the client to resubscribe from.
+                if let Some(Resync::Gap { offset, .. }) = gaps.into_iter().next() {
+                    let _ = tx
+                        .send(Err(Status::data_loss(format!(
+                            "Updates up to offset {} were dropped, resubscribe to replay them",
+                            offset
+                        ))))
+                        .await;
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(rx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pushes a change to every subscriber of the file, as the Kafka consumer does.
+    fn publish(file: &str, offset: i64) {
+        let rep = UpdateChangeMsg {
+            file: String::from(file),
+            obj_id: format!("obj {}", offset),
+            ..Default::default()
+        };
+        let bytes = encode(&rep).unwrap();
+        if let Some(mut entry) = FILE_TO_CHANNEL_MAP.get_mut(file) {
+            for send in entry.value_mut() {
+                send.push(UpdateMessage::new(file, offset, &bytes));
+            }
+        }
+    }
+
+    #[tokio_macros::test]
+    async fn test_subscribe_file_gap() {
+        let file = uuid::Uuid::new_v4().to_string();
+        let service = UpdatesService {
+            config: Config {
+                brokers: String::new(),
+                topic: String::new(),
+                rep_cache_url: String::new(),
+                acl_url: String::new(),
+                open_unclaimed: false,
+                queue_policy: QueuePolicy::Drop,
+                queue_limit: 2,
+                verifier: None,
+            },
+        };
+        let mut stream = service
+            .subscribe_file(Request::new(SubscribeFileInput {
+                file: file.clone(),
+                user: String::from("a"),
+                replay: false,
+                since_offset: 0,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        // The stream's task doesn't get to drain the queue until this one waits, so only the
+        // first two fit
+        for offset in 1..5 {
+            publish(&file, offset);
+        }
+        for offset in 1..3 {
+            let rep = stream.recv().await.unwrap().unwrap();
+            assert_eq!(rep.offset, offset);
+            assert_eq!(rep.obj_id, format!("obj {}", offset));
+        }
+        let status = stream.recv().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::DataLoss);
+        assert!(status.message().contains("offset 4"));
+        assert!(stream.recv().await.is_none());
+        assert!(FILE_TO_CHANNEL_MAP.get(&file).is_none());
+    }
+}
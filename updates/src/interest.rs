@@ -0,0 +1,378 @@
+//! Tracks the region of the model a client is looking at, so only changes inside it get pushed.
+//! Objects that move into or out of the region are announced with enter and leave events.
+
+use super::*;
+use representation::update_output_msg::Output;
+use representation::{InterestChange, UpdateChangeMsg, UpdateOutputMsg};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// At level 0, objects smaller than this fraction of the region's size are left out.  Each level
+/// above that halves the cutoff.
+const DETAIL_FRACTION: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Point3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct BoundingBox {
+    pub bottom_left: Point3,
+    pub top_right: Point3,
+}
+
+impl BoundingBox {
+    fn intersects(&self, other: &BoundingBox) -> bool {
+        self.bottom_left.x <= other.top_right.x
+            && other.bottom_left.x <= self.top_right.x
+            && self.bottom_left.y <= other.top_right.y
+            && other.bottom_left.y <= self.top_right.y
+            && self.bottom_left.z <= other.top_right.z
+            && other.bottom_left.z <= self.top_right.z
+    }
+
+    /// The largest extent of the box along any axis.
+    fn size(&self) -> f64 {
+        (self.top_right.x - self.bottom_left.x)
+            .max(self.top_right.y - self.bottom_left.y)
+            .max(self.top_right.z - self.bottom_left.z)
+    }
+
+    /// Returns the bounds of a mesh or instance representation, or None if it has no geometry.
+    fn of_update(update: &UpdateOutputMsg) -> Option<BoundingBox> {
+        match &update.output {
+            Some(Output::Instance(instance)) => {
+                match (&instance.bottom_left, &instance.top_right) {
+                    (Some(bl), Some(tr)) => Some(BoundingBox {
+                        bottom_left: Point3 {
+                            x: bl.x,
+                            y: bl.y,
+                            z: bl.z,
+                        },
+                        top_right: Point3 {
+                            x: tr.x,
+                            y: tr.y,
+                            z: tr.z,
+                        },
+                    }),
+                    _ => None,
+                }
+            }
+            Some(Output::Mesh(mesh)) => {
+                let mut points = mesh.positions.chunks_exact(3).map(|pt| Point3 {
+                    x: pt[0],
+                    y: pt[1],
+                    z: pt[2],
+                });
+                let first = points.next()?;
+                Some(points.fold(
+                    BoundingBox {
+                        bottom_left: first,
+                        top_right: first,
+                    },
+                    |bbox, pt| BoundingBox {
+                        bottom_left: Point3 {
+                            x: bbox.bottom_left.x.min(pt.x),
+                            y: bbox.bottom_left.y.min(pt.y),
+                            z: bbox.bottom_left.z.min(pt.z),
+                        },
+                        top_right: Point3 {
+                            x: bbox.top_right.x.max(pt.x),
+                            y: bbox.top_right.y.max(pt.y),
+                            z: bbox.top_right.z.max(pt.z),
+                        },
+                    },
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Interest {
+    pub bbox: BoundingBox,
+    /// Level of detail.  None sends every object in the region regardless of its size.
+    pub level: Option<u32>,
+}
+
+impl Interest {
+    /// Objects without any geometry are always of interest.
+    fn contains(&self, update: Option<&UpdateOutputMsg>) -> bool {
+        match update.and_then(BoundingBox::of_update) {
+            Some(bounds) => {
+                self.bbox.intersects(&bounds)
+                    && match self.level {
+                        Some(level) => {
+                            bounds.size()
+                                >= self.bbox.size() * DETAIL_FRACTION / 2f64.powi(level as i32)
+                        }
+                        None => true,
+                    }
+            }
+            None => true,
+        }
+    }
+}
+
+/// What to push to a client for a change in a file it has a region of interest set for.
+pub enum Delivery {
+    Send,
+    Leave,
+    Skip,
+}
+
+/// The region of interest for one file on one connection, along with the objects in it that the
+/// client currently has.
+pub struct FileInterest {
+    interest: Interest,
+    visible: HashSet<String>,
+}
+
+impl FileInterest {
+    /// Moves the region to `interest`, and returns the enter and leave events for every object in
+    /// `reps` whose visibility changed.  If `previous` is None, the client is assumed to have
+    /// every object in the file.
+    pub fn set(
+        previous: Option<&FileInterest>,
+        interest: Interest,
+        reps: Vec<UpdateChangeMsg>,
+    ) -> (FileInterest, Vec<UpdateChangeMsg>) {
+        let mut visible = HashSet::new();
+        let mut events = Vec::new();
+        for mut rep in reps {
+            let was_visible = match previous {
+                Some(prev) => prev.visible.contains(&rep.obj_id),
+                None => true,
+            };
+            let is_visible = interest.contains(rep.update.as_ref());
+            if is_visible {
+                visible.insert(rep.obj_id.clone());
+                if !was_visible {
+                    rep.interest = InterestChange::Enter as i32;
+                    events.push(rep);
+                }
+            } else if was_visible {
+                events.push(leave(rep.file, rep.obj_id, 0));
+            }
+        }
+        (FileInterest { interest, visible }, events)
+    }
+
+    pub fn deliver(&mut self, rep: &UpdateChangeMsg) -> Delivery {
+        let deleted = matches!(
+            rep.update
+                .as_ref()
+                .and_then(|update| update.output.as_ref()),
+            Some(Output::Delete(..))
+        );
+        if deleted {
+            if self.visible.remove(&rep.obj_id) {
+                Delivery::Send
+            } else {
+                Delivery::Skip
+            }
+        } else if self.interest.contains(rep.update.as_ref()) {
+            self.visible.insert(rep.obj_id.clone());
+            Delivery::Send
+        } else if self.visible.remove(&rep.obj_id) {
+            Delivery::Leave
+        } else {
+            Delivery::Skip
+        }
+    }
+}
+
+pub fn leave(file: String, obj_id: String, offset: i64) -> UpdateChangeMsg {
+    UpdateChangeMsg {
+        file,
+        user: String::new(),
+        obj_id,
+        update: None,
+        offset,
+        interest: InterestChange::Leave as i32,
+    }
+}
+
+pub fn encode(msg: &UpdateChangeMsg) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(msg.encoded_len());
+    msg.encode(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub async fn get_file_reps(rep_cache_url: &str, file: &str) -> Result<Vec<UpdateChangeMsg>> {
+    let mut client =
+        rep_cache::rep_cache_client::RepCacheClient::connect(String::from(rep_cache_url)).await?;
+    let resp = client
+        .get_file_representations(tonic::Request::new(
+            rep_cache::GetFileRepresentationsInput {
+                file: String::from(file),
+            },
+        ))
+        .await?;
+    Ok(resp.into_inner().reps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use representation::{InstanceDataMsg, MeshDataMsg};
+
+    fn point(at: f64) -> Point3 {
+        Point3 {
+            x: at,
+            y: at,
+            z: at,
+        }
+    }
+
+    fn interest(from: f64, to: f64, level: Option<u32>) -> Interest {
+        Interest {
+            bbox: BoundingBox {
+                bottom_left: point(from),
+                top_right: point(to),
+            },
+            level,
+        }
+    }
+
+    fn rep(obj_id: &str, output: Output) -> UpdateChangeMsg {
+        UpdateChangeMsg {
+            file: String::from("file"),
+            obj_id: String::from(obj_id),
+            update: Some(UpdateOutputMsg {
+                output: Some(output),
+                views: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// A mesh going from `from` to `to` along every axis.
+    fn mesh(obj_id: &str, from: f64, to: f64) -> UpdateChangeMsg {
+        rep(
+            obj_id,
+            Output::Mesh(MeshDataMsg {
+                positions: vec![from, from, from, to, to, to],
+                indices: Vec::new(),
+                meta_json: String::new(),
+            }),
+        )
+    }
+
+    fn instance(obj_id: &str, from: f64, to: f64) -> UpdateChangeMsg {
+        let corner = |at| geom::Point3Msg {
+            x: at,
+            y: at,
+            z: at,
+        };
+        rep(
+            obj_id,
+            Output::Instance(InstanceDataMsg {
+                bottom_left: Some(corner(from)),
+                top_right: Some(corner(to)),
+                ..Default::default()
+            }),
+        )
+    }
+
+    fn events(events: &[UpdateChangeMsg]) -> Vec<(&str, i32)> {
+        events
+            .iter()
+            .map(|event| (event.obj_id.as_str(), event.interest))
+            .collect()
+    }
+
+    fn is(delivery: Delivery, expected: Delivery) -> bool {
+        std::mem::discriminant(&delivery) == std::mem::discriminant(&expected)
+    }
+
+    #[test]
+    fn test_bbox() {
+        let reps = vec![
+            mesh("inside", 1.0, 2.0),
+            mesh("outside", 20.0, 21.0),
+            instance("overlapping", 5.0, 15.0),
+            rep("no geometry", Output::Empty(String::new())),
+        ];
+        // The client starts with everything, so only what's outside leaves
+        let (mut file_interest, set) = FileInterest::set(None, interest(0.0, 10.0, None), reps);
+        assert_eq!(
+            events(&set),
+            vec![("outside", InterestChange::Leave as i32)]
+        );
+        assert!(set[0].update.is_none());
+
+        assert!(is(
+            file_interest.deliver(&mesh("inside", 2.0, 3.0)),
+            Delivery::Send
+        ));
+        assert!(is(
+            file_interest.deliver(&mesh("outside", 30.0, 31.0)),
+            Delivery::Skip
+        ));
+        assert!(is(
+            file_interest.deliver(&rep("no geometry", Output::OtherJson(String::new()))),
+            Delivery::Send
+        ));
+        // Moving out leaves, and the client doesn't hear about it again until it comes back
+        assert!(is(
+            file_interest.deliver(&mesh("inside", 12.0, 13.0)),
+            Delivery::Leave
+        ));
+        assert!(is(
+            file_interest.deliver(&rep("inside", Output::Delete(String::new()))),
+            Delivery::Skip
+        ));
+        assert!(is(
+            file_interest.deliver(&rep("overlapping", Output::Delete(String::new()))),
+            Delivery::Send
+        ));
+        assert!(is(
+            file_interest.deliver(&mesh("outside", 9.0, 11.0)),
+            Delivery::Send
+        ));
+        assert!(is(
+            file_interest.deliver(&mesh("outside", 30.0, 31.0)),
+            Delivery::Leave
+        ));
+    }
+
+    #[test]
+    fn test_level_of_detail() {
+        // In a region 100 across, level 0 leaves out anything under 1, and level 2 anything
+        // under 0.25
+        let reps = || vec![mesh("small", 1.0, 1.5), mesh("large", 1.0, 3.0)];
+        let (coarse, set) = FileInterest::set(None, interest(0.0, 100.0, Some(0)), reps());
+        assert_eq!(events(&set), vec![("small", InterestChange::Leave as i32)]);
+
+        let (mut fine, set) =
+            FileInterest::set(Some(&coarse), interest(0.0, 100.0, Some(2)), reps());
+        assert_eq!(events(&set), vec![("small", InterestChange::Enter as i32)]);
+        assert!(set[0].update.is_some());
+        assert!(is(fine.deliver(&mesh("small", 1.0, 1.2)), Delivery::Leave));
+
+        let (_, set) = FileInterest::set(Some(&fine), interest(0.0, 100.0, None), reps());
+        assert_eq!(events(&set), vec![("small", InterestChange::Enter as i32)]);
+    }
+
+    #[test]
+    fn test_move_region() {
+        let reps = || vec![mesh("a", 1.0, 2.0), mesh("b", 21.0, 22.0)];
+        let (first, _) = FileInterest::set(None, interest(0.0, 10.0, None), reps());
+        let (second, set) = FileInterest::set(Some(&first), interest(20.0, 30.0, None), reps());
+        assert_eq!(
+            events(&set),
+            vec![
+                ("a", InterestChange::Leave as i32),
+                ("b", InterestChange::Enter as i32)
+            ]
+        );
+        // Nothing changes when the region stays put
+        let (_, set) = FileInterest::set(Some(&second), interest(20.0, 30.0, None), reps());
+        assert!(set.is_empty());
+    }
+}
@@ -1,15 +1,18 @@
 use super::*;
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
 use rdkafka::config::{ClientConfig, RDKafkaLogLevel};
 use rdkafka::consumer::stream_consumer::StreamConsumer;
 use rdkafka::consumer::{CommitMode, Consumer};
+use rdkafka::error::KafkaError;
 use rdkafka::message::Message;
+use rdkafka::topic_partition_list::{Offset, TopicPartitionList};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum UpdateError {
     #[error("Kafka error: {0}")]
-    KafkaError(#[from] rdkafka::error::KafkaError),
+    KafkaError(#[from] KafkaError),
     #[error("String error: {0}")]
     StringError(#[from] std::str::Utf8Error),
     #[error("Message from partition {partition} and offset {offset} has no payload")]
@@ -18,7 +21,7 @@ pub enum UpdateError {
     FileError { partition: i32, offset: i64 },
 }
 
-async fn handle_message<M: Message>(m: &M) -> Result<(), UpdateError> {
+fn handle_message<M: Message>(m: &M) -> Result<(), UpdateError> {
     let partition = m.partition();
     let offset = m.offset();
     let bytes = m
@@ -30,25 +33,9 @@ async fn handle_message<M: Message>(m: &M) -> Result<(), UpdateError> {
     let file = std::str::from_utf8(file_bytes)?;
     info!("Got msg from file: {:?}", file);
     if let Some(mut entry) = FILE_TO_CHANNEL_MAP.get_mut(file) {
-        let mut to_delete = Vec::new();
-        let mut index = 0usize;
+        let msg = UpdateMessage::new(file, offset, bytes);
         for sender in entry.value_mut() {
-            if let Err(e) = sender
-                .sender
-                .send(UpdateMessage {
-                    file: String::from(file),
-                    msg: bytes.to_vec(),
-                })
-                .await
-            {
-                error!("Channel error: {:?}", e);
-                to_delete.push(index);
-            }
-            index += 1;
-        }
-        for index in to_delete.into_iter().rev() {
-            info!("Deleting channel");
-            entry.value_mut().remove(index);
+            sender.push(msg.clone());
         }
     }
     Ok(())
@@ -73,7 +60,7 @@ async fn handle_stream(brokers: &str, group_id: &str, topic: &str) -> Result<(),
     while let Some(message) = message_stream.next().await {
         match message {
             Ok(m) => {
-                if let Err(e) = handle_message(&m).await {
+                if let Err(e) = handle_message(&m) {
                     error!("{}", e);
                 }
                 if let Err(e) = consumer.commit_message(&m, CommitMode::Async) {
@@ -88,9 +75,247 @@ async fn handle_stream(brokers: &str, group_id: &str, topic: &str) -> Result<(),
     Ok(())
 }
 
+/// How long a replay waits for the next message before taking it that it's read everything.
+/// Transaction markers take up offsets without ever being delivered, so the end of a partition
+/// can't be spotted from the offsets alone.
+const REPLAY_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The partition messages for `file` are published to, from the consistent_random partitioner
+/// the representations producer is configured with.
+fn file_partition(file: &str, partitions: i32) -> Option<i32> {
+    // Without a key the partitioner picks a random available partition, which needs the topic,
+    // but files always have an ID.
+    if file.is_empty() || partitions <= 0 {
+        return None;
+    }
+    let partition = unsafe {
+        rdkafka_sys::rd_kafka_msg_partitioner_consistent_random(
+            std::ptr::null(),
+            file.as_ptr() as *const std::os::raw::c_void,
+            file.len(),
+            partitions,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    Some(partition)
+}
+
+/// Reads the messages for `file` from a stream over one partition until the partition's end, or
+/// until nothing has come for `idle`.
+async fn read_partition<S, M>(
+    mut stream: S,
+    partition: i32,
+    file: &str,
+    idle: Duration,
+) -> Result<Vec<UpdateMessage>, UpdateError>
+where
+    S: Stream<Item = Result<M, KafkaError>> + Unpin,
+    M: Message,
+{
+    let mut replayed = Vec::new();
+    loop {
+        let m = match tokio::time::timeout(idle, stream.next()).await {
+            Ok(Some(Ok(m))) => m,
+            Ok(Some(Err(KafkaError::PartitionEOF(eof)))) if eof == partition => break,
+            Ok(Some(Err(e))) => return Err(e.into()),
+            Ok(None) => break,
+            Err(_) => {
+                warn!(
+                    "Nothing read for {:?} in {:?}, ending the replay",
+                    file, idle
+                );
+                break;
+            }
+        };
+        if m.key() == Some(file.as_bytes()) {
+            let offset = m.offset();
+            let bytes = m
+                .payload()
+                .ok_or(UpdateError::PayloadError { partition, offset })?;
+            replayed.push(UpdateMessage::new(file, offset, bytes));
+        }
+    }
+    Ok(replayed)
+}
+
+/// Where a replay from after `since_offset` starts in a partition holding offsets from `low` up
+/// to `high`, or None if there's nothing after it.  If the messages right after `since_offset`
+/// were already deleted, the replay starts from the oldest one left and the client is told to
+/// load the file again.
+fn replay_start(
+    file: &str,
+    since_offset: i64,
+    low: i64,
+    high: i64,
+) -> (Option<i64>, Option<Resync>) {
+    let resync = if since_offset + 1 < low {
+        Some(Resync::Truncated {
+            filename: String::from(file),
+            offset: low,
+        })
+    } else {
+        None
+    };
+    let start = std::cmp::max(since_offset + 1, low);
+    if start >= high {
+        (None, resync)
+    } else {
+        (Some(start), resync)
+    }
+}
+
+/// Assigns the partition of `topic` that holds `file`, from after `since_offset`, to the
+/// consumer.  Returns the partition, or None if there's nothing after `since_offset` in it, along
+/// with a resync if some of what's after it is no longer kept.
+fn assign_from<C: Consumer>(
+    consumer: &C,
+    topic: &str,
+    file: &str,
+    since_offset: i64,
+) -> Result<(Option<i32>, Option<Resync>), UpdateError> {
+    let timeout = Duration::from_secs(10);
+    let metadata = consumer.fetch_metadata(Some(topic), timeout)?;
+    let partitions = metadata
+        .topics()
+        .iter()
+        .find(|t| t.name() == topic)
+        .map(|t| t.partitions().len())
+        .unwrap_or(0);
+    let partition = match file_partition(file, partitions as i32) {
+        Some(partition) => partition,
+        None => return Ok((None, None)),
+    };
+    let (low, high) = consumer.fetch_watermarks(topic, partition, timeout)?;
+    let (start, resync) = replay_start(file, since_offset, low, high);
+    let start = match start {
+        Some(start) => start,
+        None => return Ok((None, resync)),
+    };
+    let mut assignment = TopicPartitionList::new();
+    assignment.add_partition_offset(topic, partition, Offset::Offset(start));
+    consumer.assign(&assignment)?;
+    Ok((Some(partition), resync))
+}
+
+/// Re-reads the file's partition of the topic from after `since_offset` up to its current end
+/// and returns the messages published for `file`, oldest first, along with a resync if some of
+/// them are no longer kept.
+pub async fn replay(
+    brokers: &str,
+    topic: &str,
+    file: &str,
+    since_offset: i64,
+) -> Result<(Vec<UpdateMessage>, Option<Resync>), UpdateError> {
+    let consumer: StreamConsumer<rdkafka::consumer::DefaultConsumerContext> = ClientConfig::new()
+        .set("group.id", "updates-replay")
+        .set("bootstrap.servers", brokers)
+        .set("enable.partition.eof", "true")
+        .set("enable.auto.commit", "false")
+        .set_log_level(RDKafkaLogLevel::Debug)
+        .create()?;
+    let (partition, resync) = assign_from(&consumer, topic, file, since_offset)?;
+    let replayed = match partition {
+        Some(partition) => {
+            read_partition(consumer.start(), partition, file, REPLAY_IDLE_TIMEOUT).await?
+        }
+        None => Vec::new(),
+    };
+    Ok((replayed, resync))
+}
+
 pub async fn consume(brokers: String, group_id: String, topic: String) {
     std::thread::sleep(std::time::Duration::from_secs(30));
     if let Err(e) = handle_stream(&brokers, &group_id, &topic).await {
         error!("{}", e);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rdkafka::message::{OwnedMessage, Timestamp};
+
+    fn message(file: &str, partition: i32, offset: i64) -> Result<OwnedMessage, KafkaError> {
+        let rep = representation::UpdateChangeMsg {
+            file: String::from(file),
+            obj_id: format!("obj {}", offset),
+            ..Default::default()
+        };
+        let mut payload = Vec::new();
+        rep.encode(&mut payload).unwrap();
+        Ok(OwnedMessage::new(
+            Some(payload),
+            Some(file.as_bytes().to_vec()),
+            String::from("representations"),
+            Timestamp::NotAvailable,
+            partition,
+            offset,
+            None,
+        ))
+    }
+
+    #[test]
+    fn test_file_partition() {
+        // librdkafka's CRC32 of the key, which is the standard one
+        assert_eq!(file_partition("123456789", 8), Some(6));
+        assert_eq!(file_partition("123456789", 3), Some(2));
+        assert_eq!(file_partition("", 8), None);
+        assert_eq!(file_partition("file", 0), None);
+        for index in 0..100 {
+            let file = format!("file {}", index);
+            let partition = file_partition(&file, 5).unwrap();
+            assert!((0..5).contains(&partition));
+            assert_eq!(file_partition(&file, 5), Some(partition));
+        }
+    }
+
+    #[test]
+    fn test_replay_start() {
+        assert_eq!(replay_start("a", 4, 0, 10), (Some(5), None));
+        // The client is already up to date
+        assert_eq!(replay_start("a", 9, 0, 10), (None, None));
+        // Offsets 3 and 4 were deleted, so the client has missed them
+        let truncated = Some(Resync::Truncated {
+            filename: String::from("a"),
+            offset: 5,
+        });
+        assert_eq!(replay_start("a", 2, 5, 10), (Some(5), truncated.clone()));
+        assert_eq!(replay_start("a", 2, 5, 5), (None, truncated));
+        // Replaying from just before the oldest message misses nothing
+        assert_eq!(replay_start("a", 4, 5, 10), (Some(5), None));
+    }
+
+    #[tokio_macros::test]
+    async fn test_read_partition() {
+        // Offset 6 is a transaction marker, which never arrives, and other files share the
+        // partition
+        let stream = futures::stream::iter(vec![
+            message("a", 2, 4),
+            message("b", 2, 5),
+            message("a", 2, 7),
+            Err(KafkaError::PartitionEOF(2)),
+            message("a", 2, 8),
+        ]);
+        let replayed = read_partition(stream, 2, "a", Duration::from_secs(10))
+            .await
+            .unwrap();
+        let offsets: Vec<i64> = replayed.iter().map(|msg| msg.offset).collect();
+        assert_eq!(offsets, vec![4, 7]);
+        assert_eq!(replayed[1].obj_id, "obj 7");
+
+        // Without an end of partition, the replay ends once nothing more comes
+        let stream =
+            futures::stream::iter(vec![message("a", 2, 4)]).chain(futures::stream::pending());
+        let replayed = read_partition(stream, 2, "a", Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(replayed.len(), 1);
+
+        let stream =
+            futures::stream::iter(vec![message("a", 2, 4), Err(KafkaError::NoMessageReceived)]);
+        assert!(read_partition(stream, 2, "a", Duration::from_secs(10))
+            .await
+            .is_err());
+    }
+}
@@ -4,46 +4,311 @@ use futures_util::future::{select, Either};
 use futures_util::{SinkExt, StreamExt};
 use lazy_static::lazy_static;
 use log::*;
+use prost::Message as ProstMessage;
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use tokio::sync::{mpsc, mpsc::Sender};
 use tungstenite::Message;
 
+mod access;
+mod grpc;
+mod interest;
 mod kafka;
+mod metrics;
+mod presence;
+mod queue;
+use interest::*;
+use presence::*;
+use queue::*;
+
+mod geom {
+    tonic::include_proto!("geom");
+}
+
+mod representation {
+    tonic::include_proto!("representation");
+}
+
+mod rep_cache {
+    tonic::include_proto!("rep_cache");
+}
+
+mod updates {
+    tonic::include_proto!("updates");
+}
+
+mod acl {
+    tonic::include_proto!("acl");
+}
 
 #[derive(Debug, Clone)]
 pub struct UpdateMessage {
     file: String,
+    obj_id: String,
+    offset: i64,
     msg: Vec<u8>,
 }
 
+impl UpdateMessage {
+    fn new(file: &str, offset: i64, msg: &[u8]) -> UpdateMessage {
+        let obj_id = match representation::UpdateChangeMsg::decode(msg) {
+            Ok(rep) => rep.obj_id,
+            Err(e) => {
+                error!("Invalid representation at offset {}: {:?}", offset, e);
+                String::new()
+            }
+        };
+        UpdateMessage {
+            file: String::from(file),
+            obj_id,
+            offset,
+            msg: msg.to_vec(),
+        }
+    }
+
+    /// Decodes the representation with its offset in the topic filled in.
+    fn to_rep(&self) -> Result<representation::UpdateChangeMsg, prost::DecodeError> {
+        let mut rep = representation::UpdateChangeMsg::decode(self.msg.as_slice())?;
+        rep.offset = self.offset;
+        Ok(rep)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    brokers: String,
+    topic: String,
+    rep_cache_url: String,
+    acl_url: String,
+    /// Lets anybody view files nobody has claimed.  Off unless OPEN_UNCLAIMED is set.
+    open_unclaimed: bool,
+    queue_policy: QueuePolicy,
+    queue_limit: usize,
+    verifier: Option<auth_lib::Verifier>,
+}
+
 pub struct ChannelSend {
-    sender: Sender<UpdateMessage>,
+    queue: Arc<Mutex<SendQueue>>,
+    /// Rung whenever something is pushed to the queue.
+    doorbell: Sender<()>,
     user: String,
+    connection: u64,
+    presence: Presence,
+}
+
+impl ChannelSend {
+    pub fn push(&mut self, msg: UpdateMessage) {
+        self.queue.lock().unwrap().push(msg);
+        // If the doorbell is full, the connection hasn't gotten to the last ring yet and will
+        // drain this message along with the others.
+        let _ = self.doorbell.try_send(());
+    }
+
+    pub fn push_presence(&mut self, event: PresenceEvent) {
+        self.queue.lock().unwrap().push_presence(event);
+        let _ = self.doorbell.try_send(());
+    }
+}
+
+/// The files one connection is subscribed to.  Its channels are taken out of FILE_TO_CHANNEL_MAP
+/// when it's dropped, so a connection that closes or errors out doesn't leave anything behind.
+struct Subscriptions {
+    connection: u64,
+    files: HashSet<String>,
+}
+
+impl Subscriptions {
+    fn new(connection: u64) -> Subscriptions {
+        Subscriptions {
+            connection,
+            files: HashSet::new(),
+        }
+    }
+
+    /// Announces the new subscriber to the others on the file, and tells it who is already there.
+    fn subscribe(&mut self, filename: String, mut send: ChannelSend) {
+        metrics::SUBSCRIPTIONS.fetch_add(1, Ordering::Relaxed);
+        let join = PresenceEvent::Join {
+            filename: filename.clone(),
+            user: send.user.clone(),
+        };
+        relay(&filename, self.connection, &join);
+        for event in snapshot(&filename, self.connection) {
+            send.push_presence(event);
+        }
+        match FILE_TO_CHANNEL_MAP.get_mut(&filename) {
+            Some(mut entry) => {
+                entry.value_mut().push(send);
+            }
+            None => {
+                FILE_TO_CHANNEL_MAP.insert(filename.clone(), vec![send]);
+            }
+        }
+        self.files.insert(filename);
+    }
+
+    /// Records the user's selection and cursor, and passes them on to the file's other subscribers.
+    fn set_presence(&mut self, filename: &str, user: &str, presence: Presence) {
+        let connection = self.connection;
+        let event = PresenceEvent::update(filename, user, &presence);
+        match FILE_TO_CHANNEL_MAP.get_mut(filename) {
+            Some(mut entry) => {
+                match entry
+                    .value_mut()
+                    .iter_mut()
+                    .find(|send| send.connection == connection && send.user == user)
+                {
+                    Some(send) => send.presence = presence,
+                    None => {
+                        warn!("Presence from {:?} for unsubscribed {:?}", user, filename);
+                        return;
+                    }
+                }
+            }
+            None => {
+                warn!("Presence from {:?} for unsubscribed {:?}", user, filename);
+                return;
+            }
+        }
+        relay(filename, connection, &event);
+    }
+
+    /// Removes the channels on this connection for the user, or all of them if user is None.
+    fn remove(&mut self, filename: &str, user: Option<&str>) {
+        let connection = self.connection;
+        let mut still_subscribed = false;
+        let mut removed = Vec::new();
+        if let Some(mut entry) = FILE_TO_CHANNEL_MAP.get_mut(filename) {
+            let senders = entry.value_mut();
+            let (gone, kept) = senders.drain(..).partition(|send| match user {
+                Some(user) => send.connection == connection && send.user == user,
+                None => send.connection == connection,
+            });
+            *senders = kept;
+            removed = gone;
+            metrics::SUBSCRIPTIONS.fetch_sub(removed.len() as u64, Ordering::Relaxed);
+            still_subscribed = senders.iter().any(|send| send.connection == connection);
+        }
+        FILE_TO_CHANNEL_MAP.remove_if(filename, |_, senders| senders.is_empty());
+        for send in removed {
+            let leave = PresenceEvent::Leave {
+                filename: String::from(filename),
+                user: send.user,
+            };
+            relay(filename, connection, &leave);
+        }
+        if !still_subscribed {
+            self.files.remove(filename);
+        }
+    }
+}
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        let files: Vec<String> = self.files.iter().cloned().collect();
+        for filename in files {
+            self.remove(&filename, None);
+        }
+    }
 }
 
 lazy_static! {
     pub static ref FILE_TO_CHANNEL_MAP: DashMap<String, Vec<ChannelSend>> = DashMap::default();
 }
 
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Deserialize)]
 enum Commands {
-    Subscribe { filename: String, user: String },
-    Unsubscribe { filename: String, user: String },
+    Subscribe {
+        filename: String,
+        user: String,
+        #[serde(default)]
+        since_offset: Option<i64>,
+    },
+    Unsubscribe {
+        filename: String,
+        user: String,
+    },
+    SetInterest {
+        filename: String,
+        bbox: BoundingBox,
+        #[serde(default)]
+        level: Option<u32>,
+    },
+    Presence {
+        filename: String,
+        user: String,
+        #[serde(default)]
+        selection: Vec<String>,
+        #[serde(default)]
+        cursor: Option<Point3>,
+    },
+}
+
+/// Returns the bytes to push for a change, or None if it's outside the client's region of
+/// interest for the file.
+fn to_bytes(
+    interests: &mut HashMap<String, FileInterest>,
+    msg: UpdateMessage,
+) -> Result<Option<Vec<u8>>> {
+    let rep = msg.to_rep()?;
+    let delivery = match interests.get_mut(&msg.file) {
+        Some(file_interest) => file_interest.deliver(&rep),
+        None => Delivery::Send,
+    };
+    match delivery {
+        Delivery::Send => Ok(Some(encode(&rep)?)),
+        Delivery::Leave => Ok(Some(encode(&leave(rep.file, rep.obj_id, msg.offset))?)),
+        Delivery::Skip => Ok(None),
+    }
+}
+
+/// Returns the message to push for a change, or None if it's outside the client's region of
+/// interest for the file.  A change that can't be read is reported to the client instead of
+/// closing the connection.
+fn to_send(interests: &mut HashMap<String, FileInterest>, msg: UpdateMessage) -> Option<Message> {
+    let offset = msg.offset;
+    match to_bytes(interests, msg) {
+        Ok(bytes) => bytes.map(Message::Binary),
+        Err(e) => {
+            error!("Invalid representation at offset {}: {:?}", offset, e);
+            Some(Message::Text(format!(
+                "Invalid representation at offset {}: {}",
+                offset, e
+            )))
+        }
+    }
 }
 
-async fn accept_connection(stream: tokio::net::TcpStream) {
-    if let Err(e) = handle_connection(stream).await {
+async fn accept_connection(stream: tokio::net::TcpStream, config: Config) {
+    metrics::CONNECTIONS_TOTAL.fetch_add(1, Ordering::Relaxed);
+    metrics::CONNECTIONS_OPEN.fetch_add(1, Ordering::Relaxed);
+    if let Err(e) = handle_connection(stream, config).await {
         error!("{:?}", e);
     }
+    metrics::CONNECTIONS_OPEN.fetch_sub(1, Ordering::Relaxed);
 }
 
-async fn handle_connection(stream: tokio::net::TcpStream) -> Result<()> {
-    let ws_stream = tokio_tungstenite::accept_async(stream).await?;
-    info!("New connection");
+async fn handle_connection(stream: tokio::net::TcpStream, config: Config) -> Result<()> {
+    let (ws_stream, authenticated) = access::accept(stream, &config.verifier).await?;
+    info!("New connection for {:?}", authenticated);
     let (mut ws_send, mut ws_rcv) = ws_stream.split();
-    let (channel_send, mut channel_rcv) = mpsc::channel(100);
+    let connection = NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed);
+    let mut subscriptions = Subscriptions::new(connection);
+    let queue = Arc::new(Mutex::new(SendQueue::new(
+        config.queue_policy,
+        config.queue_limit,
+    )));
+    let (doorbell, mut channel_rcv) = mpsc::channel(1);
     let mut channel_fut = channel_rcv.next();
     let mut ws_fut = ws_rcv.next();
+    // Offset of the last message sent for each file, so live messages that were already
+    // replayed aren't sent twice.
+    let mut last_sent: HashMap<String, i64> = HashMap::new();
+    let mut interests: HashMap<String, FileInterest> = HashMap::new();
     loop {
         trace!("Going to select");
         match select(ws_fut, channel_fut).await {
@@ -66,36 +331,151 @@ async fn handle_connection(stream: tokio::net::TcpStream) -> Result<()> {
                                 break;
                             }
                             Message::Text(sub_msg) => match serde_json::from_str(&sub_msg) {
-                                Ok(Commands::Subscribe { filename, user }) => {
+                                Ok(Commands::Subscribe {
+                                    filename,
+                                    user,
+                                    since_offset,
+                                }) => {
                                     info!("New subscribe cmd for {:?}", filename);
+                                    // With authentication on, users can only subscribe as
+                                    // themselves and to files they can view.
+                                    let user = match &authenticated {
+                                        Some(authenticated) => {
+                                            if let Err(e) = access::check_viewer(
+                                                &config.acl_url,
+                                                config.open_unclaimed,
+                                                &filename,
+                                                authenticated,
+                                            )
+                                            .await
+                                            {
+                                                warn!("{}", e);
+                                                ws_send
+                                                    .send(Message::Text(format!("{}", e)))
+                                                    .await?;
+                                                channel_fut = channel_fut_continue;
+                                                ws_fut = ws_rcv.next();
+                                                continue;
+                                            }
+                                            authenticated.clone()
+                                        }
+                                        None => user,
+                                    };
                                     let send = ChannelSend {
-                                        sender: channel_send.clone(),
-                                        user: user,
+                                        queue: queue.clone(),
+                                        doorbell: doorbell.clone(),
+                                        user,
+                                        connection,
+                                        presence: Presence::default(),
                                     };
-                                    match FILE_TO_CHANNEL_MAP.get_mut(&filename) {
-                                        Some(mut entry) => {
-                                            entry.value_mut().push(send);
-                                        }
-                                        None => {
-                                            FILE_TO_CHANNEL_MAP.insert(filename, vec![send]);
+                                    subscriptions.subscribe(filename.clone(), send);
+                                    if let Some(since_offset) = since_offset {
+                                        info!(
+                                            "Replaying {:?} from offset {}",
+                                            filename, since_offset
+                                        );
+                                        // The subscription stays up if the replay fails, so
+                                        // the client can still get live updates and try again.
+                                        let replayed = match kafka::replay(
+                                            &config.brokers,
+                                            &config.topic,
+                                            &filename,
+                                            since_offset,
+                                        )
+                                        .await
+                                        {
+                                            Ok((replayed, resync)) => {
+                                                if let Some(resync) = resync {
+                                                    let text = serde_json::to_string(&resync)?;
+                                                    ws_send.send(Message::Text(text)).await?;
+                                                }
+                                                replayed
+                                            }
+                                            Err(e) => {
+                                                error!("Replay of {:?} failed: {}", filename, e);
+                                                ws_send
+                                                    .send(Message::Text(format!(
+                                                        "Replay of {:?} failed: {}",
+                                                        filename, e
+                                                    )))
+                                                    .await?;
+                                                Vec::new()
+                                            }
+                                        };
+                                        for msg in replayed {
+                                            last_sent.insert(filename.clone(), msg.offset);
+                                            if let Some(msg) = to_send(&mut interests, msg) {
+                                                ws_send.send(msg).await?;
+                                                metrics::MESSAGES_SENT
+                                                    .fetch_add(1, Ordering::Relaxed);
+                                            }
                                         }
                                     }
                                 }
                                 Ok(Commands::Unsubscribe { filename, user }) => {
                                     info!("New unsubscribe cmd for {:?}", filename);
-                                    if let Some(mut entry) = FILE_TO_CHANNEL_MAP.get_mut(&filename)
+                                    let user = authenticated.clone().unwrap_or(user);
+                                    subscriptions.remove(&filename, Some(&user));
+                                    interests.remove(&filename);
+                                }
+                                Ok(Commands::SetInterest {
+                                    filename,
+                                    bbox,
+                                    level,
+                                }) => {
+                                    info!("New interest for {:?}: {:?}", filename, bbox);
+                                    if !subscriptions.files.contains(&filename) {
+                                        warn!("Interest set for unsubscribed {:?}", filename);
+                                        channel_fut = channel_fut_continue;
+                                        ws_fut = ws_rcv.next();
+                                        continue;
+                                    }
+                                    // The client keeps its old region if the file's
+                                    // representations can't be fetched, and can try again.
+                                    let reps = match get_file_reps(&config.rep_cache_url, &filename)
+                                        .await
                                     {
-                                        let senders = entry.value_mut();
-                                        let mut index = 0usize;
-                                        for send in senders.iter() {
-                                            if send.user == user {
-                                                senders.remove(index);
-                                                break;
-                                            }
-                                            index += 1;
+                                        Ok(reps) => reps,
+                                        Err(e) => {
+                                            error!(
+                                                "Couldn't set interest for {:?}: {}",
+                                                filename, e
+                                            );
+                                            ws_send
+                                                .send(Message::Text(format!(
+                                                    "Couldn't set interest for {:?}: {}",
+                                                    filename, e
+                                                )))
+                                                .await?;
+                                            channel_fut = channel_fut_continue;
+                                            ws_fut = ws_rcv.next();
+                                            continue;
                                         }
+                                    };
+                                    let (file_interest, events) = FileInterest::set(
+                                        interests.get(&filename),
+                                        Interest { bbox, level },
+                                        reps,
+                                    );
+                                    interests.insert(filename, file_interest);
+                                    for event in events {
+                                        ws_send.send(Message::Binary(encode(&event)?)).await?;
                                     }
                                 }
+                                Ok(Commands::Presence {
+                                    filename,
+                                    user,
+                                    selection,
+                                    cursor,
+                                }) => {
+                                    trace!("New presence from {:?} for {:?}", user, filename);
+                                    let user = authenticated.clone().unwrap_or(user);
+                                    subscriptions.set_presence(
+                                        &filename,
+                                        &user,
+                                        Presence { selection, cursor },
+                                    );
+                                }
                                 Err(e) => error!("Invalid JSON: {:?}", e),
                             },
                             _ => {
@@ -114,9 +494,32 @@ async fn handle_connection(stream: tokio::net::TcpStream) -> Result<()> {
             Either::Right((channel_msg, ws_fut_continue)) => {
                 trace!("Selected channel");
                 match channel_msg {
-                    Some(ws_msg) => {
-                        debug!("Got message from channel, passing on");
-                        ws_send.send(Message::Binary(ws_msg.msg)).await?;
+                    Some(()) => {
+                        let events = queue.lock().unwrap().drain_presence();
+                        for event in events {
+                            let text = serde_json::to_string(&event)?;
+                            ws_send.send(Message::Text(text)).await?;
+                        }
+                        let (pending, gaps) = queue.lock().unwrap().drain();
+                        for ws_msg in pending {
+                            match last_sent.get(&ws_msg.file) {
+                                Some(offset) if ws_msg.offset <= *offset => {
+                                    debug!("Message at offset {} already replayed", ws_msg.offset);
+                                }
+                                _ => {
+                                    last_sent.insert(ws_msg.file.clone(), ws_msg.offset);
+                                    if let Some(msg) = to_send(&mut interests, ws_msg) {
+                                        debug!("Got message from queue, passing on");
+                                        ws_send.send(msg).await?;
+                                        metrics::MESSAGES_SENT.fetch_add(1, Ordering::Relaxed);
+                                    }
+                                }
+                            }
+                        }
+                        for gap in gaps {
+                            let text = serde_json::to_string(&gap)?;
+                            ws_send.send(Message::Text(text)).await?;
+                        }
                     }
                     None => {
                         debug!("Got None, breaking");
@@ -140,11 +543,48 @@ async fn main() -> Result<(), std::io::Error> {
     let broker = std::env::var("BROKER").unwrap();
     let group = std::env::var("GROUP").unwrap();
     let topic = std::env::var("TOPIC").unwrap();
+    let grpc_url = std::env::var("GRPC_URL").unwrap().parse().unwrap();
+    let rep_cache_url = std::env::var("REP_CACHE_URL").unwrap();
+    let acl_url = std::env::var("ACL_URL").unwrap();
+    let open_unclaimed = match std::env::var("OPEN_UNCLAIMED") {
+        Ok(open) => open.parse().unwrap(),
+        Err(..) => false,
+    };
+    let verifier = auth_lib::Verifier::from_env().unwrap();
+    let queue_policy = match std::env::var("QUEUE_POLICY") {
+        Ok(policy) => policy.parse().unwrap(),
+        Err(..) => QueuePolicy::Coalesce,
+    };
+    let queue_limit = match std::env::var("QUEUE_LIMIT") {
+        Ok(limit) => limit.parse().unwrap(),
+        Err(..) => DEFAULT_QUEUE_LIMIT,
+    };
     let mut server = tokio::net::TcpListener::bind(&run_url).await.unwrap();
     info!("Listening for updates");
+    let config = Config {
+        brokers: broker.clone(),
+        topic: topic.clone(),
+        rep_cache_url,
+        acl_url,
+        open_unclaimed,
+        queue_policy,
+        queue_limit,
+        verifier,
+    };
+    if let Ok(metrics_url) = std::env::var("METRICS_URL") {
+        tokio::spawn(metrics::serve(metrics_url));
+    }
+    let updates = grpc::UpdatesService {
+        config: config.clone(),
+    };
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(updates::updates_server::UpdatesServer::new(updates))
+            .serve(grpc_url),
+    );
     tokio::spawn(kafka::consume(broker, group, topic));
     while let Ok((stream, _)) = server.accept().await {
-        tokio::spawn(accept_connection(stream));
+        tokio::spawn(accept_connection(stream, config.clone()));
     }
     Ok(())
 }
@@ -0,0 +1,74 @@
+//! Connection metrics, served over HTTP in the Prometheus text format on METRICS_URL if it's set.
+
+use super::*;
+use std::sync::atomic::AtomicU64;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+pub static CONNECTIONS_OPEN: AtomicU64 = AtomicU64::new(0);
+pub static CONNECTIONS_TOTAL: AtomicU64 = AtomicU64::new(0);
+pub static SUBSCRIPTIONS: AtomicU64 = AtomicU64::new(0);
+pub static MESSAGES_SENT: AtomicU64 = AtomicU64::new(0);
+pub static MESSAGES_DROPPED: AtomicU64 = AtomicU64::new(0);
+pub static MESSAGES_COALESCED: AtomicU64 = AtomicU64::new(0);
+
+fn render() -> String {
+    let metrics = [
+        ("updates_connections_open", "gauge", &CONNECTIONS_OPEN),
+        ("updates_connections_total", "counter", &CONNECTIONS_TOTAL),
+        ("updates_subscriptions", "gauge", &SUBSCRIPTIONS),
+        ("updates_messages_sent_total", "counter", &MESSAGES_SENT),
+        (
+            "updates_messages_dropped_total",
+            "counter",
+            &MESSAGES_DROPPED,
+        ),
+        (
+            "updates_messages_coalesced_total",
+            "counter",
+            &MESSAGES_COALESCED,
+        ),
+    ];
+    let mut output = String::new();
+    for (name, metric_type, value) in metrics.iter() {
+        output.push_str(&format!(
+            "# TYPE {} {}\n{} {}\n",
+            name,
+            metric_type,
+            name,
+            value.load(Ordering::Relaxed)
+        ));
+    }
+    output
+}
+
+async fn handle_request(mut stream: tokio::net::TcpStream) -> Result<()> {
+    // Every request gets the metrics, so there's no need to look at what was asked for.
+    let mut request = [0u8; 1024];
+    let _ = stream.read(&mut request).await?;
+    let body = render();
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(())
+}
+
+pub async fn serve(metrics_url: String) {
+    let mut listener = match tokio::net::TcpListener::bind(&metrics_url).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Couldn't serve metrics on {}: {:?}", metrics_url, e);
+            return;
+        }
+    };
+    info!("Serving metrics on {}", metrics_url);
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(async move {
+            if let Err(e) = handle_request(stream).await {
+                error!("{:?}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,198 @@
+//! Presence lets collaborators see who else has a file open, what they have selected and where
+//! their 3D cursor is.  It's only relayed between connections on this service and never written
+//! to ObjectState, so it's gone as soon as a client disconnects.
+
+use super::*;
+use serde::Serialize;
+
+/// What a subscriber currently has selected and where its cursor is.
+#[derive(Debug, Clone, Default)]
+pub struct Presence {
+    pub selection: Vec<String>,
+    pub cursor: Option<Point3>,
+}
+
+/// Sent to clients as JSON text frames, alongside the binary representation updates.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum PresenceEvent {
+    Join {
+        filename: String,
+        user: String,
+    },
+    Leave {
+        filename: String,
+        user: String,
+    },
+    Update {
+        filename: String,
+        user: String,
+        selection: Vec<String>,
+        cursor: Option<Point3>,
+    },
+}
+
+impl PresenceEvent {
+    pub fn update(filename: &str, user: &str, presence: &Presence) -> PresenceEvent {
+        PresenceEvent::Update {
+            filename: String::from(filename),
+            user: String::from(user),
+            selection: presence.selection.clone(),
+            cursor: presence.cursor,
+        }
+    }
+
+    /// A newer update for the same user in the same file makes an older queued one pointless.
+    pub fn replaces(&self, other: &PresenceEvent) -> bool {
+        match (self, other) {
+            (
+                PresenceEvent::Update { filename, user, .. },
+                PresenceEvent::Update {
+                    filename: other_filename,
+                    user: other_user,
+                    ..
+                },
+            ) => filename == other_filename && user == other_user,
+            _ => false,
+        }
+    }
+}
+
+/// Pushes the event to every subscriber of the file except those on the given connection.
+pub fn relay(filename: &str, connection: u64, event: &PresenceEvent) {
+    if let Some(mut entry) = FILE_TO_CHANNEL_MAP.get_mut(filename) {
+        for send in entry
+            .value_mut()
+            .iter_mut()
+            .filter(|send| send.connection != connection)
+        {
+            send.push_presence(event.clone());
+        }
+    }
+}
+
+/// The join and current presence of every subscriber to the file on other connections, for a
+/// client that has just subscribed.
+pub fn snapshot(filename: &str, connection: u64) -> Vec<PresenceEvent> {
+    let mut events = Vec::new();
+    if let Some(entry) = FILE_TO_CHANNEL_MAP.get(filename) {
+        for send in entry
+            .value()
+            .iter()
+            .filter(|send| send.connection != connection)
+        {
+            events.push(PresenceEvent::Join {
+                filename: String::from(filename),
+                user: send.user.clone(),
+            });
+            events.push(PresenceEvent::update(filename, &send.user, &send.presence));
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(user: &str, connection: u64) -> (ChannelSend, Arc<Mutex<SendQueue>>) {
+        let queue = Arc::new(Mutex::new(SendQueue::new(QueuePolicy::Drop, 10)));
+        let (doorbell, _) = mpsc::channel(1);
+        let send = ChannelSend {
+            queue: queue.clone(),
+            doorbell,
+            user: String::from(user),
+            connection,
+            presence: Presence::default(),
+        };
+        (send, queue)
+    }
+
+    fn join(filename: &str, user: &str) -> PresenceEvent {
+        PresenceEvent::Join {
+            filename: String::from(filename),
+            user: String::from(user),
+        }
+    }
+
+    fn drain(queue: &Arc<Mutex<SendQueue>>) -> Vec<PresenceEvent> {
+        queue.lock().unwrap().drain_presence()
+    }
+
+    #[test]
+    fn test_replaces() {
+        let presence = Presence {
+            selection: vec![String::from("obj")],
+            cursor: None,
+        };
+        let update = PresenceEvent::update("file", "a", &presence);
+        let newer = PresenceEvent::update("file", "a", &Presence::default());
+        assert!(newer.replaces(&update));
+        assert!(!PresenceEvent::update("file", "b", &presence).replaces(&update));
+        assert!(!PresenceEvent::update("other", "a", &presence).replaces(&update));
+        // Joins and leaves are never replaced, and never replace anything
+        assert!(!newer.replaces(&join("file", "a")));
+        assert!(!join("file", "a").replaces(&update));
+    }
+
+    #[test]
+    fn test_relay() {
+        // Every test works on a file of its own, since the channels are shared
+        let filename = uuid::Uuid::new_v4().to_string();
+        let mut first = Subscriptions::new(1);
+        let mut second = Subscriptions::new(2);
+        let (send_a, queue_a) = channel("a", 1);
+        let (send_b, queue_b) = channel("b", 2);
+        first.subscribe(filename.clone(), send_a);
+        assert!(drain(&queue_a).is_empty());
+
+        // The second subscriber is announced to the first, and gets a snapshot of who's there
+        second.subscribe(filename.clone(), send_b);
+        assert_eq!(drain(&queue_a), vec![join(&filename, "b")]);
+        assert_eq!(
+            drain(&queue_b),
+            vec![
+                join(&filename, "a"),
+                PresenceEvent::update(&filename, "a", &Presence::default())
+            ]
+        );
+
+        // Updates go to the others, but not back to the connection they came from
+        let presence = Presence {
+            selection: vec![String::from("wall")],
+            cursor: Some(Point3 {
+                x: 1.0,
+                y: 2.0,
+                z: 3.0,
+            }),
+        };
+        first.set_presence(&filename, "a", presence.clone());
+        assert!(drain(&queue_a).is_empty());
+        assert_eq!(
+            drain(&queue_b),
+            vec![PresenceEvent::update(&filename, "a", &presence)]
+        );
+
+        // A later snapshot has the latest presence
+        assert_eq!(
+            snapshot(&filename, 3),
+            vec![
+                join(&filename, "a"),
+                PresenceEvent::update(&filename, "a", &presence),
+                join(&filename, "b"),
+                PresenceEvent::update(&filename, "b", &Presence::default()),
+            ]
+        );
+        assert_eq!(snapshot(&filename, 2).len(), 2);
+
+        drop(second);
+        assert_eq!(
+            drain(&queue_a),
+            vec![PresenceEvent::Leave {
+                filename: filename.clone(),
+                user: String::from("b"),
+            }]
+        );
+        drop(first);
+        assert!(snapshot(&filename, 3).is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+//! Each connection gets its own send queue, filled by the Kafka consumer without waiting, so a slow
+//! client can't hold up delivery to the others.
+
+use super::*;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// How many messages each connection's send queue holds, unless QUEUE_LIMIT is set.
+pub const DEFAULT_QUEUE_LIMIT: usize = 1000;
+
+/// What to do once a connection's send queue is full, set with QUEUE_POLICY as `drop` or
+/// `coalesce`, which is the default.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueuePolicy {
+    /// New messages are dropped while the queue is full.
+    Drop,
+    /// A queued message for an object is replaced by a newer one for the same object, so a client
+    /// only gets the latest representation.  New messages are still dropped if that doesn't make
+    /// room.
+    Coalesce,
+}
+
+impl std::str::FromStr for QueuePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(policy: &str) -> Result<QueuePolicy> {
+        match policy {
+            "drop" => Ok(QueuePolicy::Drop),
+            "coalesce" => Ok(QueuePolicy::Coalesce),
+            _ => Err(anyhow::anyhow!("Unknown queue policy {}", policy)),
+        }
+    }
+}
+
+/// Sent to clients as a JSON text frame when they've missed updates for a file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Resync {
+    /// Sent after the messages that made it into the queue, when some for the file had to be
+    /// dropped.  Unsubscribing and subscribing again with the offset of the last update received
+    /// as `since_offset` replays everything that was missed.
+    Gap { filename: String, offset: i64 },
+    /// Sent before a replay when the updates after `since_offset` and before `offset` are no
+    /// longer kept, so the file has to be loaded again.
+    Truncated { filename: String, offset: i64 },
+}
+
+pub struct SendQueue {
+    policy: QueuePolicy,
+    limit: usize,
+    pending: VecDeque<UpdateMessage>,
+    presence: VecDeque<PresenceEvent>,
+    /// The last dropped offset for each file with messages dropped since the last drain.
+    gaps: HashMap<String, i64>,
+}
+
+impl SendQueue {
+    pub fn new(policy: QueuePolicy, limit: usize) -> SendQueue {
+        SendQueue {
+            policy,
+            limit,
+            pending: VecDeque::new(),
+            presence: VecDeque::new(),
+            gaps: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, msg: UpdateMessage) {
+        if self.policy == QueuePolicy::Coalesce && !msg.obj_id.is_empty() {
+            // The newer message goes to the back rather than taking the old one's place, so
+            // messages for a file still go out in offset order.
+            if let Some(index) = self
+                .pending
+                .iter()
+                .position(|queued| queued.file == msg.file && queued.obj_id == msg.obj_id)
+            {
+                self.pending.remove(index);
+                metrics::MESSAGES_COALESCED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        // Once something for a file is dropped, so is everything after it until the gap is
+        // drained, so a client never gets a message from past the gap before hearing about it.
+        if self.pending.len() >= self.limit || self.gaps.contains_key(&msg.file) {
+            warn!(
+                "Send queue full, dropping message at offset {} for {:?}",
+                msg.offset, msg.file
+            );
+            metrics::MESSAGES_DROPPED.fetch_add(1, Ordering::Relaxed);
+            self.gaps.insert(msg.file, msg.offset);
+            return;
+        }
+        self.pending.push_back(msg);
+    }
+
+    /// Presence updates are always coalesced, since only a user's latest cursor and selection
+    /// matter.  Joins and leaves are kept unless the queue is full.
+    pub fn push_presence(&mut self, event: PresenceEvent) {
+        if let Some(index) = self
+            .presence
+            .iter()
+            .position(|queued| event.replaces(queued))
+        {
+            self.presence.remove(index);
+        }
+        if self.presence.len() >= self.limit {
+            warn!("Presence queue full, dropping {:?}", event);
+            metrics::MESSAGES_DROPPED.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.presence.push_back(event);
+    }
+
+    pub fn drain_presence(&mut self) -> Vec<PresenceEvent> {
+        self.presence.drain(..).collect()
+    }
+
+    /// Returns the queued messages, and the gaps to send after them.
+    pub fn drain(&mut self) -> (Vec<UpdateMessage>, Vec<Resync>) {
+        let gaps = self
+            .gaps
+            .drain()
+            .map(|(filename, offset)| Resync::Gap { filename, offset })
+            .collect();
+        (self.pending.drain(..).collect(), gaps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(file: &str, obj_id: &str, offset: i64) -> UpdateMessage {
+        UpdateMessage {
+            file: String::from(file),
+            obj_id: String::from(obj_id),
+            offset,
+            msg: Vec::new(),
+        }
+    }
+
+    fn offsets(msgs: &[UpdateMessage]) -> Vec<i64> {
+        msgs.iter().map(|msg| msg.offset).collect()
+    }
+
+    fn gap(filename: &str, offset: i64) -> Resync {
+        Resync::Gap {
+            filename: String::from(filename),
+            offset,
+        }
+    }
+
+    #[test]
+    fn test_drop() {
+        let mut queue = SendQueue::new(QueuePolicy::Drop, 2);
+        for offset in 1..5 {
+            queue.push(msg("a", "x", offset));
+        }
+        let (pending, gaps) = queue.drain();
+        assert_eq!(offsets(&pending), vec![1, 2]);
+        assert_eq!(gaps, vec![gap("a", 4)]);
+
+        // Draining clears the gap, so the next message gets through
+        queue.push(msg("a", "x", 5));
+        let (pending, gaps) = queue.drain();
+        assert_eq!(offsets(&pending), vec![5]);
+        assert!(gaps.is_empty());
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let mut queue = SendQueue::new(QueuePolicy::Coalesce, 10);
+        queue.push(msg("a", "x", 1));
+        queue.push(msg("a", "y", 2));
+        queue.push(msg("a", "x", 3));
+        queue.push(msg("b", "x", 4));
+        // Messages without an object aren't coalesced
+        queue.push(msg("a", "", 5));
+        queue.push(msg("a", "", 6));
+        let (pending, gaps) = queue.drain();
+        assert_eq!(offsets(&pending), vec![2, 3, 4, 5, 6]);
+        assert!(gaps.is_empty());
+    }
+
+    #[test]
+    fn test_coalesce_full() {
+        let mut queue = SendQueue::new(QueuePolicy::Coalesce, 2);
+        queue.push(msg("a", "x", 1));
+        queue.push(msg("a", "y", 2));
+        queue.push(msg("a", "z", 3));
+        // Coalescing makes room, but "a" already has a gap, so this is dropped too and the gap
+        // moves up to it
+        queue.push(msg("a", "x", 4));
+        queue.push(msg("b", "x", 5));
+        let (pending, gaps) = queue.drain();
+        assert_eq!(offsets(&pending), vec![2, 5]);
+        assert_eq!(gaps, vec![gap("a", 4)]);
+    }
+}